@@ -0,0 +1,96 @@
+//! Integrates every file given on the command line, writing the radial
+//! profile averaged over all frames of `<file>` to `<file>.profile.cif`.
+
+use std::{env, f64, fs::File, io::BufWriter, path::Path, process::ExitCode};
+
+use cbf_rs::{
+	analysis::profile_cif::write_profile_cif,
+	decoder::{Frame, FrameShape, Inconsistency},
+	prelude::{open, radial_profile, AnalysisConfig, Error},
+	source::Source,
+};
+
+/// Bin radii in pixels and the profile averaged over all frames, which must all
+/// have the dimensions of the first one.
+fn integrate(source: impl Into<Source>) -> Result<(Box<[f64]>, Vec<f64>), Error> {
+	let mut radii = Box::default();
+	let mut profile: Vec<f64> = Vec::new();
+	let mut frames = 0.0;
+	let mut source = open(source)?;
+	let mut first = None;
+	for index in 0.. {
+		let Some(Frame { metadata, image, .. }) = source.read_next_frame()? else {
+			break;
+		};
+		let found = FrameShape { width: image.width(), height: image.height(), element_type: metadata.element_type };
+		let expected = *first.get_or_insert(found);
+		if (found.width, found.height) != (expected.width, expected.height) {
+			return Err(Error::InconsistentFrame(Inconsistency { index, expected, found }));
+		}
+		let config = AnalysisConfig::new(image.width() / 2, 1000, f64::consts::SQRT_2).unwrap();
+		let frame = radial_profile(&image, &config);
+		if profile.is_empty() {
+			radii = config.bin_radii(image.width());
+			profile = vec![0.0; frame.len()];
+		}
+		frames += 1.0;
+		for (average, value) in profile.iter_mut().zip(frame.iter()) {
+			*average += (value - *average) / frames;
+		}
+	}
+	Ok((radii, profile))
+}
+
+fn main() -> ExitCode {
+	let mut status = ExitCode::SUCCESS;
+	for path in env::args().skip(1) {
+		let result = integrate(Path::new(&path))
+			.map_err(|e| e.to_string())
+			.and_then(|(radii, profile)| {
+				let output = File::create(format!("{path}.profile.cif")).map_err(|e| e.to_string())?;
+				let block = Path::new(&path)
+					.file_stem()
+					.map_or("profile".into(), |s| s.to_string_lossy());
+				write_profile_cif(BufWriter::new(output), &block, &radii, &profile, None).map_err(|e| e.to_string())
+			});
+		if let Err(error) = result {
+			eprintln!("{path}: {error}");
+			status = ExitCode::FAILURE;
+		}
+	}
+	status
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use cbf_rs::{image::ImageEnum, source::Source, write_all_images, Error};
+
+	use super::integrate;
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("../../../cbf-rs/src/examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn integrates_example() {
+		let (radii, profile) = integrate(Source::reader(Cursor::new(EXAMPLE_DATA))).expect("to integrate");
+		assert_eq!(radii.len(), 1440);
+		assert_eq!(profile.len(), 1440);
+		assert!(profile.iter().any(|v| 0.0 < *v));
+	}
+
+	#[test]
+	fn rejects_mixed_dimensions() {
+		let frames = [
+			ImageEnum::from_pixels(3, 3, vec![1i32; 9].into()),
+			ImageEnum::from_pixels(2, 2, vec![1i32; 4].into()),
+		];
+		let mut data = Vec::new();
+		write_all_images(&mut data, &frames).unwrap();
+		let Err(Error::InconsistentFrame(inconsistency)) = integrate(Source::reader(Cursor::new(data))) else {
+			panic!("expected the second frame to be rejected");
+		};
+		assert_eq!(inconsistency.index, 1);
+		assert_eq!((inconsistency.found.width, inconsistency.found.height), (2, 2));
+	}
+}
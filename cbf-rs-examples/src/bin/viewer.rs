@@ -0,0 +1,78 @@
+//! Minimal native viewer: `viewer <FILE>` shows the frames of a CBF file,
+//! histogram equalized, with a slider to step through them.
+
+use std::{env, process::ExitCode};
+
+use cbf_rs::{
+	image::colormap::Colormap,
+	prelude::{open, ImageEnum},
+};
+use eframe::egui::{CentralPanel, ColorImage, Image, Panel, Slider, TextureHandle, TextureOptions, Ui};
+
+struct Viewer {
+	frames: Vec<ImageEnum>,
+	colormap: Colormap,
+	frame: usize,
+	/// Texture of the frame with the index it was drawn from
+	texture: Option<(usize, TextureHandle)>,
+}
+
+impl Viewer {
+	fn texture(&mut self, ui: &Ui) -> &TextureHandle {
+		let image = &self.frames[self.frame];
+		if self.texture.as_ref().is_none_or(|(index, _)| *index != self.frame) {
+			let levels = image.to_f64().equalized();
+			let rgba: Vec<u8> = levels.pixels().iter().flat_map(|&l| self.colormap.colour(l)).collect();
+			let pixels = ColorImage::from_rgba_unmultiplied([image.width(), image.height()], &rgba);
+			let texture = ui.ctx().load_texture("frame", pixels, TextureOptions::NEAREST);
+			self.texture = Some((self.frame, texture));
+		}
+		&self.texture.as_ref().unwrap().1
+	}
+}
+
+impl eframe::App for Viewer {
+	fn ui(&mut self, ui: &mut Ui, _frame: &mut eframe::Frame) {
+		if 1 < self.frames.len() {
+			let last = self.frames.len() - 1;
+			Panel::top("frames").show(ui, |ui| {
+				ui.add(Slider::new(&mut self.frame, 0..=last).text("frame"));
+			});
+		}
+		CentralPanel::no_frame().show(ui, |ui| {
+			let texture = self.texture(ui);
+			ui.add(Image::new(texture).shrink_to_fit());
+		});
+	}
+}
+
+fn main() -> ExitCode {
+	let Some(path) = env::args().nth(1) else {
+		eprintln!("usage: viewer <FILE>");
+		return ExitCode::FAILURE;
+	};
+	let frames = match open(path.as_str()).and_then(|frames| frames.collect::<Result<Vec<_>, _>>()) {
+		Ok(frames) if !frames.is_empty() => frames,
+		Ok(_) => {
+			eprintln!("{path}: no frames");
+			return ExitCode::FAILURE;
+		}
+		Err(error) => {
+			eprintln!("{path}: {error}");
+			return ExitCode::FAILURE;
+		}
+	};
+	let viewer = Viewer { frames, colormap: Colormap::default(), frame: 0, texture: None };
+	let result = eframe::run_native(
+		"viewer",
+		eframe::NativeOptions::default(),
+		Box::new(|_| Ok(Box::new(viewer))),
+	);
+	match result {
+		Ok(()) => ExitCode::SUCCESS,
+		Err(error) => {
+			eprintln!("{error}");
+			ExitCode::FAILURE
+		}
+	}
+}
@@ -1,4 +1,13 @@
-use cbf_rs::{image::ImageEnum, read_image};
+use cbf_rs::{
+	analysis::{
+		annotations::{render_annotations, Annotation},
+		overlay::{render_overlay, OverlayStyle},
+	},
+	geometry::DisplayTransform,
+	prelude::{
+		radial_profile_f32, read_image_from_slice, BuiltinColormap, Colormap, Geometry as CbfGeometry, ImageEnum,
+	},
+};
 use std::cmp::Ordering;
 use wasm_bindgen::prelude::wasm_bindgen;
 
@@ -8,7 +17,7 @@ pub struct Image(ImageEnum);
 #[wasm_bindgen]
 impl Image {
 	pub fn load(file: &[u8]) -> Result<Image, String> {
-		let cbf_image = read_image(file).map_err(|e| format!("{e:?}"))?;
+		let (cbf_image, _) = read_image_from_slice(file).map_err(|e| format!("{e:?}"))?;
 		Ok(Image(cbf_image))
 	}
 
@@ -23,57 +32,301 @@ impl Image {
 	}
 
 	#[wasm_bindgen(js_name = "writeImage")]
-	pub fn write_image(&self, pixel_buffer: &mut [u8]) {
+	pub fn write_image(&self, pixel_buffer: &mut [u8], options: &DisplayOptions) {
+		let levels = match options.transfer {
+			TransferFunction::Linear | TransferFunction::Log => None,
+			TransferFunction::Equalized => Some(self.0.to_f64().equalized()),
+			TransferFunction::LocalEqualized => self.0.to_f64().locally_equalized(8, 3.0),
+		};
+		if let Some(levels) = levels {
+			let pixels = levels.pixels().iter().map(|l| options.level(*l));
+			write_to_pixel_buffer(pixels, pixel_buffer, options);
+			return;
+		}
 		match &self.0 {
-			ImageEnum::U8(image) => write_image::u8(image.pixels(), pixel_buffer),
-			ImageEnum::I8(image) => write_image::i8(image.pixels(), pixel_buffer),
-			ImageEnum::U16(image) => write_image::u16(image.pixels(), pixel_buffer),
-			ImageEnum::I16(image) => write_image::i16(image.pixels(), pixel_buffer),
-			ImageEnum::U32(image) => write_image::u32(image.pixels(), pixel_buffer),
-			ImageEnum::I32(image) => write_image::i32(image.pixels(), pixel_buffer),
-			ImageEnum::F32(image) => write_image::f32(image.pixels(), pixel_buffer),
-			ImageEnum::U64(image) => write_image::u64(image.pixels(), pixel_buffer),
-			ImageEnum::I64(image) => write_image::i64(image.pixels(), pixel_buffer),
-			ImageEnum::F64(image) => write_image::f64(image.pixels(), pixel_buffer),
+			ImageEnum::U8(image) => write_image::u8(image.pixels(), pixel_buffer, options),
+			ImageEnum::I8(image) => write_image::i8(image.pixels(), pixel_buffer, options),
+			ImageEnum::U16(image) => write_image::u16(image.pixels(), pixel_buffer, options),
+			ImageEnum::I16(image) => write_image::i16(image.pixels(), pixel_buffer, options),
+			ImageEnum::U32(image) => write_image::u32(image.pixels(), pixel_buffer, options),
+			ImageEnum::I32(image) => write_image::i32(image.pixels(), pixel_buffer, options),
+			ImageEnum::F32(image) => write_image::f32(image.pixels(), pixel_buffer, options),
+			ImageEnum::U64(image) => write_image::u64(image.pixels(), pixel_buffer, options),
+			ImageEnum::I64(image) => write_image::i64(image.pixels(), pixel_buffer, options),
+			ImageEnum::F64(image) => write_image::f64(image.pixels(), pixel_buffer, options),
 		}
 	}
+
+	#[wasm_bindgen(js_name = "writeOverlay")]
+	pub fn write_overlay(&self, pixel_buffer: &mut [u8]) {
+		let config = analyze_image::config_for_width(self.width());
+		render_overlay(
+			self.width(),
+			self.height(),
+			&config,
+			&OverlayStyle::default(),
+			pixel_buffer,
+		);
+	}
+
+	/// Renders a JSON array of annotations on top of `pixel_buffer`.
+	#[wasm_bindgen(js_name = "writeAnnotations")]
+	pub fn write_annotations(&self, annotations: &str, pixel_buffer: &mut [u8]) -> Result<(), String> {
+		let annotations: Vec<Annotation> = serde_json::from_str(annotations).map_err(|e| e.to_string())?;
+		render_annotations(self.width(), self.height(), &annotations, pixel_buffer);
+		Ok(())
+	}
 }
 
 #[wasm_bindgen]
-pub struct Analysis(Vec<f64>);
+#[derive(Clone, Copy)]
+pub enum TransferFunction {
+	Linear,
+	Log,
+	/// Histogram equalization over the whole image
+	Equalized,
+	/// Contrast limited equalization adapting to local regions of the image
+	LocalEqualized,
+}
+
+#[wasm_bindgen]
+#[derive(Clone)]
+pub struct DisplayOptions {
+	/// Render high intensities dark on a light background
+	pub invert: bool,
+	/// Exponent applied after the transfer function, values below 1 brighten weak signal
+	pub gamma: f64,
+	pub transfer: TransferFunction,
+	colormap: Colormap,
+}
+
+#[wasm_bindgen]
+impl DisplayOptions {
+	#[wasm_bindgen(constructor)]
+	pub fn new() -> DisplayOptions {
+		DisplayOptions { invert: true, gamma: 1.0, transfer: TransferFunction::Linear, colormap: Colormap::default() }
+	}
+
+	/// Names of the built-in colour maps.
+	#[wasm_bindgen(js_name = "colormapNames")]
+	pub fn colormap_names() -> Vec<String> {
+		BuiltinColormap::ALL.iter().map(|map| map.name().to_owned()).collect()
+	}
+
+	/// Selects one of the built-in colour maps by name.
+	#[wasm_bindgen(js_name = "setColormap")]
+	pub fn set_colormap(&mut self, name: &str) -> Result<(), String> {
+		let map = BuiltinColormap::from_name(name).ok_or_else(|| format!("unknown colour map {name}"))?;
+		self.colormap = map.into();
+		Ok(())
+	}
+
+	/// Uses a custom table of 256 RGBA entries, either as 1024 packed bytes or
+	/// as text with one entry per line.
+	#[wasm_bindgen(js_name = "uploadColormap")]
+	pub fn upload_colormap(&mut self, lut: &[u8]) -> Result<(), String> {
+		let colormap = Colormap::from_rgba(lut)
+			.or_else(|| std::str::from_utf8(lut).ok().and_then(Colormap::parse))
+			.ok_or_else(|| "expected 256 RGBA entries".to_owned())?;
+		self.colormap = colormap;
+		Ok(())
+	}
+}
+
+impl Default for DisplayOptions {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl DisplayOptions {
+	fn scale(&self, offset: f64, magnitude: f64) -> u8 {
+		let value = match self.transfer {
+			TransferFunction::Log => offset.ln_1p() / magnitude.ln_1p(),
+			_ => offset / magnitude,
+		};
+		self.level(value)
+	}
+
+	fn level(&self, value: f64) -> u8 {
+		(value.powf(self.gamma) * 255.0) as u8
+	}
+}
+
+#[wasm_bindgen]
+pub struct Geometry(CbfGeometry);
+
+#[wasm_bindgen]
+impl Geometry {
+	#[wasm_bindgen(constructor)]
+	pub fn new(beam_x: f64, beam_y: f64, distance: f64, pixel_size: f64, wavelength: Option<f64>) -> Geometry {
+		Geometry(CbfGeometry {
+			beam_centre: (beam_x, beam_y),
+			distance,
+			pixel_size: (pixel_size, pixel_size),
+			wavelength,
+		})
+	}
+
+	pub fn readout(&self, display_x: f64, display_y: f64, zoom: f64, pan_x: f64, pan_y: f64) -> Readout {
+		let transform = DisplayTransform { zoom, pan: (pan_x, pan_y) };
+		let (x, y) = transform.display_to_detector((display_x, display_y));
+		let polar = self.0.detector_to_polar((x, y));
+		Readout { x, y, radius: polar.radius, phi: polar.phi, q: self.0.q((x, y)) }
+	}
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy)]
+pub struct Readout {
+	pub x: f64,
+	pub y: f64,
+	pub radius: f64,
+	pub phi: f64,
+	pub q: Option<f64>,
+}
+
+const ANALYSIS_FORMAT_VERSION: u8 = 1;
+
+/// Radial profile averaged over every analysed frame.
+#[wasm_bindgen]
+pub struct Analysis {
+	profile: Vec<f64>,
+	frame_count: usize,
+	/// Width of the analysed frames, which decides the bin positions
+	width: usize,
+	geometry: Option<CbfGeometry>,
+}
 
 #[wasm_bindgen]
 impl Analysis {
 	pub fn init() -> Analysis {
-		Analysis(Vec::new())
+		Analysis { profile: Vec::new(), frame_count: 0, width: 0, geometry: None }
 	}
 
+	/// Adds the profile of `image` to the running average. A profile of a
+	/// different length than the average so far restarts the average.
 	pub fn analyze(&mut self, image: &Image) {
-		match &image.0 {
-			ImageEnum::U8(image) => analyze_image::u8(image, &mut self.0),
-			ImageEnum::I8(image) => analyze_image::i8(image, &mut self.0),
-			ImageEnum::U16(image) => analyze_image::u16(image, &mut self.0),
-			ImageEnum::I16(image) => analyze_image::i16(image, &mut self.0),
-			ImageEnum::U32(image) => analyze_image::u32(image, &mut self.0),
-			ImageEnum::I32(image) => analyze_image::i32(image, &mut self.0),
-			ImageEnum::F32(image) => analyze_image::f32(image, &mut self.0),
-			ImageEnum::U64(image) => analyze_image::u64(image, &mut self.0),
-			ImageEnum::I64(image) => analyze_image::i64(image, &mut self.0),
-			ImageEnum::F64(image) => analyze_image::f64(image, &mut self.0),
+		let config = analyze_image::config_for_width(image.width());
+		let profile: Vec<f64> = radial_profile_f32(&image.0, &config)
+			.iter()
+			.map(|n| *n as f64)
+			.collect();
+
+		if self.frame_count == 0 || self.profile.len() != profile.len() {
+			self.profile = profile;
+			self.width = image.width();
+			self.frame_count = 1;
+			return;
+		}
+		self.frame_count += 1;
+		let n = self.frame_count as f64;
+		for (average, value) in self.profile.iter_mut().zip(profile) {
+			*average += (value - *average) / n;
+		}
+	}
+
+	pub fn reset(&mut self) {
+		self.profile.clear();
+		self.frame_count = 0;
+	}
+
+	#[wasm_bindgen(js_name = "setGeometry")]
+	pub fn set_geometry(&mut self, geometry: &Geometry) {
+		self.geometry = Some(geometry.0.clone());
+	}
+
+	/// Distance of each profile bin from the image centre in pixels.
+	#[wasm_bindgen(getter, js_name = "binCenters")]
+	pub fn bin_centers(&self) -> Box<[f64]> {
+		if self.frame_count == 0 {
+			return Box::new([]);
+		}
+		analyze_image::config_for_width(self.width).bin_radii(self.width)
+	}
+
+	/// Scattering vector of each profile bin, if a geometry with a wavelength
+	/// is set. The profile is centred on the image, so the beam is assumed to
+	/// hit its centre.
+	#[wasm_bindgen(getter, js_name = "binCentersQ")]
+	pub fn bin_centers_q(&self) -> Option<Box<[f64]>> {
+		let geometry = self.geometry.as_ref()?;
+		self.bin_centers().iter().map(|&r| geometry.radius_to_q(r)).collect()
+	}
+
+	/// Serialises the accumulated state, to be restored with `fromBytes`.
+	#[wasm_bindgen(js_name = "toBytes")]
+	pub fn to_bytes(&self) -> Box<[u8]> {
+		let mut bytes = vec![ANALYSIS_FORMAT_VERSION, self.geometry.is_some() as u8];
+		if let Some(geometry) = &self.geometry {
+			let (x, y) = geometry.beam_centre;
+			let (fast, slow) = geometry.pixel_size;
+			let wavelength = geometry.wavelength.unwrap_or(f64::NAN);
+			bytes.extend(
+				[x, y, geometry.distance, fast, slow, wavelength]
+					.iter()
+					.flat_map(|v| v.to_le_bytes()),
+			);
+		}
+		bytes.extend((self.frame_count as u64).to_le_bytes());
+		bytes.extend((self.width as u64).to_le_bytes());
+		bytes.extend((self.profile.len() as u64).to_le_bytes());
+		bytes.extend(self.profile.iter().flat_map(|v| v.to_le_bytes()));
+		bytes.into()
+	}
+
+	#[wasm_bindgen(js_name = "fromBytes")]
+	pub fn from_bytes(bytes: &[u8]) -> Result<Analysis, String> {
+		let [ANALYSIS_FORMAT_VERSION, has_geometry, ref rest @ ..] = *bytes else {
+			return Err("unsupported analysis state".to_owned());
+		};
+		let mut chunks = rest.chunks_exact(8).map(|c| <[u8; 8]>::try_from(c).unwrap());
+		if !rest.len().is_multiple_of(8) {
+			return Err("truncated analysis state".to_owned());
+		}
+		let mut next = || chunks.next().ok_or_else(|| "truncated analysis state".to_owned());
+
+		let geometry = match has_geometry {
+			0 => None,
+			_ => {
+				let [x, y, distance, fast, slow, wavelength] = [(); 6].map(|_| next().map(f64::from_le_bytes));
+				let wavelength = wavelength?;
+				Some(CbfGeometry {
+					beam_centre: (x?, y?),
+					distance: distance?,
+					pixel_size: (fast?, slow?),
+					wavelength: (!wavelength.is_nan()).then_some(wavelength),
+				})
+			}
 		};
+		let frame_count = u64::from_le_bytes(next()?) as usize;
+		let width = u64::from_le_bytes(next()?) as usize;
+		let len = u64::from_le_bytes(next()?) as usize;
+		if rest.len() / 8 < len {
+			return Err("truncated analysis state".to_owned());
+		}
+		let profile: Vec<f64> = (0..len)
+			.map(|_| next().map(f64::from_le_bytes))
+			.collect::<Result<_, _>>()?;
+		Ok(Analysis { profile, frame_count, width, geometry })
+	}
+
+	#[wasm_bindgen(getter, js_name = "frameCount")]
+	pub fn frame_count(&self) -> usize {
+		self.frame_count
 	}
 
 	#[wasm_bindgen(getter)]
 	pub fn raw(&self) -> Box<[f64]> {
-		self.0.clone().into()
+		self.profile.clone().into()
 	}
 
 	#[wasm_bindgen(getter, js_name = "localScaled")]
 	pub fn local_scaled(&self) -> Box<[u8]> {
-		let (min, max) = min_max(self.0.iter()).unwrap_or_else(|| (&f64::MIN, &f64::MAX));
+		let (min, max) = min_max(self.profile.iter()).unwrap_or_else(|| (&f64::MIN, &f64::MAX));
 		let magnitude = max - min;
 		let scale = 255.0 / magnitude as f64;
-		self.0
+		self.profile
 			.iter()
 			.map(|n| ((*n - min) * scale) as u8)
 			.flat_map(|v| [255 - v, 255 - v, 255 - v, 255])
@@ -82,16 +335,15 @@ impl Analysis {
 }
 
 mod write_image {
-	use super::{min_max, write_to_pixel_buffer};
+	use super::{min_max, write_to_pixel_buffer, DisplayOptions};
 
 	macro_rules! impl_write_image_for_pixels {
 		($($name:ident: $type:ty,)*) => {
-			$(pub fn $name(slice: &[$type], pixel_buffer: &mut [u8]) {
+			$(pub fn $name(slice: &[$type], pixel_buffer: &mut [u8], options: &DisplayOptions) {
 				let (min, max) = min_max(slice.iter()).unwrap_or_else(|| (&<$type>::MIN, &<$type>::MAX));
-				let magnitude = max - min;
-				let scale = 255.0 / magnitude as f64;
-				let pixels = slice.iter().map(|n| ((*n - min) as f64 * scale) as u8);
-				write_to_pixel_buffer(pixels, pixel_buffer);
+				let magnitude = (max - min) as f64;
+				let pixels = slice.iter().map(|n| options.scale((*n - min) as f64, magnitude));
+				write_to_pixel_buffer(pixels, pixel_buffer, options);
 			})*
 		};
 	}
@@ -111,36 +363,11 @@ mod write_image {
 }
 
 mod analyze_image {
-	use cbf_rs::{
-		analysis::{radial_difraction_analysis, sampler_methods::nearest_neighbour, AnalysisConfig},
-		image::Image,
-	};
+	use cbf_rs::prelude::AnalysisConfig;
 	use std::f64;
 
-	macro_rules! impl_analyze_image_for_pixels {
-		($($name:ident: $type:ty,)*) => {
-			$(pub fn $name(image: &Image<$type>, target: &mut impl Extend<f64>) {
-				let result = radial_difraction_analysis(&image, &config_for_image(&image), nearest_neighbour);
-				target.extend(result.into_iter().map(|n| *n as f64))
-			})*
-		};
-	}
-
-	fn config_for_image<P>(image: &Image<P>) -> AnalysisConfig {
-		AnalysisConfig::new(image.width / 2, 1000, f64::consts::SQRT_2).unwrap()
-	}
-
-	impl_analyze_image_for_pixels! {
-		u8: u8,
-		i8: i8,
-		u16: u16,
-		i16: i16,
-		u32: u32,
-		i32: i32,
-		f32: f32,
-		u64: u64,
-		i64: i64,
-		f64: f64,
+	pub fn config_for_width(width: usize) -> AnalysisConfig {
+		AnalysisConfig::new(width / 2, 1000, f64::consts::SQRT_2).unwrap()
 	}
 }
 
@@ -163,11 +390,10 @@ fn min_max<N: Copy + PartialOrd>(iter: impl Iterator<Item = N>) -> Option<(N, N)
 	})
 }
 
-fn write_to_pixel_buffer(pixels: impl Iterator<Item = u8>, pixel_buffer: &mut [u8]) {
-	for (i, v) in pixels.take(pixel_buffer.len() / 4).enumerate() {
-		pixel_buffer[i * 4 + 0] = 255 - v;
-		pixel_buffer[i * 4 + 1] = 255 - v;
-		pixel_buffer[i * 4 + 2] = 255 - v;
-		pixel_buffer[i * 4 + 3] = 255;
+fn write_to_pixel_buffer(pixels: impl Iterator<Item = u8>, pixel_buffer: &mut [u8], options: &DisplayOptions) {
+	let entries = options.colormap.entries();
+	for (v, target) in pixels.zip(pixel_buffer.chunks_exact_mut(4)) {
+		let v = if options.invert { 255 - v } else { v };
+		target.copy_from_slice(&entries[v as usize]);
 	}
 }
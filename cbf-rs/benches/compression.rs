@@ -0,0 +1,54 @@
+//! Compares compressed size and speed of `x-CBF_PACKED` with different block
+//! lengths against the byte-offset compressed example frame.
+//!
+//! Run with `cargo bench --bench compression`.
+
+use std::{hint::black_box, io::Cursor, time::Instant};
+
+use cbf_rs::compression::{read_byte_offset, read_packed, write_packed, PackedParameters};
+
+const EXAMPLE_DATA: &[u8] = include_bytes!("../src/compression/examples/byte_offset.bin");
+const ELEMENT_COUNT: usize = 8294400;
+const ITERATIONS: u32 = 5;
+
+fn main() {
+	let mut pixels = vec![0i32; ELEMENT_COUNT];
+	let seconds = time(|| read_byte_offset(Cursor::new(EXAMPLE_DATA), &mut pixels).unwrap());
+	report("byte offset", EXAMPLE_DATA.len(), None, seconds);
+
+	for max_block_len in [1, 8, 32, 128] {
+		let parameters = PackedParameters::new(max_block_len).unwrap();
+		let mut data = Vec::new();
+		let encode = time(|| {
+			data.clear();
+			write_packed(&mut data, &pixels, &parameters).unwrap()
+		});
+		let mut decoded = vec![0i32; ELEMENT_COUNT];
+		let decode = time(|| read_packed(Cursor::new(&data), &mut decoded).unwrap());
+		assert_eq!(decoded, pixels);
+		report(
+			&format!("packed, blocks ≤ {max_block_len}"),
+			data.len(),
+			Some(encode),
+			decode,
+		);
+	}
+}
+
+fn time<T>(mut f: impl FnMut() -> T) -> f64 {
+	let start = Instant::now();
+	for _ in 0..ITERATIONS {
+		black_box(f());
+	}
+	start.elapsed().as_secs_f64() / ITERATIONS as f64
+}
+
+fn report(name: &str, size: usize, encode: Option<f64>, decode: f64) {
+	let ratio = (ELEMENT_COUNT * 4) as f64 / size as f64;
+	let megabytes = (ELEMENT_COUNT * 4) as f64 / 1e6;
+	let encode = encode.map_or("-".to_owned(), |s| format!("{:.0} MB/s", megabytes / s));
+	println!(
+		"{name:<24} {size:>10} bytes  ratio {ratio:>5.2}  encode {encode:>10}  decode {:.0} MB/s",
+		megabytes / decode
+	);
+}
@@ -0,0 +1,109 @@
+use crate::{geometry::Geometry, image::ImageEnum};
+
+use super::{radial_difraction_analysis, sampler_methods::nearest_neighbour, AnalysisConfig, RadialUnit};
+
+/// Scale from measured intensities to an absolute differential scattering
+/// cross-section, found by measuring a standard such as glassy carbon.
+///
+/// Profiles of samples must be normalised the same way as the profile of the
+/// standard, for exposure time, monitor counts, transmission and thickness,
+/// for the scale to carry over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AbsoluteCalibration {
+	factor: f64,
+}
+
+impl AbsoluteCalibration {
+	pub fn new(factor: f64) -> Option<Self> {
+		if !(factor > 0.0 && factor.is_finite()) {
+			return None;
+		}
+		Some(Self { factor })
+	}
+
+	/// Least squares scale between the profile of a standard, `measured` at
+	/// scattering vectors `q`, and its known cross-section `reference`, given
+	/// as `(q, dσ/dΩ)` pairs sorted by `q`.
+	///
+	/// The reference is interpolated linearly at each measured `q` within its
+	/// range; points outside it or not finite are left out. Returns `None`
+	/// without any overlapping points or if the scale is not positive.
+	pub fn from_standard(q: &[f64], measured: &[f64], reference: &[(f64, f64)]) -> Option<Self> {
+		let (mut cross, mut square) = (0.0, 0.0);
+		for (&q, &intensity) in q.iter().zip(measured) {
+			let Some(expected) = interpolate(reference, q) else {
+				continue;
+			};
+			if intensity.is_finite() && expected.is_finite() {
+				cross += intensity * expected;
+				square += intensity * intensity;
+			}
+		}
+		Self::new(cross / square)
+	}
+
+	/// Calibration from a frame of a standard, whose profile is computed in
+	/// `f64` with `config` and put on the `q` axis of `geometry`. Profiles are
+	/// centred on the image, so the beam is assumed to hit its centre, like
+	/// [`AnalysisConfig::with_q_range`] does. Returns `None` if `geometry` has
+	/// no wavelength.
+	pub fn from_frame(
+		frame: &ImageEnum,
+		config: &AnalysisConfig,
+		geometry: &Geometry,
+		reference: &[(f64, f64)],
+	) -> Option<Self> {
+		let profile = radial_difraction_analysis(&frame.to_f64(), config, nearest_neighbour);
+		let q = config
+			.bin_radii(frame.width())
+			.iter()
+			.map(|&radius| RadialUnit::Q.of_radius(geometry, radius))
+			.collect::<Option<Vec<_>>>()?;
+		Self::from_standard(&q, &profile, reference)
+	}
+
+	pub fn factor(&self) -> f64 {
+		self.factor
+	}
+
+	/// Scales `profile` in place to absolute units.
+	pub fn apply(&self, profile: &mut [f64]) {
+		for value in profile {
+			*value *= self.factor;
+		}
+	}
+}
+
+/// Value of the curve `points` at `x`, if within its range.
+fn interpolate(points: &[(f64, f64)], x: f64) -> Option<f64> {
+	let after = points.partition_point(|(px, _)| *px < x);
+	match (after.checked_sub(1).map(|i| points[i]), points.get(after)) {
+		(_, Some(&(px, py))) if px == x => Some(py),
+		(Some((x0, y0)), Some(&(x1, y1))) => Some(y0 + (y1 - y0) * (x - x0) / (x1 - x0)),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::AbsoluteCalibration;
+
+	#[test]
+	fn rejects_invalid_factors() {
+		assert!(AbsoluteCalibration::new(0.0).is_none());
+		assert!(AbsoluteCalibration::new(f64::INFINITY).is_none());
+		assert!(AbsoluteCalibration::new(f64::NAN).is_none());
+	}
+
+	#[test]
+	fn scales_to_reference() {
+		let reference = [(0.01, 40.0), (0.02, 30.0), (0.04, 10.0)];
+		let q = [0.005, 0.01, 0.015, 0.03, 0.05];
+		let mut measured = [1.0, 8.0, 7.0, 4.0, f64::NAN];
+		let calibration = AbsoluteCalibration::from_standard(&q, &measured, &reference).unwrap();
+		assert!((calibration.factor() - 5.0).abs() < 1e-12);
+		calibration.apply(&mut measured);
+		assert_eq!(measured[..4], [5.0, 40.0, 35.0, 20.0]);
+		assert!(AbsoluteCalibration::from_standard(&[1.0], &[1.0], &reference).is_none());
+	}
+}
@@ -0,0 +1,186 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::overlay::Canvas;
+use crate::geometry::{Geometry, ResolutionRing};
+
+/// A mark tied to pixel coordinates, measured from the centre of the first
+/// pixel.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+	feature = "serde",
+	derive(Serialize, Deserialize),
+	serde(tag = "kind", rename_all = "lowercase")
+)]
+pub enum Annotation {
+	Point {
+		x: f64,
+		y: f64,
+		colour: [u8; 4],
+	},
+	Circle {
+		x: f64,
+		y: f64,
+		radius: f64,
+		colour: [u8; 4],
+	},
+	/// Text with its top left corner at the position
+	Text {
+		x: f64,
+		y: f64,
+		text: String,
+		colour: [u8; 4],
+	},
+}
+
+impl Annotation {
+	pub fn beam_centre(geometry: &Geometry, colour: [u8; 4]) -> Self {
+		let (x, y) = geometry.beam_centre;
+		Self::Point { x, y, colour }
+	}
+
+	/// The circle of a resolution ring and a label just outside it.
+	pub fn resolution_ring(ring: &ResolutionRing, colour: [u8; 4]) -> [Self; 2] {
+		let (x, y) = ring.centre;
+		[
+			Self::Circle { x, y, radius: ring.radius, colour },
+			Self::Text { x: x + 2.0, y: y - ring.radius - 7.0, text: format!("{} A", ring.resolution), colour },
+		]
+	}
+}
+
+/// Half the length of the cross drawn for a point.
+const POINT_ARM: isize = 3;
+/// Horizontal distance between the starts of two glyphs.
+const GLYPH_ADVANCE: isize = 4;
+
+/// Renders `annotations` into an RGBA buffer of `width` × `height` pixels, in
+/// order, leaving untouched pixels as is.
+pub fn render_annotations(width: usize, height: usize, annotations: &[Annotation], buffer: &mut [u8]) {
+	let mut canvas = Canvas { width, height, buffer };
+	for annotation in annotations {
+		match annotation {
+			&Annotation::Point { x, y, colour } => {
+				let (x, y) = (x.round() as isize, y.round() as isize);
+				for offset in -POINT_ARM..=POINT_ARM {
+					canvas.set(x + offset, y, colour);
+					canvas.set(x, y + offset, colour);
+				}
+			}
+			&Annotation::Circle { x, y, radius, colour } => {
+				let steps = (std::f64::consts::TAU * radius).ceil().max(4.0) as usize;
+				for step in 0..steps {
+					let angle = std::f64::consts::TAU * step as f64 / steps as f64;
+					let px = x + radius * angle.cos();
+					let py = y + radius * angle.sin();
+					canvas.set(px.round() as isize, py.round() as isize, colour);
+				}
+			}
+			Annotation::Text { x, y, text, colour } => {
+				let (x, y) = (x.round() as isize, y.round() as isize);
+				for (i, c) in text.chars().enumerate() {
+					let left = x + i as isize * GLYPH_ADVANCE;
+					for (row, bits) in glyph(c).iter().enumerate() {
+						for column in 0..3 {
+							if bits & (0b100 >> column) != 0 {
+								canvas.set(left + column, y + row as isize, *colour);
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+}
+
+/// Rows of a 3 × 5 bitmap glyph, most significant bit on the left. Letters
+/// are drawn in upper case and unknown characters as a question mark.
+fn glyph(c: char) -> [u8; 5] {
+	match c.to_ascii_uppercase() {
+		'0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+		'1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+		'2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+		'3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+		'4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+		'5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+		'6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+		'7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+		'8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+		'9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+		'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+		'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+		'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+		'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+		'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+		'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+		'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+		'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+		'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+		'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+		'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+		'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+		'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+		'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+		'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+		'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+		'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+		'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+		'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+		'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+		'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+		'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+		'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+		'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+		'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+		'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+		'.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+		'-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+		':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+		'/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+		' ' => [0; 5],
+		_ => [0b111, 0b001, 0b010, 0b000, 0b010],
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{render_annotations, Annotation};
+
+	const RED: [u8; 4] = [255, 0, 0, 255];
+
+	fn pixel(buffer: &[u8], width: usize, x: usize, y: usize) -> &[u8] {
+		&buffer[(y * width + x) * 4..][..4]
+	}
+
+	#[test]
+	fn renders_shapes() {
+		let (width, height) = (20, 20);
+		let mut buffer = vec![0; width * height * 4];
+		let annotations = [
+			Annotation::Point { x: 2.0, y: 2.0, colour: RED },
+			Annotation::Circle { x: 10.0, y: 10.0, radius: 5.0, colour: RED },
+			Annotation::Text { x: 0.0, y: 14.0, text: "1".into(), colour: RED },
+		];
+
+		render_annotations(width, height, &annotations, &mut buffer);
+
+		assert_eq!(pixel(&buffer, width, 5, 2), RED);
+		assert_eq!(pixel(&buffer, width, 2, 0), RED);
+		assert_eq!(pixel(&buffer, width, 15, 10), RED);
+		assert_eq!(pixel(&buffer, width, 10, 10), [0; 4]);
+		assert_eq!(pixel(&buffer, width, 1, 14), RED);
+		assert_eq!(pixel(&buffer, width, 0, 14), [0; 4]);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test]
+	fn round_trips_json() {
+		let annotations = vec![
+			Annotation::Circle { x: 1.0, y: 2.0, radius: 3.0, colour: RED },
+			Annotation::Text { x: 0.0, y: 0.0, text: "2.0 A".into(), colour: RED },
+		];
+		let json = serde_json::to_string(&annotations).unwrap();
+		assert!(json.starts_with(r#"[{"kind":"circle","x":1.0"#));
+		assert_eq!(serde_json::from_str::<Vec<Annotation>>(&json).unwrap(), annotations);
+	}
+}
@@ -0,0 +1,153 @@
+use num_traits::AsPrimitive;
+
+use crate::{
+	geometry::{resolution_to_q, Geometry},
+	image::{mask::Mask, Image},
+};
+
+/// Statistics of the unmasked pixels within a ring.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnulusStats {
+	pub count: usize,
+	pub mean: f64,
+	pub median: f64,
+	pub std_dev: f64,
+}
+
+/// Computes statistics of the pixels whose distance from `center`, in pixel
+/// coordinates, is between `r_inner` and `r_outer` inclusive. Masked and NaN
+/// pixels are skipped. Returns `None` if no pixel falls within the ring.
+pub fn annulus_stats<P: AsPrimitive<f64>>(
+	image: &Image<P>,
+	center: (f64, f64),
+	r_inner: f64,
+	r_outer: f64,
+	mask: Option<&Mask>,
+) -> Option<AnnulusStats> {
+	let mut values = annulus_values(image, center, r_inner, r_outer, mask);
+	if values.is_empty() {
+		return None;
+	}
+	values.sort_unstable_by(f64::total_cmp);
+
+	let count = values.len();
+	let mean = values.iter().sum::<f64>() / count as f64;
+	let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / count as f64;
+	let median = if count.is_multiple_of(2) {
+		(values[count / 2 - 1] + values[count / 2]) / 2.0
+	} else {
+		values[count / 2]
+	};
+	Some(AnnulusStats { count, mean, median, std_dev: variance.sqrt() })
+}
+
+/// Like [`annulus_stats`] for the ring around the beam centre between `q_min`
+/// and `q_max` in inverse ångström. Returns `None` if `geometry` has no
+/// wavelength.
+pub fn q_range_stats<P: AsPrimitive<f64>>(
+	image: &Image<P>,
+	geometry: &Geometry,
+	q_min: f64,
+	q_max: f64,
+	mask: Option<&Mask>,
+) -> Option<AnnulusStats> {
+	let inner = geometry.q_to_radius(q_min)?;
+	let outer = geometry.q_to_radius(q_max).unwrap_or(f64::INFINITY);
+	annulus_stats(image, geometry.beam_centre, inner, outer, mask)
+}
+
+/// Like [`q_range_stats`] with limits as resolutions in ångström, `low` being
+/// the larger one.
+pub fn resolution_stats<P: AsPrimitive<f64>>(
+	image: &Image<P>,
+	geometry: &Geometry,
+	low: f64,
+	high: f64,
+	mask: Option<&Mask>,
+) -> Option<AnnulusStats> {
+	q_range_stats(image, geometry, resolution_to_q(low), resolution_to_q(high), mask)
+}
+
+fn annulus_values<P: AsPrimitive<f64>>(
+	image: &Image<P>,
+	(cx, cy): (f64, f64),
+	r_inner: f64,
+	r_outer: f64,
+	mask: Option<&Mask>,
+) -> Vec<f64> {
+	let mut values = Vec::new();
+	if r_outer.is_nan() || r_outer < 0.0 {
+		return values;
+	}
+	// Float to integer casts saturate, so negative bounds become zero.
+	let x_range = (cx - r_outer).ceil() as usize..((cx + r_outer).floor() + 1.0).min(image.width as f64) as usize;
+	let y_range = (cy - r_outer).ceil() as usize..((cy + r_outer).floor() + 1.0).min(image.height as f64) as usize;
+	for (y, row) in image.rows().enumerate().take(y_range.end).skip(y_range.start) {
+		for x in x_range.clone() {
+			let dx = x as f64 - cx;
+			let dy = y as f64 - cy;
+			let r2 = dx * dx + dy * dy;
+			if r2 < r_inner * r_inner || r_outer * r_outer < r2 || mask.is_some_and(|m| m.is_masked(x, y)) {
+				continue;
+			}
+			let value = row[x].as_();
+			if !value.is_nan() {
+				values.push(value);
+			}
+		}
+	}
+	values
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{annulus_stats, q_range_stats};
+	use crate::{
+		geometry::Geometry,
+		image::{
+			mask::{Mask, MaskEdit},
+			Image,
+		},
+	};
+
+	#[test]
+	fn ring_statistics() {
+		// Distance from (2, 2) decides the value: 0 in the centre, 1 on the
+		// four direct neighbours, 2 further out.
+		let pixels: Vec<u16> = (0..25i32)
+			.map(|i| match (i % 5 - 2).pow(2) + (i / 5 - 2).pow(2) {
+				0 => 0,
+				1 => 1,
+				_ => 2,
+			})
+			.collect();
+		let image = Image::new(5, 5, pixels.into()).unwrap();
+
+		let stats = annulus_stats(&image, (2.0, 2.0), 0.5, 1.0, None).unwrap();
+		assert_eq!(stats.count, 4);
+		assert_eq!(stats.mean, 1.0);
+		assert_eq!(stats.std_dev, 0.0);
+
+		let stats = annulus_stats(&image, (2.0, 2.0), 0.0, 1.5, None).unwrap();
+		assert_eq!(stats.count, 9);
+		assert_eq!(stats.median, 1.0);
+
+		let mut mask = Mask::empty(5, 5).unwrap();
+		MaskEdit::Rectangle { x: 0, y: 0, width: 5, height: 2, masked: true }.apply(&mut mask);
+		let stats = annulus_stats(&image, (2.0, 2.0), 0.5, 1.0, Some(&mask)).unwrap();
+		assert_eq!(stats.count, 3);
+
+		assert!(annulus_stats(&image, (20.0, 20.0), 0.0, 1.0, None).is_none());
+	}
+
+	#[test]
+	fn q_range_statistics() {
+		let geometry =
+			Geometry { beam_centre: (2.0, 2.0), distance: 1.0, pixel_size: (1.0, 1.0), wavelength: Some(1e-10) };
+		let image = Image::new(5, 5, vec![1u8; 25].into()).unwrap();
+		let q = geometry.q((3.0, 2.0)).unwrap();
+		let stats = q_range_stats(&image, &geometry, 0.0, q, None).unwrap();
+		assert_eq!(stats.count, 5);
+		assert!(q_range_stats(&image, &Geometry { wavelength: None, ..geometry }, 0.0, q, None).is_none());
+	}
+}
@@ -1,31 +1,80 @@
 use std::ops::{AddAssign, Div};
 
-use num::{BigInt, One, Zero};
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+use num_traits::{One, ToPrimitive, Zero};
 
+/// Sum type for 64-bit integers, wide enough that averaging cannot overflow in
+/// practice.
+#[cfg(feature = "bigint")]
+type IntegerSum = BigInt;
+#[cfg(not(feature = "bigint"))]
+type IntegerSum = i128;
+
+/// Mean of a series of samples, each counted either once through [`add`] or
+/// with a fractional weight through [`add_weighted`].
+///
+/// Without weighted samples the mean is computed exactly in [`BigNum::BigType`].
+/// Once a weighted sample with a positive weight has been added, the mean is
+/// `Σ wᵢvᵢ / Σ wᵢ` over all samples, where plain samples have weight 1,
+/// computed in `f64`. Integer means are truncated towards zero in both cases
+/// and saturate at the bounds of the pixel type. The integer mean of no
+/// samples is zero, the floating point one NaN.
+///
+/// [`add`]: Average::add
+/// [`add_weighted`]: Average::add_weighted
 pub struct Average<P: BigNum> {
 	sum: <P as BigNum>::BigType,
 	count: <P as BigNum>::BigType,
+	weighted_sum: f64,
+	total_weight: f64,
 }
 
 impl<P: BigNum> Default for Average<P> {
 	fn default() -> Self {
-		Self { sum: <P as BigNum>::BigType::zero(), count: <P as BigNum>::BigType::zero() }
+		Self {
+			sum: <P as BigNum>::BigType::zero(),
+			count: <P as BigNum>::BigType::zero(),
+			weighted_sum: 0.0,
+			total_weight: 0.0,
+		}
 	}
 }
 
 impl<P: BigNum> Average<P> {
 	pub fn add(&mut self, value: P) {
-		self.sum += value;
+		self.sum += value.widen();
 		self.count += <P as BigNum>::BigType::one();
 	}
 
+	/// Adds `value` counted `weight` times. Weights that are not finite and
+	/// positive are ignored.
+	pub fn add_weighted(&mut self, value: P, weight: f64) {
+		if !(weight.is_finite() && 0.0 < weight) {
+			return;
+		}
+		let value = value.widen().to_f64().unwrap_or(f64::NAN);
+		self.weighted_sum += value * weight;
+		self.total_weight += weight;
+	}
+
 	pub fn average(&self) -> P {
-		<P as BigNum>::div(&self.sum, &self.count)
+		if self.total_weight == 0.0 {
+			return <P as BigNum>::div(&self.sum, &self.count);
+		}
+		let sum = self.sum.to_f64().unwrap_or(f64::NAN) + self.weighted_sum;
+		let weight = self.count.to_f64().unwrap_or(f64::NAN) + self.total_weight;
+		P::from_mean(sum / weight)
 	}
 }
 
 pub trait BigNum: Sized {
-	type BigType: AddAssign<Self> + AddAssign<Self::BigType> + TryInto<Self> + Div<Output = Self::BigType> + Zero + One;
+	type BigType: AddAssign<Self::BigType> + TryInto<Self> + Div<Output = Self::BigType> + Zero + One + ToPrimitive;
+
+	fn widen(self) -> Self::BigType;
+
+	/// Converts a mean computed in `f64` to the pixel type.
+	fn from_mean(mean: f64) -> Self;
 
 	fn div(a: &Self::BigType, b: &Self::BigType) -> Self;
 }
@@ -33,22 +82,65 @@ pub trait BigNum: Sized {
 macro_rules! integer_big_num {
 	($($type:ty),*) => {
 		$(impl BigNum for $type {
-			type BigType = BigInt;
+			type BigType = IntegerSum;
+
+			fn widen(self) -> IntegerSum {
+				#[cfg(feature = "bigint")]
+				return BigInt::from(self);
+				#[cfg(not(feature = "bigint"))]
+				return self as i128;
+			}
+
+			fn from_mean(mean: f64) -> $type {
+				mean.trunc() as $type
+			}
+
+			fn div(a: &IntegerSum, b: &IntegerSum) -> $type {
+				num_traits::CheckedDiv::checked_div(a, b).and_then(|r| r.try_into().ok()).unwrap_or(0)
+			}
+		})*
+	};
+}
+
+integer_big_num!(u64, i64, usize, isize);
+
+/// Sums of up to 32-bit integers fit in an `i128` for any realistic count, so
+/// they avoid the allocations of arbitrary precision.
+macro_rules! small_integer_big_num {
+	($($type:ty),*) => {
+		$(impl BigNum for $type {
+			type BigType = i128;
+
+			fn widen(self) -> i128 {
+				i128::from(self)
+			}
+
+			fn from_mean(mean: f64) -> $type {
+				mean.trunc() as $type
+			}
 
-			fn div(a: &BigInt, b: &BigInt) -> $type {
-				a.checked_div(b).and_then(|r| r.try_into().ok()).unwrap()
+			fn div(a: &i128, b: &i128) -> $type {
+				a.checked_div(*b).and_then(|r| r.try_into().ok()).unwrap_or(0)
 			}
 		})*
 	};
 }
 
-integer_big_num!(u8, i8, u16, i16, u32, i32, u64, i64, usize, isize);
+small_integer_big_num!(u8, i8, u16, i16, u32, i32);
 
 macro_rules! float_big_num {
 	($($type:ty),*) => {
 		$(impl BigNum for $type {
 			type BigType = $type;
 
+			fn widen(self) -> $type {
+				self
+			}
+
+			fn from_mean(mean: f64) -> $type {
+				mean as $type
+			}
+
 			fn div(a: &$type, b: &$type) -> $type {
 				a / b
 			}
@@ -140,6 +232,48 @@ mod tests {
 		assert_eq!(average.average(), 123);
 	}
 
+	#[test]
+	fn empty() {
+		assert_eq!(Average::<u8>::default().average(), 0);
+		assert!(Average::<f32>::default().average().is_nan());
+	}
+
+	#[test]
+	fn big_u64() {
+		let mut average: Average<u64> = Average::default();
+		average.add(u64::MAX);
+		average.add(u64::MAX - 2);
+		assert_eq!(average.average(), u64::MAX - 1);
+	}
+
+	#[test]
+	fn weighted_integers() {
+		let mut average: Average<u16> = Average::default();
+		average.add(10);
+		average.add_weighted(20, 0.5);
+		average.add_weighted(30, 0.5);
+		assert_eq!(average.average(), 17); // 35 / 2
+		average.add_weighted(1000, 0.0);
+		average.add_weighted(1000, f64::NAN);
+		assert_eq!(average.average(), 17);
+	}
+
+	#[test]
+	fn weighted_floats() {
+		let mut average: Average<f64> = Average::default();
+		average.add_weighted(1.0, 0.25);
+		average.add_weighted(2.0, 0.75);
+		assert_eq!(average.average(), 1.75);
+	}
+
+	#[test]
+	fn weighted_truncates_towards_zero() {
+		let mut average: Average<i32> = Average::default();
+		average.add_weighted(-3, 1.0);
+		average.add_weighted(-4, 1.0);
+		assert_eq!(average.average(), -3);
+	}
+
 	#[test]
 	fn small_i32() {
 		let mut average: Average<i32> = Average::default();
@@ -0,0 +1,173 @@
+use std::{
+	f64,
+	io::{Error, ErrorKind, Read, Result, Write},
+};
+
+use super::{
+	allocate_slice, average::BigNum, compute_average_slice, sampler_methods::polar_to_cartesian, AnalysisConfig,
+};
+use crate::image::{Image, ImageCoordinate};
+
+const MAGIC: &[u8; 6] = b"CBFLUT";
+const VERSION: u8 = 1;
+
+/// Pixels sampled by nearest neighbour radial analysis of images of a fixed
+/// size, computed once so that analysing every further image is a table
+/// lookup. The table can be stored with [`AnalysisContext::write_to`] and
+/// reused across runs with the same geometry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisContext {
+	width: usize,
+	height: usize,
+	/// Pixel indices sampled for each bin, one per sample
+	bins: Box<[Box<[usize]>]>,
+}
+
+impl AnalysisContext {
+	pub fn new(config: &AnalysisConfig, width: usize, height: usize) -> Self {
+		let mut bins = vec![Vec::new(); config.theta_sample_count];
+
+		for (j, bin) in bins.iter_mut().enumerate() {
+			let r = config.sample_radius(j as f64);
+			let angles = config.angular_sample_count(width, r);
+			let rot = f64::consts::PI / (angles as f64);
+			for i in 0..angles {
+				let angle = (i as f64) * rot;
+				if config
+					.roi
+					.is_some_and(|roi| !roi.contains_sample(width, height, angle, r))
+					|| !config.contains_radius(width, r)
+				{
+					continue;
+				}
+				let (x, y) = polar_to_cartesian(width as f64, angle, r);
+				if let Some(index) = (x.round() as isize, y.round() as isize).index(width, height) {
+					bin.push(index);
+				}
+			}
+		}
+
+		Self { width, height, bins: bins.into_iter().map(Vec::into_boxed_slice).collect() }
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	/// Same result as [`radial_difraction_analysis`] with
+	/// [`nearest_neighbour`] sampling. Returns `None` if the image does not
+	/// have the dimensions the context was prepared for.
+	///
+	/// [`radial_difraction_analysis`]: super::radial_difraction_analysis
+	/// [`nearest_neighbour`]: super::sampler_methods::nearest_neighbour
+	pub fn analyze<P: BigNum + Copy>(&self, image: &Image<P>) -> Option<Box<[P]>> {
+		if image.width != self.width || image.height != self.height {
+			return None;
+		}
+		let mut samples = allocate_slice(self.bins.len());
+		for (average, bin) in samples.iter_mut().zip(self.bins.iter()) {
+			for &index in bin.iter() {
+				average.add(image.pixels()[index]);
+			}
+		}
+		Some(compute_average_slice(samples))
+	}
+
+	/// Writes the table in a little endian binary format.
+	pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+		writer.write_all(MAGIC)?;
+		writer.write_all(&[VERSION])?;
+		for value in [self.width, self.height, self.bins.len()] {
+			writer.write_all(&(value as u64).to_le_bytes())?;
+		}
+		for bin in self.bins.iter() {
+			writer.write_all(&(bin.len() as u64).to_le_bytes())?;
+			for &index in bin.iter() {
+				writer.write_all(&(index as u64).to_le_bytes())?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Reads a table written by [`AnalysisContext::write_to`], checking that
+	/// every index lies within the image.
+	pub fn read_from(mut reader: impl Read) -> Result<Self> {
+		let mut magic = [0; 7];
+		reader.read_exact(&mut magic)?;
+		if magic[..6] != MAGIC[..] || magic[6] != VERSION {
+			return Err(Error::new(ErrorKind::InvalidData, "not an analysis context"));
+		}
+		let width = read_usize(&mut reader)?;
+		let height = read_usize(&mut reader)?;
+		let pixel_count = width
+			.checked_mul(height)
+			.ok_or_else(|| Error::new(ErrorKind::InvalidData, "dimensions overflow"))?;
+		let bin_count = read_usize(&mut reader)?;
+		let mut bins = Vec::new();
+		for _ in 0..bin_count {
+			let len = read_usize(&mut reader)?;
+			// Grown as read, so a corrupt length fails at the end of the data
+			// rather than allocating it up front.
+			let mut bin = Vec::new();
+			for _ in 0..len {
+				let index = read_usize(&mut reader)?;
+				if pixel_count <= index {
+					return Err(Error::new(ErrorKind::InvalidData, "index outside of the image"));
+				}
+				bin.push(index);
+			}
+			bins.push(bin.into_boxed_slice());
+		}
+		Ok(Self { width, height, bins: bins.into_boxed_slice() })
+	}
+}
+
+fn read_usize(reader: &mut impl Read) -> Result<usize> {
+	let mut bytes = [0; 8];
+	reader.read_exact(&mut bytes)?;
+	usize::try_from(u64::from_le_bytes(bytes)).map_err(|_| Error::new(ErrorKind::InvalidData, "value too large"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::AnalysisContext;
+	use crate::{
+		analysis::{radial_difraction_analysis, sampler_methods::nearest_neighbour, AnalysisConfig, Roi},
+		image::Image,
+	};
+
+	#[test]
+	fn matches_direct_analysis() {
+		let pixels: Vec<u32> = (0..24 * 16).map(|i| (i * 7919) % 1000).collect();
+		let image = Image::new(24, 16, pixels.into()).unwrap();
+		let config = AnalysisConfig::new(10, 50, 1.2)
+			.unwrap()
+			.with_roi(Roi { x: 2, y: 0, width: 20, height: 12 });
+
+		let context = AnalysisContext::new(&config, 24, 16);
+		assert_eq!(
+			context.analyze(&image),
+			Some(radial_difraction_analysis(&image, &config, nearest_neighbour))
+		);
+		assert_eq!(
+			context.analyze(&Image::new(16, 24, vec![0u32; 24 * 16].into()).unwrap()),
+			None
+		);
+	}
+
+	#[test]
+	fn round_trips() {
+		let context = AnalysisContext::new(&AnalysisConfig::new(8, 20, 1.0).unwrap(), 16, 16);
+		let mut bytes = Vec::new();
+		context.write_to(&mut bytes).unwrap();
+		assert_eq!(AnalysisContext::read_from(&bytes[..]).unwrap(), context);
+
+		assert!(AnalysisContext::read_from(&bytes[..bytes.len() - 1]).is_err());
+		bytes[0] = b'X';
+		assert!(AnalysisContext::read_from(&bytes[..]).is_err());
+	}
+}
@@ -0,0 +1,108 @@
+use num_traits::AsPrimitive;
+
+use crate::{image::Image, metadata::miniheader::MiniHeader};
+
+/// Non-paralyzable dead-time (pile-up) correction of photon counts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CountRateCorrection {
+	/// Detector dead time in seconds
+	tau: f64,
+	/// Exposure time of the frame in seconds
+	exposure_time: f64,
+}
+
+impl CountRateCorrection {
+	pub fn new(tau: f64, exposure_time: f64) -> Option<Self> {
+		if !(tau >= 0.0 && exposure_time > 0.0 && tau.is_finite() && exposure_time.is_finite()) {
+			return None;
+		}
+		Some(Self { tau, exposure_time })
+	}
+
+	/// Correction for the dead time and exposure time of a Pilatus style
+	/// mini-header, if it has both.
+	pub fn from_miniheader(header: &MiniHeader) -> Option<Self> {
+		Self::new(header.tau?.seconds(), header.exposure_time?.seconds())
+	}
+
+	pub fn tau(&self) -> f64 {
+		self.tau
+	}
+
+	pub fn exposure_time(&self) -> f64 {
+		self.exposure_time
+	}
+
+	/// Corrects the counts of a single pixel. Non-positive values, used by
+	/// detectors to mark gaps and bad pixels, are returned unchanged. Returns
+	/// `None` if the measured rate saturates the detector, so that the true
+	/// count cannot be recovered.
+	pub fn correct(&self, counts: f64) -> Option<f64> {
+		if counts <= 0.0 {
+			return Some(counts);
+		}
+		let measured_rate = counts / self.exposure_time;
+		let live_fraction = 1.0 - measured_rate * self.tau;
+		(0.0 < live_fraction).then(|| counts / live_fraction)
+	}
+}
+
+/// Corrects every pixel of `image`, setting saturated pixels to NaN.
+pub fn correct_count_rate<P: AsPrimitive<f64>>(image: &Image<P>, correction: &CountRateCorrection) -> Image<f64> {
+	image.map(|p| correction.correct(p.as_()).unwrap_or(f64::NAN))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{correct_count_rate, CountRateCorrection};
+	use crate::{image::Image, metadata::miniheader::parse_miniheader};
+
+	#[test]
+	fn rejects_invalid_parameters() {
+		assert!(CountRateCorrection::new(-1.0, 1.0).is_none());
+		assert!(CountRateCorrection::new(1e-7, 0.0).is_none());
+		assert!(CountRateCorrection::new(f64::NAN, 1.0).is_none());
+	}
+
+	#[test]
+	fn zero_dead_time_is_identity() {
+		let correction = CountRateCorrection::new(0.0, 1.0).unwrap();
+		assert_eq!(correction.correct(1000.0), Some(1000.0));
+	}
+
+	#[test]
+	fn corrects_high_rates() {
+		let correction = CountRateCorrection::new(1e-6, 1.0).unwrap();
+		// 100 000 counts/s with 1 µs dead time loses 10% of the time.
+		let corrected = correction.correct(100_000.0).unwrap();
+		assert!((corrected - 100_000.0 / 0.9).abs() < 1e-6, "corrected {corrected}");
+	}
+
+	#[test]
+	fn leaves_flagged_pixels_unchanged() {
+		let correction = CountRateCorrection::new(1e-6, 1.0).unwrap();
+		assert_eq!(correction.correct(-1.0), Some(-1.0));
+		assert_eq!(correction.correct(-2.0), Some(-2.0));
+	}
+
+	#[test]
+	fn saturated_pixels_have_no_value() {
+		let correction = CountRateCorrection::new(1e-3, 1.0).unwrap();
+		assert_eq!(correction.correct(1000.0), None);
+		let image = Image::new(2, 1, vec![1u32, 1000].into()).unwrap();
+		let corrected = correct_count_rate(&image, &correction);
+		assert!(corrected.pixels()[1].is_nan());
+	}
+
+	#[test]
+	fn from_miniheader() {
+		let header = parse_miniheader("# Exposure_time 0.5 s\r\n# Tau = 200e-09 s\r\n");
+		let correction = CountRateCorrection::from_miniheader(&header).expect("dead time and exposure");
+		assert_eq!(correction.exposure_time(), 0.5);
+		assert!((correction.tau() - 200e-9).abs() < 1e-18);
+		assert_eq!(
+			CountRateCorrection::from_miniheader(&parse_miniheader("# Tau = 1 s")),
+			None
+		);
+	}
+}
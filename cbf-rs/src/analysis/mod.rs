@@ -1,34 +1,204 @@
-mod average;
+pub mod absolute;
+pub mod annotations;
+mod annulus;
+pub mod average;
+pub mod context;
+pub mod count_rate;
+pub mod modules;
+pub mod overlay;
+pub mod peak_list;
+mod preset;
+pub mod profile_cif;
 pub mod sampler_methods;
+pub mod session;
+mod split;
+pub mod spots;
+pub mod stream;
+mod strip;
 
 use std::f64;
 
-use crate::image::Image;
+use num_traits::AsPrimitive;
 
-use self::average::{Average, BigNum};
+use crate::{
+	geometry::{resolution_to_q, Geometry},
+	image::{Image, ImageEnum},
+};
+
+pub use self::{
+	annulus::{annulus_stats, q_range_stats, resolution_stats, AnnulusStats},
+	preset::{Preset, RadialUnit},
+	split::pixel_split_profile,
+	stream::{analyze_stream, analyze_stream_with, Analysed, AnalysisStream},
+	strip::{average_profiles, normalize_profile, strip_frame_profile, strip_profile},
+};
+use self::{
+	average::{Average, BigNum},
+	count_rate::CountRateCorrection,
+	sampler_methods::{diagnosed, polar_to_cartesian},
+};
+
+/// Matches on the pixel type of an image to compute its profile in `$real`.
+macro_rules! converted_profile {
+	($image:expr, $config:expr, $real:ty) => {
+		match $image {
+			ImageEnum::U8(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::I8(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::U16(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::I16(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::U32(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::I32(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::F32(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::U64(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::I64(image) => converted_profile(image, $config, |p| p as $real),
+			ImageEnum::F64(image) => converted_profile(image, $config, |p| p as $real),
+		}
+	};
+}
+
+/// Radial profile of an image of any pixel type, computed in `f64` with
+/// nearest neighbour sampling or pixel splitting, as chosen by the
+/// [`Integration`] of `config`. Pixels are converted, and corrected for the
+/// count rate if `config` asks for it, as they are sampled, so the image is not
+/// copied. Pixels saturated beyond correction are skipped like masked ones.
+pub fn radial_profile(image: &ImageEnum, config: &AnalysisConfig) -> Box<[f64]> {
+	converted_profile!(image, config, f64)
+}
+
+/// [`radial_profile`] computed in `f32`, halving the memory traffic of the
+/// averages at the cost of precision.
+pub fn radial_profile_f32(image: &ImageEnum, config: &AnalysisConfig) -> Box<[f32]> {
+	converted_profile!(image, config, f32)
+}
+
+/// Profile of `image` averaged in the type `convert` returns.
+fn converted_profile<Q: Copy + AsPrimitive<f64>, P: BigNum + Copy>(
+	image: &Image<Q>,
+	config: &AnalysisConfig,
+	convert: impl Fn(Q) -> P,
+) -> Box<[P]> {
+	let convert = |pixel: Q| match &config.count_rate {
+		Some(correction) => correction.correct(pixel.as_()).map(P::from_mean),
+		None => Some(convert(pixel)),
+	};
+	match config.integration {
+		Integration::PointSampling => {
+			let sampler = |image: &Image<Q>, angle, radius| {
+				let value = sampler_methods::nearest_neighbour(image, angle, radius).and_then(&convert);
+				Sample { valid: value.is_some() as usize, total: 1, value }
+			};
+			radial_difraction_analysis_with_coverage(image, config, sampler).values
+		}
+		Integration::PixelSplitting => split::converted_split_profile(image, config, None, convert),
+	}
+}
 
 pub fn radial_difraction_analysis<P: BigNum>(
 	image: &Image<P>,
 	config: &AnalysisConfig,
-	mut sampler_method: impl FnMut(&Image<P>, f64, f64) -> Option<P>,
+	sampler_method: impl FnMut(&Image<P>, f64, f64) -> Option<P>,
 ) -> Box<[P]> {
+	radial_difraction_analysis_with_coverage(image, config, diagnosed(sampler_method)).values
+}
+
+/// Result of a sampler in diagnostic mode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample<P> {
+	pub value: Option<P>,
+	/// Source pixels that contributed to `value`
+	pub valid: usize,
+	/// Source pixels looked at, including masked and out of bounds ones
+	pub total: usize,
+}
+
+/// Radial profile with the fraction of valid source pixels behind each bin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile<P> {
+	pub values: Box<[P]>,
+	/// Valid source pixels over all source pixels looked at, per bin
+	pub coverage: Box<[f64]>,
+	/// Boundaries of the bins in pixels from the image centre, one more than
+	/// there are bins
+	pub edges: Box<[f64]>,
+}
+
+pub fn radial_difraction_analysis_with_coverage<Q, P: BigNum>(
+	image: &Image<Q>,
+	config: &AnalysisConfig,
+	mut sampler_method: impl FnMut(&Image<Q>, f64, f64) -> Sample<P>,
+) -> Profile<P> {
 	let mut samples = allocate_slice(config.theta_sample_count);
+	let mut counts = vec![(0, 0); config.theta_sample_count];
 
-	let rot = f64::consts::PI / (config.intensity_sample_count as f64);
-	let rad = config.radius / (config.theta_sample_count as f64);
-	for i in 0..config.intensity_sample_count {
-		let angle = (i as f64) * rot;
-		for j in 0..config.theta_sample_count {
-			let r = (j as f64) * rad;
-			if let Some(value) = sampler_method(image, angle, r) {
+	for j in 0..config.theta_sample_count {
+		let r = config.sample_radius(j as f64);
+		let angles = config.angular_sample_count(image.width, r);
+		let rot = f64::consts::PI / (angles as f64);
+		for i in 0..angles {
+			let angle = (i as f64) * rot;
+			if config
+				.roi
+				.is_some_and(|roi| !roi.contains_sample(image.width, image.height, angle, r))
+				|| !config.contains_radius(image.width, r)
+			{
+				continue;
+			}
+			let sample = sampler_method(image, angle, r);
+			if let Some(value) = sample.value {
 				samples[j].add(value);
 			}
+			counts[j].0 += sample.valid;
+			counts[j].1 += sample.total;
 		}
 	}
 
-	compute_average_slice(samples)
+	let coverage = counts
+		.iter()
+		.map(|&(valid, total)| if total == 0 { 0.0 } else { valid as f64 / total as f64 })
+		.collect();
+	Profile { values: compute_average_slice(samples), coverage, edges: config.bin_edges(image.width) }
+}
+
+/// How the bins of a profile are spread over the sampled radius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BinSpacing {
+	/// Equal steps in radius, starting at the centre
+	#[default]
+	Linear,
+	/// Equal steps in squared radius, starting at the centre, giving bins of
+	/// equal area
+	Sqrt,
+	/// Equal ratios between consecutive radii, from one linear step to the
+	/// full radius, as is standard for small angle scattering
+	Log,
+}
+
+/// How many angles each bin of a profile is sampled at.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AngularSampling {
+	/// The configured intensity sample count for every bin
+	#[default]
+	Fixed,
+	/// A number proportional to the circumference of the bin, so that
+	/// samples are evenly spread over the image
+	Adaptive {
+		/// Samples per pixel of arc length
+		samples_per_pixel: f64,
+	},
+}
+
+/// How pixels are assigned to the bins of [`radial_profile`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integration {
+	/// Values sampled at points along each bin's radius
+	#[default]
+	PointSampling,
+	/// Every pixel spread over the bins its area overlaps, see
+	/// [`pixel_split_profile`]
+	PixelSplitting,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct AnalysisConfig {
 	/// Points along radius
 	theta_sample_count: usize,
@@ -36,6 +206,41 @@ pub struct AnalysisConfig {
 	intensity_sample_count: usize,
 	/// Size of sample area
 	radius: f64,
+	/// Region outside of which samples are skipped
+	roi: Option<Roi>,
+	/// Inclusive range of distances in pixels outside of which samples are
+	/// skipped
+	radial_range: Option<(f64, f64)>,
+	bin_spacing: BinSpacing,
+	angular_sampling: AngularSampling,
+	integration: Integration,
+	/// Correction applied to every pixel before it is integrated
+	count_rate: Option<CountRateCorrection>,
+}
+
+/// Rectangular region of interest in pixel coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Roi {
+	pub x: usize,
+	pub y: usize,
+	pub width: usize,
+	pub height: usize,
+}
+
+impl Roi {
+	/// Whether the pixel at `x`, `y` lies within the region.
+	pub(crate) fn contains(&self, x: usize, y: usize) -> bool {
+		x.checked_sub(self.x).is_some_and(|dx| dx < self.width)
+			&& y.checked_sub(self.y).is_some_and(|dy| dy < self.height)
+	}
+
+	/// Whether the pixel nearest to a sample lies within the region.
+	fn contains_sample(&self, image_width: usize, image_height: usize, angle: f64, radius: f64) -> bool {
+		let (x, y) = polar_to_cartesian(image_width as f64, angle, radius);
+		let x = x.round() + (image_width / 2) as f64;
+		let y = y.round() + (image_height / 2) as f64;
+		0.0 <= x && 0.0 <= y && self.contains(x as usize, y as usize)
+	}
 }
 
 impl AnalysisConfig {
@@ -43,7 +248,133 @@ impl AnalysisConfig {
 		if radius < 0.0 || f64::consts::SQRT_2 < radius {
 			return None;
 		}
-		Some(Self { theta_sample_count, intensity_sample_count, radius })
+		Some(Self {
+			theta_sample_count,
+			intensity_sample_count,
+			radius,
+			roi: None,
+			radial_range: None,
+			bin_spacing: BinSpacing::default(),
+			angular_sampling: AngularSampling::default(),
+			integration: Integration::default(),
+			count_rate: None,
+		})
+	}
+
+	/// Restricts the analysis to samples within `roi`.
+	pub fn with_roi(self, roi: Roi) -> Self {
+		Self { roi: Some(roi), ..self }
+	}
+
+	pub fn roi(&self) -> Option<Roi> {
+		self.roi
+	}
+
+	pub fn with_bin_spacing(self, bin_spacing: BinSpacing) -> Self {
+		Self { bin_spacing, ..self }
+	}
+
+	pub fn bin_spacing(&self) -> BinSpacing {
+		self.bin_spacing
+	}
+
+	/// Samples each bin at [`AngularSampling::Adaptive`] angles. Returns
+	/// `None` unless `samples_per_pixel` is finite and positive.
+	pub fn with_adaptive_sampling(self, samples_per_pixel: f64) -> Option<Self> {
+		if !(samples_per_pixel.is_finite() && 0.0 < samples_per_pixel) {
+			return None;
+		}
+		Some(Self { angular_sampling: AngularSampling::Adaptive { samples_per_pixel }, ..self })
+	}
+
+	pub fn angular_sampling(&self) -> AngularSampling {
+		self.angular_sampling
+	}
+
+	pub fn with_integration(self, integration: Integration) -> Self {
+		Self { integration, ..self }
+	}
+
+	pub fn integration(&self) -> Integration {
+		self.integration
+	}
+
+	/// Corrects pixels for the dead time of the detector, such as read by
+	/// [`CountRateCorrection::from_miniheader`], before they are integrated
+	/// by [`radial_profile`].
+	pub fn with_count_rate_correction(self, correction: CountRateCorrection) -> Self {
+		Self { count_rate: Some(correction), ..self }
+	}
+
+	pub fn count_rate_correction(&self) -> Option<CountRateCorrection> {
+		self.count_rate
+	}
+
+	/// Angles sampled over the half circle at `radius`, as a fraction of half
+	/// the image width, in an image `image_width` pixels wide.
+	fn angular_sample_count(&self, image_width: usize, radius: f64) -> usize {
+		match self.angular_sampling {
+			AngularSampling::Fixed => self.intensity_sample_count,
+			AngularSampling::Adaptive { samples_per_pixel } => {
+				let arc = f64::consts::PI * radius * image_width as f64 / 2.0;
+				((samples_per_pixel * arc).ceil() as usize).max(1)
+			}
+		}
+	}
+
+	/// Radius, as a fraction of half the image width, of the sample at
+	/// `position` along the radius. Fractional positions fall between bins.
+	fn sample_radius(&self, position: f64) -> f64 {
+		let count = self.theta_sample_count as f64;
+		match self.bin_spacing {
+			BinSpacing::Linear => position.max(0.0) * self.radius / count,
+			BinSpacing::Sqrt => (position.max(0.0) / count).sqrt() * self.radius,
+			BinSpacing::Log => self.radius / count * count.powf(position / count),
+		}
+	}
+
+	/// Restricts the analysis to samples between `q_min` and `q_max`, in
+	/// inverse ångström, assuming the beam hits the centre of the image.
+	/// Returns `None` if `geometry` has no wavelength or the range does not
+	/// reach the detector.
+	pub fn with_q_range(self, geometry: &Geometry, q_min: f64, q_max: f64) -> Option<Self> {
+		let inner = geometry.q_to_radius(q_min)?;
+		let outer = geometry.q_to_radius(q_max).unwrap_or(f64::INFINITY);
+		Some(Self { radial_range: Some((inner, outer)), ..self })
+	}
+
+	/// Like [`AnalysisConfig::with_q_range`] with limits as resolutions in
+	/// ångström, `low` being the larger one.
+	pub fn with_resolution_range(self, geometry: &Geometry, low: f64, high: f64) -> Option<Self> {
+		self.with_q_range(geometry, resolution_to_q(low), resolution_to_q(high))
+	}
+
+	pub fn radial_range(&self) -> Option<(f64, f64)> {
+		self.radial_range
+	}
+
+	fn contains_radius(&self, image_width: usize, radius: f64) -> bool {
+		self.radial_range.is_none_or(|(inner, outer)| {
+			let radius = radius * image_width as f64 / 2.0;
+			inner <= radius && radius <= outer
+		})
+	}
+
+	/// Distance in pixels from the image centre of each sample of a profile
+	/// computed for an image `width` pixels wide.
+	pub fn bin_radii(&self, width: usize) -> Box<[f64]> {
+		(0..self.theta_sample_count)
+			.map(|j| self.sample_radius(j as f64) * (width as f64) / 2.0)
+			.collect()
+	}
+
+	/// Boundaries in pixels from the image centre of the bins of a profile
+	/// computed for an image `width` pixels wide, halfway between samples in
+	/// the chosen spacing.
+	pub fn bin_edges(&self, width: usize) -> Box<[f64]> {
+		(0..=self.theta_sample_count)
+			.map(|j| self.sample_radius(j as f64 - 0.5) * (width as f64) / 2.0)
+			.collect()
 	}
 }
 
@@ -57,12 +388,159 @@ fn compute_average_slice<P: BigNum>(averages: Box<[Average<P>]>) -> Box<[P]> {
 
 #[cfg(test)]
 mod tests {
-	use super::{radial_difraction_analysis, sampler_methods::nearest_neighbour, AnalysisConfig};
-	use crate::{image::ImageEnum, read_image};
+	use super::{
+		radial_difraction_analysis, radial_difraction_analysis_with_coverage, radial_profile, radial_profile_f32,
+		sampler_methods::{diagnosed, masked_nearest_neighbour, nearest_neighbour},
+		AnalysisConfig, AngularSampling, BinSpacing, CountRateCorrection, Integration, Roi,
+	};
+	use crate::{
+		geometry::Geometry,
+		image::{
+			mask::{Mask, MaskEdit},
+			Image, ImageEnum,
+		},
+		read_image,
+	};
 
 	use std::f64;
 	use std::io::Cursor;
 
+	#[test]
+	fn coverage_of_masked_half() {
+		let image = Image::new(4, 4, vec![1u8; 16].into()).unwrap();
+		let mut mask = Mask::empty(4, 4).unwrap();
+		MaskEdit::Rectangle { x: 0, y: 0, width: 2, height: 4, masked: true }.apply(&mut mask);
+		let config = AnalysisConfig::new(2, 4, 1.0).unwrap();
+
+		let profile =
+			radial_difraction_analysis_with_coverage(&image, &config, diagnosed(masked_nearest_neighbour(&mask)));
+		assert_eq!(&*profile.values, &[1, 1]);
+		assert_eq!(&*profile.coverage, &[1.0, 0.75]);
+	}
+
+	#[test]
+	fn restricted_to_roi() {
+		let pixels: Vec<u8> = (0..64).map(|i| if i % 8 < 4 { 1 } else { 5 }).collect();
+		let image = Image::new(8, 8, pixels.into()).unwrap();
+		let config = AnalysisConfig::new(4, 16, 1.0).unwrap();
+		let right_half = config.clone().with_roi(Roi { x: 4, y: 0, width: 4, height: 8 });
+
+		let profile = radial_difraction_analysis(&image, &config, nearest_neighbour);
+		assert!(profile.iter().any(|v| *v < 5));
+		let profile = radial_difraction_analysis(&image, &right_half, nearest_neighbour);
+		assert!(profile.iter().all(|v| *v == 5));
+
+		let unbounded = config.with_roi(Roi { x: 4, y: 0, width: usize::MAX, height: usize::MAX });
+		let profile = radial_difraction_analysis(&image, &unbounded, nearest_neighbour);
+		assert!(profile.iter().all(|v| *v == 5));
+	}
+
+	#[test]
+	fn restricted_to_q_range() {
+		let geometry =
+			Geometry { beam_centre: (4.0, 4.0), distance: 1.0, pixel_size: (1.0, 1.0), wavelength: Some(1e-10) };
+		let image = Image::new(8, 8, vec![1u8; 64].into()).unwrap();
+		let q_max = geometry.q((6.0, 4.0)).unwrap();
+		let config = AnalysisConfig::new(4, 4, 1.0)
+			.unwrap()
+			.with_q_range(&geometry, 0.0, q_max)
+			.unwrap();
+
+		let profile = radial_difraction_analysis_with_coverage(&image, &config, diagnosed(nearest_neighbour));
+		assert_eq!(&*profile.coverage, &[1.0, 1.0, 1.0, 0.0]);
+		assert!(AnalysisConfig::new(4, 4, 1.0)
+			.unwrap()
+			.with_q_range(&Geometry { wavelength: None, ..geometry }, 0.0, q_max)
+			.is_none());
+	}
+
+	#[test]
+	fn profile_in_either_precision() {
+		let image = ImageEnum::from_pixels(4, 4, vec![3i64; 16].into());
+		let config = AnalysisConfig::new(2, 4, 1.0).unwrap();
+		assert_eq!(&*radial_profile(&image, &config), &[3.0, 3.0]);
+		assert_eq!(&*radial_profile_f32(&image, &config), &[3.0f32, 3.0]);
+
+		let pixels: Vec<u16> = (0..256).collect();
+		let image = ImageEnum::from_pixels(16, 16, pixels.into());
+		let config = AnalysisConfig::new(6, 8, 1.0).unwrap();
+		let copied = radial_difraction_analysis(&image.to_f64(), &config, nearest_neighbour);
+		assert_eq!(radial_profile(&image, &config), copied);
+	}
+
+	#[test]
+	fn corrects_count_rate_before_integrating() {
+		let image = ImageEnum::from_pixels(4, 4, vec![1000u32; 16].into());
+		// Half of the exposure is dead time at 1000 counts/s.
+		let correction = CountRateCorrection::new(5e-4, 1.0).unwrap();
+		let config = AnalysisConfig::new(2, 4, 1.0)
+			.unwrap()
+			.with_count_rate_correction(correction);
+		assert_eq!(&*radial_profile(&image, &config), &[2000.0, 2000.0]);
+		let split = config.clone().with_integration(Integration::PixelSplitting);
+		assert!(radial_profile(&image, &split).iter().all(|v| (v - 2000.0).abs() < 1e-9));
+
+		let saturated = CountRateCorrection::new(1e-3, 1.0).unwrap();
+		let config = config.with_count_rate_correction(saturated);
+		assert!(radial_profile(&image, &config).iter().all(|v| v.is_nan()));
+	}
+
+	#[test]
+	fn bin_radii() {
+		let config = AnalysisConfig::new(4, 10, 1.0).unwrap();
+		assert_eq!(&*config.bin_radii(100), &[0.0, 12.5, 25.0, 37.5]);
+		assert_eq!(&*config.bin_edges(100), &[0.0, 6.25, 18.75, 31.25, 43.75]);
+	}
+
+	#[test]
+	fn non_linear_bin_spacing() {
+		let sqrt = AnalysisConfig::new(4, 10, 1.0)
+			.unwrap()
+			.with_bin_spacing(BinSpacing::Sqrt);
+		let areas: Vec<f64> = sqrt.bin_radii(100).iter().map(|r| r * r).collect();
+		assert!(areas
+			.iter()
+			.zip([0.0, 625.0, 1250.0, 1875.0])
+			.all(|(a, b)| (a - b).abs() < 1e-9));
+
+		let log = AnalysisConfig::new(4, 10, 1.0)
+			.unwrap()
+			.with_bin_spacing(BinSpacing::Log);
+		let radii = log.bin_radii(100);
+		assert!((radii[0] - 12.5).abs() < 1e-9);
+		let ratios: Vec<f64> = radii.windows(2).map(|w| w[1] / w[0]).collect();
+		assert!(ratios.iter().all(|r| (r - 2.0_f64.sqrt()).abs() < 1e-9));
+		let edges = log.bin_edges(100);
+		assert!(edges.windows(2).zip(radii.iter()).all(|(e, r)| e[0] < *r && *r < e[1]));
+
+		let image = Image::new(8, 8, vec![2u8; 64].into()).unwrap();
+		let profile = radial_difraction_analysis_with_coverage(&image, &log, diagnosed(nearest_neighbour));
+		assert_eq!(profile.edges, log.bin_edges(8));
+		assert!(profile.values.iter().all(|v| *v == 2));
+	}
+
+	#[test]
+	fn adaptive_angular_sampling() {
+		let pixels: Vec<u16> = (0..64 * 64).map(|i| (i % 64) as u16).collect();
+		let image = Image::new(64, 64, pixels.into()).unwrap();
+		let fixed = AnalysisConfig::new(16, 200, 1.0).unwrap();
+		let adaptive = fixed.clone().with_adaptive_sampling(2.0).unwrap();
+		assert_eq!(
+			adaptive.angular_sampling(),
+			AngularSampling::Adaptive { samples_per_pixel: 2.0 }
+		);
+		assert!(fixed.clone().with_adaptive_sampling(0.0).is_none());
+
+		// One sample at the centre, proportionally more further out.
+		assert_eq!(adaptive.angular_sample_count(64, 0.0), 1);
+		assert_eq!(adaptive.angular_sample_count(64, 0.5), 101);
+
+		let fixed = radial_difraction_analysis(&image, &fixed, nearest_neighbour);
+		let adaptive = radial_difraction_analysis(&image, &adaptive, nearest_neighbour);
+		assert_eq!(fixed[0], adaptive[0]);
+		assert!(fixed.iter().zip(adaptive.iter()).all(|(a, b)| a.abs_diff(*b) <= 1));
+	}
+
 	#[test]
 	fn analyse_real_image() {
 		const EXAMPLE_DATA: &'static [u8] = include_bytes!("../examples/snap_V4_00013.cbf");
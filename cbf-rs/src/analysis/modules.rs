@@ -0,0 +1,147 @@
+use num_traits::AsPrimitive;
+
+use crate::image::Image;
+
+use super::{average::BigNum, radial_difraction_analysis_with_coverage, AnalysisConfig, Roi, Sample};
+
+/// Regular grid of equally sized detector modules separated by gaps, starting
+/// in the top left corner of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleLayout {
+	pub module_width: usize,
+	pub module_height: usize,
+	pub gap_x: usize,
+	pub gap_y: usize,
+}
+
+impl ModuleLayout {
+	/// Regions of the modules that fit entirely in an image, row by row.
+	pub fn modules(&self, width: usize, height: usize) -> Vec<Roi> {
+		if self.module_width == 0 || self.module_height == 0 {
+			return Vec::new();
+		}
+		let step_x = self.module_width + self.gap_x;
+		let step_y = self.module_height + self.gap_y;
+		(0..)
+			.map(|row| row * step_y)
+			.take_while(|y| y + self.module_height <= height)
+			.flat_map(|y| {
+				(0..)
+					.map(move |column| column * step_x)
+					.take_while(move |x| x + self.module_width <= width)
+					.map(move |x| Roi { x, y, width: self.module_width, height: self.module_height })
+			})
+			.collect()
+	}
+}
+
+/// Radial profiles of every module, computed with the same bins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleProfiles<P> {
+	pub modules: Vec<Roi>,
+	pub profiles: Vec<Box<[P]>>,
+	/// Coefficient of variation across the modules covering each bin, NaN
+	/// where fewer than two modules do
+	pub dispersion: Box<[f64]>,
+	/// Mean relative deviation of each module from the mean over all modules,
+	/// over the bins it covers; far from zero for miscalibrated modules and NaN
+	/// for modules without any samples
+	pub deviations: Box<[f64]>,
+}
+
+pub fn module_profiles<P: BigNum + AsPrimitive<f64>>(
+	image: &Image<P>,
+	config: &AnalysisConfig,
+	layout: &ModuleLayout,
+	mut sampler_method: impl FnMut(&Image<P>, f64, f64) -> Sample<P>,
+) -> ModuleProfiles<P> {
+	let modules = layout.modules(image.width, image.height);
+	let results: Vec<_> = modules
+		.iter()
+		.map(|roi| radial_difraction_analysis_with_coverage(image, &config.clone().with_roi(*roi), &mut sampler_method))
+		.collect();
+
+	let bins = config.theta_sample_count;
+	let mut means = vec![f64::NAN; bins].into_boxed_slice();
+	let mut dispersion = vec![f64::NAN; bins].into_boxed_slice();
+	for bin in 0..bins {
+		let values: Vec<f64> = results
+			.iter()
+			.filter(|r| 0.0 < r.coverage[bin])
+			.map(|r| r.values[bin].as_())
+			.collect();
+		if values.is_empty() {
+			continue;
+		}
+		let mean = values.iter().sum::<f64>() / values.len() as f64;
+		means[bin] = mean;
+		if 2 <= values.len() {
+			let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / values.len() as f64;
+			dispersion[bin] = variance.sqrt() / mean.abs();
+		}
+	}
+
+	let deviations = results
+		.iter()
+		.map(|r| {
+			let relative: Vec<f64> = (0..bins)
+				.filter(|&bin| 0.0 < r.coverage[bin] && means[bin] != 0.0)
+				.map(|bin| (r.values[bin].as_() - means[bin]) / means[bin].abs())
+				.collect();
+			relative.iter().sum::<f64>() / relative.len() as f64
+		})
+		.collect();
+
+	ModuleProfiles { modules, profiles: results.into_iter().map(|r| r.values).collect(), dispersion, deviations }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{module_profiles, ModuleLayout};
+	use crate::{
+		analysis::{
+			sampler_methods::{diagnosed, nearest_neighbour},
+			AnalysisConfig, Roi,
+		},
+		image::Image,
+	};
+
+	#[test]
+	fn grid_layout() {
+		let layout = ModuleLayout { module_width: 4, module_height: 2, gap_x: 1, gap_y: 2 };
+		let modules = layout.modules(10, 7);
+		assert_eq!(
+			modules,
+			[
+				Roi { x: 0, y: 0, width: 4, height: 2 },
+				Roi { x: 5, y: 0, width: 4, height: 2 },
+				Roi { x: 0, y: 4, width: 4, height: 2 },
+				Roi { x: 5, y: 4, width: 4, height: 2 },
+			]
+		);
+	}
+
+	#[test]
+	fn finds_deviating_module() {
+		// Four 4x4 quadrants, the bottom right one reading twice as high.
+		let pixels: Vec<f64> = (0..64)
+			.map(|i| if 4 <= i % 8 && 32 <= i { 20.0 } else { 10.0 })
+			.collect();
+		let image = Image::new(8, 8, pixels.into()).unwrap();
+		let layout = ModuleLayout { module_width: 4, module_height: 4, gap_x: 0, gap_y: 0 };
+		let config = AnalysisConfig::new(4, 64, 1.0).unwrap();
+
+		let result = module_profiles(&image, &config, &layout, diagnosed(nearest_neighbour));
+		assert_eq!(result.modules.len(), 4);
+		let worst = result
+			.deviations
+			.iter()
+			.enumerate()
+			.filter(|(_, d)| !d.is_nan())
+			.max_by(|a, b| a.1.total_cmp(b.1))
+			.unwrap()
+			.0;
+		assert_eq!(worst, 3);
+		assert!(result.dispersion.iter().any(|d| 0.0 < *d));
+	}
+}
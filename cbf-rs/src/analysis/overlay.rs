@@ -0,0 +1,116 @@
+use std::f64;
+
+use super::{sampler_methods::polar_to_cartesian, AnalysisConfig};
+use crate::image::dimensions::pixel_index;
+
+/// RGBA colours used when rendering an integration overlay.
+pub struct OverlayStyle {
+	pub centre: [u8; 4],
+	pub bin_boundaries: [u8; 4],
+	pub sector_limits: [u8; 4],
+}
+
+impl Default for OverlayStyle {
+	fn default() -> Self {
+		Self { centre: [255, 0, 0, 255], bin_boundaries: [0, 255, 0, 96], sector_limits: [0, 128, 255, 255] }
+	}
+}
+
+/// Renders the geometry of `config` into an RGBA buffer of `width` × `height`
+/// pixels: a cross at the centre, an arc at the edge of every radial bin, and
+/// rays at the first and last sampled angle. Untouched pixels are left as is.
+pub fn render_overlay(width: usize, height: usize, config: &AnalysisConfig, style: &OverlayStyle, buffer: &mut [u8]) {
+	let mut canvas = Canvas { width, height, buffer };
+
+	let rad = config.radius / (config.theta_sample_count as f64);
+	let last_angle = (config.intensity_sample_count.saturating_sub(1) as f64) * f64::consts::PI
+		/ (config.intensity_sample_count as f64);
+
+	for j in 0..config.theta_sample_count {
+		let r = (j as f64 + 0.5) * rad;
+		let arc_length = last_angle * r * width as f64 / 2.0;
+		let steps = arc_length.ceil() as usize + 1;
+		for k in 0..=steps {
+			let angle = last_angle * (k as f64) / (steps as f64);
+			canvas.plot(polar_to_cartesian(width as f64, angle, r), style.bin_boundaries);
+		}
+	}
+
+	let outer_radius = config.radius * width as f64 / 2.0;
+	for angle in [0.0, last_angle] {
+		for step in 0..=outer_radius.ceil() as usize {
+			let r = (step as f64) / (width as f64 / 2.0);
+			canvas.plot(polar_to_cartesian(width as f64, angle, r), style.sector_limits);
+		}
+	}
+
+	let arm = (width.max(height) / 50).max(3) as f64;
+	for offset in -arm as isize..=arm as isize {
+		canvas.plot((offset as f64, 0.0), style.centre);
+		canvas.plot((0.0, offset as f64), style.centre);
+	}
+}
+
+pub(super) struct Canvas<'a> {
+	pub(super) width: usize,
+	pub(super) height: usize,
+	pub(super) buffer: &'a mut [u8],
+}
+
+impl Canvas<'_> {
+	/// Plots a position relative to the centre of the canvas.
+	fn plot(&mut self, (x, y): (f64, f64), colour: [u8; 4]) {
+		let x = x.round() as isize + (self.width / 2) as isize;
+		let y = y.round() as isize + (self.height / 2) as isize;
+		self.set(x, y, colour);
+	}
+
+	/// Sets a pixel, ignoring positions outside the canvas or buffer.
+	pub(super) fn set(&mut self, x: isize, y: isize, colour: [u8; 4]) {
+		if x < 0 || y < 0 || self.width as isize <= x || self.height as isize <= y {
+			return;
+		}
+		let Some(offset) = pixel_index(x as usize, y as usize, self.width)
+			.ok()
+			.and_then(|i| i.checked_mul(4))
+		else {
+			return;
+		};
+		if let Some(pixel) = self.buffer.get_mut(offset..offset + 4) {
+			pixel.copy_from_slice(&colour);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{render_overlay, OverlayStyle};
+	use crate::analysis::AnalysisConfig;
+
+	fn pixel(buffer: &[u8], width: usize, x: usize, y: usize) -> &[u8] {
+		&buffer[(y * width + x) * 4..][..4]
+	}
+
+	#[test]
+	fn draws_centre_bins_and_sectors() {
+		let (width, height) = (100, 100);
+		let mut buffer = vec![0; width * height * 4];
+		let style = OverlayStyle::default();
+		let config = AnalysisConfig::new(5, 100, 1.0).unwrap();
+
+		render_overlay(width, height, &config, &style, &mut buffer);
+
+		assert_eq!(pixel(&buffer, width, 50, 50), style.centre);
+		assert_eq!(pixel(&buffer, width, 50, 48), style.centre);
+		// First bin boundary lies at 0.1 of the half width, straight down.
+		assert_eq!(pixel(&buffer, width, 50, 55), style.bin_boundaries);
+		assert_eq!(pixel(&buffer, width, 99, 50), style.sector_limits);
+		assert_eq!(pixel(&buffer, width, 0, 0), [0, 0, 0, 0]);
+	}
+
+	#[test]
+	fn ignores_short_buffers() {
+		let config = AnalysisConfig::new(5, 100, 1.0).unwrap();
+		render_overlay(10, 10, &config, &OverlayStyle::default(), &mut [0; 8]);
+	}
+}
@@ -0,0 +1,77 @@
+use std::{f64, io::Write};
+
+use crate::geometry::Geometry;
+
+use super::spots::IntegratedSpot;
+
+/// Writes spots frame by frame as chunks of a CrystFEL stream, so that the
+/// list can be read by tools expecting CrystFEL's peak search output.
+pub struct PeakListWriter<W> {
+	writer: W,
+	geometry: Option<Geometry>,
+	started: bool,
+}
+
+impl<W: Write> PeakListWriter<W> {
+	/// Resolutions are only written when `geometry` has a wavelength.
+	pub fn new(writer: W, geometry: Option<Geometry>) -> Self {
+		Self { writer, geometry, started: false }
+	}
+
+	pub fn write_frame(&mut self, filename: &str, event: usize, spots: &[IntegratedSpot]) -> std::io::Result<()> {
+		if !self.started {
+			writeln!(self.writer, "CrystFEL stream format 2.3")?;
+			writeln!(self.writer, "Generated by cbf-rs {}", env!("CARGO_PKG_VERSION"))?;
+			self.started = true;
+		}
+		writeln!(self.writer, "----- Begin chunk -----")?;
+		writeln!(self.writer, "Image filename: {filename}")?;
+		writeln!(self.writer, "Event: //{event}")?;
+		writeln!(self.writer, "num_peaks = {}", spots.len())?;
+		writeln!(self.writer, "Peaks from peak search")?;
+		writeln!(self.writer, "  fs/px   ss/px (1/d)/nm^-1   Intensity  Panel")?;
+		for spot in spots {
+			// CrystFEL places pixel centres at half-integer coordinates.
+			let position = (spot.spot.x, spot.spot.y);
+			let resolution = self
+				.geometry
+				.as_ref()
+				.and_then(|g| g.q(position))
+				.map_or(0.0, |q| q / (2.0 * f64::consts::PI) * 10.0);
+			writeln!(
+				self.writer,
+				"{:7.2} {:7.2} {:10.2} {:11.2}   p0",
+				position.0 + 0.5,
+				position.1 + 0.5,
+				resolution,
+				spot.intensity,
+			)?;
+		}
+		writeln!(self.writer, "End of peak list")?;
+		writeln!(self.writer, "----- End chunk -----")
+	}
+
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::PeakListWriter;
+	use crate::analysis::spots::{IntegratedSpot, Spot};
+
+	#[test]
+	fn writes_chunks() {
+		let spot = IntegratedSpot { spot: Spot { x: 10.0, y: 20.5 }, intensity: 123.456, sigma: 12.0, background: 1.0 };
+		let mut writer = PeakListWriter::new(Vec::new(), None);
+		writer.write_frame("a.cbf", 0, &[spot]).unwrap();
+		writer.write_frame("b.cbf", 0, &[]).unwrap();
+		let text = String::from_utf8(writer.into_inner()).unwrap();
+
+		assert_eq!(text.matches("CrystFEL stream format").count(), 1);
+		assert_eq!(text.matches("----- Begin chunk -----").count(), 2);
+		assert!(text.contains("num_peaks = 1\n"));
+		assert!(text.contains("  10.50   21.00       0.00      123.46   p0\n"));
+	}
+}
@@ -0,0 +1,129 @@
+use std::f64;
+
+use super::{AnalysisConfig, BinSpacing};
+use crate::geometry::Geometry;
+
+/// Common experiment types, each with analysis settings suited to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+	/// Small angle scattering: logarithmic bins close to the beam
+	Saxs,
+	/// Wide angle scattering: linear bins out to the corners
+	Waxs,
+	/// Powder diffraction: many fine linear bins out to the corners, densely
+	/// sampled to resolve sharp rings
+	Powder,
+	/// Serial crystallography: few equal area bins sampled quickly, for hit
+	/// finding and background estimation over many frames
+	SerialCrystallography,
+}
+
+/// Unit of the radial axis of an exported profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadialUnit {
+	/// Pixels from the beam centre
+	Pixels,
+	/// Scattering angle 2θ in degrees
+	TwoTheta,
+	/// Scattering vector in inverse ångström
+	Q,
+}
+
+impl Preset {
+	pub const ALL: [Preset; 4] = [Self::Saxs, Self::Waxs, Self::Powder, Self::SerialCrystallography];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Saxs => "saxs",
+			Self::Waxs => "waxs",
+			Self::Powder => "powder",
+			Self::SerialCrystallography => "serial",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL
+			.into_iter()
+			.find(|preset| preset.name().eq_ignore_ascii_case(name))
+	}
+
+	/// Unit profiles of this kind are usually reported in.
+	pub fn radial_unit(self) -> RadialUnit {
+		match self {
+			Self::Saxs | Self::Waxs | Self::SerialCrystallography => RadialUnit::Q,
+			Self::Powder => RadialUnit::TwoTheta,
+		}
+	}
+
+	/// Whether the count rate, high next to the beam stop in small angle
+	/// scattering, calls for a
+	/// [`CountRateCorrection`](super::count_rate::CountRateCorrection).
+	pub fn corrects_count_rate(self) -> bool {
+		self == Self::Saxs
+	}
+}
+
+impl RadialUnit {
+	/// Value at `radius` pixels from the beam centre, such as a bin of a
+	/// profile centred on the image when the beam hits its centre. Returns
+	/// `None` for `Q` if `geometry` has no wavelength.
+	pub fn of_radius(self, geometry: &Geometry, radius: f64) -> Option<f64> {
+		match self {
+			Self::Pixels => Some(radius),
+			Self::TwoTheta => Some(geometry.radius_to_two_theta(radius).to_degrees()),
+			Self::Q => geometry.radius_to_q(radius),
+		}
+	}
+}
+
+impl AnalysisConfig {
+	/// Bins and sampling suited to an experiment type, sampled with nearest
+	/// neighbours like every other configuration.
+	pub fn preset(preset: Preset) -> Self {
+		let (bins, radius, spacing, samples_per_pixel) = match preset {
+			Preset::Saxs => (500, 1.0, BinSpacing::Log, Some(1.0)),
+			Preset::Waxs => (1000, f64::consts::SQRT_2, BinSpacing::Linear, Some(1.0)),
+			Preset::Powder => (2000, f64::consts::SQRT_2, BinSpacing::Linear, Some(2.0)),
+			Preset::SerialCrystallography => (250, 1.0, BinSpacing::Sqrt, None),
+		};
+		let config = AnalysisConfig::new(bins, 360, radius)
+			.unwrap()
+			.with_bin_spacing(spacing);
+		match samples_per_pixel {
+			Some(samples_per_pixel) => config.with_adaptive_sampling(samples_per_pixel).unwrap(),
+			None => config,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Preset, RadialUnit};
+	use crate::{
+		analysis::{AnalysisConfig, AngularSampling, BinSpacing},
+		geometry::Geometry,
+	};
+
+	#[test]
+	fn presets() {
+		for preset in Preset::ALL {
+			assert_eq!(Preset::from_name(&preset.name().to_uppercase()), Some(preset));
+		}
+		let saxs = AnalysisConfig::preset(Preset::Saxs);
+		assert_eq!(saxs.bin_spacing(), BinSpacing::Log);
+		assert!(Preset::Saxs.corrects_count_rate());
+		let serial = AnalysisConfig::preset(Preset::SerialCrystallography);
+		assert_eq!(serial.angular_sampling(), AngularSampling::Fixed);
+		assert_eq!(AnalysisConfig::preset(Preset::Powder).bin_radii(100).len(), 2000);
+	}
+
+	#[test]
+	fn radial_units() {
+		let geometry =
+			Geometry { beam_centre: (10.0, 10.0), distance: 0.1, pixel_size: (1e-4, 1e-4), wavelength: None };
+		assert_eq!(RadialUnit::Pixels.of_radius(&geometry, 3.0), Some(3.0));
+		let two_theta = RadialUnit::TwoTheta.of_radius(&geometry, 1000.0).unwrap();
+		assert!((two_theta - 45.0).abs() < 1e-9);
+		assert_eq!(RadialUnit::Q.of_radius(&geometry, 1000.0), None);
+	}
+}
@@ -0,0 +1,88 @@
+use std::{f64, io::Write};
+
+use crate::geometry::Geometry;
+
+/// Writes a radial profile as a CIF data block with a powder diffraction
+/// (`_pd_`) loop, one row per bin.
+///
+/// `radii` are bin centres in pixels from the beam centre. The 2θ column is
+/// written when `geometry` is given, and the Q column when it also has a
+/// wavelength.
+pub fn write_profile_cif(
+	mut writer: impl Write,
+	block: &str,
+	radii: &[f64],
+	values: &[f64],
+	geometry: Option<&Geometry>,
+) -> std::io::Result<()> {
+	writeln!(writer, "#\\#CIF_1.1")?;
+	writeln!(writer, "# Generated by cbf-rs {}", env!("CARGO_PKG_VERSION"))?;
+	writeln!(writer, "data_{block}")?;
+	if let Some(wavelength) = geometry.and_then(|g| g.wavelength) {
+		writeln!(writer, "_diffrn_radiation_wavelength.wavelength {}", wavelength * 1e10)?;
+	}
+	let with_q = geometry.is_some_and(|g| g.wavelength.is_some());
+	writeln!(writer, "loop_")?;
+	writeln!(writer, "_pd_proc.point_id")?;
+	writeln!(writer, "_pd_proc.radius_pixels")?;
+	if geometry.is_some() {
+		writeln!(writer, "_pd_proc.2theta_corrected")?;
+	}
+	if with_q {
+		writeln!(writer, "_pd_proc.recip_len_Q")?;
+	}
+	writeln!(writer, "_pd_proc.intensity_total")?;
+	for (i, (radius, value)) in radii.iter().zip(values).enumerate() {
+		write!(writer, "{i} {radius}")?;
+		if let Some(geometry) = geometry {
+			let (x, y) = geometry.beam_centre;
+			let position = (x + radius, y);
+			write!(writer, " {}", geometry.two_theta(position).to_degrees())?;
+			if let Some(q) = geometry.q(position) {
+				write!(writer, " {q}")?;
+			}
+		}
+		writeln!(writer, " {}", format_value(*value))?;
+	}
+	Ok(())
+}
+
+/// CIF has no spelling of NaN, so empty bins are written as unknown.
+fn format_value(value: f64) -> String {
+	if value.is_finite() {
+		value.to_string()
+	} else {
+		"?".to_owned()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::write_profile_cif;
+	use crate::geometry::Geometry;
+
+	#[test]
+	fn writes_loop() {
+		let mut out = Vec::new();
+		write_profile_cif(&mut out, "profile", &[0.0, 1.0], &[2.5, f64::NAN], None).unwrap();
+		let text = String::from_utf8(out).unwrap();
+		assert!(text.contains("data_profile\n"));
+		assert!(!text.contains("2theta"));
+		assert!(text.ends_with("_pd_proc.intensity_total\n0 0 2.5\n1 1 ?\n"));
+	}
+
+	#[test]
+	fn writes_scattering_columns() {
+		let geometry =
+			Geometry { beam_centre: (0.0, 0.0), distance: 1.0, pixel_size: (1.0, 1.0), wavelength: Some(1e-10) };
+		let mut out = Vec::new();
+		write_profile_cif(&mut out, "profile", &[1.0], &[3.0], Some(&geometry)).unwrap();
+		let text = String::from_utf8(out).unwrap();
+		assert!(text.contains("_diffrn_radiation_wavelength.wavelength 1\n"));
+		let row = text.lines().last().unwrap();
+		let columns: Vec<f64> = row.split(' ').map(|c| c.parse().unwrap()).collect();
+		assert_eq!(columns.len(), 5);
+		assert!((columns[2] - 45.0).abs() < 1e-9);
+		assert_eq!(columns[4], 3.0);
+	}
+}
@@ -1,11 +1,36 @@
-use crate::image::Image;
+use crate::image::{mask::Mask, Image};
+
+use super::Sample;
 
 pub fn nearest_neighbour<P: Copy>(image: &Image<P>, angle: f64, radius: f64) -> Option<P> {
 	let (x, y) = polar_to_cartesian(image.width as f64, angle, radius);
 	image.get_pixel((x.round() as isize, y.round() as isize)).copied()
 }
 
-fn polar_to_cartesian(width: f64, angle: f64, radius: f64) -> (f64, f64) {
+/// Nearest neighbour sampling skipping every pixel set in `mask`.
+pub fn masked_nearest_neighbour<P: Copy>(mask: &Mask) -> impl Fn(&Image<P>, f64, f64) -> Option<P> + '_ {
+	move |image, angle, radius| {
+		let (x, y) = polar_to_cartesian(image.width as f64, angle, radius);
+		let coordinate = (x.round() as isize, y.round() as isize);
+		if *mask.get_pixel(coordinate)? {
+			return None;
+		}
+		image.get_pixel(coordinate).copied()
+	}
+}
+
+/// Turns a single pixel sampler into one reporting diagnostics, counting the
+/// pixel as valid whenever a value is returned.
+pub fn diagnosed<P>(
+	mut sampler: impl FnMut(&Image<P>, f64, f64) -> Option<P>,
+) -> impl FnMut(&Image<P>, f64, f64) -> Sample<P> {
+	move |image, angle, radius| {
+		let value = sampler(image, angle, radius);
+		Sample { valid: value.is_some() as usize, total: 1, value }
+	}
+}
+
+pub(crate) fn polar_to_cartesian(width: f64, angle: f64, radius: f64) -> (f64, f64) {
 	let radius = radius * width / 2.0;
 	(radius * angle.cos(), radius * angle.sin())
 }
@@ -0,0 +1,413 @@
+use std::{
+	fmt,
+	fs::File,
+	io::{BufRead, BufReader, Error as IOError, Write},
+	path::PathBuf,
+	str::FromStr,
+};
+
+use thiserror::Error as ThisError;
+
+use crate::{
+	decoder::Decoder,
+	image::mask::{Mask, MaskEdit},
+	warning::Warning,
+	Error as ReadError,
+};
+
+use super::{
+	count_rate::CountRateCorrection, radial_difraction_analysis, sampler_methods::masked_nearest_neighbour,
+	strip_frame_profile, AnalysisConfig, AngularSampling, BinSpacing, Integration, Roi,
+};
+
+/// A single recorded operation of an analysis session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+	Load(PathBuf),
+	Configure(AnalysisConfig),
+	EditMask(MaskEdit),
+	Analyse,
+}
+
+impl fmt::Display for Event {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Event::Load(path) => write!(f, "load {}", path.display()),
+			Event::Configure(config) => {
+				write!(
+					f,
+					"configure {} {} {}",
+					config.theta_sample_count, config.intensity_sample_count, config.radius
+				)?;
+				if let Some(roi) = config.roi {
+					write!(f, " {} {} {} {}", roi.x, roi.y, roi.width, roi.height)?;
+				}
+				if let Some((inner, outer)) = config.radial_range {
+					write!(f, " range {inner} {outer}")?;
+				}
+				match config.bin_spacing {
+					BinSpacing::Linear => {}
+					BinSpacing::Sqrt => write!(f, " spacing sqrt")?,
+					BinSpacing::Log => write!(f, " spacing log")?,
+				}
+				if let AngularSampling::Adaptive { samples_per_pixel } = config.angular_sampling {
+					write!(f, " sampling {samples_per_pixel}")?;
+				}
+				if config.integration == Integration::PixelSplitting {
+					write!(f, " integration split")?;
+				}
+				match config.count_rate {
+					Some(correction) => write!(f, " deadtime {} {}", correction.tau(), correction.exposure_time()),
+					None => Ok(()),
+				}
+			}
+			Event::EditMask(edit) => write!(f, "mask {edit}"),
+			Event::Analyse => write!(f, "analyse"),
+		}
+	}
+}
+
+impl FromStr for Event {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (name, rest) = s.split_once(' ').unwrap_or((s, ""));
+		match name {
+			"load" if !rest.is_empty() => Ok(Event::Load(rest.into())),
+			"configure" => {
+				let mut parts = rest.split_whitespace().peekable();
+				let theta_sample_count = parse(parts.next())?;
+				let intensity_sample_count = parse(parts.next())?;
+				let radius = parse(parts.next())?;
+				let mut config = AnalysisConfig::new(theta_sample_count, intensity_sample_count, radius)
+					.ok_or(Error::InvalidEvent)?;
+				// The region is written as four numbers ahead of the named settings.
+				if parts.peek().is_some_and(|part| part.parse::<usize>().is_ok()) {
+					let (x, y) = (parse(parts.next())?, parse(parts.next())?);
+					let (width, height) = (parse(parts.next())?, parse(parts.next())?);
+					config = config.with_roi(Roi { x, y, width, height });
+				}
+				while let Some(setting) = parts.next() {
+					config = match (setting, parts.next()) {
+						("range", inner) => {
+							let radial_range = Some((parse(inner)?, parse(parts.next())?));
+							AnalysisConfig { radial_range, ..config }
+						}
+						("spacing", Some("linear")) => config.with_bin_spacing(BinSpacing::Linear),
+						("spacing", Some("sqrt")) => config.with_bin_spacing(BinSpacing::Sqrt),
+						("spacing", Some("log")) => config.with_bin_spacing(BinSpacing::Log),
+						("sampling", samples_per_pixel) => config
+							.with_adaptive_sampling(parse(samples_per_pixel)?)
+							.ok_or(Error::InvalidEvent)?,
+						("integration", Some("point")) => config.with_integration(Integration::PointSampling),
+						("integration", Some("split")) => config.with_integration(Integration::PixelSplitting),
+						("deadtime", tau) => {
+							let correction = CountRateCorrection::new(parse(tau)?, parse(parts.next())?);
+							config.with_count_rate_correction(correction.ok_or(Error::InvalidEvent)?)
+						}
+						_ => return Err(Error::InvalidEvent),
+					};
+				}
+				Ok(Event::Configure(config))
+			}
+			"mask" => rest.parse().map(Event::EditMask).map_err(|_| Error::InvalidEvent),
+			"analyse" if rest.is_empty() => Ok(Event::Analyse),
+			_ => Err(Error::InvalidEvent),
+		}
+	}
+}
+
+fn parse<T: FromStr>(part: Option<&str>) -> Result<T, Error> {
+	part.and_then(|part| part.parse().ok()).ok_or(Error::InvalidEvent)
+}
+
+/// An ordered log of events that can be stored as text, one event per line,
+/// and replayed to reproduce the analysis results.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Session {
+	events: Vec<Event>,
+}
+
+impl Session {
+	pub fn record(&mut self, event: Event) {
+		self.events.push(event);
+	}
+
+	pub fn events(&self) -> &[Event] {
+		&self.events
+	}
+
+	pub fn read_from(reader: impl BufRead) -> Result<Self, Error> {
+		let mut session = Session::default();
+		for line in reader.lines() {
+			let line = line?;
+			if !line.is_empty() {
+				session.record(line.parse()?);
+			}
+		}
+		Ok(session)
+	}
+
+	pub fn write_to(&self, mut writer: impl Write) -> Result<(), Error> {
+		for event in &self.events {
+			writeln!(writer, "{event}")?;
+		}
+		Ok(())
+	}
+
+	/// Replays every event in order, returning the result of each
+	/// [`Event::Analyse`]. One dimensional images are taken as profiles as
+	/// they are, see [`strip_frame_profile`].
+	pub fn replay(&self) -> Result<Vec<Box<[f64]>>, Error> {
+		self.replay_with_warnings(1.0).map(|replay| replay.results)
+	}
+
+	/// Like [`Session::replay`], also returning the warnings raised while
+	/// loading images and by analyses with more than `masked_fraction_limit`
+	/// of the image masked.
+	pub fn replay_with_warnings(&self, masked_fraction_limit: f64) -> Result<Replay, Error> {
+		let mut image = None;
+		let mut mask: Option<Mask> = None;
+		let mut config = None;
+		let mut results = Vec::new();
+		let mut warnings = Vec::new();
+
+		for event in &self.events {
+			match event {
+				Event::Load(path) => {
+					let (loaded, load_warnings) =
+						Decoder::default().read_image_with_warnings(BufReader::new(File::open(path)?))?;
+					warnings.extend(load_warnings);
+					let loaded = loaded.to_f64();
+					if !mask
+						.as_ref()
+						.is_some_and(|m| m.width == loaded.width && m.height == loaded.height)
+					{
+						mask = Mask::empty(loaded.width, loaded.height);
+					}
+					image = Some(loaded);
+				}
+				Event::Configure(new_config) => config = Some(new_config.clone()),
+				Event::EditMask(edit) => edit.apply(mask.as_mut().ok_or(Error::NoImage)?),
+				Event::Analyse => {
+					let image = image.as_ref().ok_or(Error::NoImage)?;
+					let mask = mask.as_ref().ok_or(Error::NoImage)?;
+					let config = config.as_ref().ok_or(Error::NoConfig)?;
+					warnings.extend(Warning::for_mask(mask, masked_fraction_limit));
+					let profile = match strip_frame_profile(image, Some(mask)) {
+						Some(profile) => profile,
+						None => {
+							let sampler = masked_nearest_neighbour(mask);
+							let correction = config.count_rate_correction();
+							radial_difraction_analysis(image, config, |image, angle, radius| {
+								let value = sampler(image, angle, radius)?;
+								correction.map_or(Some(value), |correction| correction.correct(value))
+							})
+						}
+					};
+					results.push(profile);
+				}
+			}
+		}
+
+		Ok(Replay { results, warnings })
+	}
+}
+
+/// Outcome of [`Session::replay_with_warnings`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Replay {
+	/// Result of each [`Event::Analyse`]
+	pub results: Vec<Box<[f64]>>,
+	pub warnings: Vec<Warning>,
+}
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+	#[error("invalid session event")]
+	InvalidEvent,
+	#[error("no image loaded")]
+	NoImage,
+	#[error("no analysis configured")]
+	NoConfig,
+	#[error(transparent)]
+	Read(#[from] ReadError),
+	#[error(transparent)]
+	IO(#[from] IOError),
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{f64, io::Cursor};
+
+	use super::{Error, Event, Session};
+	use crate::{
+		analysis::{count_rate::CountRateCorrection, AnalysisConfig, BinSpacing, Integration, Roi},
+		image::mask::MaskEdit,
+		warning::Warning,
+	};
+
+	#[test]
+	fn text_round_trip() {
+		let mut session = Session::default();
+		session.record(Event::Load("examples/with space.cbf".into()));
+		session.record(Event::Configure(AnalysisConfig::new(10, 20, 0.5).unwrap()));
+		session.record(Event::EditMask(MaskEdit::Rectangle {
+			x: 0,
+			y: 1,
+			width: 2,
+			height: 3,
+			masked: true,
+		}));
+		session.record(Event::Analyse);
+		session.record(Event::Configure(
+			AnalysisConfig::new(10, 20, 0.5)
+				.unwrap()
+				.with_roi(Roi { x: 1, y: 2, width: 3, height: 4 }),
+		));
+
+		let mut text = Vec::new();
+		session.write_to(&mut text).expect("to write session");
+		assert_eq!(
+			String::from_utf8_lossy(&text),
+			"load examples/with space.cbf\nconfigure 10 20 0.5\nmask rectangle 0 1 2 3 true\nanalyse\nconfigure 10 20 0.5 1 2 3 4\n"
+		);
+		assert_eq!(Session::read_from(Cursor::new(text)).expect("to read session"), session);
+	}
+
+	fn assert_round_trip(config: AnalysisConfig, text: &str) {
+		let event = Event::Configure(config);
+		assert_eq!(event.to_string(), text);
+		assert_eq!(text.parse::<Event>().expect("to parse event"), event);
+	}
+
+	#[test]
+	fn configure_radial_range() {
+		let config = AnalysisConfig::new(10, 20, 0.5).unwrap();
+		let config = AnalysisConfig { radial_range: Some((1.5, f64::INFINITY)), ..config };
+		assert_round_trip(config, "configure 10 20 0.5 range 1.5 inf");
+	}
+
+	#[test]
+	fn configure_bin_spacing() {
+		let config = AnalysisConfig::new(10, 20, 0.5).unwrap();
+		assert_round_trip(
+			config.clone().with_bin_spacing(BinSpacing::Sqrt),
+			"configure 10 20 0.5 spacing sqrt",
+		);
+		assert_round_trip(
+			config.with_bin_spacing(BinSpacing::Log),
+			"configure 10 20 0.5 spacing log",
+		);
+	}
+
+	#[test]
+	fn configure_angular_sampling() {
+		let config = AnalysisConfig::new(10, 20, 0.5)
+			.unwrap()
+			.with_adaptive_sampling(0.25)
+			.unwrap();
+		assert_round_trip(config, "configure 10 20 0.5 sampling 0.25");
+	}
+
+	#[test]
+	fn configure_integration() {
+		let config = AnalysisConfig::new(10, 20, 0.5).unwrap();
+		let config = config.with_integration(Integration::PixelSplitting);
+		assert_round_trip(config, "configure 10 20 0.5 integration split");
+	}
+
+	#[test]
+	fn configure_count_rate_correction() {
+		let correction = CountRateCorrection::new(2e-7, 0.5).unwrap();
+		let config = AnalysisConfig::new(10, 20, 0.5)
+			.unwrap()
+			.with_count_rate_correction(correction);
+		assert_round_trip(config, "configure 10 20 0.5 deadtime 0.0000002 0.5");
+	}
+
+	#[test]
+	fn configure_every_setting() {
+		let config = AnalysisConfig::new(10, 20, 0.5)
+			.unwrap()
+			.with_roi(Roi { x: 1, y: 2, width: 3, height: 4 })
+			.with_bin_spacing(BinSpacing::Log)
+			.with_integration(Integration::PixelSplitting);
+		let config = AnalysisConfig { radial_range: Some((0.0, 8.0)), ..config };
+		assert_round_trip(
+			config,
+			"configure 10 20 0.5 1 2 3 4 range 0 8 spacing log integration split",
+		);
+	}
+
+	#[test]
+	fn rejects_invalid_events() {
+		assert!(matches!("load".parse::<Event>(), Err(Error::InvalidEvent)));
+		assert!(matches!("configure 1 2 3".parse::<Event>(), Err(Error::InvalidEvent)));
+		assert!(matches!(
+			"configure 1 2 0.5 1 2".parse::<Event>(),
+			Err(Error::InvalidEvent)
+		));
+		assert!(matches!(
+			"configure 1 2 0.5 spacing cubic".parse::<Event>(),
+			Err(Error::InvalidEvent)
+		));
+		assert!(matches!(
+			"configure 1 2 0.5 sampling 0".parse::<Event>(),
+			Err(Error::InvalidEvent)
+		));
+		assert!(matches!("analyse now".parse::<Event>(), Err(Error::InvalidEvent)));
+	}
+
+	#[test]
+	fn replay_real_image() {
+		let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/examples/snap_V4_00013.cbf");
+		let mut session = Session::default();
+		session.record(Event::Load(path.into()));
+		session.record(Event::Configure(
+			AnalysisConfig::new(100, 10, f64::consts::SQRT_2).unwrap(),
+		));
+		session.record(Event::Analyse);
+		session.record(Event::EditMask(MaskEdit::Circle {
+			x: 1440.0,
+			y: 1440.0,
+			radius: 4000.0,
+			masked: true,
+		}));
+		session.record(Event::Analyse);
+
+		let results = session.replay().expect("to replay session");
+		assert_eq!(results.len(), 2);
+		assert!(results[0].iter().any(|v| !v.is_nan()));
+		assert!(results[1].iter().all(|v| v.is_nan()));
+	}
+
+	#[test]
+	fn replay_warns_about_mask() {
+		let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/examples/snap_V4_00013.cbf");
+		let mut session = Session::default();
+		session.record(Event::Load(path.into()));
+		session.record(Event::Configure(AnalysisConfig::new(10, 10, 1.0).unwrap()));
+		session.record(Event::Analyse);
+		session.record(Event::EditMask(MaskEdit::Rectangle {
+			x: 0,
+			y: 0,
+			width: 2880,
+			height: 2000,
+			masked: true,
+		}));
+		session.record(Event::Analyse);
+
+		let replay = session.replay_with_warnings(0.5).expect("to replay session");
+		assert_eq!(replay.results.len(), 2);
+		assert_eq!(replay.warnings.len(), 1);
+		assert!(matches!(replay.warnings[0], Warning::HighMaskedFraction { fraction } if 0.69 < fraction));
+	}
+
+	#[test]
+	fn replay_requires_image() {
+		let mut session = Session::default();
+		session.record(Event::Analyse);
+		assert!(matches!(session.replay(), Err(Error::NoImage)));
+	}
+}
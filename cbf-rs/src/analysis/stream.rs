@@ -0,0 +1,163 @@
+use std::{
+	collections::BTreeMap,
+	io::Cursor,
+	sync::{
+		mpsc::{sync_channel, Receiver, SyncSender},
+		Arc, Mutex,
+	},
+	thread,
+};
+
+use super::{radial_profile, strip_profile, AnalysisConfig};
+use crate::{decoder::Decoder, Error};
+
+/// Radial profile of a frame, or the reason it could not be computed, tagged
+/// with the index of the frame.
+pub type Analysed = (usize, Result<Box<[f64]>, Error>);
+
+/// Profiles of a stream of frames, yielded in input order along with the
+/// index of each frame. Returned by [`analyze_stream`].
+///
+/// At most twice as many frames as there are worker threads are decoded,
+/// analysed or waiting to be yielded at any time, so memory stays bounded
+/// however far the input reaches. Dropping the stream stops the worker threads.
+pub struct AnalysisStream {
+	results: Receiver<Analysed>,
+	pending: BTreeMap<usize, Result<Box<[f64]>, Error>>,
+	next: usize,
+	tokens: SyncSender<()>,
+}
+
+impl Iterator for AnalysisStream {
+	type Item = Analysed;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			if let Some(result) = self.pending.remove(&self.next) {
+				let index = self.next;
+				self.next += 1;
+				// The feeder stops taking tokens once the input is exhausted.
+				let _ = self.tokens.try_send(());
+				return Some((index, result));
+			}
+			let (index, result) = self.results.recv().ok()?;
+			self.pending.insert(index, result);
+		}
+	}
+}
+
+/// Decodes and integrates encoded CBF files on one thread per available core,
+/// see [`analyze_stream_with`].
+pub fn analyze_stream(
+	frames: impl Iterator<Item = Vec<u8>> + Send + 'static,
+	config: AnalysisConfig,
+) -> AnalysisStream {
+	let threads = thread::available_parallelism().map_or(1, |n| n.get());
+	analyze_stream_with(frames, config, &Decoder::default(), threads)
+}
+
+/// Decodes the first image of each encoded CBF file in `frames` with
+/// `decoder` and computes its [`radial_profile`] on `threads` worker threads.
+/// One dimensional frames are yielded as they are, one bin per pixel, see
+/// [`strip_profile`], and frames without pixels fail with
+/// [`Error::EmptyImage`].
+pub fn analyze_stream_with(
+	frames: impl Iterator<Item = Vec<u8>> + Send + 'static,
+	config: AnalysisConfig,
+	decoder: &Decoder,
+	threads: usize,
+) -> AnalysisStream {
+	let threads = threads.max(1);
+	let window = threads * 2;
+	let (tokens_tx, tokens_rx) = sync_channel(window);
+	for _ in 0..window {
+		tokens_tx.send(()).unwrap();
+	}
+	let (jobs_tx, jobs_rx) = sync_channel::<(usize, Vec<u8>)>(threads);
+	let (results_tx, results_rx) = sync_channel(window);
+
+	thread::spawn(move || {
+		for job in frames.enumerate() {
+			if tokens_rx.recv().is_err() || jobs_tx.send(job).is_err() {
+				return;
+			}
+		}
+	});
+
+	let jobs = Arc::new(Mutex::new(jobs_rx));
+	let config = Arc::new(config);
+	for _ in 0..threads {
+		let jobs = jobs.clone();
+		let results = results_tx.clone();
+		let decoder = decoder.clone();
+		let config = config.clone();
+		thread::spawn(move || loop {
+			let job = jobs.lock().ok().and_then(|jobs| jobs.recv().ok());
+			let Some((index, data)) = job else {
+				return;
+			};
+			let profile = decoder
+				.read_image(Cursor::new(data))
+				.and_then(|image| match image.is_strip() {
+					true => strip_profile(&image, image.width() * image.height()).ok_or(Error::EmptyImage),
+					false => Ok(radial_profile(&image, &config)),
+				});
+			if results.send((index, profile)).is_err() {
+				return;
+			}
+		});
+	}
+
+	AnalysisStream { results: results_rx, pending: BTreeMap::new(), next: 0, tokens: tokens_tx }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::analyze_stream_with;
+	use crate::{
+		analysis::{radial_profile, AnalysisConfig},
+		decoder::Decoder,
+		image::ImageEnum,
+		read_image, write_image, Error,
+	};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("../examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn yields_in_order() {
+		let config = AnalysisConfig::new(360, 100, 1.0).unwrap();
+		let expected = radial_profile(&read_image(EXAMPLE_DATA).unwrap(), &config);
+		let frames = (0..5).map(|i| match i {
+			2 => b"not a cbf".to_vec(),
+			_ => EXAMPLE_DATA.to_vec(),
+		});
+
+		let results: Vec<_> = analyze_stream_with(frames, config, &Decoder::default(), 3).collect();
+		let indices: Vec<_> = results.iter().map(|(index, _)| *index).collect();
+		assert_eq!(indices, [0, 1, 2, 3, 4]);
+		assert!(results[2].1.is_err());
+		assert_eq!(results[4].1.as_ref().unwrap(), &expected);
+	}
+
+	#[test]
+	fn strips() {
+		let frames = [(1, 3), (0, 3)].map(|(width, height)| {
+			let image = ImageEnum::from_pixels(width, height, vec![7i32; width * height].into());
+			let mut data = Vec::new();
+			write_image(&mut data, &image).unwrap();
+			data
+		});
+		let config = AnalysisConfig::new(36, 10, 1.0).unwrap();
+		let results: Vec<_> = analyze_stream_with(frames.into_iter(), config, &Decoder::default(), 2).collect();
+		assert_eq!(results[0].1.as_ref().unwrap()[..], [7.0; 3]);
+		assert!(matches!(results[1].1, Err(Error::EmptyImage)));
+	}
+
+	#[test]
+	fn stops_when_dropped() {
+		let config = AnalysisConfig::new(36, 10, 1.0).unwrap();
+		let frames = std::iter::repeat_with(|| EXAMPLE_DATA.to_vec());
+		let mut stream = analyze_stream_with(frames, config, &Decoder::default(), 2);
+		assert_eq!(stream.next().map(|(index, _)| index), Some(0));
+	}
+}
@@ -0,0 +1,119 @@
+use num_traits::Float;
+
+use super::{allocate_slice, compute_average_slice};
+use crate::image::{mask::Mask, Image, ImageEnum};
+
+/// The pixels of a one dimensional frame taken as a profile as they are,
+/// without integration, with masked pixels as NaN. Bin `i` lies `i` pixels
+/// from the start of the strip, which are the radii to export it with.
+/// Returns `None` if the image is more than one pixel wide and high, or if
+/// `mask` does not match it.
+pub fn strip_frame_profile<P: Float>(image: &Image<P>, mask: Option<&Mask>) -> Option<Box<[P]>> {
+	if !image.is_strip() || mask.is_some_and(|m| m.width != image.width || m.height != image.height) {
+		return None;
+	}
+	let masked = |index: usize| mask.is_some_and(|m| m.pixels()[index]);
+	Some(
+		(image.pixels().iter().enumerate())
+			.map(|(index, &pixel)| if masked(index) { P::nan() } else { pixel })
+			.collect(),
+	)
+}
+
+/// Divides every bin of `profile` by `monitor`, such as the incident flux or
+/// the exposure time of the frame.
+pub fn normalize_profile(profile: &mut [f64], monitor: f64) {
+	for value in profile {
+		*value /= monitor;
+	}
+}
+
+/// Mean of each bin over a series of profiles, skipping NaN bins. Returns
+/// `None` without profiles or if their lengths differ.
+pub fn average_profiles<T: AsRef<[f64]>>(profiles: &[T]) -> Option<Box<[f64]>> {
+	let len = profiles.first()?.as_ref().len();
+	let mut averages = allocate_slice::<f64>(len);
+	for profile in profiles {
+		let profile = profile.as_ref();
+		if profile.len() != len {
+			return None;
+		}
+		for (average, &value) in averages.iter_mut().zip(profile) {
+			if !value.is_nan() {
+				average.add(value);
+			}
+		}
+	}
+	Some(compute_average_slice(averages))
+}
+
+/// Profile along a one dimensional image, such as one read from a strip
+/// detector, averaging the pixels of each of `bins` equal runs. Bins without
+/// finite pixels are NaN. Returns `None` without bins or if the image is more
+/// than one pixel wide and high, where [`radial_profile`](super::radial_profile)
+/// applies instead.
+pub fn strip_profile(image: &ImageEnum, bins: usize) -> Option<Box<[f64]>> {
+	if bins == 0 || !image.is_strip() {
+		return None;
+	}
+	let image = image.to_f64();
+	let pixels = image.pixels();
+	let mut averages = allocate_slice::<f64>(bins);
+	for (index, &pixel) in pixels.iter().enumerate() {
+		if pixel.is_finite() {
+			averages[index * bins / pixels.len()].add(pixel);
+		}
+	}
+	Some(compute_average_slice(averages))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{average_profiles, normalize_profile, strip_frame_profile, strip_profile};
+	use crate::image::{
+		mask::{Mask, MaskEdit},
+		pixel::Pixels,
+		Image, ImageEnum,
+	};
+
+	#[test]
+	fn frame_as_profile() {
+		let frame = Image::new(1, 4, vec![1.0f64, 2.0, 3.0, 4.0].into()).unwrap();
+		let mut mask = Mask::empty(1, 4).unwrap();
+		MaskEdit::Rectangle { x: 0, y: 1, width: 1, height: 1, masked: true }.apply(&mut mask);
+		let profile = strip_frame_profile(&frame, Some(&mask)).unwrap();
+		assert_eq!(profile[0], 1.0);
+		assert!(profile[1].is_nan());
+		assert_eq!(profile[2..], [3.0, 4.0]);
+		assert!(strip_frame_profile(&frame, Some(&Mask::empty(4, 1).unwrap())).is_none());
+		assert!(strip_frame_profile(&Image::new(2, 2, vec![0.0f32; 4].into()).unwrap(), None).is_none());
+	}
+
+	#[test]
+	fn normalized_average() {
+		let mut first = vec![2.0, f64::NAN, 6.0];
+		let mut second = vec![6.0, 4.0, 10.0];
+		normalize_profile(&mut first, 2.0);
+		normalize_profile(&mut second, 2.0);
+		assert_eq!(average_profiles(&[first, second]).unwrap()[..], [2.0, 2.0, 4.0]);
+		assert!(average_profiles(&[vec![1.0], vec![1.0, 2.0]]).is_none());
+		assert!(average_profiles::<Vec<f64>>(&[]).is_none());
+	}
+
+	#[test]
+	fn averages_runs() {
+		let row = ImageEnum::from_pixels(6, 1, Pixels::from(vec![1u16, 3, 5, 7, 9, 11]));
+		assert_eq!(strip_profile(&row, 3).unwrap()[..], [2.0, 6.0, 10.0]);
+		let column = ImageEnum::from_pixels(1, 4, Pixels::from(vec![2.0, f64::NAN, 4.0, 6.0]));
+		assert_eq!(strip_profile(&column, 2).unwrap()[..], [2.0, 5.0]);
+		assert!(strip_profile(&row, 0).is_none());
+	}
+
+	#[test]
+	fn degenerate_shapes() {
+		let empty = ImageEnum::from_pixels(0, 3, Pixels::from(Vec::<i32>::new()));
+		assert!(strip_profile(&empty, 2).unwrap().iter().all(|v| v.is_nan()));
+		let square = ImageEnum::from_pixels(2, 2, Pixels::from(vec![1u8; 4]));
+		assert!(strip_profile(&square, 2).is_none());
+	}
+}
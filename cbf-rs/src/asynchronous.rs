@@ -0,0 +1,144 @@
+use std::io::ErrorKind;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+use crate::{
+	binary_section_len, decoder::Decoder, has_binary_header, image::ImageEnum, metadata::read_metadata, Error,
+	SECTION_END, SECTION_START,
+};
+
+/// Reads the next image with the default options without blocking on
+/// `reader`.
+///
+/// Only reading is asynchronous. The section is buffered whole and then
+/// decoded on the calling task, so callers decoding large frames on a busy
+/// executor may want to read them within `spawn_blocking`.
+pub async fn read_image<R: AsyncBufRead + Unpin>(reader: R) -> Result<ImageEnum, Error> {
+	read_image_with(reader, &Decoder::default()).await
+}
+
+pub async fn read_image_with<R: AsyncBufRead + Unpin>(mut reader: R, decoder: &Decoder) -> Result<ImageEnum, Error> {
+	let section = read_section(&mut reader, decoder).await?.ok_or(Error::NoImage)?;
+	decoder.read_image(&section[..])
+}
+
+/// Reads all images with the default options without blocking on `reader`,
+/// holding one compressed section in memory at a time.
+pub async fn read_all_images<R: AsyncBufRead + Unpin>(reader: R) -> Result<Vec<ImageEnum>, Error> {
+	read_all_images_with(reader, &Decoder::default()).await
+}
+
+pub async fn read_all_images_with<R: AsyncBufRead + Unpin>(
+	mut reader: R,
+	decoder: &Decoder,
+) -> Result<Vec<ImageEnum>, Error> {
+	let mut images = Vec::new();
+	while let Some(section) = read_section(&mut reader, decoder).await? {
+		decoder.options().limits.check_image_count(images.len())?;
+		images.push(decoder.read_image(&section[..])?);
+	}
+	Ok(images)
+}
+
+/// Reads the next binary section, from its opening boundary up to and
+/// including its closing boundary, or `None` if there is no other section.
+/// The declared size is checked against the limits of `decoder` before the
+/// data is read.
+async fn read_section<R: AsyncBufRead + Unpin>(reader: &mut R, decoder: &Decoder) -> Result<Option<Vec<u8>>, Error> {
+	let mut line = Vec::new();
+	loop {
+		line.clear();
+		if reader.read_until(b'\n', &mut line).await? == 0 {
+			return Ok(None);
+		}
+		if line == SECTION_START.as_bytes() {
+			break;
+		}
+	}
+	let mut section = line.clone();
+	loop {
+		line.clear();
+		if reader.read_until(b'\n', &mut line).await? == 0 {
+			return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+		}
+		section.extend_from_slice(&line);
+		if line == b"\r\n" {
+			break;
+		}
+	}
+	let metadata = read_metadata(&section[SECTION_START.len()..])?;
+	decoder.options().limits.check(&metadata)?;
+	// Encoded sections are text, ended by the closing boundary alone.
+	if has_binary_header(&metadata) {
+		let len = binary_section_len(&metadata)?;
+		let read = (&mut *reader).take(len as u64).read_to_end(&mut section).await?;
+		if read < len {
+			return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+		}
+	}
+	loop {
+		line.clear();
+		if reader.read_until(b'\n', &mut line).await? == 0 {
+			break;
+		}
+		section.extend_from_slice(&line);
+		if line == SECTION_END.as_bytes() {
+			break;
+		}
+	}
+	Ok(Some(section))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::future::Future;
+
+	use super::{read_all_images, read_image};
+	use crate::Error;
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	fn block_on<F: Future>(future: F) -> F::Output {
+		tokio::runtime::Builder::new_current_thread()
+			.build()
+			.unwrap()
+			.block_on(future)
+	}
+
+	#[test]
+	fn reads_like_blocking() {
+		let expected = crate::read_image(EXAMPLE_DATA).unwrap().to_f64();
+		let image = block_on(read_image(EXAMPLE_DATA)).expect("to read image");
+		assert_eq!(image.to_f64().pixels(), expected.pixels());
+
+		let data = [EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		let images = block_on(read_all_images(&data[..])).expect("to read images");
+		assert_eq!(images.len(), 2);
+		assert!(matches!(
+			block_on(read_image(&b"data_empty\r\n"[..])),
+			Err(Error::NoImage)
+		));
+	}
+
+	#[test]
+	fn overflowing_size() {
+		let data = format!(
+			"--CIF-BINARY-FORMAT-SECTION--\r\n\
+Content-Type: application/octet-stream;\r\n     conversions=\"x-CBF_BYTE_OFFSET\"\r\n\
+Content-Transfer-Encoding: BINARY\r\n\
+X-Binary-Size: {}\r\n\
+X-Binary-Size-Padding: 4095\r\n\
+X-Binary-Element-Type: \"signed 32-bit integer\"\r\n\
+X-Binary-Element-Byte-Order: LITTLE_ENDIAN\r\n\
+X-Binary-Number-of-Elements: 1\r\n\
+X-Binary-Size-Fastest-Dimension: 1\r\n\
+X-Binary-Size-Second-Dimension: 1\r\n\
+\r\n",
+			usize::MAX,
+		);
+		assert!(matches!(
+			block_on(read_image(data.as_bytes())),
+			Err(Error::LimitExceeded)
+		));
+	}
+}
@@ -0,0 +1,74 @@
+use std::io::{Read, Result, Write};
+
+/// Writes bits packed least significant first.
+pub(super) struct BitWriter<W> {
+	writer: W,
+	byte: u8,
+	used: u32,
+}
+
+impl<W: Write> BitWriter<W> {
+	pub fn new(writer: W) -> Self {
+		Self { writer, byte: 0, used: 0 }
+	}
+
+	pub fn write_bit(&mut self, bit: u64) -> Result<()> {
+		self.byte |= ((bit & 1) as u8) << self.used;
+		self.used += 1;
+		if self.used == 8 {
+			self.flush()?;
+		}
+		Ok(())
+	}
+
+	pub fn write_raw(&mut self, value: u64, width: u32) -> Result<()> {
+		for i in 0..width {
+			self.write_bit(value >> i)?;
+		}
+		Ok(())
+	}
+
+	/// Writes the final partial byte padded with zeros.
+	pub fn flush(&mut self) -> Result<()> {
+		if 0 < self.used {
+			self.writer.write_all(&[self.byte])?;
+			self.byte = 0;
+			self.used = 0;
+		}
+		Ok(())
+	}
+}
+
+/// Reads bits packed least significant first.
+pub(super) struct BitReader<R> {
+	reader: R,
+	byte: u8,
+	available: u32,
+}
+
+impl<R: Read> BitReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader, byte: 0, available: 0 }
+	}
+
+	pub fn read_bit(&mut self) -> Result<u64> {
+		if self.available == 0 {
+			let mut byte = [0];
+			self.reader.read_exact(&mut byte)?;
+			self.byte = byte[0];
+			self.available = 8;
+		}
+		let bit = self.byte & 1;
+		self.byte >>= 1;
+		self.available -= 1;
+		Ok(bit as u64)
+	}
+
+	pub fn read_raw(&mut self, width: u32) -> Result<u64> {
+		let mut value = 0;
+		for i in 0..width {
+			value |= self.read_bit()? << i;
+		}
+		Ok(value)
+	}
+}
@@ -1,36 +1,149 @@
-use std::{
-	io::{Read, Result},
-	ops::AddAssign,
-};
+use std::io::{Error, ErrorKind, Read, Result, Write};
 
-use super::from_bytes::FromBytes;
+use num_traits::{AsPrimitive, CheckedAdd, SaturatingAdd, WrappingAdd};
+
+use crate::metadata::ByteOrder;
+
+use super::from_bytes::{to_little_endian, FromBytes};
+
+/// What to do when adding a delta overflows the running pixel value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+	#[default]
+	Error,
+	Saturate,
+	Wrap,
+}
 
 pub fn read_byte_offset<P>(reader: impl Read, buf: &mut [P]) -> Result<()>
 where
-	P: FromBytes + AddAssign + Copy,
+	P: FromBytes + CheckedAdd + SaturatingAdd + WrappingAdd + Copy,
+{
+	read_byte_offset_with_policy(reader, buf, OverflowPolicy::default())
+}
+
+pub fn read_byte_offset_with_policy<P>(reader: impl Read, buf: &mut [P], policy: OverflowPolicy) -> Result<()>
+where
+	P: FromBytes + CheckedAdd + SaturatingAdd + WrappingAdd + Copy,
 {
-	let mut byte_offset_reader = ByteOffsetReader::new(reader);
+	let mut byte_offset_reader = ByteOffsetReader::new(reader, policy);
 	byte_offset_reader.read(buf)
 }
 
-struct ByteOffsetReader<R, P> {
+/// Compresses `pixels` with `x-CBF_BYTE_OFFSET`, writing the difference from
+/// the previous pixel in the fewest little endian bytes of 1, 2, 4 or 8 that
+/// hold it. Each wider size is announced by the most negative value of the
+/// narrower one, `0x80`, `0x8000` and `0x80000000`.
+pub fn write_byte_offset<P: AsPrimitive<i64>>(writer: impl Write, pixels: &[P]) -> Result<()> {
+	ByteOffsetWriter::new(writer).write(pixels)
+}
+
+/// Byte offset encoder keeping the previous pixel between writes, so a
+/// buffer can be compressed in several parts.
+pub struct ByteOffsetWriter<W> {
+	writer: W,
+	previous: i64,
+	byte_order: ByteOrder,
+}
+
+impl<W> ByteOffsetWriter<W> {
+	/// Encoder of little endian deltas.
+	pub fn new(writer: W) -> Self {
+		Self { writer, previous: 0, byte_order: ByteOrder::LittleEndian }
+	}
+
+	/// Writes the multi-byte deltas, and their escape markers, in
+	/// `byte_order`.
+	pub fn byte_order(mut self, byte_order: ByteOrder) -> Self {
+		self.byte_order = byte_order;
+		self
+	}
+
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+}
+
+impl<W: Write> ByteOffsetWriter<W> {
+	pub fn write<P: AsPrimitive<i64>>(&mut self, pixels: &[P]) -> Result<()> {
+		for pixel in pixels {
+			let value = pixel.as_();
+			self.write_delta(value.wrapping_sub(self.previous))?;
+			self.previous = value;
+		}
+		Ok(())
+	}
+
+	fn write_delta(&mut self, delta: i64) -> Result<()> {
+		if let -127..=127 = delta {
+			return self.writer.write_all(&[delta as u8]);
+		}
+		self.writer.write_all(&[0x80])?;
+		if let -32767..=32767 = delta {
+			return self.write_n_bytes((delta as i16).to_le_bytes());
+		}
+		self.write_n_bytes(0x8000u16.to_le_bytes())?;
+		if let -2147483647..=2147483647 = delta {
+			return self.write_n_bytes((delta as i32).to_le_bytes());
+		}
+		self.write_n_bytes(0x80000000u32.to_le_bytes())?;
+		self.write_n_bytes(delta.to_le_bytes())
+	}
+
+	/// Writes little endian `bytes` in the byte order of the encoder.
+	fn write_n_bytes<const N: usize>(&mut self, bytes: [u8; N]) -> Result<()> {
+		self.writer.write_all(&to_little_endian(bytes, self.byte_order))
+	}
+}
+
+/// Byte offset decoder keeping its running value between reads, so a buffer
+/// can be decoded in several parts.
+pub(crate) struct ByteOffsetReader<R, P> {
 	reader: R,
 	base_value: P,
+	policy: OverflowPolicy,
+	byte_order: ByteOrder,
 }
 
 impl<R, P> ByteOffsetReader<R, P>
 where
 	P: FromBytes,
 {
-	fn new(reader: R) -> Self {
-		Self { reader, base_value: P::from_1_bytes([0]) }
+	/// Decoder of little endian deltas.
+	pub fn new(reader: R, policy: OverflowPolicy) -> Self {
+		Self { reader, base_value: P::from_1_bytes([0]), policy, byte_order: ByteOrder::LittleEndian }
+	}
+
+	/// Reads the multi-byte deltas in `byte_order`.
+	pub fn byte_order(mut self, byte_order: ByteOrder) -> Self {
+		self.byte_order = byte_order;
+		self
+	}
+
+	/// Resumes decoding after a pixel of value `value`, whose deltas have
+	/// already been consumed from the reader.
+	pub fn resume_from(mut self, value: P) -> Self {
+		self.base_value = value;
+		self
+	}
+
+	/// Value of the last decoded pixel.
+	pub fn value(&self) -> P
+	where
+		P: Copy,
+	{
+		self.base_value
+	}
+
+	pub fn get_ref(&self) -> &R {
+		&self.reader
 	}
 }
 
 impl<R, P> ByteOffsetReader<R, P>
 where
 	R: Read,
-	P: FromBytes + AddAssign + Copy,
+	P: FromBytes + CheckedAdd + SaturatingAdd + WrappingAdd + Copy,
 {
 	pub fn read(&mut self, buf: &mut [P]) -> Result<()> {
 		for i in 0..buf.len() {
@@ -43,40 +156,52 @@ where
 	}
 
 	fn read_value(&mut self) -> Result<P> {
-		let value = read_value(&mut self.reader)?;
-		self.base_value += value;
+		let value = read_value(&mut self.reader, self.byte_order)?;
+		self.base_value = match self.policy {
+			OverflowPolicy::Error => self
+				.base_value
+				.checked_add(&value)
+				.ok_or_else(|| Error::new(ErrorKind::InvalidData, "byte offset value overflowed"))?,
+			OverflowPolicy::Saturate => self.base_value.saturating_add(&value),
+			OverflowPolicy::Wrap => self.base_value.wrapping_add(&value),
+		};
 		Ok(self.base_value)
 	}
 }
 
-fn read_value<P: FromBytes>(mut reader: impl Read) -> Result<P> {
-	let bytes = read_n_bytes::<1>(&mut reader)?;
+fn read_value<P: FromBytes>(mut reader: impl Read, byte_order: ByteOrder) -> Result<P> {
+	let bytes = read_n_bytes::<1>(&mut reader, byte_order)?;
 	if u8::from_1_bytes(bytes) != 0x80 {
 		return Ok(P::from_1_bytes(bytes));
 	}
-	let bytes = read_n_bytes::<2>(&mut reader)?;
+	let bytes = read_n_bytes::<2>(&mut reader, byte_order)?;
 	if u16::from_2_bytes(bytes) != 0x8000 {
 		return Ok(P::from_2_bytes(bytes));
 	}
-	let bytes = read_n_bytes::<4>(&mut reader)?;
+	let bytes = read_n_bytes::<4>(&mut reader, byte_order)?;
 	if u32::from_4_bytes(bytes) != 0x80000000 {
 		return Ok(P::from_4_bytes(bytes));
 	}
-	let bytes = read_n_bytes::<8>(&mut reader)?;
+	let bytes = read_n_bytes::<8>(&mut reader, byte_order)?;
 	Ok(P::from_8_bytes(bytes))
 }
 
-fn read_n_bytes<const N: usize>(mut reader: impl Read) -> Result<[u8; N]> {
+/// Reads `N` bytes stored in `byte_order`, as little endian.
+fn read_n_bytes<const N: usize>(mut reader: impl Read, byte_order: ByteOrder) -> Result<[u8; N]> {
 	let mut data = [0; N];
 	reader.read_exact(&mut data)?;
-	Ok(data)
+	Ok(to_little_endian(data, byte_order))
 }
 
 #[cfg(test)]
 mod tests {
 	use std::io::Cursor;
 
-	use super::{read_byte_offset, ByteOffsetReader};
+	use super::{
+		read_byte_offset, read_byte_offset_with_policy, write_byte_offset, ByteOffsetReader, ByteOffsetWriter,
+		OverflowPolicy,
+	};
+	use crate::metadata::ByteOrder;
 
 	#[test]
 	fn test_real_binary() {
@@ -96,21 +221,21 @@ mod tests {
 	#[test]
 	fn read_reader_as_8_bits() {
 		let mut reader = Cursor::new([0x42]);
-		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader);
+		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader, OverflowPolicy::Error);
 		assert_eq!(byte_offset_reader.read_value().expect("to successfully read"), 0x42);
 	}
 
 	#[test]
 	fn read_reader_as_16_bits() {
 		let mut reader = Cursor::new([0x80, 0x20, 0x04]);
-		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader);
+		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader, OverflowPolicy::Error);
 		assert_eq!(byte_offset_reader.read_value().expect("to successfully read"), 0x0420);
 	}
 
 	#[test]
 	fn read_reader_as_32_bits() {
 		let mut reader = Cursor::new([0x80, 0x00, 0x80, 0x20, 0x04, 0x20, 0x04]);
-		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader);
+		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader, OverflowPolicy::Error);
 		assert_eq!(
 			byte_offset_reader.read_value().expect("to successfully read"),
 			0x04200420
@@ -122,7 +247,7 @@ mod tests {
 		let mut reader = Cursor::new([
 			0x80, 0x00, 0x80, 0x00, 0x00, 0x00, 0x80, 0x20, 0x04, 0x20, 0x04, 0x20, 0x04, 0x20, 0x04,
 		]);
-		let mut byte_offset_reader = ByteOffsetReader::<_, i64>::new(&mut reader);
+		let mut byte_offset_reader = ByteOffsetReader::<_, i64>::new(&mut reader, OverflowPolicy::Error);
 		assert_eq!(
 			byte_offset_reader.read_value().expect("to successfully read"),
 			0x0420042004200420
@@ -132,8 +257,65 @@ mod tests {
 	#[test]
 	fn combine_with_base_value() {
 		let mut reader = Cursor::new([0x42, 0x24]);
-		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader);
+		let mut byte_offset_reader = ByteOffsetReader::<_, i32>::new(&mut reader, OverflowPolicy::Error);
 		assert_eq!(byte_offset_reader.read_value().expect("to successfully read"), 0x42);
 		assert_eq!(byte_offset_reader.read_value().expect("to successfully read"), 0x66);
 	}
+
+	#[test]
+	fn resume_decoding() {
+		let data = [0x42, 0x24, 0x01];
+		let mut buf = [0i32; 2];
+		let mut reader = ByteOffsetReader::new(&data[..], OverflowPolicy::Error);
+		reader.read(&mut buf).unwrap();
+		let (value, rest) = (reader.value(), *reader.get_ref());
+		assert_eq!((value, rest), (0x66, &[0x01][..]));
+		let mut resumed = ByteOffsetReader::new(rest, OverflowPolicy::Error).resume_from(value);
+		assert_eq!(resumed.read_value().unwrap(), 0x67);
+	}
+
+	#[test]
+	fn overflow_policies() {
+		let data = [0x7F, 0x7F, 0x81];
+		let mut buf = [0i8; 3];
+		assert!(read_byte_offset(Cursor::new(data), &mut buf).is_err());
+
+		read_byte_offset_with_policy(Cursor::new(data), &mut buf, OverflowPolicy::Saturate).unwrap();
+		assert_eq!(buf, [127, 127, 0]);
+
+		read_byte_offset_with_policy(Cursor::new(data), &mut buf, OverflowPolicy::Wrap).unwrap();
+		assert_eq!(buf, [127, -2, 127]);
+	}
+
+	#[test]
+	fn write_escaped_deltas() {
+		let pixels = [0x42i64, 0x42 + 0x0420, 0x42 + 0x0420 + 0x04200420, 0, i64::MIN + 1];
+		let mut data = Vec::new();
+		write_byte_offset(&mut data, &pixels).unwrap();
+		assert_eq!(data[..4], [0x42, 0x80, 0x20, 0x04]);
+		let mut buf = [0i64; 5];
+		read_byte_offset(Cursor::new(&data), &mut buf).unwrap();
+		assert_eq!(buf, pixels);
+	}
+
+	#[test]
+	fn rewrite_real_binary() {
+		const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/byte_offset.bin");
+		let mut buf = vec![0i32; 8294400];
+		read_byte_offset(Cursor::new(EXAMPLE_DATA), &mut buf).expect("to successfully read");
+		let mut data = Vec::new();
+		write_byte_offset(&mut data, &buf).expect("to successfully write");
+		assert_eq!(data, EXAMPLE_DATA[..data.len()]);
+	}
+
+	#[test]
+	fn write_in_parts_and_byte_order() {
+		let mut writer = ByteOffsetWriter::new(Vec::new()).byte_order(ByteOrder::BigEndian);
+		writer.write(&[0x42i32]).unwrap();
+		writer.write(&[0x42 + 0x0420, 0x42 + 0x0420 + 0x04200420]).unwrap();
+		assert_eq!(
+			writer.into_inner(),
+			[0x42, 0x80, 0x04, 0x20, 0x80, 0x80, 0x00, 0x04, 0x20, 0x04, 0x20]
+		);
+	}
 }
@@ -1,3 +1,14 @@
+use crate::metadata::ByteOrder;
+
+/// Reorders `bytes` stored in `byte_order` to little endian, the order
+/// [`FromBytes`] expects.
+pub fn to_little_endian<const N: usize>(mut bytes: [u8; N], byte_order: ByteOrder) -> [u8; N] {
+	if byte_order == ByteOrder::BigEndian {
+		bytes.reverse();
+	}
+	bytes
+}
+
 /// Trait for converting little endian byte arrays to a primitives.
 pub trait FromBytes: Sized {
 	fn from_1_bytes(bytes: [u8; 1]) -> Self;
@@ -1,4 +1,10 @@
+mod bits;
 mod byte_offset;
 pub mod from_bytes;
+mod packed;
 
-pub use byte_offset::read_byte_offset;
+pub(crate) use byte_offset::ByteOffsetReader;
+pub use byte_offset::{
+	read_byte_offset, read_byte_offset_with_policy, write_byte_offset, ByteOffsetWriter, OverflowPolicy,
+};
+pub use packed::{read_packed, write_packed, PackedParameters, PackedReader};
@@ -0,0 +1,293 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use num_traits::AsPrimitive;
+
+use super::{
+	bits::{BitReader, BitWriter},
+	from_bytes::FromBytes,
+};
+
+/// Bits per difference selectable in a block header.
+const BIT_SIZES: [u32; 8] = [0, 4, 5, 6, 7, 8, 16, 65];
+const MAX_BLOCK_LEN: usize = 128;
+
+/// Tuning of the `x-CBF_PACKED` encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedParameters {
+	/// Longest block of differences sharing a header
+	max_block_len: usize,
+}
+
+impl PackedParameters {
+	/// `max_block_len` must be a power of two of at most 128.
+	pub fn new(max_block_len: usize) -> Option<Self> {
+		if !max_block_len.is_power_of_two() || MAX_BLOCK_LEN < max_block_len {
+			return None;
+		}
+		Some(Self { max_block_len })
+	}
+
+	pub fn max_block_len(&self) -> usize {
+		self.max_block_len
+	}
+}
+
+impl Default for PackedParameters {
+	fn default() -> Self {
+		Self { max_block_len: MAX_BLOCK_LEN }
+	}
+}
+
+/// Compresses `pixels` with the flat variant of `x-CBF_PACKED`.
+///
+/// The differences between consecutive pixels are stored in blocks of a
+/// power of two differences. Every block starts with a 6-bit header, 3 bits
+/// of the base 2 logarithm of its length followed by 3 bits indexing the bits
+/// used for each difference, `[0, 4, 5, 6, 7, 8, 16, 65]`. Bits are packed
+/// least significant first.
+///
+/// This is the crate's own layout: CBFlib starts its packed data with a
+/// header this omits, so the [`Encoder`](crate::Encoder) does not write it
+/// and only [`read_packed`] reads it back.
+pub fn write_packed<P: AsPrimitive<i64>>(
+	writer: impl Write,
+	pixels: &[P],
+	parameters: &PackedParameters,
+) -> Result<()> {
+	let mut bits = BitWriter::new(writer);
+	write_blocks(&mut bits, &deltas(pixels), parameters)?;
+	bits.flush()
+}
+
+fn write_blocks(bits: &mut BitWriter<impl Write>, deltas: &[i64], parameters: &PackedParameters) -> Result<()> {
+	let choices = choose_blocks(deltas, parameters.max_block_len);
+	let mut start = 0;
+	while start < deltas.len() {
+		let level = choices[start];
+		let block = &deltas[start..start + (1 << level)];
+		let size_index = size_index(block.iter().map(|d| width(*d)).max().unwrap_or(0));
+		bits.write_raw(level as u64, 3)?;
+		bits.write_raw(size_index as u64, 3)?;
+		for delta in block {
+			match BIT_SIZES[size_index] {
+				0 => (),
+				65 => {
+					bits.write_raw(*delta as u64, 64)?;
+					bits.write_bit((*delta < 0) as u64)?;
+				}
+				size => bits.write_raw(*delta as u64, size)?,
+			}
+		}
+		start += block.len();
+	}
+	Ok(())
+}
+
+pub fn read_packed<P: FromBytes>(reader: impl Read, buf: &mut [P]) -> Result<()> {
+	PackedReader::new(reader, buf.len()).read(buf)
+}
+
+/// Decoder of the flat layout written by [`write_packed`], keeping its place
+/// in the current block between reads, so a buffer can be decoded in several
+/// parts.
+pub struct PackedReader<R> {
+	bits: BitReader<R>,
+	value: i64,
+	/// Pixels decoded so far
+	position: usize,
+	/// Pixels in the whole array
+	len: usize,
+	/// Differences left in the current block, and their bits
+	block: (usize, u32),
+}
+
+impl<R: Read> PackedReader<R> {
+	/// Decoder for an array of `len` pixels.
+	pub fn new(reader: R, len: usize) -> Self {
+		Self { bits: BitReader::new(reader), value: 0, position: 0, len, block: (0, 0) }
+	}
+
+	pub fn read<P: FromBytes>(&mut self, buf: &mut [P]) -> Result<()> {
+		for pixel in buf {
+			*pixel = P::from_8_bytes(self.read_value()?.to_le_bytes());
+		}
+		Ok(())
+	}
+
+	fn read_value(&mut self) -> Result<i64> {
+		if self.len <= self.position {
+			return Err(invalid_data());
+		}
+		if self.block.0 == 0 {
+			let len = 1 << self.bits.read_raw(3)?;
+			let size = BIT_SIZES[self.bits.read_raw(3)? as usize];
+			if self.len < self.position + len {
+				return Err(invalid_data());
+			}
+			self.block = (len, size);
+		}
+		let delta = match self.block.1 {
+			0 => 0,
+			65 => {
+				let delta = self.bits.read_raw(64)? as i64;
+				self.bits.read_bit()?;
+				delta
+			}
+			size => sign_extend(self.bits.read_raw(size)?, size),
+		};
+		self.block.0 -= 1;
+		self.position += 1;
+		self.value = self.value.wrapping_add(delta);
+		Ok(self.value)
+	}
+}
+
+/// Splits `deltas` into the blocks spending the fewest bits in total,
+/// returning the base 2 logarithm of the length of the block starting at
+/// every index.
+///
+/// Works backwards from the end, keeping only the widths and costs of the
+/// next `2 * MAX_BLOCK_LEN` indices.
+fn choose_blocks(deltas: &[i64], max_block_len: usize) -> Vec<u8> {
+	const RING: usize = 2 * MAX_BLOCK_LEN;
+	let levels = max_block_len.trailing_zeros() as usize + 1;
+	let mut widths = vec![[0u8; RING]; levels];
+	let mut costs = [0u64; RING];
+	let mut choices = vec![0; deltas.len()];
+
+	for i in (0..deltas.len()).rev() {
+		widths[0][i % RING] = width(deltas[i]);
+		let mut best = (u64::MAX, 0);
+		for level in 0..levels {
+			let len = 1 << level;
+			if deltas.len() < i + len {
+				break;
+			}
+			if 0 < level {
+				let half = len / 2;
+				widths[level][i % RING] = widths[level - 1][i % RING].max(widths[level - 1][(i + half) % RING]);
+			}
+			let size = BIT_SIZES[size_index(widths[level][i % RING])] as u64;
+			let cost = 6 + len as u64 * size + costs[(i + len) % RING];
+			if cost <= best.0 {
+				best = (cost, level as u8);
+			}
+		}
+		costs[i % RING] = best.0;
+		choices[i] = best.1;
+	}
+
+	choices
+}
+
+fn deltas<P: AsPrimitive<i64>>(pixels: &[P]) -> Vec<i64> {
+	let mut previous = 0i64;
+	pixels
+		.iter()
+		.map(|p| {
+			let value = p.as_();
+			let delta = value.wrapping_sub(previous);
+			previous = value;
+			delta
+		})
+		.collect()
+}
+
+/// Number of bits needed to store `value` in two's complement.
+fn signed_width(value: i64) -> u32 {
+	65 - (value ^ (value >> 63)).leading_zeros()
+}
+
+fn sign_extend(value: u64, width: u32) -> i64 {
+	let shift = 64 - width;
+	((value << shift) as i64) >> shift
+}
+
+fn width(delta: i64) -> u8 {
+	if delta == 0 {
+		0
+	} else {
+		signed_width(delta) as u8
+	}
+}
+
+fn size_index(width: u8) -> usize {
+	BIT_SIZES.iter().position(|size| width as u32 <= *size).unwrap()
+}
+
+fn invalid_data() -> Error {
+	Error::new(ErrorKind::InvalidData, "invalid packed compressed data")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::{read_packed, write_packed, PackedParameters, PackedReader};
+
+	fn round_trip(pixels: &[i64], parameters: &PackedParameters) -> Vec<u8> {
+		let mut data = Vec::new();
+		write_packed(&mut data, pixels, parameters).expect("to compress");
+		let mut reader = Cursor::new(&data);
+		let mut decoded = vec![0i64; pixels.len()];
+		read_packed(&mut reader, &mut decoded).expect("to decompress");
+		assert_eq!(decoded, pixels);
+		assert_eq!(reader.position(), data.len() as u64);
+		data
+	}
+
+	#[test]
+	fn parameters() {
+		assert_eq!(PackedParameters::new(0), None);
+		assert_eq!(PackedParameters::new(3), None);
+		assert_eq!(PackedParameters::new(256), None);
+		assert_eq!(PackedParameters::new(16).map(|p| p.max_block_len()), Some(16));
+	}
+
+	#[test]
+	fn round_trip_small() {
+		let parameters = PackedParameters::default();
+		round_trip(&[], &parameters);
+		round_trip(&[0], &parameters);
+		round_trip(&[100, 100, 101, 99, 100, 102, 100], &parameters);
+		round_trip(&[0, i64::MAX, i64::MIN, -1, 1 << 40, -(1 << 20), 7], &parameters);
+	}
+
+	#[test]
+	fn constant_data_uses_long_blocks() {
+		let data = round_trip(&[0; 1024], &PackedParameters::default());
+		assert_eq!(data.len(), 6);
+		let data = round_trip(&[0; 1024], &PackedParameters::new(1).unwrap());
+		assert_eq!(data.len(), 768);
+	}
+
+	#[test]
+	fn round_trip_real_binary() {
+		const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/byte_offset.bin");
+		let mut pixels = vec![0i64; 8294400];
+		super::super::read_byte_offset(Cursor::new(EXAMPLE_DATA), &mut pixels).expect("to read byte offset");
+		let data = round_trip(&pixels, &PackedParameters::default());
+		assert!(data.len() < EXAMPLE_DATA.len(), "{} bytes", data.len());
+	}
+
+	#[test]
+	fn decodes_in_parts() {
+		let pixels: Vec<i64> = (0..24).map(|i| 1000 * (i / 8) + i % 3).collect();
+		let mut data = Vec::new();
+		write_packed(&mut data, &pixels, &PackedParameters::default()).expect("to compress");
+		let mut decoded = vec![0i64; pixels.len()];
+		let mut reader = PackedReader::new(Cursor::new(&data), pixels.len());
+		for part in decoded.chunks_mut(5) {
+			reader.read(part).expect("to decompress part");
+		}
+		assert_eq!(decoded, pixels);
+	}
+
+	#[test]
+	fn rejects_block_past_end() {
+		let mut data = Vec::new();
+		write_packed(&mut data, &[0i32; 4], &PackedParameters::default()).expect("to compress");
+		let mut decoded = [0i32; 3];
+		assert!(read_packed(Cursor::new(&data), &mut decoded).is_err());
+	}
+}
@@ -0,0 +1,358 @@
+mod zip;
+
+use std::{
+	collections::BTreeMap,
+	fmt::Write as _,
+	io::{Error as IOError, ErrorKind, Read, Write},
+};
+
+use thiserror::Error as ThisError;
+
+use crate::{
+	analysis::Profile,
+	geometry::Geometry,
+	image::{mask::Mask, Image},
+};
+
+const MANIFEST: &str = "manifest.txt";
+const VERSION_LINE: &str = "cbfa 1";
+const GEOMETRY: &str = "geometry.txt";
+const PROVENANCE: &str = "provenance.txt";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+	#[error(transparent)]
+	IO(#[from] IOError),
+	#[error("not a zip archive")]
+	InvalidArchive,
+	#[error("entry {0} is compressed, only stored entries are supported")]
+	UnsupportedCompression(String),
+	#[error("entry {0} is corrupt")]
+	ChecksumMismatch(String),
+	#[error("not a cbfa container")]
+	NotAContainer,
+	#[error("missing entry {0}")]
+	MissingEntry(String),
+	#[error("entry {0} is malformed")]
+	InvalidEntry(String),
+}
+
+/// Results of an analysis session, written as a `.cbfa` container: a zip
+/// archive of plain files that other tools can unpack.
+///
+/// `manifest.txt` starts with the format version and lists the other entries
+/// with their kind. Profiles are text files of their edges, values and
+/// coverage, masks are PBM images, the geometry and provenance are text files
+/// of one `key value` pair per line, and thumbnails are PNG images stored as
+/// given.
+#[derive(Default)]
+pub struct Container {
+	pub profiles: BTreeMap<String, Profile<f64>>,
+	pub masks: BTreeMap<String, Mask>,
+	pub geometry: Option<Geometry>,
+	/// Where the results came from, such as input files and settings
+	pub provenance: BTreeMap<String, String>,
+	/// PNG images, such as frames rendered by [`write_png`](crate::export::write_png)
+	pub thumbnails: BTreeMap<String, Vec<u8>>,
+}
+
+/// What an entry of a container holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	Profile,
+	Mask,
+	Geometry,
+	Provenance,
+	Thumbnail,
+}
+
+impl Kind {
+	const ALL: [Kind; 5] = [
+		Kind::Profile,
+		Kind::Mask,
+		Kind::Geometry,
+		Kind::Provenance,
+		Kind::Thumbnail,
+	];
+
+	fn name(self) -> &'static str {
+		match self {
+			Kind::Profile => "profile",
+			Kind::Mask => "mask",
+			Kind::Geometry => "geometry",
+			Kind::Provenance => "provenance",
+			Kind::Thumbnail => "thumbnail",
+		}
+	}
+
+	fn path(self, name: &str) -> String {
+		match self {
+			Kind::Profile => format!("profiles/{name}.txt"),
+			Kind::Mask => format!("masks/{name}.pbm"),
+			Kind::Geometry => GEOMETRY.to_owned(),
+			Kind::Provenance => PROVENANCE.to_owned(),
+			Kind::Thumbnail => format!("thumbnails/{name}.png"),
+		}
+	}
+
+	/// Name of the item stored at `path`.
+	fn item_name(self, path: &str) -> Option<&str> {
+		let (prefix, suffix) = match self {
+			Kind::Profile => ("profiles/", ".txt"),
+			Kind::Mask => ("masks/", ".pbm"),
+			Kind::Thumbnail => ("thumbnails/", ".png"),
+			Kind::Geometry | Kind::Provenance => return Some(""),
+		};
+		path.strip_prefix(prefix)?.strip_suffix(suffix)
+	}
+}
+
+impl Container {
+	/// Writes the container as a zip archive. Names must be non-empty and must
+	/// not contain `/`, `\` or line breaks.
+	pub fn write_to(&self, writer: impl Write) -> std::io::Result<()> {
+		let mut entries: Vec<(Kind, String, Vec<u8>)> = Vec::new();
+		for (name, profile) in &self.profiles {
+			entries.push((Kind::Profile, check_name(name)?, profile_text(profile).into_bytes()));
+		}
+		for (name, mask) in &self.masks {
+			entries.push((Kind::Mask, check_name(name)?, pbm(mask)));
+		}
+		if let Some(geometry) = &self.geometry {
+			entries.push((Kind::Geometry, String::new(), geometry_text(geometry).into_bytes()));
+		}
+		if !self.provenance.is_empty() {
+			let mut text = String::new();
+			for (key, value) in &self.provenance {
+				if key.is_empty() || key.contains(char::is_whitespace) || value.contains(['\r', '\n']) {
+					return Err(IOError::new(ErrorKind::InvalidInput, "invalid provenance entry"));
+				}
+				writeln!(text, "{key} {value}").unwrap();
+			}
+			entries.push((Kind::Provenance, String::new(), text.into_bytes()));
+		}
+		for (name, png) in &self.thumbnails {
+			entries.push((Kind::Thumbnail, check_name(name)?, png.clone()));
+		}
+
+		let mut manifest = format!("{VERSION_LINE}\n");
+		let mut files = Vec::with_capacity(entries.len() + 1);
+		for (kind, name, data) in entries {
+			let path = kind.path(&name);
+			writeln!(manifest, "{} {path}", kind.name()).unwrap();
+			files.push((path, data));
+		}
+		files.insert(0, (MANIFEST.to_owned(), manifest.into_bytes()));
+		zip::write_archive(writer, &files)
+	}
+
+	/// Reads a container written by [`Container::write_to`], or repacked by
+	/// a tool that stores entries uncompressed.
+	pub fn read_from(mut reader: impl Read) -> Result<Self, Error> {
+		let mut data = Vec::new();
+		reader.read_to_end(&mut data)?;
+		let mut files: BTreeMap<String, Vec<u8>> = zip::read_archive(&data)?.into_iter().collect();
+		let manifest = files.remove(MANIFEST).ok_or(Error::NotAContainer)?;
+		let manifest = String::from_utf8(manifest).map_err(|_| Error::NotAContainer)?;
+		let mut lines = manifest.lines();
+		if lines.next() != Some(VERSION_LINE) {
+			return Err(Error::NotAContainer);
+		}
+
+		let mut container = Container::default();
+		for line in lines.filter(|line| !line.is_empty()) {
+			let invalid = || Error::InvalidEntry(MANIFEST.to_owned());
+			let (kind, path) = line.split_once(' ').ok_or_else(invalid)?;
+			let kind = Kind::ALL.into_iter().find(|k| k.name() == kind).ok_or_else(invalid)?;
+			let name = kind.item_name(path).ok_or_else(invalid)?.to_owned();
+			let data = files.remove(path).ok_or_else(|| Error::MissingEntry(path.to_owned()))?;
+			let malformed = || Error::InvalidEntry(path.to_owned());
+			let text = || String::from_utf8(data.clone()).map_err(|_| malformed());
+			match kind {
+				Kind::Profile => {
+					container
+						.profiles
+						.insert(name, parse_profile(&text()?).ok_or_else(malformed)?);
+				}
+				Kind::Mask => {
+					container.masks.insert(name, parse_pbm(&data).ok_or_else(malformed)?);
+				}
+				Kind::Geometry => container.geometry = Some(parse_geometry(&text()?).ok_or_else(malformed)?),
+				Kind::Provenance => {
+					for line in text()?.lines() {
+						let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+						container.provenance.insert(key.to_owned(), value.to_owned());
+					}
+				}
+				Kind::Thumbnail => {
+					container.thumbnails.insert(name, data);
+				}
+			}
+		}
+		Ok(container)
+	}
+}
+
+fn check_name(name: &str) -> std::io::Result<String> {
+	if name.is_empty() || name.contains(['/', '\\', '\r', '\n']) {
+		return Err(IOError::new(
+			ErrorKind::InvalidInput,
+			format!("invalid entry name {name:?}"),
+		));
+	}
+	Ok(name.to_owned())
+}
+
+fn joined(values: &[f64]) -> String {
+	values.iter().map(f64::to_string).collect::<Vec<_>>().join(" ")
+}
+
+fn parse_values(line: &str) -> Option<Box<[f64]>> {
+	line.split_whitespace().map(|v| v.parse().ok()).collect()
+}
+
+fn profile_text(profile: &Profile<f64>) -> String {
+	format!(
+		"edges {}\nvalues {}\ncoverage {}\n",
+		joined(&profile.edges),
+		joined(&profile.values),
+		joined(&profile.coverage)
+	)
+}
+
+fn parse_profile(text: &str) -> Option<Profile<f64>> {
+	let mut lines = text.lines();
+	let mut field = |key: &str| parse_values(lines.next()?.strip_prefix(key)?);
+	let profile = Profile { edges: field("edges")?, values: field("values")?, coverage: field("coverage")? };
+	(profile.values.len() == profile.coverage.len() && profile.edges.len() == profile.values.len() + 1)
+		.then_some(profile)
+}
+
+/// Binary PBM image with masked pixels black.
+fn pbm(mask: &Mask) -> Vec<u8> {
+	let mut data = format!("P4\n{} {}\n", mask.width, mask.height).into_bytes();
+	for row in mask.pixels().chunks(mask.width.max(1)) {
+		for bits in row.chunks(8) {
+			data.push(
+				bits.iter()
+					.enumerate()
+					.fold(0, |byte, (i, &masked)| byte | (masked as u8) << (7 - i)),
+			);
+		}
+	}
+	data
+}
+
+fn parse_pbm(data: &[u8]) -> Option<Mask> {
+	let mut rest = data.strip_prefix(b"P4")?;
+	let mut number = || {
+		let start = rest.iter().position(|b| !b.is_ascii_whitespace())?;
+		let length = rest[start..].iter().position(|b| !b.is_ascii_digit())?;
+		let value = std::str::from_utf8(&rest[start..start + length])
+			.ok()?
+			.parse::<usize>()
+			.ok();
+		// A single whitespace character ends the header.
+		rest = rest.get(start + length + 1..)?;
+		value
+	};
+	let (width, height) = (number()?, number()?);
+	let stride = width.div_ceil(8);
+	let bits = rest.get(..stride.checked_mul(height)?)?;
+	let pixels = (0..width * height)
+		.map(|i| bits[i / width * stride + i % width / 8] & (0x80 >> (i % width % 8)) != 0)
+		.collect::<Vec<_>>();
+	Image::new(width, height, pixels.into())
+}
+
+fn geometry_text(geometry: &Geometry) -> String {
+	let mut text = format!(
+		"beam_centre {} {}\ndistance {}\npixel_size {} {}\n",
+		geometry.beam_centre.0, geometry.beam_centre.1, geometry.distance, geometry.pixel_size.0, geometry.pixel_size.1
+	);
+	if let Some(wavelength) = geometry.wavelength {
+		writeln!(text, "wavelength {wavelength}").unwrap();
+	}
+	text
+}
+
+fn parse_geometry(text: &str) -> Option<Geometry> {
+	let (mut beam_centre, mut distance, mut pixel_size, mut wavelength) = (None, None, None, None);
+	for line in text.lines() {
+		let (key, values) = line.split_once(' ')?;
+		let values = parse_values(values)?;
+		match (key, &*values) {
+			("beam_centre", &[x, y]) => beam_centre = Some((x, y)),
+			("distance", &[d]) => distance = Some(d),
+			("pixel_size", &[fast, slow]) => pixel_size = Some((fast, slow)),
+			("wavelength", &[w]) => wavelength = Some(w),
+			_ => return None,
+		}
+	}
+	Some(Geometry { beam_centre: beam_centre?, distance: distance?, pixel_size: pixel_size?, wavelength })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Container, Error};
+	use crate::{
+		analysis::Profile,
+		geometry::Geometry,
+		image::mask::{Mask, MaskEdit},
+	};
+
+	fn example() -> Container {
+		let mut container = Container::default();
+		container.profiles.insert(
+			"frame 1".to_owned(),
+			Profile {
+				values: vec![1.5, f64::INFINITY, 0.1].into(),
+				coverage: vec![1.0, 0.5, 0.0].into(),
+				edges: vec![0.0, 1.0, 2.0, 3.0].into(),
+			},
+		);
+		let mut mask = Mask::empty(11, 3).unwrap();
+		MaskEdit::Rectangle { x: 8, y: 1, width: 3, height: 1, masked: true }.apply(&mut mask);
+		container.masks.insert("beamstop".to_owned(), mask);
+		container.geometry =
+			Some(Geometry { beam_centre: (5.5, 1.0), distance: 0.25, pixel_size: (172e-6, 172e-6), wavelength: None });
+		container
+			.provenance
+			.insert("input".to_owned(), "snap_V4_00013.cbf".to_owned());
+		container.thumbnails.insert("frame 1".to_owned(), b"\x89PNG".to_vec());
+		container
+	}
+
+	#[test]
+	fn round_trip() {
+		let container = example();
+		let mut archive = Vec::new();
+		container.write_to(&mut archive).unwrap();
+		assert!(archive.starts_with(b"PK\x03\x04"));
+		let read = Container::read_from(archive.as_slice()).expect("to read");
+		assert_eq!(read.profiles, container.profiles);
+		assert_eq!(read.geometry, container.geometry);
+		assert_eq!(read.provenance, container.provenance);
+		assert_eq!(read.thumbnails, container.thumbnails);
+		let mask = &read.masks["beamstop"];
+		assert_eq!((mask.width, mask.height), (11, 3));
+		assert_eq!(mask.pixels(), container.masks["beamstop"].pixels());
+	}
+
+	#[test]
+	fn rejects_damage() {
+		let mut archive = Vec::new();
+		example().write_to(&mut archive).unwrap();
+		let position = archive.windows(6).position(|w| w == b"values").unwrap();
+		archive[position] = b'V';
+		assert!(matches!(
+			Container::read_from(archive.as_slice()),
+			Err(Error::ChecksumMismatch(name)) if name == "profiles/frame 1.txt"
+		));
+		assert!(matches!(Container::read_from(&b"PK"[..]), Err(Error::InvalidArchive)));
+
+		let mut container = example();
+		container.masks.insert("a/b".to_owned(), Mask::empty(1, 1).unwrap());
+		assert!(container.write_to(Vec::new()).is_err());
+	}
+}
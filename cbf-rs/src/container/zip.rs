@@ -0,0 +1,52 @@
+use std::io::{Cursor, Read, Result, Write};
+
+use ::zip::{result::ZipError, write::SimpleFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use super::Error;
+
+/// Writes `entries` as a zip archive of stored, uncompressed, files.
+pub(super) fn write_archive(mut writer: impl Write, entries: &[(String, Vec<u8>)]) -> Result<()> {
+	// 1980-01-01, the earliest date zip can hold, keeping archives reproducible.
+	let options = SimpleFileOptions::default()
+		.compression_method(CompressionMethod::Stored)
+		.last_modified_time(DateTime::default());
+	let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+	for (name, data) in entries {
+		archive.start_file(name.as_str(), options)?;
+		archive.write_all(data)?;
+	}
+	writer.write_all(&archive.finish()?.into_inner())
+}
+
+/// Names and contents of the files in the zip archive `data`, in the order of
+/// its central directory. Only stored files are supported.
+pub(super) fn read_archive(data: &[u8]) -> std::result::Result<Vec<(String, Vec<u8>)>, Error> {
+	let mut archive = ZipArchive::new(Cursor::new(data)).map_err(archive_error)?;
+	let mut entries = Vec::with_capacity(archive.len());
+	for index in 0..archive.len() {
+		let method = archive.by_index_raw(index).map_err(archive_error)?.compression();
+		let name = archive.name_for_index(index).ok_or(Error::InvalidArchive)?.to_owned();
+		if method != CompressionMethod::Stored {
+			return Err(Error::UnsupportedCompression(name));
+		}
+		let mut contents = Vec::new();
+		// The checksum is verified once the whole entry is read.
+		if archive
+			.by_index(index)
+			.map_err(archive_error)?
+			.read_to_end(&mut contents)
+			.is_err()
+		{
+			return Err(Error::ChecksumMismatch(name));
+		}
+		entries.push((name, contents));
+	}
+	Ok(entries)
+}
+
+fn archive_error(error: ZipError) -> Error {
+	match error {
+		ZipError::Io(error) => Error::IO(error),
+		_ => Error::InvalidArchive,
+	}
+}
@@ -0,0 +1,59 @@
+use std::io::{BufRead, Read, Result};
+
+/// Reader adapter counting the bytes consumed from `reader`.
+pub struct CountingReader<R> {
+	reader: R,
+	count: usize,
+}
+
+impl<R> CountingReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader, count: 0 }
+	}
+
+	/// Bytes consumed since the reader was created.
+	pub fn count(&self) -> usize {
+		self.count
+	}
+
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+impl<R: Read> Read for CountingReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let n = self.reader.read(buf)?;
+		self.count += n;
+		Ok(n)
+	}
+}
+
+impl<R: BufRead> BufRead for CountingReader<R> {
+	fn fill_buf(&mut self) -> Result<&[u8]> {
+		self.reader.fill_buf()
+	}
+
+	fn consume(&mut self, amt: usize) {
+		self.count += amt;
+		self.reader.consume(amt);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{BufRead, Read};
+
+	use super::CountingReader;
+
+	#[test]
+	fn counts_reads_and_consumes() {
+		let mut reader = CountingReader::new(&b"line\nrest"[..]);
+		let mut line = String::new();
+		reader.read_line(&mut line).unwrap();
+		assert_eq!(reader.count(), 5);
+		let mut rest = [0; 2];
+		reader.read_exact(&mut rest).unwrap();
+		assert_eq!(reader.count(), 7);
+	}
+}
@@ -0,0 +1,198 @@
+use num_traits::AsPrimitive;
+
+use crate::image::Image;
+
+use super::Error;
+
+/// Estimates how far the content of `frame` has moved relative to
+/// `reference`, as `(dx, dy)` in pixels such that
+/// `frame(x + dx, y + dy) ≈ reference(x, y)`.
+///
+/// Integer shifts up to `max_shift` in each direction are scored by the
+/// cross-correlation of the mean subtracted images over their overlap, and
+/// the best one is refined to sub-pixel precision by fitting a Gaussian, or a
+/// parabola where the scores around it are not all positive.
+pub fn estimate_shift<P: AsPrimitive<f64>>(
+	reference: &Image<P>,
+	frame: &Image<P>,
+	max_shift: usize,
+) -> Result<(f64, f64), Error> {
+	check_dimensions(reference, frame, 1)?;
+	let reference = centred(reference);
+	let frame = centred(frame);
+	let max_shift = max_shift as isize;
+
+	let side = 2 * max_shift as usize + 1;
+	let mut scores = vec![f64::NEG_INFINITY; side * side];
+	for sy in -max_shift..=max_shift {
+		for sx in -max_shift..=max_shift {
+			let index = (sy + max_shift) as usize * side + (sx + max_shift) as usize;
+			scores[index] = correlation(&reference, &frame, sx, sy);
+		}
+	}
+	let best = (0..scores.len())
+		.max_by(|a, b| scores[*a].total_cmp(&scores[*b]))
+		.unwrap_or(0);
+	let (bx, by) = (best % side, best / side);
+
+	let refine = |before: Option<f64>, at: f64, after: Option<f64>| {
+		let (Some(mut before), mut at, Some(mut after)) = (before, at, after) else {
+			return 0.0;
+		};
+		if 0.0 < before && 0.0 < at && 0.0 < after {
+			(before, at, after) = (before.ln(), at.ln(), after.ln());
+		}
+		let curvature = before - 2.0 * at + after;
+		if curvature < 0.0 {
+			(before - after) / (2.0 * curvature)
+		} else {
+			0.0
+		}
+	};
+	let at = scores[best];
+	let dx = refine(
+		bx.checked_sub(1).map(|x| scores[by * side + x]),
+		at,
+		(bx + 1 < side).then(|| scores[by * side + bx + 1]),
+	);
+	let dy = refine(
+		by.checked_sub(1).map(|y| scores[y * side + bx]),
+		at,
+		(by + 1 < side).then(|| scores[(by + 1) * side + bx]),
+	);
+	Ok((bx as f64 - max_shift as f64 + dx, by as f64 - max_shift as f64 + dy))
+}
+
+/// Resamples `frame` with bilinear interpolation so that
+/// `shifted(x, y) = frame(x + dx, y + dy)`, using `fill` outside the frame.
+pub fn shift_frame<P: AsPrimitive<f64>>(frame: &Image<P>, (dx, dy): (f64, f64), fill: f64) -> Image<f64> {
+	let width = frame.width;
+	let height = frame.height;
+	let pixel = |x: isize, y: isize| -> Option<f64> {
+		if x < 0 || y < 0 || width as isize <= x || height as isize <= y {
+			return None;
+		}
+		Some(frame.pixels()[y as usize * width + x as usize].as_())
+	};
+	let pixels = (0..width * height)
+		.map(|i| {
+			let x = (i % width) as f64 + dx;
+			let y = (i / width) as f64 + dy;
+			let (x0, y0) = (x.floor(), y.floor());
+			let (fx, fy) = (x - x0, y - y0);
+			let (x0, y0) = (x0 as isize, y0 as isize);
+			let mut value = 0.0;
+			for (px, py, weight) in [
+				(x0, y0, (1.0 - fx) * (1.0 - fy)),
+				(x0 + 1, y0, fx * (1.0 - fy)),
+				(x0, y0 + 1, (1.0 - fx) * fy),
+				(x0 + 1, y0 + 1, fx * fy),
+			] {
+				if 0.0 < weight {
+					match pixel(px, py) {
+						Some(p) => value += p * weight,
+						None => return fill,
+					}
+				}
+			}
+			value
+		})
+		.collect();
+	Image::new(width, height, pixels).expect("dimensions of the source frame")
+}
+
+/// Aligns every frame to the first one, see [`estimate_shift`] and
+/// [`shift_frame`].
+pub fn align_frames<P: AsPrimitive<f64>>(
+	frames: &[Image<P>],
+	max_shift: usize,
+	fill: f64,
+) -> Result<Vec<Image<f64>>, Error> {
+	let reference = frames.first().ok_or(Error::NoFrames)?;
+	frames
+		.iter()
+		.enumerate()
+		.map(|(index, frame)| {
+			check_dimensions(reference, frame, index)?;
+			let shift = estimate_shift(reference, frame, max_shift)?;
+			Ok(shift_frame(frame, shift, fill))
+		})
+		.collect()
+}
+
+fn check_dimensions<P>(reference: &Image<P>, frame: &Image<P>, index: usize) -> Result<(), Error> {
+	if frame.width != reference.width || frame.height != reference.height {
+		return Err(Error::DimensionMismatch {
+			index,
+			width: frame.width,
+			height: frame.height,
+			expected_width: reference.width,
+			expected_height: reference.height,
+		});
+	}
+	Ok(())
+}
+
+fn centred<P: AsPrimitive<f64>>(image: &Image<P>) -> Image<f64> {
+	let mean = image.pixels().iter().map(|p| p.as_()).sum::<f64>() / image.pixels().len().max(1) as f64;
+	image.map(|p| p.as_() - mean)
+}
+
+/// Sum of the products of `reference(x, y)` and `frame(x + sx, y + sy)` over
+/// the overlapping pixels.
+fn correlation(reference: &Image<f64>, frame: &Image<f64>, sx: isize, sy: isize) -> f64 {
+	let width = reference.width as isize;
+	let height = reference.height as isize;
+	let xs = 0.max(-sx)..width.min(width - sx);
+	let ys = 0.max(-sy)..height.min(height - sy);
+	if xs.is_empty() || ys.is_empty() {
+		return f64::NEG_INFINITY;
+	}
+	let mut sum = 0.0;
+	for y in ys {
+		for x in xs.clone() {
+			let r = reference.pixels()[(y * width + x) as usize];
+			let f = frame.pixels()[((y + sy) * width + x + sx) as usize];
+			sum += r * f;
+		}
+	}
+	sum
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{align_frames, estimate_shift};
+	use crate::image::Image;
+
+	fn blob(cx: f64, cy: f64) -> Image<f64> {
+		let pixels = (0..32 * 32)
+			.map(|i| {
+				let dx = (i % 32) as f64 - cx;
+				let dy = (i / 32) as f64 - cy;
+				100.0 * (-(dx * dx + dy * dy) / 8.0).exp()
+			})
+			.collect();
+		Image::new(32, 32, pixels).unwrap()
+	}
+
+	#[test]
+	fn estimates_sub_pixel_shift() {
+		let (dx, dy) = estimate_shift(&blob(14.0, 16.0), &blob(16.3, 15.0), 4).unwrap();
+		assert!((dx - 2.3).abs() < 0.05, "dx = {dx}");
+		assert!((dy + 1.0).abs() < 0.05, "dy = {dy}");
+	}
+
+	#[test]
+	fn aligns_to_first_frame() {
+		let frames = [blob(14.0, 16.0), blob(17.0, 14.0)];
+		let aligned = align_frames(&frames, 4, 0.0).unwrap();
+		let peak = aligned[1]
+			.pixels()
+			.iter()
+			.enumerate()
+			.max_by(|a, b| a.1.total_cmp(b.1))
+			.unwrap()
+			.0;
+		assert_eq!((peak % 32, peak / 32), (14, 16));
+	}
+}
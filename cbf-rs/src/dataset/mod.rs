@@ -0,0 +1,33 @@
+mod align;
+mod monitor;
+mod patches;
+mod reorder;
+mod screen;
+mod sum;
+mod timing;
+
+pub use align::{align_frames, estimate_shift, shift_frame};
+pub use monitor::{frame_intensity, monitor_series, Alert, IntensityMonitor, MonitorCriteria};
+pub use patches::{extract_patches, Patches};
+pub use reorder::{frame_order, renumber_blocks, renumbered_name, OrderKey};
+pub use screen::{screen_frames, BlankCriteria, FrameStatus, ScreeningReport};
+pub use sum::{sum_frames, SentinelPolicy, SummedFrames, Widen};
+pub use timing::{frame_times, frame_times_from_headers, FrameTime, TimeSource};
+
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError, PartialEq, Eq)]
+pub enum Error {
+	#[error("no frames")]
+	NoFrames,
+	#[error("frame {index} has dimensions {width}x{height}, expected {expected_width}x{expected_height}")]
+	DimensionMismatch {
+		index: usize,
+		width: usize,
+		height: usize,
+		expected_width: usize,
+		expected_height: usize,
+	},
+	#[error("region lies outside frame {index} of {width}x{height}")]
+	RegionOutOfBounds { index: usize, width: usize, height: usize },
+}
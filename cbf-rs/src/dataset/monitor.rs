@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+
+use num_traits::AsPrimitive;
+
+use crate::{analysis::Roi, image::Image};
+
+/// Total intensity of a frame, or of the pixels within `roi`. NaN pixels are
+/// skipped.
+pub fn frame_intensity<P: AsPrimitive<f64>>(frame: &Image<P>, roi: Option<Roi>) -> f64 {
+	let roi = roi.unwrap_or(Roi { x: 0, y: 0, width: frame.width, height: frame.height });
+	let columns = roi.x.min(frame.width)..(roi.x.saturating_add(roi.width)).min(frame.width);
+	(frame.rows().skip(roi.y).take(roi.height))
+		.flat_map(|row| row[columns.clone()].iter())
+		.map(|p| p.as_())
+		.filter(|v| !v.is_nan())
+		.sum()
+}
+
+/// Thresholds of an [`IntensityMonitor`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonitorCriteria {
+	window: usize,
+	drop_out: f64,
+	drift: f64,
+}
+
+impl MonitorCriteria {
+	/// A frame is a drop-out when its intensity falls more than `drop_out`
+	/// below the median of the last `window` frames, and the series drifts
+	/// when the mean of the last `window` frames is more than `drift` away
+	/// from the mean of the first `window` frames. Both are fractions.
+	pub fn new(window: usize, drop_out: f64, drift: f64) -> Option<Self> {
+		if window == 0 || !(0.0..=1.0).contains(&drop_out) || drift.is_nan() || drift < 0.0 {
+			return None;
+		}
+		Some(Self { window, drop_out, drift })
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alert {
+	DropOut {
+		frame: usize,
+		intensity: f64,
+		expected: f64,
+	},
+	/// The series moved `relative` away from its baseline, reported once
+	/// until it returns within the threshold.
+	Drift { frame: usize, relative: f64 },
+}
+
+/// Watches the intensity of frames as they are collected.
+#[derive(Debug, Clone)]
+pub struct IntensityMonitor {
+	criteria: MonitorCriteria,
+	recent: VecDeque<f64>,
+	baseline: Option<f64>,
+	drifting: bool,
+	frame: usize,
+}
+
+impl IntensityMonitor {
+	pub fn new(criteria: MonitorCriteria) -> Self {
+		Self { criteria, recent: VecDeque::new(), baseline: None, drifting: false, frame: 0 }
+	}
+
+	/// Adds the intensity of the next frame. Drop-outs are not added to the
+	/// window, so they do not lower the expectation for the following frames.
+	pub fn push(&mut self, intensity: f64) -> Option<Alert> {
+		let frame = self.frame;
+		self.frame += 1;
+
+		if self.recent.len() == self.criteria.window {
+			let expected = median(&self.recent);
+			if intensity < expected * (1.0 - self.criteria.drop_out) {
+				return Some(Alert::DropOut { frame, intensity, expected });
+			}
+			self.recent.pop_front();
+		}
+		self.recent.push_back(intensity);
+		if self.recent.len() < self.criteria.window {
+			return None;
+		}
+
+		let mean = self.recent.iter().sum::<f64>() / self.recent.len() as f64;
+		let baseline = *self.baseline.get_or_insert(mean);
+		let relative = mean / baseline - 1.0;
+		let drifting = self.criteria.drift < relative.abs();
+		let started = drifting && !self.drifting;
+		self.drifting = drifting;
+		started.then_some(Alert::Drift { frame, relative })
+	}
+}
+
+fn median(values: &VecDeque<f64>) -> f64 {
+	let mut values: Vec<_> = values.iter().copied().collect();
+	values.sort_unstable_by(f64::total_cmp);
+	let middle = values.len() / 2;
+	if values.len().is_multiple_of(2) {
+		(values[middle - 1] + values[middle]) / 2.0
+	} else {
+		values[middle]
+	}
+}
+
+/// Runs an [`IntensityMonitor`] over the intensities of a whole series.
+pub fn monitor_series<'a, P: AsPrimitive<f64>>(
+	frames: impl IntoIterator<Item = &'a Image<P>>,
+	roi: Option<Roi>,
+	criteria: MonitorCriteria,
+) -> (Vec<f64>, Vec<Alert>) {
+	let mut monitor = IntensityMonitor::new(criteria);
+	let intensities: Vec<_> = frames.into_iter().map(|f| frame_intensity(f, roi)).collect();
+	let alerts = intensities.iter().filter_map(|&i| monitor.push(i)).collect();
+	(intensities, alerts)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{frame_intensity, Alert, IntensityMonitor, MonitorCriteria};
+	use crate::{analysis::Roi, image::Image};
+
+	#[test]
+	fn sums_roi() {
+		let frame = Image::new(3, 2, vec![1u8, 2, 3, 4, 5, 6].into()).unwrap();
+		assert_eq!(frame_intensity(&frame, None), 21.0);
+		let roi = Roi { x: 1, y: 1, width: 5, height: 5 };
+		assert_eq!(frame_intensity(&frame, Some(roi)), 11.0);
+	}
+
+	#[test]
+	fn detects_drop_out_and_drift() {
+		let mut monitor = IntensityMonitor::new(MonitorCriteria::new(3, 0.5, 0.2).unwrap());
+		let series = [100.0, 101.0, 99.0, 10.0, 100.0, 90.0, 80.0, 70.0, 70.0];
+		let alerts: Vec<_> = series.iter().filter_map(|&i| monitor.push(i)).collect();
+
+		assert_eq!(alerts.len(), 2);
+		assert_eq!(alerts[0], Alert::DropOut { frame: 3, intensity: 10.0, expected: 100.0 });
+		assert!(matches!(alerts[1], Alert::Drift { frame: 8, relative } if relative < -0.2));
+	}
+}
@@ -0,0 +1,94 @@
+use crate::{analysis::Roi, image::Image};
+
+use super::Error;
+
+/// The same region cut from every frame of a series, as an array indexed by
+/// frame, row and column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patches<P> {
+	pub frame_count: usize,
+	pub width: usize,
+	pub height: usize,
+	values: Box<[P]>,
+}
+
+impl<P> Patches<P> {
+	/// All values, frame by frame with each frame in row major order.
+	pub fn values(&self) -> &[P] {
+		&self.values
+	}
+
+	/// The patch of one frame in row major order.
+	pub fn frame(&self, index: usize) -> Option<&[P]> {
+		let len = self.width * self.height;
+		(index < self.frame_count).then(|| &self.values[index * len..(index + 1) * len])
+	}
+
+	pub fn get(&self, frame: usize, x: usize, y: usize) -> Option<&P> {
+		if self.width <= x || self.height <= y {
+			return None;
+		}
+		self.frame(frame).map(|patch| &patch[y * self.width + x])
+	}
+
+	/// The value of one pixel of the patch in every frame.
+	pub fn series(&self, x: usize, y: usize) -> impl Iterator<Item = &P> {
+		(0..self.frame_count).filter_map(move |frame| self.get(frame, x, y))
+	}
+}
+
+/// Copies the pixels within `rect` from every frame. Returns an error if the
+/// region does not lie entirely within a frame.
+pub fn extract_patches<'a, P: Copy + 'a>(
+	frames: impl IntoIterator<Item = &'a Image<P>>,
+	rect: Roi,
+) -> Result<Patches<P>, Error> {
+	let columns = rect.x..rect.x.saturating_add(rect.width);
+	let mut values = Vec::new();
+	let mut frame_count = 0;
+	for (index, frame) in frames.into_iter().enumerate() {
+		if frame.width < columns.end || frame.height < rect.y.saturating_add(rect.height) {
+			return Err(Error::RegionOutOfBounds { index, width: frame.width, height: frame.height });
+		}
+		for row in frame.rows().skip(rect.y).take(rect.height) {
+			values.extend_from_slice(&row[columns.clone()]);
+		}
+		frame_count += 1;
+	}
+	if frame_count == 0 {
+		return Err(Error::NoFrames);
+	}
+	Ok(Patches { frame_count, width: rect.width, height: rect.height, values: values.into() })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::extract_patches;
+	use crate::{analysis::Roi, dataset::Error, image::Image};
+
+	fn frame(offset: u16) -> Image<u16> {
+		Image::new(4, 3, (0..12).map(|i| i + offset).collect::<Vec<_>>().into()).unwrap()
+	}
+
+	#[test]
+	fn stacks_regions() {
+		let frames = [frame(0), frame(100)];
+		let patches = extract_patches(&frames, Roi { x: 1, y: 1, width: 2, height: 2 }).unwrap();
+		assert_eq!((patches.frame_count, patches.width, patches.height), (2, 2, 2));
+		assert_eq!(patches.frame(1), Some(&[105, 106, 109, 110][..]));
+		assert_eq!(patches.series(1, 0).copied().collect::<Vec<_>>(), [6, 106]);
+		assert_eq!(patches.get(0, 2, 0), None);
+		assert_eq!(patches.frame(2), None);
+	}
+
+	#[test]
+	fn rejects_region_outside_frame() {
+		let frames = [frame(0), Image::new(2, 2, vec![0; 4].into()).unwrap()];
+		let region = Roi { x: 1, y: 0, width: 3, height: 1 };
+		assert_eq!(
+			extract_patches(&frames, region),
+			Err(Error::RegionOutOfBounds { index: 1, width: 2, height: 2 })
+		);
+		assert_eq!(extract_patches::<u16>([], region), Err(Error::NoFrames));
+	}
+}
@@ -0,0 +1,125 @@
+use crate::{debug::find, decoder::Decoder, metadata::miniheader::MiniHeader, Error};
+
+const SECTION_START: &[u8] = b"--CIF-BINARY-FORMAT-SECTION--";
+
+/// Header value frames are put in order by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderKey {
+	/// Goniometer angle at the start of the exposure
+	Angle,
+	Timestamp,
+}
+
+impl OrderKey {
+	fn of(self, header: &MiniHeader) -> Option<f64> {
+		match self {
+			OrderKey::Angle => header.start_angle,
+			OrderKey::Timestamp => header.timestamp.map(|t| t.seconds()),
+		}
+	}
+}
+
+/// Indices of the frames sorted by `key`, for merging datasets collected in
+/// interleaved wedges. Frames with equal keys keep their order. Returns
+/// `None` if any header lacks the key.
+pub fn frame_order(headers: &[MiniHeader], key: OrderKey) -> Option<Vec<usize>> {
+	let keys = headers
+		.iter()
+		.map(|h| key.of(h).filter(|k| !k.is_nan()))
+		.collect::<Option<Vec<_>>>()?;
+	let mut order: Vec<usize> = (0..headers.len()).collect();
+	order.sort_by(|a, b| keys[*a].total_cmp(&keys[*b]));
+	Some(order)
+}
+
+/// `name` with its last run of digits replaced by `number`, zero padded to
+/// the width of the run, such as `scan_00013.cbf` to `scan_00002.cbf`.
+/// Returns `None` if `name` contains no digits.
+pub fn renumbered_name(name: &str, number: usize) -> Option<String> {
+	let end = name.rfind(|c: char| c.is_ascii_digit())? + 1;
+	let start = name[..end].rfind(|c: char| !c.is_ascii_digit()).map_or(0, |i| i + 1);
+	Some(format!(
+		"{}{number:0width$}{}",
+		&name[..start],
+		&name[end..],
+		width = end - start
+	))
+}
+
+/// Copy of the file `data` with the sequence number ending each `data_` block
+/// name rewritten, numbering its frames from `first`. Binary sections are
+/// copied unchanged.
+pub fn renumber_blocks(data: &[u8], first: usize, decoder: &Decoder) -> Result<Vec<u8>, Error> {
+	let index = decoder.index_frames(data)?;
+	let mut output = Vec::with_capacity(data.len());
+	let mut copied = 0;
+	for (number, frame) in (first..).zip(index.frames()) {
+		let text_end = frame.range.start + find(&data[frame.range.clone()], SECTION_START).unwrap_or(0);
+		let mut line_start = frame.range.start;
+		for line in data[frame.range.start..text_end].split_inclusive(|b| *b == b'\n') {
+			let renamed = line
+				.strip_prefix(b"data_")
+				.and_then(|_| std::str::from_utf8(line).ok())
+				.and_then(|line| {
+					let name = line.trim_end();
+					renumbered_name(name, number).map(|name| name + &line[line.trim_end().len()..])
+				});
+			if let Some(renamed) = renamed {
+				output.extend_from_slice(&data[copied..line_start]);
+				output.extend_from_slice(renamed.as_bytes());
+				copied = line_start + line.len();
+			}
+			line_start += line.len();
+		}
+	}
+	output.extend_from_slice(&data[copied..]);
+	Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{frame_order, renumber_blocks, renumbered_name, OrderKey};
+	use crate::{
+		decoder::Decoder,
+		metadata::miniheader::{read_miniheader, MiniHeader, Timestamp},
+	};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("../examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn orders_interleaved_wedges() {
+		let header = |angle: f64, time: f64| MiniHeader {
+			start_angle: Some(angle),
+			timestamp: Some(Timestamp(time)),
+			..MiniHeader::default()
+		};
+		let headers = [header(0.0, 0.0), header(10.0, 1.0), header(0.1, 2.0), header(10.1, 3.0)];
+		assert_eq!(frame_order(&headers, OrderKey::Angle), Some(vec![0, 2, 1, 3]));
+		assert_eq!(frame_order(&headers, OrderKey::Timestamp), Some(vec![0, 1, 2, 3]));
+		assert_eq!(frame_order(&[MiniHeader::default()], OrderKey::Angle), None);
+	}
+
+	#[test]
+	fn renumbers_names() {
+		assert_eq!(renumbered_name("scan_00013.cbf", 2).as_deref(), Some("scan_00002.cbf"));
+		assert_eq!(renumbered_name("data_7", 123).as_deref(), Some("data_123"));
+		assert_eq!(renumbered_name("image.cbf", 1), None);
+	}
+
+	#[test]
+	fn renumbers_blocks() {
+		let data = [EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		let renumbered = renumber_blocks(&data, 41, &Decoder::default()).expect("to renumber");
+		assert_eq!(renumbered.len(), data.len());
+		let text = String::from_utf8_lossy(&renumbered);
+		assert_eq!(text.matches("data_snap_V4_00041\r\n").count(), 1);
+		assert_eq!(text.matches("data_snap_V4_00042\r\n").count(), 1);
+		assert!(!text.contains("data_snap_V4_00013"));
+		let decoder = Decoder::default();
+		assert_eq!(decoder.read_all_headers(renumbered.as_slice()).unwrap().len(), 2);
+		assert_eq!(
+			read_miniheader(renumbered.as_slice()).unwrap(),
+			read_miniheader(EXAMPLE_DATA).unwrap()
+		);
+	}
+}
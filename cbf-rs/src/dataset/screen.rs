@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use num_traits::AsPrimitive;
+
+use crate::image::{checksum::LeBytes, Image};
+
+/// Thresholds below which a frame is considered blank, e.g. because the
+/// shutter was closed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlankCriteria {
+	max_mean: f64,
+	max_std_dev: f64,
+}
+
+impl BlankCriteria {
+	pub fn new(max_mean: f64, max_std_dev: f64) -> Option<Self> {
+		if max_mean.is_nan() || max_std_dev.is_nan() || max_std_dev < 0.0 {
+			return None;
+		}
+		Some(Self { max_mean, max_std_dev })
+	}
+
+	fn is_blank<P: AsPrimitive<f64>>(&self, frame: &Image<P>) -> bool {
+		let count = frame.pixels().len() as f64;
+		let mean = frame.pixels().iter().map(|p| p.as_()).sum::<f64>() / count;
+		if mean.is_nan() || self.max_mean < mean {
+			return false;
+		}
+		let variance = frame.pixels().iter().map(|p| (p.as_() - mean).powi(2)).sum::<f64>() / count;
+		variance.sqrt() <= self.max_std_dev
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameStatus {
+	Kept,
+	/// Identical to the earlier frame at index `of`
+	Duplicate {
+		of: usize,
+	},
+	Blank,
+}
+
+/// Status of every frame of a dataset, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreeningReport {
+	pub statuses: Vec<FrameStatus>,
+}
+
+impl ScreeningReport {
+	/// Indices of the frames that were not flagged.
+	pub fn kept(&self) -> impl Iterator<Item = usize> + '_ {
+		(self.statuses.iter().enumerate()).filter_map(|(i, s)| (*s == FrameStatus::Kept).then_some(i))
+	}
+
+	/// Indices of the frames flagged as duplicate or blank.
+	pub fn excluded(&self) -> impl Iterator<Item = usize> + '_ {
+		(self.statuses.iter().enumerate()).filter_map(|(i, s)| (*s != FrameStatus::Kept).then_some(i))
+	}
+
+	/// The frames of `frames` that were not flagged, e.g. for [`sum_frames`].
+	///
+	/// [`sum_frames`]: super::sum_frames
+	pub fn retain<'a, T>(&'a self, frames: &'a [T]) -> impl Iterator<Item = &'a T> + 'a {
+		self.kept().filter_map(|i| frames.get(i))
+	}
+}
+
+/// Flags frames that are blank according to `blank`, and frames identical
+/// to an earlier one. Duplicates are found by checksum and confirmed by
+/// comparing pixels. A blank frame is flagged as blank even if it repeats an
+/// earlier one.
+pub fn screen_frames<'a, P>(frames: impl IntoIterator<Item = &'a Image<P>>, blank: &BlankCriteria) -> ScreeningReport
+where
+	P: LeBytes + AsPrimitive<f64> + PartialEq,
+{
+	// Earlier kept frames by dimensions and checksum
+	let mut seen = HashMap::<_, Vec<(usize, &Image<P>)>>::new();
+	let mut statuses = Vec::new();
+
+	for (index, frame) in frames.into_iter().enumerate() {
+		if blank.is_blank(frame) {
+			statuses.push(FrameStatus::Blank);
+			continue;
+		}
+		let candidates = seen.entry((frame.width, frame.height, frame.crc32())).or_default();
+		match candidates.iter().find(|(_, other)| other.pixels() == frame.pixels()) {
+			Some(&(of, _)) => statuses.push(FrameStatus::Duplicate { of }),
+			None => {
+				candidates.push((index, frame));
+				statuses.push(FrameStatus::Kept);
+			}
+		}
+	}
+
+	ScreeningReport { statuses }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{screen_frames, BlankCriteria, FrameStatus};
+	use crate::image::Image;
+
+	fn frame(pixels: &[u16]) -> Image<u16> {
+		Image::new(2, 2, pixels.into()).unwrap()
+	}
+
+	#[test]
+	fn flags_duplicates_and_blanks() {
+		let frames = [
+			frame(&[10, 20, 30, 40]),
+			frame(&[0, 1, 0, 0]),
+			frame(&[10, 20, 30, 41]),
+			frame(&[10, 20, 30, 40]),
+			frame(&[0, 1, 0, 0]),
+		];
+		let report = screen_frames(&frames, &BlankCriteria::new(1.0, 1.0).unwrap());
+
+		assert_eq!(
+			report.statuses,
+			[
+				FrameStatus::Kept,
+				FrameStatus::Blank,
+				FrameStatus::Kept,
+				FrameStatus::Duplicate { of: 0 },
+				FrameStatus::Blank,
+			]
+		);
+		assert_eq!(report.excluded().collect::<Vec<_>>(), [1, 3, 4]);
+		assert_eq!(report.retain(&frames).count(), 2);
+	}
+
+	#[test]
+	fn rejects_invalid_criteria() {
+		assert!(BlankCriteria::new(f64::NAN, 1.0).is_none());
+		assert!(BlankCriteria::new(1.0, -1.0).is_none());
+	}
+}
@@ -0,0 +1,160 @@
+use crate::image::Image;
+
+use super::Error;
+
+/// How saturated pixels are recognised in the contributing frames.
+pub enum SentinelPolicy<P> {
+	/// No pixel is considered saturated
+	Ignore,
+	/// Pixels equal to the value are saturated
+	Equal(P),
+	/// Pixels at or above the value are saturated
+	AtLeast(P),
+}
+
+impl<P: PartialOrd> SentinelPolicy<P> {
+	pub fn is_saturated(&self, value: &P) -> bool {
+		match self {
+			SentinelPolicy::Ignore => false,
+			SentinelPolicy::Equal(sentinel) => value == sentinel,
+			SentinelPolicy::AtLeast(threshold) => value >= threshold,
+		}
+	}
+}
+
+pub struct SummedFrames<W> {
+	pub image: Image<W>,
+	/// `true` for every pixel saturated in at least one frame
+	pub saturated: Image<bool>,
+	pub frame_count: usize,
+}
+
+/// Sums frames pixel by pixel in a widened accumulator type, flagging every
+/// pixel that was saturated in any of the contributing frames.
+pub fn sum_frames<'a, P>(
+	frames: impl IntoIterator<Item = &'a Image<P>>,
+	sentinel_policy: &SentinelPolicy<P>,
+) -> Result<SummedFrames<P::Wide>, Error>
+where
+	P: Widen + PartialOrd + 'a,
+{
+	let mut frames = frames.into_iter();
+	let first = frames.next().ok_or(Error::NoFrames)?;
+
+	let mut image = first.map(|p| p.widen());
+	let mut saturated = first.map(|p| sentinel_policy.is_saturated(p));
+	let mut frame_count = 1;
+
+	for (index, frame) in frames.enumerate() {
+		if frame.width != image.width || frame.height != image.height {
+			return Err(Error::DimensionMismatch {
+				index: index + 1,
+				width: frame.width,
+				height: frame.height,
+				expected_width: image.width,
+				expected_height: image.height,
+			});
+		}
+		let sums = image.pixels_mut().iter_mut().zip(saturated.pixels_mut());
+		for ((sum, saturated), pixel) in sums.zip(frame.pixels()) {
+			P::accumulate(sum, *pixel);
+			*saturated |= sentinel_policy.is_saturated(pixel);
+		}
+		frame_count += 1;
+	}
+
+	Ok(SummedFrames { image, saturated, frame_count })
+}
+
+/// Pixel types that can be summed without overflowing in a wider type.
+pub trait Widen: Copy {
+	type Wide: Copy;
+
+	fn widen(self) -> Self::Wide;
+
+	fn accumulate(sum: &mut Self::Wide, value: Self);
+}
+
+macro_rules! integer_widen {
+	($($type:ty => $wide:ty),*) => {
+		$(impl Widen for $type {
+			type Wide = $wide;
+
+			fn widen(self) -> $wide {
+				self as $wide
+			}
+
+			fn accumulate(sum: &mut $wide, value: $type) {
+				*sum = sum.saturating_add(value as $wide);
+			}
+		})*
+	};
+}
+
+integer_widen!(u8 => u64, i8 => i64, u16 => u64, i16 => i64, u32 => u64, i32 => i64, u64 => u64, i64 => i64);
+
+macro_rules! float_widen {
+	($($type:ty),*) => {
+		$(impl Widen for $type {
+			type Wide = f64;
+
+			fn widen(self) -> f64 {
+				self as f64
+			}
+
+			fn accumulate(sum: &mut f64, value: $type) {
+				*sum += value as f64;
+			}
+		})*
+	};
+}
+
+float_widen!(f32, f64);
+
+#[cfg(test)]
+mod tests {
+	use super::{sum_frames, SentinelPolicy};
+	use crate::{dataset::Error, image::Image};
+
+	fn image<P>(width: usize, height: usize, pixels: Vec<P>) -> Image<P> {
+		Image::new(width, height, pixels.into()).expect("valid dimensions")
+	}
+
+	#[test]
+	fn sums_in_wider_type() {
+		let frames = [image(2, 1, vec![200u8, 1]), image(2, 1, vec![200u8, 2])];
+		let summed = sum_frames(&frames, &SentinelPolicy::Ignore).expect("to sum frames");
+		assert_eq!(summed.image.pixels(), &[400u64, 3]);
+		assert_eq!(summed.saturated.pixels(), &[false, false]);
+		assert_eq!(summed.frame_count, 2);
+	}
+
+	#[test]
+	fn propagates_saturation() {
+		let frames = [
+			image(3, 1, vec![1i32, 1_048_500, 5]),
+			image(3, 1, vec![1_048_500i32, 3, 5]),
+		];
+		let summed = sum_frames(&frames, &SentinelPolicy::AtLeast(1_048_500)).expect("to sum frames");
+		assert_eq!(summed.image.pixels(), &[1_048_501i64, 1_048_503, 10]);
+		assert_eq!(summed.saturated.pixels(), &[true, true, false]);
+	}
+
+	#[test]
+	fn rejects_mismatched_dimensions() {
+		let frames = [image(2, 1, vec![0u16, 0]), image(1, 2, vec![0u16, 0])];
+		assert_eq!(
+			sum_frames(&frames, &SentinelPolicy::Equal(u16::MAX)).err(),
+			Some(Error::DimensionMismatch { index: 1, width: 1, height: 2, expected_width: 2, expected_height: 1 })
+		);
+	}
+
+	#[test]
+	fn rejects_empty_series() {
+		let frames: [Image<f32>; 0] = [];
+		assert_eq!(
+			sum_frames(&frames, &SentinelPolicy::Ignore).err(),
+			Some(Error::NoFrames)
+		);
+	}
+}
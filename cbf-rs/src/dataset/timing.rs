@@ -0,0 +1,147 @@
+use crate::metadata::miniheader::MiniHeader;
+
+/// How the time of a frame was determined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeSource {
+	/// Read from the frame's own header
+	Measured,
+	/// Between two frames with known times
+	Interpolated,
+	/// Before the first or after the last frame with a known time
+	Extrapolated,
+}
+
+/// Start of the acquisition of a frame, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameTime {
+	pub seconds: f64,
+	pub source: TimeSource,
+}
+
+/// Acquisition times of every frame of a series, given the times known for
+/// some of them and the frame period, the exposure time plus the readout dead
+/// time.
+///
+/// Frames between two known times are spaced evenly between them, which
+/// follows the actual rate of the detector. Frames outside the known times
+/// are spaced by `period`, or without one by the rate between the nearest
+/// two known times. Without any known time the axis starts at zero. Returns
+/// `None` if neither the known times nor `period` determine the spacing.
+pub fn frame_times(timestamps: &[Option<f64>], period: Option<f64>) -> Option<Vec<FrameTime>> {
+	if timestamps.is_empty() {
+		return Some(Vec::new());
+	}
+	let period = period.filter(|p| p.is_finite() && 0.0 < *p);
+	let known: Vec<(usize, f64)> = timestamps
+		.iter()
+		.enumerate()
+		.filter_map(|(index, time)| time.filter(|t| t.is_finite()).map(|t| (index, t)))
+		.collect();
+	let rate = |(a, ta): (usize, f64), (b, tb): (usize, f64)| (tb - ta) / (b - a) as f64;
+	let (first, last, before, after) = match known[..] {
+		[] => ((0, 0.0), (0, 0.0), period?, period?),
+		[only] => (only, only, period?, period?),
+		[first, second, ..] => {
+			let (second_last, last) = (known[known.len() - 2], known[known.len() - 1]);
+			(
+				first,
+				last,
+				period.unwrap_or_else(|| rate(first, second)),
+				period.unwrap_or_else(|| rate(second_last, last)),
+			)
+		}
+	};
+
+	let mut times = Vec::with_capacity(timestamps.len());
+	let mut next = 0;
+	for index in 0..timestamps.len() {
+		while next < known.len() && known[next].0 < index {
+			next += 1;
+		}
+		let time = match known.get(next) {
+			Some(&(at, seconds)) if at == index => FrameTime { seconds, source: TimeSource::Measured },
+			_ if index < first.0 => {
+				FrameTime { seconds: first.1 - before * (first.0 - index) as f64, source: TimeSource::Extrapolated }
+			}
+			Some(&following) if 0 < next => {
+				let previous = known[next - 1];
+				FrameTime {
+					seconds: previous.1 + rate(previous, following) * (index - previous.0) as f64,
+					source: TimeSource::Interpolated,
+				}
+			}
+			_ => FrameTime { seconds: last.1 + after * (index - last.0) as f64, source: TimeSource::Extrapolated },
+		};
+		times.push(time);
+	}
+	Some(times)
+}
+
+/// Like [`frame_times`], with the times and period read from the mini-header
+/// of each frame. The period is the first `Exposure_period` found, or failing
+/// that the first `Exposure_time`, which assumes no dead time.
+pub fn frame_times_from_headers(headers: &[MiniHeader]) -> Option<Vec<FrameTime>> {
+	let timestamps: Vec<_> = headers.iter().map(|h| h.timestamp.map(|t| t.seconds())).collect();
+	let period = headers
+		.iter()
+		.find_map(|h| h.exposure_period)
+		.or_else(|| headers.iter().find_map(|h| h.exposure_time))
+		.map(|t| t.seconds());
+	frame_times(&timestamps, period)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{frame_times, frame_times_from_headers, TimeSource};
+	use crate::metadata::miniheader::{MiniHeader, Time, Timestamp};
+
+	fn seconds(times: &[super::FrameTime]) -> Vec<f64> {
+		times.iter().map(|t| t.seconds).collect()
+	}
+
+	#[test]
+	fn interpolates_and_extrapolates() {
+		let times = frame_times(&[None, Some(10.0), None, None, Some(13.0), None], Some(0.5)).unwrap();
+		assert_eq!(seconds(&times), [9.5, 10.0, 11.0, 12.0, 13.0, 13.5]);
+		let sources: Vec<_> = times.iter().map(|t| t.source).collect();
+		assert_eq!(
+			sources,
+			[
+				TimeSource::Extrapolated,
+				TimeSource::Measured,
+				TimeSource::Interpolated,
+				TimeSource::Interpolated,
+				TimeSource::Measured,
+				TimeSource::Extrapolated,
+			]
+		);
+	}
+
+	#[test]
+	fn rate_without_period() {
+		let times = frame_times(&[Some(1.0), None, Some(2.0), None], None).unwrap();
+		assert_eq!(seconds(&times), [1.0, 1.5, 2.0, 2.5]);
+		assert!(frame_times(&[None, Some(1.0)], None).is_none());
+		assert!(frame_times(&[None, None], None).is_none());
+	}
+
+	#[test]
+	fn period_only() {
+		let times = frame_times(&[None, None, None], Some(0.25)).unwrap();
+		assert_eq!(seconds(&times), [0.0, 0.25, 0.5]);
+		assert!(times.iter().all(|t| t.source == TimeSource::Extrapolated));
+	}
+
+	#[test]
+	fn from_headers() {
+		let header = |timestamp: Option<f64>| MiniHeader {
+			exposure_time: Some(Time(0.097)),
+			exposure_period: Some(Time(0.1)),
+			timestamp: timestamp.map(Timestamp),
+			..MiniHeader::default()
+		};
+		let headers = [header(Some(100.0)), header(None), header(None)];
+		let times = frame_times_from_headers(&headers).unwrap();
+		assert_eq!(seconds(&times), [100.0, 100.1, 100.2]);
+	}
+}
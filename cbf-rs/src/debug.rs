@@ -0,0 +1,542 @@
+use std::{
+	borrow::Cow,
+	collections::{BTreeSet, HashMap},
+	fmt::Debug,
+	io::{Cursor, Read, Result, Write},
+};
+
+use crate::{
+	decoder::Decoder,
+	image::{Image, ImageEnum},
+	metadata::{
+		headers::{read_ordered_headers, Headers},
+		read_metadata, Error as MetadataError, ErrorKind,
+	},
+	progress_reader_to_cbf_end, progress_reader_to_cbf_start, Error, BINARY_HEADER, SECTION_END, SECTION_START,
+};
+
+pub(crate) const BINARY_SECTION_START: &[u8] = SECTION_START.as_bytes();
+pub(crate) const BINARY_SECTION_END: &[u8] = SECTION_END.as_bytes();
+pub(crate) const BINARY_MARKER: [u8; 4] = BINARY_HEADER;
+const SNIPPET_LENGTH: usize = 32;
+
+/// Writes the location and length of every CIF data block, MIME header,
+/// binary marker and terminator found in `reader` to `writer`, with hexdumps
+/// of the bytes around anything that does not look like a valid CBF.
+pub fn dump_structure(mut reader: impl Read, mut writer: impl Write) -> Result<()> {
+	let mut data = Vec::new();
+	reader.read_to_end(&mut data)?;
+
+	let mut offset = 0;
+	while offset < data.len() {
+		let line = next_line(&data, offset);
+		if line == BINARY_SECTION_START {
+			writeln!(writer, "{offset:#010x} binary section start")?;
+			offset = dump_binary_section(&data, offset + line.len(), &mut writer)?;
+			continue;
+		}
+		if line == BINARY_SECTION_END {
+			writeln!(writer, "{offset:#010x} anomaly: binary section end without start")?;
+			hexdump(&data, offset, &mut writer)?;
+		} else if let Some(name) = line.strip_prefix(b"data_") {
+			writeln!(
+				writer,
+				"{offset:#010x} data block {:?}",
+				String::from_utf8_lossy(name).trim_end()
+			)?;
+		}
+		offset += line.len();
+	}
+	writeln!(writer, "{offset:#010x} end of file")
+}
+
+fn dump_binary_section(data: &[u8], start: usize, mut writer: impl Write) -> Result<usize> {
+	let mut offset = start;
+	loop {
+		let line = next_line(data, offset);
+		if line.is_empty() || line == b"\r\n" {
+			offset += line.len();
+			break;
+		}
+		let text = String::from_utf8_lossy(line);
+		writeln!(writer, "{offset:#010x}   header {}", text.trim_end())?;
+		offset += line.len();
+	}
+
+	let mut cursor = Cursor::new(&data[start..]);
+	let metadata = match read_metadata(&mut cursor) {
+		Ok(metadata) => metadata,
+		Err(error) => {
+			writeln!(writer, "{start:#010x} anomaly: invalid headers: {error} ({error:?})")?;
+			hexdump(data, start, &mut writer)?;
+			return Ok(offset);
+		}
+	};
+
+	if data.get(offset..offset + BINARY_MARKER.len()) != Some(&BINARY_MARKER) {
+		writeln!(
+			writer,
+			"{offset:#010x} anomaly: expected binary marker {BINARY_MARKER:02x?}"
+		)?;
+		hexdump(data, offset, &mut writer)?;
+		return Ok(offset);
+	}
+	writeln!(writer, "{offset:#010x} binary marker")?;
+	offset += BINARY_MARKER.len();
+
+	// The declared size is untrusted and may not even fit in the address space.
+	let Some(end) = offset.checked_add(metadata.size).filter(|end| *end <= data.len()) else {
+		writeln!(
+			writer,
+			"{offset:#010x} anomaly: binary data of {} bytes truncated to {} bytes",
+			metadata.size,
+			data.len() - offset
+		)?;
+		hexdump(data, data.len().saturating_sub(SNIPPET_LENGTH / 2), &mut writer)?;
+		return Ok(data.len());
+	};
+	writeln!(writer, "{offset:#010x} binary data, {} bytes", metadata.size)?;
+
+	match find(&data[end..], BINARY_SECTION_END) {
+		Some(position) => {
+			if 0 < position {
+				writeln!(
+					writer,
+					"{end:#010x} {position} bytes between binary data and section end"
+				)?;
+			}
+			writeln!(writer, "{:#010x} binary section end", end + position)?;
+			Ok(end + position + BINARY_SECTION_END.len())
+		}
+		None => {
+			writeln!(writer, "{end:#010x} anomaly: missing binary section end")?;
+			hexdump(data, end, &mut writer)?;
+			Ok(end)
+		}
+	}
+}
+
+/// Sizes of a binary section and of its pixels under byte offset
+/// compression, whatever compression the section itself uses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionReport {
+	/// Size of the binary data as stored
+	pub compressed_size: usize,
+	/// Size of the pixels in the declared element type
+	pub decompressed_size: usize,
+	/// Number of byte offset deltas taking 1, 3, 7 and 15 bytes
+	pub delta_sizes: [usize; 4],
+	/// Shannon entropy of the deltas in bits per pixel, a bound on what any
+	/// coder of independent deltas could reach
+	pub entropy: f64,
+}
+
+impl CompressionReport {
+	/// Decompressed over compressed size.
+	pub fn ratio(&self) -> f64 {
+		self.decompressed_size as f64 / self.compressed_size as f64
+	}
+
+	/// Size in bytes of the pixels compressed with byte offset.
+	pub fn byte_offset_size(&self) -> usize {
+		self.delta_sizes
+			.iter()
+			.zip([1, 3, 7, 15])
+			.map(|(count, size)| count * size)
+			.sum()
+	}
+
+	/// Size in bytes of the deltas at their entropy.
+	pub fn entropy_size(&self) -> f64 {
+		self.entropy * self.delta_sizes.iter().sum::<usize>() as f64 / 8.0
+	}
+}
+
+/// Computes a [`CompressionReport`] for every binary section in `data`, to
+/// judge whether byte offset suits the data. Pixels are taken as integers,
+/// rounding any floating point ones.
+pub fn compression_report(data: &[u8], decoder: &Decoder) -> std::result::Result<Vec<CompressionReport>, Error> {
+	let headers = decoder.read_all_headers(data)?;
+	let mut reports = Vec::with_capacity(headers.len());
+	for (metadata, image) in headers.iter().zip(decoder.images(data)) {
+		let image = image?;
+		let pixels = integer_pixels(&image);
+		let mut delta_sizes = [0; 4];
+		let mut histogram = HashMap::new();
+		let mut previous = 0i64;
+		for &value in pixels.iter() {
+			let delta = value.wrapping_sub(previous);
+			previous = value;
+			let size = match delta {
+				-127..=127 => 0,
+				-32767..=32767 => 1,
+				-2147483647..=2147483647 => 2,
+				_ => 3,
+			};
+			delta_sizes[size] += 1;
+			*histogram.entry(delta).or_insert(0usize) += 1;
+		}
+		let count = pixels.len() as f64;
+		let entropy = histogram
+			.values()
+			.map(|&n| {
+				let p = n as f64 / count;
+				-p * p.log2()
+			})
+			.sum();
+		reports.push(CompressionReport {
+			compressed_size: metadata.size,
+			decompressed_size: (metadata.element_count * metadata.element_type.bits()).div_ceil(8),
+			delta_sizes,
+			entropy,
+		});
+	}
+	Ok(reports)
+}
+
+/// Pixels of `image` as 64-bit integers, borrowed if they already are.
+fn integer_pixels(image: &ImageEnum) -> Cow<'_, [i64]> {
+	fn widen<P: Copy>(image: &Image<P>, widen: impl Fn(P) -> i64) -> Cow<'static, [i64]> {
+		Cow::Owned(image.pixels().iter().map(|p| widen(*p)).collect())
+	}
+	match image {
+		ImageEnum::I64(image) => Cow::Borrowed(image.pixels()),
+		ImageEnum::U8(image) => widen(image, i64::from),
+		ImageEnum::I8(image) => widen(image, i64::from),
+		ImageEnum::U16(image) => widen(image, i64::from),
+		ImageEnum::I16(image) => widen(image, i64::from),
+		ImageEnum::U32(image) => widen(image, i64::from),
+		ImageEnum::I32(image) => widen(image, i64::from),
+		ImageEnum::U64(image) => widen(image, |p| p as i64),
+		ImageEnum::F32(image) => widen(image, |p| p.round() as i64),
+		ImageEnum::F64(image) => widen(image, |p| p.round() as i64),
+	}
+}
+
+/// First difference found between two decodings.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Difference {
+	/// Only one side could be decoded, or both failed differently
+	Decoding {
+		left: Option<String>,
+		right: Option<String>,
+	},
+	ImageCount {
+		left: usize,
+		right: usize,
+	},
+	Header {
+		frame: usize,
+		name: String,
+		left: Option<String>,
+		right: Option<String>,
+	},
+	Dimensions {
+		frame: usize,
+		left: (usize, usize),
+		right: (usize, usize),
+	},
+	PixelType {
+		frame: usize,
+	},
+	Pixel {
+		frame: usize,
+		index: usize,
+		left: String,
+		right: String,
+	},
+}
+
+/// Decodes `data` with both decoders and returns the first difference in the
+/// decoded images.
+pub fn compare_decoders(data: &[u8], left: &Decoder, right: &Decoder) -> Option<Difference> {
+	compare_images(left.read_all_images(data), right.read_all_images(data))
+}
+
+/// Decodes both files with `decoder` and returns the first difference in
+/// their binary section headers or decoded images.
+pub fn compare_files(left: &[u8], right: &[u8], decoder: &Decoder) -> Option<Difference> {
+	if let (Ok(left_headers), Ok(right_headers)) = (read_section_headers(left), read_section_headers(right)) {
+		for (frame, (left, right)) in left_headers.iter().zip(&right_headers).enumerate() {
+			let names: BTreeSet<_> = left.keys().chain(right.keys()).collect();
+			for name in names {
+				if left.get(name) != right.get(name) {
+					return Some(Difference::Header {
+						frame,
+						name: name.to_owned(),
+						left: left.get(name).map(str::to_owned),
+						right: right.get(name).map(str::to_owned),
+					});
+				}
+			}
+		}
+	}
+	compare_images(decoder.read_all_images(left), decoder.read_all_images(right))
+}
+
+fn compare_images(
+	left: std::result::Result<Vec<ImageEnum>, Error>,
+	right: std::result::Result<Vec<ImageEnum>, Error>,
+) -> Option<Difference> {
+	let (left, right) = match (left, right) {
+		(Ok(left), Ok(right)) => (left, right),
+		(left, right) => {
+			let left = left.err().map(|e| e.to_string());
+			let right = right.err().map(|e| e.to_string());
+			return (left != right).then_some(Difference::Decoding { left, right });
+		}
+	};
+	if left.len() != right.len() {
+		return Some(Difference::ImageCount { left: left.len(), right: right.len() });
+	}
+	for (frame, (left, right)) in left.iter().zip(&right).enumerate() {
+		if (left.width(), left.height()) != (right.width(), right.height()) {
+			return Some(Difference::Dimensions {
+				frame,
+				left: (left.width(), left.height()),
+				right: (right.width(), right.height()),
+			});
+		}
+		let difference = match (left, right) {
+			(ImageEnum::U8(l), ImageEnum::U8(r)) => first_difference(l, r),
+			(ImageEnum::I8(l), ImageEnum::I8(r)) => first_difference(l, r),
+			(ImageEnum::U16(l), ImageEnum::U16(r)) => first_difference(l, r),
+			(ImageEnum::I16(l), ImageEnum::I16(r)) => first_difference(l, r),
+			(ImageEnum::U32(l), ImageEnum::U32(r)) => first_difference(l, r),
+			(ImageEnum::I32(l), ImageEnum::I32(r)) => first_difference(l, r),
+			(ImageEnum::F32(l), ImageEnum::F32(r)) => first_difference(l, r),
+			(ImageEnum::U64(l), ImageEnum::U64(r)) => first_difference(l, r),
+			(ImageEnum::I64(l), ImageEnum::I64(r)) => first_difference(l, r),
+			(ImageEnum::F64(l), ImageEnum::F64(r)) => first_difference(l, r),
+			_ => return Some(Difference::PixelType { frame }),
+		};
+		if let Some((index, left, right)) = difference {
+			return Some(Difference::Pixel { frame, index, left, right });
+		}
+	}
+	None
+}
+
+fn first_difference<P: PartialEq + Debug>(left: &Image<P>, right: &Image<P>) -> Option<(usize, String, String)> {
+	let (index, (left, right)) = left
+		.pixels()
+		.iter()
+		.zip(right.pixels())
+		.enumerate()
+		.find(|(_, (l, r))| l != r)?;
+	Some((index, format!("{left:?}"), format!("{right:?}")))
+}
+
+fn read_section_headers(mut data: &[u8]) -> std::result::Result<Vec<Headers>, Error> {
+	let mut sections = Vec::new();
+	loop {
+		match progress_reader_to_cbf_start(&mut data) {
+			Ok(()) => (),
+			Err(Error::NoImage) => return Ok(sections),
+			Err(error) => return Err(error),
+		}
+		let headers = read_ordered_headers(&mut data).map_err(|e| Error::Metadata(e.into()))?;
+		let size: usize = headers
+			.get("x-binary-size")
+			.and_then(|size| size.trim().parse().ok())
+			.ok_or(Error::Metadata(MetadataError::Parsing(ErrorKind::MissingSize)))?;
+		// The declared size is untrusted; a section running past the end of
+		// the data is the last one.
+		let Some(rest) = BINARY_MARKER.len().checked_add(size).and_then(|end| data.get(end..)) else {
+			sections.push(headers);
+			return Ok(sections);
+		};
+		data = rest;
+		progress_reader_to_cbf_end(&mut data)?;
+		sections.push(headers);
+	}
+}
+
+pub(crate) fn next_line(data: &[u8], offset: usize) -> &[u8] {
+	let rest = &data[offset.min(data.len())..];
+	match rest.iter().position(|b| *b == b'\n') {
+		Some(position) => &rest[..=position],
+		None => rest,
+	}
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn hexdump(data: &[u8], around: usize, mut writer: impl Write) -> Result<()> {
+	let start = around.saturating_sub(SNIPPET_LENGTH / 2) / 16 * 16;
+	let end = (around + SNIPPET_LENGTH / 2).min(data.len());
+	for row in (start..end).step_by(16) {
+		let bytes = &data[row..(row + 16).min(end)];
+		let hex: Vec<_> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+		let ascii: String = bytes
+			.iter()
+			.map(|b| {
+				if b.is_ascii_graphic() || *b == b' ' {
+					*b as char
+				} else {
+					'.'
+				}
+			})
+			.collect();
+		writeln!(writer, "    {row:#010x}  {:<47}  |{ascii}|", hex.join(" "))?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::borrow::Cow;
+
+	use super::{compare_decoders, compare_files, compression_report, dump_structure, integer_pixels, Difference};
+	use crate::{
+		decoder::{Decoder, Limits, ReadOptions},
+		image::{pixel::Pixels, ImageEnum},
+	};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	fn dump(data: &[u8]) -> String {
+		let mut output = Vec::new();
+		dump_structure(data, &mut output).expect("to dump structure");
+		String::from_utf8(output).expect("dump to be utf-8")
+	}
+
+	#[test]
+	fn reports_compression() {
+		let reports = compression_report(EXAMPLE_DATA, &Decoder::default()).expect("to report");
+		assert_eq!(reports.len(), 1);
+		let report = &reports[0];
+		assert_eq!(report.compressed_size, 10161580);
+		assert_eq!(report.decompressed_size, 2880 * 2880 * 4);
+		assert_eq!(report.delta_sizes.iter().sum::<usize>(), 2880 * 2880);
+		// The section is byte offset compressed, so recompressing matches it.
+		assert_eq!(report.byte_offset_size(), report.compressed_size);
+		assert!(0.0 < report.entropy && report.entropy_size() < report.compressed_size as f64);
+		assert!(3.0 < report.ratio());
+	}
+
+	#[test]
+	fn exact_integer_pixels() {
+		let large = ImageEnum::from_pixels(2, 1, Pixels::I64(vec![1 << 60, (1 << 60) + 1].into()));
+		let pixels = integer_pixels(&large);
+		assert!(matches!(pixels, Cow::Borrowed(_)));
+		assert_eq!(pixels[1] - pixels[0], 1);
+		let floats = ImageEnum::from_pixels(2, 1, Pixels::F64(vec![1.4, -2.6].into()));
+		assert_eq!(&*integer_pixels(&floats), &[1, -3]);
+	}
+
+	#[test]
+	fn dump_real_image() {
+		let output = dump(EXAMPLE_DATA);
+		assert!(output.contains("data block \"snap_V4_00013\""), "{output}");
+		assert!(output.contains("header X-Binary-Size:   10161580"), "{output}");
+		assert!(output.contains("binary data, 10161580 bytes"), "{output}");
+		assert!(output.contains("binary section end"), "{output}");
+		assert!(!output.contains("anomaly"), "{output}");
+	}
+
+	#[test]
+	fn dump_broken_marker() {
+		let marker = EXAMPLE_DATA
+			.windows(4)
+			.position(|w| w == [0x0C, 0x1A, 0x04, 0xD5])
+			.unwrap();
+		let mut data = EXAMPLE_DATA.to_vec();
+		data[marker] = b'X';
+		let output = dump(&data);
+		assert!(output.contains("anomaly: expected binary marker"), "{output}");
+		assert!(output.contains("....X...d.."), "{output}");
+	}
+
+	#[test]
+	fn dump_truncated_data() {
+		let output = dump(&EXAMPLE_DATA[..5000]);
+		assert!(
+			output.contains("anomaly: binary data of 10161580 bytes truncated"),
+			"{output}"
+		);
+	}
+
+	/// The example with an `X-Binary-Size` of `usize::MAX`.
+	fn overflowing_size() -> Vec<u8> {
+		let header: &[u8] = b"X-Binary-Size:   10161580";
+		let position = EXAMPLE_DATA.windows(header.len()).position(|w| w == header).unwrap();
+		[
+			&EXAMPLE_DATA[..position],
+			format!("X-Binary-Size: {}", usize::MAX).as_bytes(),
+			&EXAMPLE_DATA[position + header.len()..],
+		]
+		.concat()
+	}
+
+	#[test]
+	fn dump_overflowing_size() {
+		let output = dump(&overflowing_size());
+		assert!(output.contains("anomaly: binary data of"), "{output}");
+	}
+
+	#[test]
+	fn compare_identical() {
+		assert_eq!(
+			compare_decoders(EXAMPLE_DATA, &Decoder::default(), &Decoder::default()),
+			None
+		);
+		assert_eq!(compare_files(EXAMPLE_DATA, EXAMPLE_DATA, &Decoder::default()), None);
+	}
+
+	#[test]
+	fn compare_decoder_failure() {
+		let strict =
+			Decoder::new(ReadOptions::default().limits(Limits { max_element_count: Some(1), ..Limits::default() }));
+		assert_eq!(
+			compare_decoders(EXAMPLE_DATA, &Decoder::default(), &strict),
+			Some(Difference::Decoding { left: None, right: Some("declared size exceeds limit".to_owned()) })
+		);
+	}
+
+	#[test]
+	fn compare_changed_header() {
+		let position = EXAMPLE_DATA.windows(14).position(|w| w == b"X-Binary-ID: 1").unwrap();
+		let mut data = EXAMPLE_DATA.to_vec();
+		data[position + 13] = b'2';
+		assert_eq!(
+			compare_files(EXAMPLE_DATA, &data, &Decoder::default()),
+			Some(Difference::Header {
+				frame: 0,
+				name: "x-binary-id".to_owned(),
+				left: Some("1".to_owned()),
+				right: Some("2".to_owned())
+			})
+		);
+	}
+
+	#[test]
+	fn compare_changed_pixel() {
+		let marker = EXAMPLE_DATA
+			.windows(4)
+			.position(|w| w == [0x0C, 0x1A, 0x04, 0xD5])
+			.unwrap();
+		let mut data = EXAMPLE_DATA.to_vec();
+		// The first delta is the value of the first pixel.
+		data[marker + 4] += 1;
+		assert_eq!(
+			compare_files(EXAMPLE_DATA, &data, &Decoder::default()),
+			Some(Difference::Pixel { frame: 0, index: 0, left: "100".to_owned(), right: "101".to_owned() })
+		);
+	}
+
+	#[test]
+	fn compare_overflowing_size() {
+		assert_eq!(
+			compare_files(EXAMPLE_DATA, &overflowing_size(), &Decoder::default()),
+			Some(Difference::Header {
+				frame: 0,
+				name: "x-binary-size".to_owned(),
+				left: Some("10161580".to_owned()),
+				right: Some(usize::MAX.to_string())
+			})
+		);
+	}
+}
@@ -0,0 +1,1149 @@
+use std::{
+	fmt,
+	io::{BufRead, Read},
+	sync::Arc,
+	time::{Duration, Instant},
+};
+
+#[cfg(feature = "md5")]
+use crate::md5::content_md5;
+use crate::{
+	check_deadline, check_declared_range,
+	compression::{ByteOffsetReader, OverflowPolicy},
+	counting::CountingReader,
+	decoded_size, has_binary_header,
+	image::{dimensions::element_count, pixel::Pixels, ImageEnum},
+	index::{FrameIndex, IndexedFrame},
+	is_byte_offset_type,
+	lazy::LazyImage,
+	memory::MemoryBudget,
+	metadata::{
+		cif::{parse_cif, CifHeader},
+		read_metadata, read_metadata_warned, Conversion, ElementType, Metadata,
+	},
+	progress_reader_to_cbf_end, progress_reader_to_cbf_start_with, read_binary_header, read_pixels,
+	warning::{Deviation, FrameWarning, Warning},
+	Error, DEADLINE_CHECK_INTERVAL,
+};
+
+/// Time allowed for each image by [`Decoder::untrusted`].
+const UNTRUSTED_DEADLINE: Duration = Duration::from_secs(10);
+
+/// Callback receiving a line of text, without its line terminator.
+pub type TextLineHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Callback receiving a frame whose shape differs from the first frame of its
+/// file.
+pub type InconsistencyHook = Arc<dyn Fn(&Inconsistency) + Send + Sync>;
+
+/// Dimensions and declared element type of a binary section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameShape {
+	pub width: usize,
+	pub height: usize,
+	pub element_type: ElementType,
+}
+
+impl FrameShape {
+	fn of(metadata: &Metadata) -> Result<Self, Error> {
+		let (width, height) = check_dimensions(metadata)?;
+		Ok(Self { width, height, element_type: metadata.element_type })
+	}
+}
+
+/// A frame whose shape differs from the first frame of its file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Inconsistency {
+	/// Position of the frame in the file, from 0
+	pub index: usize,
+	pub expected: FrameShape,
+	pub found: FrameShape,
+}
+
+/// How the `read_all` methods treat frames whose shape differs from the
+/// first frame of the file, a sign of a corrupted acquisition.
+#[derive(Clone, Default)]
+pub enum FrameConsistency {
+	#[default]
+	Ignore,
+	/// Fail with [`Error::InconsistentFrame`]
+	Fail,
+	/// Report the frame and read it anyway
+	Warn(InconsistencyHook),
+}
+
+impl fmt::Debug for FrameConsistency {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			FrameConsistency::Ignore => write!(f, "Ignore"),
+			FrameConsistency::Fail => write!(f, "Fail"),
+			FrameConsistency::Warn(_) => write!(f, "Warn(..)"),
+		}
+	}
+}
+
+/// Options controlling how images are read.
+#[derive(Clone, Default)]
+pub struct ReadOptions {
+	/// How byte offset decoding handles a running value that overflows.
+	pub overflow: OverflowPolicy,
+	/// Longest time a single image may take to read before failing with
+	/// [`Error::TimedOut`].
+	pub deadline: Option<Duration>,
+	/// Upper bounds on the declared sizes of an image.
+	pub limits: Limits,
+	/// Convert decoded pixels to the declared element type, failing with
+	/// [`Error::ElementOutOfRange`] if any does not fit, instead of returning
+	/// them as 64-bit integers.
+	pub strict_element_type: bool,
+	/// Accept `X-Binary-Size-Padding` bytes that are not NUL.
+	pub lenient_padding: bool,
+	/// Check the binary data of sections with a `Content-MD5` header against
+	/// its digest, failing with [`Error::ChecksumMismatch`] if they differ.
+	/// Only sections with the binary transfer encoding are checked.
+	#[cfg(feature = "md5")]
+	pub verify_checksum: bool,
+	/// Called with every line of CIF text skipped on the way to a binary
+	/// section.
+	pub on_text_line: Option<TextLineHook>,
+	pub frame_consistency: FrameConsistency,
+}
+
+impl ReadOptions {
+	pub fn overflow(mut self, overflow: OverflowPolicy) -> Self {
+		self.overflow = overflow;
+		self
+	}
+
+	pub fn deadline(mut self, deadline: Duration) -> Self {
+		self.deadline = Some(deadline);
+		self
+	}
+
+	pub fn limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	pub fn strict_element_type(mut self) -> Self {
+		self.strict_element_type = true;
+		self
+	}
+
+	pub fn lenient_padding(mut self) -> Self {
+		self.lenient_padding = true;
+		self
+	}
+
+	#[cfg(feature = "md5")]
+	pub fn verify_checksum(mut self) -> Self {
+		self.verify_checksum = true;
+		self
+	}
+
+	pub fn on_text_line(mut self, hook: impl Fn(&str) + Send + Sync + 'static) -> Self {
+		self.on_text_line = Some(Arc::new(hook));
+		self
+	}
+
+	pub fn frame_consistency(mut self, frame_consistency: FrameConsistency) -> Self {
+		self.frame_consistency = frame_consistency;
+		self
+	}
+
+	pub fn on_inconsistent_frame(self, hook: impl Fn(&Inconsistency) + Send + Sync + 'static) -> Self {
+		self.frame_consistency(FrameConsistency::Warn(Arc::new(hook)))
+	}
+
+	/// Compares the shape of frame `index` with `first`, the shape of the
+	/// first frame of the file, which is recorded when there is none yet.
+	fn check_consistency(
+		&self,
+		first: &mut Option<FrameShape>,
+		index: usize,
+		metadata: &Metadata,
+	) -> Result<(), Error> {
+		if let FrameConsistency::Ignore = self.frame_consistency {
+			return Ok(());
+		}
+		let found = FrameShape::of(metadata)?;
+		let expected = *first.get_or_insert(found);
+		if found == expected {
+			return Ok(());
+		}
+		let inconsistency = Inconsistency { index, expected, found };
+		match &self.frame_consistency {
+			FrameConsistency::Warn(hook) => hook(&inconsistency),
+			_ => return Err(Error::InconsistentFrame(inconsistency)),
+		}
+		Ok(())
+	}
+
+	fn text_line(&self, line: &str) {
+		if let Some(hook) = &self.on_text_line {
+			hook(line.trim_end_matches(['\r', '\n']));
+		}
+	}
+}
+
+impl fmt::Debug for ReadOptions {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut debug = f.debug_struct("ReadOptions");
+		debug
+			.field("overflow", &self.overflow)
+			.field("deadline", &self.deadline)
+			.field("limits", &self.limits)
+			.field("strict_element_type", &self.strict_element_type)
+			.field("lenient_padding", &self.lenient_padding);
+		#[cfg(feature = "md5")]
+		debug.field("verify_checksum", &self.verify_checksum);
+		debug
+			.field("on_text_line", &self.on_text_line.as_ref().map(|_| ".."))
+			.field("frame_consistency", &self.frame_consistency)
+			.finish()
+	}
+}
+
+/// Upper bounds on the declared sizes of an image, checked before any pixel
+/// data is decoded.
+#[derive(Debug, Clone, Default)]
+pub struct Limits {
+	pub max_element_count: Option<usize>,
+	pub max_binary_size: Option<usize>,
+	/// Most binary sections read from one file by the `read_all` methods.
+	pub max_images: Option<usize>,
+}
+
+impl Limits {
+	/// Bounds fitting the largest current detectors, around 33 million pixels
+	/// in 256 MiB of compressed data, and at most 100 images per file.
+	pub fn untrusted() -> Self {
+		Self { max_element_count: Some(1 << 25), max_binary_size: Some(1 << 28), max_images: Some(100) }
+	}
+
+	pub(crate) fn check(&self, metadata: &Metadata) -> Result<(), Error> {
+		if self.max_element_count.is_some_and(|max| max < metadata.element_count)
+			|| self.max_binary_size.is_some_and(|max| max < metadata.size)
+		{
+			return Err(Error::LimitExceeded);
+		}
+		Ok(())
+	}
+
+	/// Fails if another image would exceed [`Limits::max_images`].
+	pub(crate) fn check_image_count(&self, read: usize) -> Result<(), Error> {
+		if self.max_images.is_some_and(|max| max <= read) {
+			return Err(Error::LimitExceeded);
+		}
+		Ok(())
+	}
+}
+
+/// An image and the name of the CIF `data_` block containing it.
+pub struct NamedImage {
+	/// `None` if no block was opened before the binary section
+	pub block: Option<String>,
+	pub image: ImageEnum,
+}
+
+/// An image with the CIF text preceding it and the headers of its binary
+/// section, as read by [`Decoder::read_frame`].
+pub struct Frame {
+	pub header: CifHeader,
+	pub metadata: Metadata,
+	pub image: ImageEnum,
+}
+
+/// Decoder for binary sections not supported by the crate itself.
+pub trait CustomDecoder: Send + Sync {
+	fn supports(&self, metadata: &Metadata) -> bool;
+
+	/// Must return `metadata.element_count` pixels, or reading fails with
+	/// [`Error::DimensionMismatch`].
+	fn decode(&self, reader: &mut dyn Read, metadata: &Metadata) -> Result<Pixels, Error>;
+}
+
+/// Reads images with an explicit, shareable configuration. Custom decoders
+/// are tried in registration order before the built-in ones.
+#[derive(Clone, Default)]
+pub struct Decoder {
+	options: ReadOptions,
+	memory_budget: Option<MemoryBudget>,
+	custom_decoders: Vec<Arc<dyn CustomDecoder>>,
+}
+
+impl Decoder {
+	pub fn new(options: ReadOptions) -> Self {
+		Self { options, memory_budget: None, custom_decoders: Vec::new() }
+	}
+
+	/// Hardened profile for files from untrusted sources, such as uploads to a
+	/// web service: [`Limits::untrusted`], a deadline of ten seconds per image
+	/// and strict element types. Pixels are only ever read from the given
+	/// reader, nothing is memory mapped. A memory budget shared by concurrent
+	/// requests can be added with [`Decoder::set_memory_budget`].
+	pub fn untrusted() -> Self {
+		Self::new(
+			ReadOptions::default()
+				.limits(Limits::untrusted())
+				.deadline(UNTRUSTED_DEADLINE)
+				.strict_element_type(),
+		)
+	}
+
+	/// Accounts every decoded pixel buffer against `budget`.
+	pub fn set_memory_budget(&mut self, budget: MemoryBudget) {
+		self.memory_budget = Some(budget);
+	}
+
+	pub fn register(&mut self, decoder: impl CustomDecoder + 'static) {
+		self.custom_decoders.push(Arc::new(decoder));
+	}
+
+	pub fn options(&self) -> &ReadOptions {
+		&self.options
+	}
+
+	pub fn memory_budget(&self) -> Option<&MemoryBudget> {
+		self.memory_budget.as_ref()
+	}
+
+	pub fn read_all_images(&self, reader: impl BufRead) -> Result<Vec<ImageEnum>, Error> {
+		self.read_all_images_with_warnings(reader).map(|(images, _)| images)
+	}
+
+	/// Like [`Decoder::read_all_images`], also returning the warnings raised.
+	pub fn read_all_images_with_warnings(
+		&self,
+		mut reader: impl BufRead,
+	) -> Result<(Vec<ImageEnum>, Vec<FrameWarning>), Error> {
+		let mut images = Vec::new();
+		let mut warnings = Vec::new();
+		let mut first = None;
+
+		loop {
+			let mut image_warnings = Vec::new();
+			let Some(image) = self.read_next_image(&mut reader, images.len(), &mut first, &mut image_warnings)? else {
+				break;
+			};
+			warnings.extend(image_warnings.into_iter().map(|warning| (images.len(), warning)));
+			images.push(image);
+		}
+
+		Ok((images, warnings))
+	}
+
+	/// Iterates over the images of `reader`, reading each one only when the
+	/// iterator advances. Warnings are not collected, see
+	/// [`Decoder::read_all_images_with_warnings`].
+	pub fn images<R: BufRead>(&self, reader: R) -> ImageIter<R> {
+		ImageIter { decoder: self.clone(), reader, read: 0, first: None, finished: false }
+	}
+
+	/// Reads the next image of a file from which `read` images have already
+	/// been read, or `None` at its end.
+	fn read_next_image(
+		&self,
+		mut reader: impl BufRead,
+		read: usize,
+		first: &mut Option<FrameShape>,
+		warnings: &mut Vec<Warning>,
+	) -> Result<Option<ImageEnum>, Error> {
+		let started = Instant::now();
+		match progress_reader_to_cbf_start_with(&mut reader, |line| self.options.text_line(line)) {
+			Ok(()) => {}
+			Err(Error::NoImage) => return Ok(None),
+			Err(error) => return Err(error),
+		}
+		self.options.limits.check_image_count(read)?;
+		let metadata = read_metadata_warned(&mut reader, warnings)?;
+		self.options.check_consistency(first, read, &metadata)?;
+		self.decode_section(&mut reader, &metadata, started, warnings).map(Some)
+	}
+
+	/// Reads all images along with the name of the `data_` block each one is in.
+	pub fn read_all_named_images(&self, reader: impl BufRead) -> Result<Vec<NamedImage>, Error> {
+		self.read_all_named_images_with_warnings(reader)
+			.map(|(images, _)| images)
+	}
+
+	/// Like [`Decoder::read_all_named_images`], also returning the warnings
+	/// raised.
+	pub fn read_all_named_images_with_warnings(
+		&self,
+		mut reader: impl BufRead,
+	) -> Result<(Vec<NamedImage>, Vec<FrameWarning>), Error> {
+		let mut images = Vec::new();
+		let mut warnings = Vec::new();
+		let mut block = None;
+		let mut first = None;
+
+		loop {
+			let start = progress_reader_to_cbf_start_with(&mut reader, |line| {
+				self.options.text_line(line);
+				if let Some(name) = line.strip_prefix("data_") {
+					block = Some(name.trim_end().to_owned());
+				}
+			});
+			match start {
+				Ok(()) => {}
+				Err(Error::NoImage) => break,
+				Err(error) => return Err(error),
+			}
+			self.options.limits.check_image_count(images.len())?;
+			let started = Instant::now();
+			let mut image_warnings = Vec::new();
+			let metadata = read_metadata_warned(&mut reader, &mut image_warnings)?;
+			self.options.check_consistency(&mut first, images.len(), &metadata)?;
+			let image = self.decode_section(&mut reader, &metadata, started, &mut image_warnings)?;
+			warnings.extend(image_warnings.into_iter().map(|warning| (images.len(), warning)));
+			images.push(NamedImage { block: block.clone(), image });
+		}
+
+		Ok((images, warnings))
+	}
+
+	/// Reads the metadata of every binary section, skipping the pixel data
+	/// without decoding it.
+	pub fn read_all_headers(&self, reader: impl BufRead) -> Result<Vec<Metadata>, Error> {
+		let index = self.index_frames(reader)?;
+		Ok(index.frames().iter().map(|frame| frame.metadata.clone()).collect())
+	}
+
+	/// Finds where every binary section is, skipping the pixel data without
+	/// decoding it.
+	pub fn index_frames(&self, reader: impl BufRead) -> Result<FrameIndex, Error> {
+		self.index_frames_with_warnings(reader).map(|(index, _)| index)
+	}
+
+	/// Like [`Decoder::index_frames`], also returning the warnings raised by
+	/// the headers. The data is not read, so no warnings about it are raised.
+	pub fn index_frames_with_warnings(&self, reader: impl BufRead) -> Result<(FrameIndex, Vec<FrameWarning>), Error> {
+		let mut reader = CountingReader::new(reader);
+		let mut frames = Vec::new();
+		let mut warnings = Vec::new();
+		let mut first = None;
+
+		loop {
+			let start = reader.count();
+			match progress_reader_to_cbf_start_with(&mut reader, |line| self.options.text_line(line)) {
+				Ok(()) => {}
+				Err(Error::NoImage) => break,
+				Err(error) => return Err(error),
+			}
+			self.options.limits.check_image_count(frames.len())?;
+			let mut frame_warnings = Vec::new();
+			let metadata = read_metadata_warned(&mut reader, &mut frame_warnings)?;
+			self.options.check_consistency(&mut first, frames.len(), &metadata)?;
+			warnings.extend(frame_warnings.into_iter().map(|warning| (frames.len(), warning)));
+			// Encoded sections are text, ended by the closing boundary alone.
+			if has_binary_header(&metadata) {
+				read_binary_header(&mut reader)?;
+				let skipped = std::io::copy(&mut (&mut reader).take(metadata.size as u64), &mut std::io::sink())?;
+				if skipped < metadata.size as u64 {
+					return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+				}
+			}
+			progress_reader_to_cbf_end(&mut reader)?;
+			frames.push(IndexedFrame { range: start..reader.count(), metadata });
+		}
+
+		Ok((FrameIndex::new(frames), warnings))
+	}
+
+	pub fn read_image(&self, reader: impl BufRead) -> Result<ImageEnum, Error> {
+		self.read_image_with_warnings(reader).map(|(image, _)| image)
+	}
+
+	/// Like [`Decoder::read_image`], also returning the warnings raised.
+	pub fn read_image_with_warnings(&self, mut reader: impl BufRead) -> Result<(ImageEnum, Vec<Warning>), Error> {
+		let started = Instant::now();
+		progress_reader_to_cbf_start_with(&mut reader, |line| self.options.text_line(line))?;
+		let mut warnings = Vec::new();
+		let image = self.read_section(reader, started, &mut warnings)?;
+		Ok((image, warnings))
+	}
+
+	/// Like [`Decoder::read_image`], also returning the CIF text preceding the
+	/// binary section, parsed.
+	pub fn read_image_with_header(&self, reader: impl BufRead) -> Result<(CifHeader, ImageEnum), Error> {
+		self.read_frame(reader).map(|frame| (frame.header, frame.image))
+	}
+
+	/// Like [`Decoder::read_image_with_header`], also returning the headers of
+	/// the binary section, so a file needs to be read only once to show all
+	/// three.
+	pub fn read_frame(&self, mut reader: impl BufRead) -> Result<Frame, Error> {
+		let started = Instant::now();
+		let mut text = String::new();
+		progress_reader_to_cbf_start_with(&mut reader, |line| {
+			self.options.text_line(line);
+			text.push_str(line);
+		})?;
+		let metadata = read_metadata(&mut reader)?;
+		let image = self.decode_section(reader, &metadata, started, &mut Vec::new())?;
+		Ok(Frame { header: parse_cif(&text), metadata, image })
+	}
+
+	/// Reads the headers and compressed data of the next image, leaving its
+	/// pixels to be decoded when asked for. Only sections with the binary
+	/// transfer encoding can be read lazily.
+	pub fn read_lazy_image(&self, mut reader: impl BufRead) -> Result<LazyImage, Error> {
+		progress_reader_to_cbf_start_with(&mut reader, |line| self.options.text_line(line))?;
+		let metadata = read_metadata(&mut reader)?;
+		let (width, height) = check_dimensions(&metadata)?;
+		self.options.limits.check(&metadata)?;
+		if !has_binary_header(&metadata) {
+			return Err(Error::UnsupportedEncoding);
+		}
+		read_binary_header(&mut reader)?;
+		let mut data = Vec::new();
+		(&mut reader).take(metadata.size as u64).read_to_end(&mut data)?;
+		if data.len() < metadata.size {
+			return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+		}
+		self.read_padding(&mut reader, &metadata, &mut Vec::new())?;
+		progress_reader_to_cbf_end(&mut reader)?;
+		Ok(LazyImage::new(
+			metadata,
+			width,
+			height,
+			data.into(),
+			self.options.clone(),
+		))
+	}
+
+	/// Like [`Decoder::read_image`], also returning the number of bytes consumed.
+	pub fn read_image_counted(&self, reader: impl BufRead) -> Result<(ImageEnum, usize), Error> {
+		let mut reader = CountingReader::new(reader);
+		let image = self.read_image(&mut reader)?;
+		Ok((image, reader.count()))
+	}
+
+	/// Like [`Decoder::read_image`], decoding the pixels into `pixels` so its
+	/// allocation is reused from frame to frame, and returning the metadata of
+	/// the frame. Pixels are widened to `i64` whatever the declared type, and
+	/// float frames are not supported.
+	///
+	/// Byte offset frames are decompressed straight into `pixels`. Other
+	/// frames, and frames decoded by a custom decoder or with their checksum
+	/// verified, are decoded into an image first and then copied.
+	pub fn read_image_into(&self, mut reader: impl BufRead, pixels: &mut Vec<i64>) -> Result<Metadata, Error> {
+		let started = Instant::now();
+		progress_reader_to_cbf_start_with(&mut reader, |line| self.options.text_line(line))?;
+		let metadata = read_metadata(&mut reader)?;
+		if !self.decodes_in_place(&metadata) {
+			let image = self.decode_section(reader, &metadata, started, &mut Vec::new())?;
+			self.release(image.byte_size());
+			copy_integer_pixels(&image, pixels)?;
+			return Ok(metadata);
+		}
+		check_dimensions(&metadata)?;
+		self.options.limits.check(&metadata)?;
+		read_binary_header(&mut reader)?;
+		pixels.clear();
+		pixels.resize(metadata.element_count, 0);
+		let mut decompressor =
+			ByteOffsetReader::new(&mut reader, self.options.overflow).byte_order(metadata.byte_order);
+		for chunk in pixels.chunks_mut(DEADLINE_CHECK_INTERVAL) {
+			check_deadline(self.deadline(started))?;
+			decompressor.read(chunk)?;
+		}
+		if self.options.strict_element_type {
+			check_declared_range(pixels, &metadata.element_type)?;
+		}
+		self.read_padding(&mut reader, &metadata, &mut Vec::new())?;
+		progress_reader_to_cbf_end(&mut reader)?;
+		Ok(metadata)
+	}
+
+	/// Whether [`Decoder::read_image_into`] can decompress the section without
+	/// decoding it into an image first.
+	fn decodes_in_place(&self, metadata: &Metadata) -> bool {
+		#[cfg(feature = "md5")]
+		if self.options.verify_checksum && metadata.md5_digest.is_some() {
+			return false;
+		}
+		let content_type = &metadata.content_type;
+		content_type.mime_type == "application"
+			&& content_type.subtype == "octet-stream"
+			&& content_type.conversion == Some(Conversion::ByteOffset)
+			&& has_binary_header(metadata)
+			&& is_byte_offset_type(&metadata.element_type)
+			&& !self.custom_decoders.iter().any(|d| d.supports(metadata))
+	}
+
+	/// Reads a binary section whose start marker has already been consumed.
+	fn read_section(
+		&self,
+		mut reader: impl BufRead,
+		started: Instant,
+		warnings: &mut Vec<Warning>,
+	) -> Result<ImageEnum, Error> {
+		let metadata = read_metadata_warned(&mut reader, warnings)?;
+		self.decode_section(reader, &metadata, started, warnings)
+	}
+
+	/// Reads the pixels of a binary section whose headers have already been
+	/// consumed.
+	fn decode_section(
+		&self,
+		mut reader: impl BufRead,
+		metadata: &Metadata,
+		started: Instant,
+		warnings: &mut Vec<Warning>,
+	) -> Result<ImageEnum, Error> {
+		let (width, height) = check_dimensions(metadata)?;
+		self.options.limits.check(metadata)?;
+		if has_binary_header(metadata) {
+			read_binary_header(&mut reader)?;
+		}
+		let reserved = self.reserve(metadata)?;
+		let pixels = match self.verified_data(&mut reader, metadata) {
+			Ok(Some(data)) => self.decode_pixels(&data[..], metadata, started),
+			Ok(None) => self.decode_pixels(&mut reader, metadata, started),
+			Err(error) => Err(error),
+		};
+		let pixels = pixels.inspect_err(|_| self.release(reserved))?;
+		self.read_padding(&mut reader, metadata, warnings)
+			.inspect_err(|_| self.release(reserved))?;
+		progress_reader_to_cbf_end(&mut reader).inspect_err(|_| self.release(reserved))?;
+		let image = ImageEnum::from_pixels(width, height, pixels);
+		// The reservation assumed the widest pixels; keep only what the image holds.
+		self.release(reserved.saturating_sub(image.byte_size()));
+		Ok(image)
+	}
+}
+
+/// Images of a file, read one at a time so only one is held in memory.
+/// Created by [`Decoder::images`]. Iteration ends after the first error.
+pub struct ImageIter<R> {
+	decoder: Decoder,
+	reader: R,
+	/// Images read so far
+	read: usize,
+	/// Shape of the first image, for [`ReadOptions::frame_consistency`]
+	first: Option<FrameShape>,
+	finished: bool,
+}
+
+impl<R> ImageIter<R> {
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+}
+
+impl<R: BufRead> Iterator for ImageIter<R> {
+	type Item = Result<ImageEnum, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if self.finished {
+			return None;
+		}
+		let image = (self.decoder).read_next_image(&mut self.reader, self.read, &mut self.first, &mut Vec::new());
+		self.read += 1;
+		self.finished = !matches!(image, Ok(Some(_)));
+		image.transpose()
+	}
+}
+
+/// Replaces `pixels` with the integer pixels of `image`.
+fn copy_integer_pixels(image: &ImageEnum, pixels: &mut Vec<i64>) -> Result<(), Error> {
+	pixels.clear();
+	match image {
+		ImageEnum::U8(image) => pixels.extend(image.pixels().iter().map(|&p| i64::from(p))),
+		ImageEnum::I8(image) => pixels.extend(image.pixels().iter().map(|&p| i64::from(p))),
+		ImageEnum::U16(image) => pixels.extend(image.pixels().iter().map(|&p| i64::from(p))),
+		ImageEnum::I16(image) => pixels.extend(image.pixels().iter().map(|&p| i64::from(p))),
+		ImageEnum::U32(image) => pixels.extend(image.pixels().iter().map(|&p| i64::from(p))),
+		ImageEnum::I32(image) => pixels.extend(image.pixels().iter().map(|&p| i64::from(p))),
+		ImageEnum::I64(image) => pixels.extend_from_slice(image.pixels()),
+		ImageEnum::U64(_) | ImageEnum::F32(_) | ImageEnum::F64(_) => return Err(Error::UnsupportedPixelFormat),
+	}
+	Ok(())
+}
+
+/// Width and height of the section. Sections with only a fastest dimension,
+/// as written for strip detectors, are a single row. Images are
+/// two-dimensional, so sections of more than one plane are rejected.
+fn check_dimensions(metadata: &Metadata) -> Result<(usize, usize), Error> {
+	let width = metadata.width.ok_or(Error::MissingDimension)?;
+	let height = match (metadata.height, metadata.depth) {
+		(Some(height), _) => height,
+		(None, None) => 1,
+		(None, Some(_)) => return Err(Error::MissingDimension),
+	};
+	let depth = metadata.depth.unwrap_or(1);
+	if element_count(width, height, depth)? != metadata.element_count {
+		return Err(Error::DimensionMismatch);
+	}
+	if depth != 1 {
+		return Err(Error::UnsupportedDepth(depth));
+	}
+	Ok((width, height))
+}
+
+impl Decoder {
+	fn deadline(&self, started: Instant) -> Option<Instant> {
+		self.options.deadline.map(|deadline| started + deadline)
+	}
+
+	fn decode_pixels(&self, mut reader: impl Read, metadata: &Metadata, started: Instant) -> Result<Pixels, Error> {
+		match self.custom_decoders.iter().find(|d| d.supports(metadata)) {
+			Some(decoder) => {
+				let pixels = decoder.decode(&mut reader, metadata)?;
+				if pixels.len() != metadata.element_count {
+					return Err(Error::DimensionMismatch);
+				}
+				Ok(pixels)
+			}
+			None => read_pixels(reader, metadata, &self.options, self.deadline(started)),
+		}
+	}
+
+	/// Reads the whole binary data of the section and checks it against its
+	/// `Content-MD5` digest, if the options ask for it and the section has one.
+	#[cfg(feature = "md5")]
+	fn verified_data(&self, reader: impl Read, metadata: &Metadata) -> Result<Option<Vec<u8>>, Error> {
+		let Some(expected) = &metadata.md5_digest else {
+			return Ok(None);
+		};
+		if !self.options.verify_checksum || !has_binary_header(metadata) {
+			return Ok(None);
+		}
+		let mut data = Vec::new();
+		reader.take(metadata.size as u64).read_to_end(&mut data)?;
+		if data.len() < metadata.size {
+			return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+		}
+		let found = content_md5(&data);
+		if &found != expected {
+			return Err(Error::ChecksumMismatch { expected: expected.clone(), found });
+		}
+		Ok(Some(data))
+	}
+
+	#[cfg(not(feature = "md5"))]
+	fn verified_data(&self, _reader: impl Read, _metadata: &Metadata) -> Result<Option<Vec<u8>>, Error> {
+		Ok(None)
+	}
+
+	/// Consumes the padding declared after the binary data.
+	fn read_padding(&self, reader: impl Read, metadata: &Metadata, warnings: &mut Vec<Warning>) -> Result<(), Error> {
+		let Some(padding) = metadata.padding else {
+			return Ok(());
+		};
+		let mut bytes = Vec::new();
+		reader.take(padding as u64).read_to_end(&mut bytes)?;
+		if bytes.len() < padding {
+			return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+		}
+		if bytes.iter().any(|b| *b != 0) {
+			if !self.options.lenient_padding {
+				return Err(Error::InvalidPadding);
+			}
+			warnings.push(Warning::ToleratedDeviation(Deviation::NonNulPadding));
+		}
+		Ok(())
+	}
+
+	fn reserve(&self, metadata: &Metadata) -> Result<usize, Error> {
+		let Some(budget) = &self.memory_budget else {
+			return Ok(0);
+		};
+		let bytes = decoded_size(metadata).ok_or(Error::MemoryLimitExceeded)?;
+		if !budget.reserve(bytes) {
+			return Err(Error::MemoryLimitExceeded);
+		}
+		Ok(bytes)
+	}
+
+	fn release(&self, bytes: usize) {
+		if let Some(budget) = &self.memory_budget {
+			budget.release(bytes);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		io::{Cursor, Read},
+		sync::{Arc, Mutex},
+		thread,
+		time::Duration,
+	};
+
+	use super::{CustomDecoder, Decoder, FrameConsistency, Limits, ReadOptions};
+	use crate::{
+		encoder::{Compression, Encoder, WriteOptions},
+		image::{pixel::Pixels, Image, ImageEnum},
+		memory::MemoryBudget,
+		metadata::{Conversion, Metadata},
+		warning::{Deviation, Warning},
+		Error,
+	};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	struct Constant;
+
+	impl CustomDecoder for Constant {
+		fn supports(&self, metadata: &Metadata) -> bool {
+			metadata.content_type.conversion == Some(Conversion::ByteOffset)
+		}
+
+		fn decode(&self, reader: &mut dyn Read, metadata: &Metadata) -> Result<Pixels, Error> {
+			let mut data = vec![0; metadata.size];
+			reader.read_exact(&mut data)?;
+			Ok(vec![7i64; metadata.element_count].into())
+		}
+	}
+
+	/// Decodes one pixel too few.
+	struct Short;
+
+	impl CustomDecoder for Short {
+		fn supports(&self, metadata: &Metadata) -> bool {
+			metadata.content_type.conversion == Some(Conversion::ByteOffset)
+		}
+
+		fn decode(&self, _reader: &mut dyn Read, metadata: &Metadata) -> Result<Pixels, Error> {
+			Ok(vec![7i64; metadata.element_count - 1].into())
+		}
+	}
+
+	#[test]
+	fn iterates_images() {
+		let data = [EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		let decoder = Decoder::default();
+		let mut images = decoder.images(&data[..]);
+		assert_eq!(images.next().unwrap().unwrap().width(), 2880);
+		assert_eq!(images.next().unwrap().unwrap().width(), 2880);
+		assert!(images.next().is_none());
+		assert!(images.next().is_none());
+
+		let limited = Decoder::new(ReadOptions::default().limits(Limits { max_images: Some(1), ..Limits::default() }));
+		let results: Vec<_> = limited.images(&data[..]).collect();
+		assert_eq!(results.len(), 2);
+		assert!(matches!(results[1], Err(Error::LimitExceeded)));
+	}
+
+	#[test]
+	fn reuses_pixel_buffer() {
+		let expected = Decoder::default().read_image(EXAMPLE_DATA).unwrap().into_i64().unwrap();
+		let data = [EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		let decoder = Decoder::new(ReadOptions::default().strict_element_type());
+		let mut reader = &data[..];
+		let mut pixels = Vec::new();
+		let metadata = decoder
+			.read_image_into(&mut reader, &mut pixels)
+			.expect("to read image");
+		assert_eq!(metadata.width, Some(2880));
+		assert_eq!(pixels, expected.pixels());
+		let allocation = pixels.as_ptr();
+		decoder
+			.read_image_into(&mut reader, &mut pixels)
+			.expect("to read image");
+		assert_eq!(pixels.as_ptr(), allocation);
+		assert_eq!(pixels, expected.pixels());
+		assert!(matches!(
+			decoder.read_image_into(&mut reader, &mut pixels),
+			Err(Error::NoImage)
+		));
+
+		let mut custom = Decoder::default();
+		custom.register(Constant);
+		custom
+			.read_image_into(EXAMPLE_DATA, &mut pixels)
+			.expect("to read image");
+		assert!(pixels.iter().all(|&p| p == 7));
+	}
+
+	#[test]
+	fn uses_custom_decoder() {
+		let mut decoder = Decoder::default();
+		decoder.register(Constant);
+		let ImageEnum::I64(image) = decoder.read_image(Cursor::new(EXAMPLE_DATA)).expect("to read image") else {
+			panic!("expected i64 pixels")
+		};
+		assert!(image.pixels().iter().all(|p| *p == 7));
+
+		let mut decoder = Decoder::default();
+		decoder.register(Short);
+		assert!(matches!(
+			decoder.read_image(Cursor::new(EXAMPLE_DATA)),
+			Err(Error::DimensionMismatch)
+		));
+		let mut pixels = Vec::new();
+		assert!(matches!(
+			decoder.read_image_into(EXAMPLE_DATA, &mut pixels),
+			Err(Error::DimensionMismatch)
+		));
+	}
+
+	#[test]
+	fn enforces_limits() {
+		let limits = Limits { max_element_count: Some(1000), ..Limits::default() };
+		let decoder = Decoder::new(ReadOptions::default().limits(limits));
+		assert!(matches!(
+			decoder.read_image(Cursor::new(EXAMPLE_DATA)),
+			Err(Error::LimitExceeded)
+		));
+	}
+
+	#[test]
+	fn untrusted_profile() {
+		let decoder = Decoder::untrusted();
+		assert!(decoder.options().strict_element_type);
+		let ImageEnum::I32(_) = decoder.read_image(Cursor::new(EXAMPLE_DATA)).expect("to read image") else {
+			panic!("expected the declared element type")
+		};
+
+		let huge = with_header("X-Binary-Size:", &format!(" {}", 1usize << 29));
+		assert!(matches!(
+			decoder.read_image(Cursor::new(huge)),
+			Err(Error::LimitExceeded)
+		));
+
+		let limits = Limits { max_images: Some(1), ..Limits::default() };
+		let decoder = Decoder::new(ReadOptions::default().limits(limits));
+		let two = Cursor::new([EXAMPLE_DATA, EXAMPLE_DATA].concat());
+		assert!(matches!(decoder.read_all_headers(two), Err(Error::LimitExceeded)));
+		assert_eq!(decoder.read_all_images(Cursor::new(EXAMPLE_DATA)).unwrap().len(), 1);
+	}
+
+	fn with_header(header: &str, value: &str) -> Vec<u8> {
+		let text = String::from_utf8_lossy(&EXAMPLE_DATA[..4096]).into_owned();
+		let start = text.find(header).expect("header to exist") + header.len();
+		let end = start + text[start..].find('\r').unwrap();
+		[&EXAMPLE_DATA[..start], value.as_bytes(), &EXAMPLE_DATA[end..]].concat()
+	}
+
+	#[test]
+	fn checks_frame_consistency() {
+		let unsigned = with_header("X-Binary-Element-Type:", " \"unsigned 32-bit integer\"");
+		let data = [EXAMPLE_DATA, &unsigned].concat();
+		let read = |options: ReadOptions| Decoder::new(options).read_all_images(Cursor::new(&data));
+		assert_eq!(read(ReadOptions::default()).unwrap().len(), 2);
+
+		let failing = ReadOptions::default().frame_consistency(FrameConsistency::Fail);
+		let Err(Error::InconsistentFrame(inconsistency)) = read(failing.clone()) else {
+			panic!("expected an inconsistent frame")
+		};
+		assert_eq!(inconsistency.index, 1);
+		assert_eq!(inconsistency.expected.width, inconsistency.found.width);
+		assert_ne!(inconsistency.expected.element_type, inconsistency.found.element_type);
+		let decoder = Decoder::new(failing);
+		assert!(decoder.read_all_headers(Cursor::new(&data)).is_err());
+		let same = [EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		assert_eq!(decoder.read_all_named_images(Cursor::new(same)).unwrap().len(), 2);
+
+		let reported = Arc::new(Mutex::new(Vec::new()));
+		let sink = reported.clone();
+		let warning = ReadOptions::default().on_inconsistent_frame(move |i| sink.lock().unwrap().push(i.index));
+		assert_eq!(read(warning).unwrap().len(), 2);
+		assert_eq!(*reported.lock().unwrap(), [1]);
+	}
+
+	#[test]
+	fn times_out() {
+		let decoder = Decoder::new(ReadOptions::default().deadline(Duration::ZERO));
+		assert!(matches!(
+			decoder.read_image(Cursor::new(EXAMPLE_DATA)),
+			Err(Error::TimedOut)
+		));
+	}
+
+	#[test]
+	fn validates_dimensions() {
+		let decoder = Decoder::default();
+		let data = with_header("X-Binary-Size-Fastest-Dimension:", " 2879");
+		assert!(matches!(
+			decoder.read_image(Cursor::new(data)),
+			Err(Error::DimensionMismatch)
+		));
+		let data = with_header("X-Binary-Size-Fastest-Dimension:", &format!(" {}", usize::MAX));
+		assert!(matches!(
+			decoder.read_image(Cursor::new(data)),
+			Err(Error::DimensionOverflow(_))
+		));
+		let data = with_header(
+			"X-Binary-Size-Second-Dimension:",
+			" 1440\r\nX-Binary-Size-Third-Dimension: 2",
+		);
+		assert!(matches!(
+			decoder.read_image(Cursor::new(data)),
+			Err(Error::UnsupportedDepth(2))
+		));
+	}
+
+	#[test]
+	fn accounts_memory() {
+		let budget = MemoryBudget::new(100_000_000);
+		let mut decoder = Decoder::default();
+		decoder.set_memory_budget(budget.clone());
+
+		let image = decoder.read_image(Cursor::new(EXAMPLE_DATA)).expect("to read image");
+		assert_eq!(budget.used(), image.byte_size());
+		assert!(matches!(
+			decoder.read_image(Cursor::new(EXAMPLE_DATA)),
+			Err(Error::MemoryLimitExceeded)
+		));
+		assert_eq!(budget.used(), image.byte_size());
+
+		budget.release(image.byte_size());
+		assert!(decoder.read_image(Cursor::new(&EXAMPLE_DATA[..1000])).is_err());
+		assert_eq!(budget.used(), 0);
+
+		let mut decoder = Decoder::new(ReadOptions::default().strict_element_type());
+		decoder.set_memory_budget(budget.clone());
+		let image = decoder.read_image(Cursor::new(EXAMPLE_DATA)).expect("to read image");
+		assert!(matches!(image, ImageEnum::I32(_)));
+		assert_eq!(budget.used(), image.byte_size());
+		let mut pixels = Vec::new();
+		decoder
+			.read_image_into(EXAMPLE_DATA, &mut pixels)
+			.expect("to read image");
+		assert_eq!(budget.used(), image.byte_size());
+		budget.release(image.byte_size());
+
+		let float = ImageEnum::F32(Image::new(3, 2, vec![0.5; 6].into()).unwrap());
+		let mut data = Vec::new();
+		let options = WriteOptions::default().compression(Compression::None);
+		Encoder::new(options).write_image(&mut data, &float).unwrap();
+		let image = decoder.read_image(Cursor::new(data)).expect("to read image");
+		assert!(matches!(image, ImageEnum::F32(_)));
+		assert_eq!(budget.used(), image.byte_size());
+		budget.release(image.byte_size());
+		assert_eq!(budget.used(), 0);
+	}
+
+	#[test]
+	fn names_images_by_block() {
+		let name = EXAMPLE_DATA
+			.windows(18)
+			.position(|w| w == b"data_snap_V4_00013")
+			.unwrap();
+		let second = [&EXAMPLE_DATA[..name], b"data_second", &EXAMPLE_DATA[name + 18..]].concat();
+		let mut decoder = Decoder::default();
+		decoder.register(Constant);
+
+		let images = decoder
+			.read_all_named_images(Cursor::new([EXAMPLE_DATA, &second].concat()))
+			.expect("to read images");
+		let blocks: Vec<_> = images.iter().map(|i| i.block.as_deref()).collect();
+		assert_eq!(blocks, [Some("snap_V4_00013"), Some("second")]);
+	}
+
+	#[test]
+	fn reports_text_lines() {
+		let lines = Arc::new(Mutex::new(Vec::new()));
+		let options = ReadOptions::default().on_text_line({
+			let lines = lines.clone();
+			move |line| lines.lock().unwrap().push(line.to_owned())
+		});
+		let mut decoder = Decoder::new(options);
+		decoder.register(Constant);
+		decoder.read_image(Cursor::new(EXAMPLE_DATA)).expect("to read image");
+
+		let lines = lines.lock().unwrap();
+		assert_eq!(lines[0], "###CBF: VERSION 1.5, created by ngMultiXRD4343 V0.8.18beta");
+		assert!(lines.iter().any(|l| l == "data_snap_V4_00013"));
+		assert!(lines.iter().all(|l| !l.ends_with(['\r', '\n'])));
+	}
+
+	#[test]
+	fn validates_padding() {
+		let end = EXAMPLE_DATA.len() - b"\r\n--CIF-BINARY-FORMAT-SECTION----\r\n;\r\n".len();
+		let mut data = EXAMPLE_DATA.to_vec();
+		data[end - 1] = b'x';
+		let mut decoder = Decoder::default();
+		decoder.register(Constant);
+		assert!(matches!(
+			decoder.read_image(Cursor::new(&data)),
+			Err(Error::InvalidPadding)
+		));
+
+		let mut decoder = Decoder::new(ReadOptions::default().lenient_padding());
+		decoder.register(Constant);
+		let (_, warnings) = decoder.read_image_with_warnings(Cursor::new(&data)).unwrap();
+		assert_eq!(warnings, [Warning::ToleratedDeviation(Deviation::NonNulPadding)]);
+	}
+
+	#[test]
+	fn collects_warnings() {
+		let decoder = Decoder::default();
+		let (_, warnings) = decoder.read_image_with_warnings(Cursor::new(EXAMPLE_DATA)).unwrap();
+		assert!(warnings.is_empty());
+
+		let text = String::from_utf8_lossy(&EXAMPLE_DATA[..4096]);
+		let start = text.find("Content-MD5:").unwrap();
+		let mut data = EXAMPLE_DATA.to_vec();
+		data[start..start + 11].copy_from_slice(b"X-Custom-MD");
+		let (_, warnings) = decoder.read_image_with_warnings(Cursor::new(&data)).unwrap();
+		assert_eq!(
+			warnings,
+			[
+				Warning::UnknownHeader { name: "x-custom-md".to_owned() },
+				Warning::MissingMd5
+			]
+		);
+
+		// Inconsistent frames are reported through the hook alone.
+		let unsigned = with_header("X-Binary-Element-Type:", " \"unsigned 32-bit integer\"");
+		let series = [EXAMPLE_DATA, &data, &unsigned].concat();
+		let decoder = Decoder::new(ReadOptions::default().on_inconsistent_frame(|_| {}));
+		let (images, warnings) = decoder.read_all_images_with_warnings(Cursor::new(&series)).unwrap();
+		assert_eq!(images.len(), 3);
+		let indices: Vec<_> = warnings.iter().map(|(index, _)| *index).collect();
+		assert_eq!(indices, [1, 1]);
+
+		let (named, named_warnings) = decoder
+			.read_all_named_images_with_warnings(Cursor::new(&series))
+			.unwrap();
+		assert_eq!(named.len(), 3);
+		assert_eq!(named_warnings, warnings);
+		let (index, index_warnings) = decoder.index_frames_with_warnings(Cursor::new(&series)).unwrap();
+		assert_eq!(index.frames().len(), 3);
+		assert_eq!(index_warnings, warnings);
+	}
+
+	#[test]
+	fn reads_headers_only() {
+		let data = [EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		let headers = Decoder::default()
+			.read_all_headers(Cursor::new(&data))
+			.expect("to read headers");
+		assert_eq!(headers.len(), 2);
+		assert!(headers
+			.iter()
+			.all(|h| h.width == Some(2880) && h.element_count == 2880 * 2880));
+
+		let truncated = &EXAMPLE_DATA[..EXAMPLE_DATA.len() / 2];
+		assert!(matches!(
+			Decoder::default().read_all_headers(Cursor::new(truncated)),
+			Err(Error::IO(_))
+		));
+	}
+
+	#[test]
+	fn counts_consumed_bytes() {
+		let data = [EXAMPLE_DATA, b"trailer"].concat();
+		let mut reader = Cursor::new(&data);
+		let (_, consumed) = Decoder::default()
+			.read_image_counted(&mut reader)
+			.expect("to read image");
+		assert_eq!(consumed, EXAMPLE_DATA.len() - 3);
+		assert_eq!(reader.position() as usize, consumed);
+	}
+
+	#[test]
+	fn shared_between_threads() {
+		let decoder = Decoder::default();
+		thread::scope(|scope| {
+			let handles = [(); 2].map(|_| scope.spawn(|| decoder.read_image(Cursor::new(EXAMPLE_DATA)).is_ok()));
+			assert!(handles.into_iter().all(|h| h.join().unwrap()));
+		});
+	}
+}
@@ -0,0 +1,328 @@
+use std::io::Write;
+
+use num_traits::{AsPrimitive, ToBytes};
+
+use crate::{
+	compression::write_byte_offset,
+	image::{Image, ImageEnum},
+	metadata::{Conversion, ElementType, MetadataTemplate},
+	validate::validate_strict,
+	Error,
+};
+
+/// Magic number starting the data of a binary section.
+const BINARY_HEADER: [u8; 4] = [0x0C, 0x1A, 0x04, 0xD5];
+
+/// Compression applied to the pixels of a written binary section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+	/// Uncompressed little endian pixels
+	None,
+	#[default]
+	ByteOffset,
+}
+
+/// Options controlling how images are written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+	pub compression: Compression,
+	/// Include a `Content-MD5` header with the digest of the binary data.
+	pub md5: bool,
+	/// Zero bytes written after the binary data, announced in
+	/// `X-Binary-Size-Padding`.
+	pub padding: usize,
+	/// Check the written file against the strict CBF/imgCIF layout of
+	/// [`validate_strict`], and write nothing if it deviates. The whole file
+	/// is held in memory until checked.
+	pub strict: bool,
+}
+
+impl Default for WriteOptions {
+	fn default() -> Self {
+		Self { compression: Compression::default(), md5: true, padding: 0, strict: false }
+	}
+}
+
+impl WriteOptions {
+	pub fn compression(mut self, compression: Compression) -> Self {
+		self.compression = compression;
+		self
+	}
+
+	pub fn md5(mut self, md5: bool) -> Self {
+		self.md5 = md5;
+		self
+	}
+
+	pub fn padding(mut self, padding: usize) -> Self {
+		self.padding = padding;
+		self
+	}
+
+	pub fn strict(mut self, strict: bool) -> Self {
+		self.strict = strict;
+		self
+	}
+}
+
+impl Compression {
+	fn conversion(self) -> Option<Conversion> {
+		match self {
+			Compression::None => None,
+			Compression::ByteOffset => Some(Conversion::ByteOffset),
+		}
+	}
+}
+
+/// Writes images as CBF files, each image in a `data_` block of its own,
+/// numbered from 1.
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+	options: WriteOptions,
+}
+
+impl Encoder {
+	pub fn new(options: WriteOptions) -> Self {
+		Self { options }
+	}
+
+	pub fn options(&self) -> &WriteOptions {
+		&self.options
+	}
+
+	pub fn write_image(&self, writer: impl Write, image: &ImageEnum) -> Result<(), Error> {
+		self.write_all_images(writer, std::slice::from_ref(image))
+	}
+
+	pub fn write_all_images(&self, mut writer: impl Write, images: &[ImageEnum]) -> Result<(), Error> {
+		if self.options.strict {
+			let mut file = Vec::new();
+			self.write_file(&mut file, images)?;
+			let violations = validate_strict(&file);
+			if !violations.is_empty() {
+				return Err(Error::StrictViolation(violations));
+			}
+			writer.write_all(&file)?;
+			return Ok(());
+		}
+		self.write_file(writer, images)
+	}
+
+	fn write_file(&self, mut writer: impl Write, images: &[ImageEnum]) -> Result<(), Error> {
+		write!(
+			writer,
+			"###CBF: VERSION 1.5, created by cbf-rs {}\r\n",
+			env!("CARGO_PKG_VERSION")
+		)?;
+		for (index, image) in images.iter().enumerate() {
+			self.write_block(&mut writer, image, index + 1)?;
+		}
+		Ok(())
+	}
+
+	/// Writes `image` as the binary section of data block `binary_id`.
+	fn write_block(&self, mut writer: impl Write, image: &ImageEnum, binary_id: usize) -> Result<(), Error> {
+		let compression = self.options.compression;
+		let (element_type, data) = encode_pixels(image, compression)?;
+		let mut template = MetadataTemplate::new(element_type)
+			.conversion(compression.conversion())
+			.dimensions(image.width(), image.height())
+			.md5(self.options.md5)
+			.first_binary_id(binary_id);
+		if 0 < self.options.padding {
+			template = template.padding(self.options.padding);
+		}
+		let frame = template.fill(image.width() * image.height(), &data);
+
+		write!(writer, "\r\ndata_image_{binary_id}\r\n\r\n_array_data.data\r\n;\r\n")?;
+		writer.write_all(b"--CIF-BINARY-FORMAT-SECTION--\r\n")?;
+		frame.write_to(&mut writer)?;
+		writer.write_all(&BINARY_HEADER)?;
+		writer.write_all(&data)?;
+		writer.write_all(&vec![0; self.options.padding])?;
+		writer.write_all(b"\r\n--CIF-BINARY-FORMAT-SECTION----\r\n;\r\n")?;
+		Ok(())
+	}
+}
+
+/// Element type and compressed data of the pixels of `image`. 64-bit integer
+/// pixels are written as signed 32-bit integers, the widest integer element
+/// type, and floats only uncompressed.
+fn encode_pixels(image: &ImageEnum, compression: Compression) -> Result<(ElementType, Vec<u8>), Error> {
+	let mut data = Vec::new();
+	let element_type = match image {
+		ImageEnum::U8(image) => {
+			encode_integers(&mut data, image.pixels(), compression, ElementType::Unsigned8bitInteger)
+		}
+		ImageEnum::I8(image) => encode_integers(&mut data, image.pixels(), compression, ElementType::Signed8bitInteger),
+		ImageEnum::U16(image) => encode_integers(
+			&mut data,
+			image.pixels(),
+			compression,
+			ElementType::Unsigned16bitInteger,
+		),
+		ImageEnum::I16(image) => {
+			encode_integers(&mut data, image.pixels(), compression, ElementType::Signed16bitInteger)
+		}
+		ImageEnum::U32(image) => encode_integers(
+			&mut data,
+			image.pixels(),
+			compression,
+			ElementType::Unsigned32bitInteger,
+		),
+		ImageEnum::I32(image) => {
+			encode_integers(&mut data, image.pixels(), compression, ElementType::Signed32bitInteger)
+		}
+		ImageEnum::U64(image) => {
+			let pixels = narrow(image, |p| i64::try_from(*p).unwrap_or(i64::MAX))?;
+			encode_integers(&mut data, &pixels, compression, ElementType::Signed32bitInteger)
+		}
+		ImageEnum::I64(image) => {
+			let pixels = narrow(image, |p| *p)?;
+			encode_integers(&mut data, &pixels, compression, ElementType::Signed32bitInteger)
+		}
+		ImageEnum::F32(image) => encode_floats(&mut data, image.pixels(), compression, ElementType::Signed32bitReal),
+		ImageEnum::F64(image) => encode_floats(&mut data, image.pixels(), compression, ElementType::Signed64bitReal),
+	}?;
+	Ok((element_type, data))
+}
+
+fn encode_integers<P: AsPrimitive<i64> + ToBytes>(
+	data: &mut Vec<u8>,
+	pixels: &[P],
+	compression: Compression,
+	element_type: ElementType,
+) -> Result<ElementType, Error> {
+	match compression {
+		Compression::None => encode_raw(data, pixels),
+		Compression::ByteOffset => write_byte_offset(data, pixels)?,
+	};
+	Ok(element_type)
+}
+
+/// Floats can only be written uncompressed.
+fn encode_floats<P: ToBytes>(
+	data: &mut Vec<u8>,
+	pixels: &[P],
+	compression: Compression,
+	element_type: ElementType,
+) -> Result<ElementType, Error> {
+	if compression != Compression::None {
+		return Err(Error::UnsupportedPixelFormat);
+	}
+	encode_raw(data, pixels);
+	Ok(element_type)
+}
+
+/// Appends `pixels` as little endian bytes.
+fn encode_raw<P: ToBytes>(data: &mut Vec<u8>, pixels: &[P]) {
+	for pixel in pixels {
+		data.extend_from_slice(pixel.to_le_bytes().as_ref());
+	}
+}
+
+/// Converts 64-bit pixels to `i32`, failing on the first value outside of its
+/// range.
+fn narrow<P>(image: &Image<P>, value: impl Fn(&P) -> i64) -> Result<Vec<i32>, Error> {
+	image
+		.pixels()
+		.iter()
+		.enumerate()
+		.map(|(index, pixel)| {
+			let value = value(pixel);
+			i32::try_from(value).map_err(|_| Error::ElementOutOfRange { index, value })
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Compression, Encoder, WriteOptions};
+	use crate::{
+		image::{Image, ImageEnum},
+		read_all_images, read_image_with, Error, ReadOptions,
+	};
+
+	fn image() -> ImageEnum {
+		ImageEnum::I32(Image::new(3, 2, vec![0, 100, -40000, 7, 1 << 30, -1].into()).unwrap())
+	}
+
+	#[test]
+	fn round_trips_compressions() {
+		for compression in [Compression::None, Compression::ByteOffset] {
+			let options = WriteOptions::default().compression(compression).padding(3);
+			let mut file = Vec::new();
+			Encoder::new(options)
+				.write_image(&mut file, &image())
+				.expect("to write image");
+			let read =
+				read_image_with(&file[..], &ReadOptions::default().strict_element_type()).expect("to read image");
+			let ImageEnum::I32(read) = read else {
+				panic!("expected i32 pixels")
+			};
+			assert_eq!((read.width, read.height), (3, 2));
+			assert_eq!(read.pixels(), image().as_i32().unwrap().pixels(), "{compression:?}");
+		}
+	}
+
+	#[test]
+	fn writes_all_images() {
+		let images = [image(), ImageEnum::U8(Image::new(1, 1, vec![255].into()).unwrap())];
+		let mut file = Vec::new();
+		Encoder::default()
+			.write_all_images(&mut file, &images)
+			.expect("to write images");
+		let text = String::from_utf8_lossy(&file);
+		assert!(text.contains("data_image_2\r\n"));
+		assert!(text.contains("X-Binary-ID: 2\r\n"));
+		let read = read_all_images(&file[..]).expect("to read images");
+		assert_eq!(read.len(), 2);
+		assert_eq!(read[1].as_i64().unwrap().pixels(), [255]);
+	}
+
+	#[test]
+	fn strict_layout() {
+		let images = [image(), image()];
+		for options in [WriteOptions::default(), WriteOptions::default().md5(false).padding(2)] {
+			let mut strict = Vec::new();
+			Encoder::new(options.clone().strict(true))
+				.write_all_images(&mut strict, &images)
+				.expect("to write images");
+			let mut file = Vec::new();
+			Encoder::new(options).write_all_images(&mut file, &images).unwrap();
+			assert_eq!(strict, file);
+			assert_eq!(crate::validate::validate_strict(&file), []);
+		}
+	}
+
+	#[test]
+	fn narrows_wide_integers() {
+		let wide = ImageEnum::I64(Image::new(2, 1, vec![1, 1 << 40].into()).unwrap());
+		let error = Encoder::default().write_image(Vec::new(), &wide).unwrap_err();
+		assert!(matches!(error, Error::ElementOutOfRange { index: 1, value } if value == 1 << 40));
+		let float = ImageEnum::F32(Image::new(1, 1, vec![0.5].into()).unwrap());
+		assert!(matches!(
+			Encoder::default().write_image(Vec::new(), &float),
+			Err(Error::UnsupportedPixelFormat)
+		));
+	}
+
+	#[test]
+	fn round_trips_uncompressed() {
+		let options = WriteOptions::default().compression(Compression::None);
+		let float = ImageEnum::F64(Image::new(2, 1, vec![0.5, -1e300].into()).unwrap());
+		let mut file = Vec::new();
+		Encoder::new(options.clone())
+			.write_image(&mut file, &float)
+			.expect("to write image");
+		let read = read_image_with(&file[..], &ReadOptions::default()).expect("to read image");
+		assert_eq!(read.as_f64().unwrap().pixels(), [0.5, -1e300]);
+
+		let mut file = Vec::new();
+		Encoder::new(options)
+			.write_image(&mut file, &image())
+			.expect("to write image");
+		let read = read_image_with(&file[..], &ReadOptions::default().strict_element_type()).expect("to read image");
+		assert_eq!(read.as_i32().unwrap().pixels(), image().as_i32().unwrap().pixels());
+	}
+}
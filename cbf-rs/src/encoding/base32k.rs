@@ -0,0 +1,122 @@
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// First code point of the characters carrying data.
+const OFFSET: u32 = 0x4000;
+
+/// Decodes the X-Base32K encoding read from the underlying reader, in which
+/// every 15 bits of the data, most significant first, are written as the UTF-8
+/// character `U+4000` plus their value. Whitespace is skipped and reading ends
+/// at the `=` padding, so the text after the data is left unread.
+///
+/// The bits of the last character are zero filled, so its trailing byte may be
+/// padding rather than data. Only as many bytes as the section holds should be
+/// read.
+pub struct Base32KReader<R: Read> {
+	reader: R,
+	bits: u32,
+	len: u32,
+	ended: bool,
+}
+
+impl<R: Read> Base32KReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader, bits: 0, len: 0, ended: false }
+	}
+
+	fn next(&mut self) -> Result<Option<u8>> {
+		let mut byte = [0];
+		Ok((self.reader.read(&mut byte)? == 1).then_some(byte[0]))
+	}
+
+	/// Reads the 15 bits of the next character, or `None` at the end of the
+	/// text.
+	fn decode_character(&mut self) -> Result<Option<u32>> {
+		let lead = loop {
+			match self.next()? {
+				None | Some(b'=') => return Ok(None),
+				Some(byte) if byte.is_ascii_whitespace() => continue,
+				Some(byte) => break byte,
+			}
+		};
+		if lead & 0xF0 != 0xE0 {
+			return Err(invalid_data());
+		}
+		let mut code = (lead & 0x0F) as u32;
+		for _ in 0..2 {
+			let byte = self.next()?.ok_or(ErrorKind::UnexpectedEof)?;
+			if byte & 0xC0 != 0x80 {
+				return Err(invalid_data());
+			}
+			code = code << 6 | (byte & 0x3F) as u32;
+		}
+		match code.checked_sub(OFFSET) {
+			Some(value) if value < 1 << 15 => Ok(Some(value)),
+			_ => Err(invalid_data()),
+		}
+	}
+}
+
+impl<R: Read> Read for Base32KReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let mut written = 0;
+		while written < buf.len() {
+			if self.len < 8 {
+				if self.ended {
+					break;
+				}
+				match self.decode_character()? {
+					Some(value) => {
+						self.bits = self.bits << 15 | value;
+						self.len += 15;
+					}
+					None => self.ended = true,
+				}
+				continue;
+			}
+			self.len -= 8;
+			buf[written] = (self.bits >> self.len) as u8;
+			self.bits &= (1 << self.len) - 1;
+			written += 1;
+		}
+		Ok(written)
+	}
+}
+
+fn invalid_data() -> Error {
+	Error::new(ErrorKind::InvalidData, "invalid base32k")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Read;
+
+	use super::Base32KReader;
+
+	fn decode(text: &str) -> std::io::Result<Vec<u8>> {
+		let mut data = Vec::new();
+		Base32KReader::new(text.as_bytes()).read_to_end(&mut data)?;
+		Ok(data)
+	}
+
+	#[test]
+	fn decodes_characters() {
+		assert_eq!(decode("").unwrap(), Vec::<u8>::new());
+		assert_eq!(
+			decode("\u{42C0}\u{4280}\u{6000}=").unwrap(),
+			[0x05, 0x80, 0x0A, 0x01, 0x00]
+		);
+		assert_eq!(decode("\u{BFFF}\r\n\u{4000}").unwrap(), [0xFF, 0xFE, 0x00]);
+		assert!(decode("\u{3FFF}").is_err());
+		assert!(decode("A").is_err());
+	}
+
+	#[test]
+	fn reads_no_further_than_needed() {
+		let text = "\u{42C0}\u{4280}\u{6000}=\r\n--CIF-BINARY-FORMAT-SECTION----\r\n";
+		let mut rest = text.as_bytes();
+		let mut data = [0; 4];
+		Base32KReader::new(&mut rest).read_exact(&mut data).unwrap();
+		assert_eq!(data, [0x05, 0x80, 0x0A, 0x01]);
+		assert_eq!(rest, "=\r\n--CIF-BINARY-FORMAT-SECTION----\r\n".as_bytes());
+	}
+}
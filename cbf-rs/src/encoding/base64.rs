@@ -0,0 +1,225 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const LINE_LENGTH: usize = 76;
+
+/// Base64 encodes everything written to it, wrapping lines at 76 characters
+/// as required by MIME. [`Base64Writer::finish`] must be called to write the
+/// final, padded group.
+pub struct Base64Writer<W: Write> {
+	writer: W,
+	pending: [u8; 3],
+	pending_len: usize,
+	line_len: usize,
+}
+
+impl<W: Write> Base64Writer<W> {
+	pub fn new(writer: W) -> Self {
+		Self { writer, pending: [0; 3], pending_len: 0, line_len: 0 }
+	}
+
+	/// Writes the remaining bytes with padding and ends the last line.
+	pub fn finish(mut self) -> Result<W> {
+		if 0 < self.pending_len {
+			self.pending[self.pending_len..].fill(0);
+			let mut group = encode_group(self.pending);
+			group[self.pending_len + 1..].fill(b'=');
+			self.pending_len = 0;
+			self.write_group(group)?;
+		}
+		if 0 < self.line_len {
+			self.writer.write_all(b"\r\n")?;
+		}
+		Ok(self.writer)
+	}
+
+	fn write_group(&mut self, group: [u8; 4]) -> Result<()> {
+		if self.line_len == LINE_LENGTH {
+			self.writer.write_all(b"\r\n")?;
+			self.line_len = 0;
+		}
+		self.writer.write_all(&group)?;
+		self.line_len += group.len();
+		Ok(())
+	}
+}
+
+impl<W: Write> Write for Base64Writer<W> {
+	fn write(&mut self, buf: &[u8]) -> Result<usize> {
+		for byte in buf {
+			self.pending[self.pending_len] = *byte;
+			self.pending_len += 1;
+			if self.pending_len == 3 {
+				self.pending_len = 0;
+				self.write_group(encode_group(self.pending))?;
+			}
+		}
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> Result<()> {
+		self.writer.flush()
+	}
+}
+
+fn encode_group([a, b, c]: [u8; 3]) -> [u8; 4] {
+	[
+		ALPHABET[(a >> 2) as usize],
+		ALPHABET[((a & 0x03) << 4 | b >> 4) as usize],
+		ALPHABET[((b & 0x0F) << 2 | c >> 6) as usize],
+		ALPHABET[(c & 0x3F) as usize],
+	]
+}
+
+/// Decodes base64 read from the underlying reader, skipping line breaks and
+/// other whitespace. Reading ends at the padding of the final group, and no
+/// further than needed otherwise, so the text after the data is left unread.
+pub struct Base64Reader<R: Read> {
+	reader: R,
+	decoded: [u8; 3],
+	position: usize,
+	len: usize,
+	ended: bool,
+}
+
+impl<R: Read> Base64Reader<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader, decoded: [0; 3], position: 0, len: 0, ended: false }
+	}
+
+	/// Decodes the next group of four symbols into `decoded`.
+	fn decode_group(&mut self) -> Result<()> {
+		let mut group = [0; 4];
+		let mut count = 0;
+		while count < 4 {
+			let mut byte = [0];
+			if self.reader.read(&mut byte)? == 0 {
+				if count == 0 {
+					(self.position, self.len, self.ended) = (0, 0, true);
+					return Ok(());
+				}
+				return Err(ErrorKind::UnexpectedEof.into());
+			}
+			if byte[0].is_ascii_whitespace() {
+				continue;
+			}
+			group[count] = byte[0];
+			count += 1;
+		}
+		let padding = group.iter().rev().take_while(|b| **b == b'=').count();
+		if 2 < padding {
+			return Err(invalid_data());
+		}
+		let mut bits = 0u32;
+		for symbol in &group[..4 - padding] {
+			bits = bits << 6 | decode_symbol(*symbol).ok_or_else(invalid_data)? as u32;
+		}
+		bits <<= 6 * padding;
+		self.decoded = [(bits >> 16) as u8, (bits >> 8) as u8, bits as u8];
+		self.len = 3 - padding;
+		self.position = 0;
+		self.ended = 0 < padding;
+		Ok(())
+	}
+}
+
+impl<R: Read> Read for Base64Reader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let mut written = 0;
+		while written < buf.len() {
+			if self.position == self.len {
+				if self.ended {
+					break;
+				}
+				self.decode_group()?;
+				continue;
+			}
+			let available = (self.len - self.position).min(buf.len() - written);
+			buf[written..written + available].copy_from_slice(&self.decoded[self.position..self.position + available]);
+			self.position += available;
+			written += available;
+		}
+		Ok(written)
+	}
+}
+
+fn decode_symbol(symbol: u8) -> Option<u8> {
+	match symbol {
+		b'A'..=b'Z' => Some(symbol - b'A'),
+		b'a'..=b'z' => Some(symbol - b'a' + 26),
+		b'0'..=b'9' => Some(symbol - b'0' + 52),
+		b'+' => Some(62),
+		b'/' => Some(63),
+		_ => None,
+	}
+}
+
+fn invalid_data() -> Error {
+	Error::new(ErrorKind::InvalidData, "invalid base64")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::{Read, Write};
+
+	use super::{Base64Reader, Base64Writer};
+
+	fn encode(data: &[u8]) -> String {
+		let mut writer = Base64Writer::new(Vec::new());
+		writer.write_all(data).expect("to write");
+		String::from_utf8(writer.finish().expect("to finish")).expect("base64 to be ascii")
+	}
+
+	#[test]
+	fn encode_padding() {
+		assert_eq!(encode(b""), "");
+		assert_eq!(encode(b"f"), "Zg==\r\n");
+		assert_eq!(encode(b"fo"), "Zm8=\r\n");
+		assert_eq!(encode(b"foo"), "Zm9v\r\n");
+		assert_eq!(encode(b"foobar"), "Zm9vYmFy\r\n");
+		assert_eq!(encode(&[0x0C, 0x1A, 0x04, 0xD5]), "DBoE1Q==\r\n");
+	}
+
+	#[test]
+	fn wraps_lines() {
+		let encoded = encode(&[0; 60]);
+		let lines: Vec<_> = encoded.split("\r\n").collect();
+		assert_eq!(lines.len(), 3);
+		assert_eq!(lines[0].len(), 76);
+		assert_eq!(lines[1], "AAAA");
+		assert_eq!(lines[2], "");
+	}
+
+	fn decode(text: &[u8]) -> std::io::Result<Vec<u8>> {
+		let mut data = Vec::new();
+		Base64Reader::new(text).read_to_end(&mut data)?;
+		Ok(data)
+	}
+
+	#[test]
+	fn decode_padding() {
+		assert_eq!(decode(b"").unwrap(), b"");
+		assert_eq!(decode(b"Zg==\r\n").unwrap(), b"f");
+		assert_eq!(decode(b"Zm8=").unwrap(), b"fo");
+		assert_eq!(decode(b"Zm9v\r\nYmFy").unwrap(), b"foobar");
+		assert!(decode(b"Zm9").is_err());
+		assert!(decode(b"Zm9*").is_err());
+		assert!(decode(b"Z===").is_err());
+	}
+
+	#[test]
+	fn round_trips() {
+		let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+		assert_eq!(decode(encode(&data).as_bytes()).unwrap(), data);
+	}
+
+	#[test]
+	fn stops_after_padding() {
+		let mut text = &b"Zm8=\r\n--CIF-BINARY-FORMAT-SECTION----\r\n"[..];
+		let mut reader = Base64Reader::new(&mut text);
+		let mut data = Vec::new();
+		reader.read_to_end(&mut data).unwrap();
+		assert_eq!(data, b"fo");
+		assert_eq!(text, b"\r\n--CIF-BINARY-FORMAT-SECTION----\r\n");
+	}
+}
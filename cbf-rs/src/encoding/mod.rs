@@ -0,0 +1,9 @@
+mod base32k;
+#[cfg(feature = "base64")]
+mod base64;
+mod quoted_printable;
+
+pub use base32k::Base32KReader;
+#[cfg(feature = "base64")]
+pub use base64::{Base64Reader, Base64Writer};
+pub use quoted_printable::QuotedPrintableReader;
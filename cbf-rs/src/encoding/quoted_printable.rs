@@ -0,0 +1,90 @@
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// Decodes quoted-printable text read from the underlying reader.
+///
+/// Binary data has its line breaks escaped, so literal line breaks only wrap
+/// the text and are skipped, as are soft line breaks. Reading goes no further
+/// than needed, leaving the text after the data unread.
+pub struct QuotedPrintableReader<R: Read> {
+	reader: R,
+}
+
+impl<R: Read> QuotedPrintableReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader }
+	}
+
+	/// Next character of the text, or `None` at its end.
+	fn next(&mut self) -> Result<Option<u8>> {
+		let mut byte = [0];
+		Ok((self.reader.read(&mut byte)? == 1).then_some(byte[0]))
+	}
+
+	/// Next decoded byte, or `None` at the end of the text.
+	fn decode(&mut self) -> Result<Option<u8>> {
+		loop {
+			match self.next()? {
+				None => return Ok(None),
+				Some(b'\r' | b'\n') => continue,
+				Some(b'=') => {}
+				Some(byte) => return Ok(Some(byte)),
+			}
+			let high = self.next()?.ok_or(ErrorKind::UnexpectedEof)?;
+			if high == b'\r' || high == b'\n' {
+				continue;
+			}
+			let low = self.next()?.ok_or(ErrorKind::UnexpectedEof)?;
+			return match (hex_digit(high), hex_digit(low)) {
+				(Some(high), Some(low)) => Ok(Some(high << 4 | low)),
+				_ => Err(Error::new(ErrorKind::InvalidData, "invalid quoted-printable escape")),
+			};
+		}
+	}
+}
+
+impl<R: Read> Read for QuotedPrintableReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		for (written, slot) in buf.iter_mut().enumerate() {
+			match self.decode()? {
+				Some(byte) => *slot = byte,
+				None => return Ok(written),
+			}
+		}
+		Ok(buf.len())
+	}
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+	(digit as char).to_digit(16).map(|d| d as u8)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Read;
+
+	use super::QuotedPrintableReader;
+
+	fn decode(text: &[u8]) -> std::io::Result<Vec<u8>> {
+		let mut data = Vec::new();
+		QuotedPrintableReader::new(text).read_to_end(&mut data)?;
+		Ok(data)
+	}
+
+	#[test]
+	fn decodes_escapes() {
+		assert_eq!(decode(b"a=3Db").unwrap(), b"a=b");
+		assert_eq!(decode(b"=0C=1A=04=d5").unwrap(), [0x0C, 0x1A, 0x04, 0xD5]);
+		assert_eq!(decode(b"ab=\r\ncd\r\nef=0D=0A").unwrap(), b"abcdef\r\n");
+		assert!(decode(b"=G1").is_err());
+		assert!(decode(b"=4").is_err());
+	}
+
+	#[test]
+	fn reads_no_further_than_needed() {
+		let mut text = &b"=01=02\r\n--CIF-BINARY-FORMAT-SECTION----\r\n"[..];
+		let mut data = [0; 2];
+		QuotedPrintableReader::new(&mut text).read_exact(&mut data).unwrap();
+		assert_eq!(data, [1, 2]);
+		assert_eq!(text, b"\r\n--CIF-BINARY-FORMAT-SECTION----\r\n");
+	}
+}
@@ -0,0 +1,221 @@
+use std::io::{Error, ErrorKind, Result, Write};
+
+#[cfg(feature = "png")]
+use super::png::write_png;
+#[cfg(feature = "png")]
+use crate::encoding::Base64Writer;
+
+/// Smallest side of the profile panel, in pixels.
+const MIN_PANEL: usize = 200;
+const LINE: [u8; 3] = [31, 119, 180];
+
+/// A rendered frame and its radial profile, drawn side by side by [`figure`].
+#[derive(Debug, Clone, Copy)]
+pub struct Figure<'a> {
+	/// RGBA pixels of the frame, such as a frame mapped through a
+	/// [`Colormap`](crate::image::colormap::Colormap) with overlays drawn on
+	/// top by [`render_overlay`](crate::analysis::overlay::render_overlay)
+	pub frame: &'a [u8],
+	pub width: usize,
+	pub height: usize,
+	/// Bin centres of the profile in pixels from the beam centre
+	pub radii: &'a [f64],
+	pub profile: &'a [f64],
+	/// Written above the plot
+	pub title: Option<&'a str>,
+}
+
+/// Positions of the parts of a figure, in pixels from its top left corner.
+struct Layout {
+	width: usize,
+	height: usize,
+	/// Left, top, right and bottom edge of the plot area
+	plot: [f64; 4],
+	/// Ranges of the finite radii and values
+	x_range: (f64, f64),
+	y_range: (f64, f64),
+}
+
+impl Layout {
+	fn new(figure: &Figure) -> Self {
+		let panel = figure.height.max(MIN_PANEL);
+		let margin = (panel / 8) as f64;
+		let left = frame_width(figure) as f64;
+		let plot = [
+			left + margin,
+			margin / 2.0,
+			left + panel as f64 - margin / 2.0,
+			panel as f64 - margin,
+		];
+		let points = || finite_points(figure.radii, figure.profile);
+		Self {
+			width: frame_width(figure) + panel,
+			height: panel,
+			plot,
+			x_range: range(points().map(|(x, _)| x)),
+			y_range: range(points().map(|(_, y)| y)),
+		}
+	}
+
+	/// Runs of consecutive finite points in figure coordinates, broken at
+	/// empty bins.
+	fn segments(&self, figure: &Figure) -> Vec<Vec<(f64, f64)>> {
+		let [left, top, right, bottom] = self.plot;
+		let scale = |v: f64, (low, high): (f64, f64)| (v - low) / (high - low);
+		let mut segments = vec![Vec::new()];
+		for (&x, &y) in figure.radii.iter().zip(figure.profile) {
+			if x.is_finite() && y.is_finite() {
+				let position = (
+					left + scale(x, self.x_range) * (right - left),
+					bottom - scale(y, self.y_range) * (bottom - top),
+				);
+				segments.last_mut().unwrap().push(position);
+			} else if !segments.last().unwrap().is_empty() {
+				segments.push(Vec::new());
+			}
+		}
+		segments.retain(|s| !s.is_empty());
+		segments
+	}
+}
+
+fn finite_points<'a>(radii: &'a [f64], profile: &'a [f64]) -> impl Iterator<Item = (f64, f64)> + 'a {
+	radii
+		.iter()
+		.zip(profile)
+		.map(|(&x, &y)| (x, y))
+		.filter(|(x, y)| x.is_finite() && y.is_finite())
+}
+
+/// Lowest and highest value, widened to a unit range when they coincide.
+fn range(values: impl Iterator<Item = f64>) -> (f64, f64) {
+	let (low, high) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(l, h), v| (l.min(v), h.max(v)));
+	match (low, high) {
+		(low, high) if low < high => (low, high),
+		(low, _) if low.is_finite() => (low - 0.5, low + 0.5),
+		_ => (0.0, 1.0),
+	}
+}
+
+/// Width the frame takes up in the figure, none if it cannot be embedded.
+fn frame_width(figure: &Figure) -> usize {
+	if cfg!(feature = "png") {
+		figure.width
+	} else {
+		0
+	}
+}
+
+/// Writes the frame with a plot of its profile to its right as an SVG image,
+/// for logbooks and publications. The frame is embedded as a PNG image, so it
+/// is left out without the `png` feature.
+pub fn figure(writer: impl Write, figure: &Figure) -> Result<()> {
+	if figure.width.checked_mul(figure.height).and_then(|n| n.checked_mul(4)) != Some(figure.frame.len()) {
+		return Err(Error::new(
+			ErrorKind::InvalidInput,
+			"frame does not match its dimensions",
+		));
+	}
+	write_svg(writer, figure, &Layout::new(figure))
+}
+
+fn write_svg(mut writer: impl Write, figure: &Figure, layout: &Layout) -> Result<()> {
+	let (width, height) = (layout.width, layout.height);
+	writeln!(
+		writer,
+		r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">"#
+	)?;
+	writeln!(writer, r#"<rect width="{width}" height="{height}" fill="white"/>"#)?;
+	#[cfg(feature = "png")]
+	if 0 < figure.width && 0 < figure.height {
+		write!(
+			writer,
+			r#"<image width="{}" height="{}" style="image-rendering:pixelated" href="data:image/png;base64,"#,
+			figure.width, figure.height
+		)?;
+		let mut encoded = Base64Writer::new(&mut writer);
+		write_png(&mut encoded, figure.width, figure.height, figure.frame)?;
+		encoded.finish()?;
+		writeln!(writer, r#""/>"#)?;
+	}
+
+	let [left, top, right, bottom] = layout.plot;
+	let font = (height / 30).max(8);
+	writeln!(
+		writer,
+		r#"<path d="M{left} {top}V{bottom}H{right}" fill="none" stroke="black"/>"#
+	)?;
+	for segment in layout.segments(figure) {
+		let points: Vec<String> = segment.iter().map(|(x, y)| format!("{x:.2},{y:.2}")).collect();
+		writeln!(
+			writer,
+			r#"<polyline points="{}" fill="none" stroke="rgb({},{},{})"/>"#,
+			points.join(" "),
+			LINE[0],
+			LINE[1],
+			LINE[2]
+		)?;
+	}
+
+	let text = |anchor: &str, x: f64, y: f64, content: &str| {
+		format!(
+			r#"<text x="{x}" y="{y}" font-family="sans-serif" font-size="{font}" text-anchor="{anchor}">{content}</text>"#
+		)
+	};
+	let label = |v: f64| format!("{v:.4}");
+	let below = bottom + font as f64 * 1.2;
+	writeln!(writer, "{}", text("start", left, below, &label(layout.x_range.0)))?;
+	writeln!(writer, "{}", text("end", right, below, &label(layout.x_range.1)))?;
+	writeln!(
+		writer,
+		"{}",
+		text(
+			"middle",
+			(left + right) / 2.0,
+			below + font as f64 * 1.2,
+			"radius (pixels)"
+		)
+	)?;
+	writeln!(writer, "{}", text("end", left - 4.0, bottom, &label(layout.y_range.0)))?;
+	writeln!(
+		writer,
+		"{}",
+		text("end", left - 4.0, top + font as f64, &label(layout.y_range.1))
+	)?;
+	if let Some(title) = figure.title {
+		writeln!(writer, "{}", text("middle", (left + right) / 2.0, top, &escape(title)))?;
+	}
+	writeln!(writer, "</svg>")
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{figure, Figure};
+
+	fn example<'a>(frame: &'a [u8], profile: &'a [f64]) -> Figure<'a> {
+		Figure { frame, width: 2, height: 2, radii: &[0.0, 1.0, 2.0, 3.0], profile, title: Some("a & b") }
+	}
+
+	#[test]
+	fn svg_composite() {
+		let frame = [0; 16];
+		let mut svg = Vec::new();
+		figure(&mut svg, &example(&frame, &[1.0, 3.0, f64::NAN, 2.0])).unwrap();
+		let svg = String::from_utf8(svg).unwrap();
+		#[cfg(feature = "png")]
+		assert!(svg.contains("data:image/png;base64,iVBORw0KGgo"));
+		// The panel is at least 200 pixels square, next to the frame.
+		let width = if cfg!(feature = "png") { 202 } else { 200 };
+		assert!(svg.contains(&format!(r#"width="{width}" height="200""#)));
+		// The empty bin splits the profile in two lines.
+		assert_eq!(svg.matches("<polyline").count(), 2);
+		assert!(svg.contains(">a &amp; b</text>"));
+		assert!(svg.contains(">radius (pixels)</text>"));
+		assert!(svg.trim_end().ends_with("</svg>"));
+		assert!(figure(Vec::new(), &example(&frame[..4], &[])).is_err());
+	}
+}
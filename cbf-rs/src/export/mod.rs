@@ -0,0 +1,7 @@
+mod figure;
+#[cfg(feature = "png")]
+mod png;
+
+#[cfg(feature = "png")]
+pub use self::png::write_png;
+pub use figure::{figure, Figure};
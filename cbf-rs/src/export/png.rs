@@ -0,0 +1,51 @@
+use std::io::{Error, ErrorKind, Result, Write};
+
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+
+/// Writes 8-bit RGBA pixels as a compressed PNG image.
+pub fn write_png(writer: impl Write, width: usize, height: usize, rgba: &[u8]) -> Result<()> {
+	let (Ok(png_width), Ok(png_height)) = (u32::try_from(width), u32::try_from(height)) else {
+		return Err(Error::new(ErrorKind::InvalidInput, "image too large for PNG"));
+	};
+	if width.checked_mul(height).and_then(|n| n.checked_mul(4)) != Some(rgba.len()) {
+		return Err(Error::new(
+			ErrorKind::InvalidInput,
+			"buffer does not match the dimensions",
+		));
+	}
+
+	let mut encoder = Encoder::new(writer, png_width, png_height);
+	encoder.set_color(ColorType::Rgba);
+	encoder.set_depth(BitDepth::Eight);
+	let mut writer = encoder.write_header().map_err(io_error)?;
+	writer.write_image_data(rgba).map_err(io_error)?;
+	writer.finish().map_err(io_error)
+}
+
+fn io_error(error: EncodingError) -> Error {
+	match error {
+		EncodingError::IoError(error) => error,
+		error => Error::new(ErrorKind::InvalidInput, error),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::write_png;
+
+	#[test]
+	fn png_layout() {
+		let mut png = Vec::new();
+		write_png(&mut png, 2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]).unwrap();
+		assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR\0\0\0\x02\0\0\0\x01\x08\x06"));
+		assert!(png.ends_with(b"IEND\xae\x42\x60\x82"));
+		assert!(write_png(&mut Vec::new(), 2, 2, &[0; 8]).is_err());
+	}
+
+	#[test]
+	fn compresses() {
+		let mut png = Vec::new();
+		write_png(&mut png, 1000, 1000, &[7; 4_000_000]).unwrap();
+		assert!(png.len() < 100_000, "{} bytes", png.len());
+	}
+}
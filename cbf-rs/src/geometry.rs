@@ -0,0 +1,297 @@
+use std::{f64, io::Write};
+
+/// Experimental geometry of a flat detector perpendicular to the beam.
+///
+/// Lengths are in metres, detector positions in (fractional) pixels measured
+/// from the centre of the first pixel, angles in radians, and scattering
+/// vectors in inverse ångström.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geometry {
+	/// Beam centre in pixels (fast, slow)
+	pub beam_centre: (f64, f64),
+	/// Sample to detector distance
+	pub distance: f64,
+	/// Pixel size (fast, slow)
+	pub pixel_size: (f64, f64),
+	pub wavelength: Option<f64>,
+}
+
+/// Circle in pixel coordinates on which a single resolution is observed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResolutionRing {
+	/// Resolution in ångström
+	pub resolution: f64,
+	pub centre: (f64, f64),
+	pub radius: f64,
+}
+
+/// Position relative to the beam centre.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Polar {
+	/// Distance from the beam centre in pixels
+	pub radius: f64,
+	/// Angle from the fast axis towards the slow axis
+	pub phi: f64,
+}
+
+impl Geometry {
+	pub fn detector_to_polar(&self, (x, y): (f64, f64)) -> Polar {
+		let dx = x - self.beam_centre.0;
+		let dy = y - self.beam_centre.1;
+		Polar { radius: dx.hypot(dy), phi: dy.atan2(dx) }
+	}
+
+	pub fn polar_to_detector(&self, polar: Polar) -> (f64, f64) {
+		(
+			self.beam_centre.0 + polar.radius * polar.phi.cos(),
+			self.beam_centre.1 + polar.radius * polar.phi.sin(),
+		)
+	}
+
+	/// Scattering angle 2θ of a detector position.
+	pub fn two_theta(&self, (x, y): (f64, f64)) -> f64 {
+		let dx = (x - self.beam_centre.0) * self.pixel_size.0;
+		let dy = (y - self.beam_centre.1) * self.pixel_size.1;
+		dx.hypot(dy).atan2(self.distance)
+	}
+
+	/// Magnitude of the scattering vector at a detector position, if the
+	/// wavelength is known.
+	pub fn q(&self, position: (f64, f64)) -> Option<f64> {
+		let wavelength = self.wavelength?;
+		Some(two_theta_to_q(self.two_theta(position), wavelength))
+	}
+
+	/// Scattering angle 2θ at `radius` pixels from the beam centre.
+	pub fn radius_to_two_theta(&self, radius: f64) -> f64 {
+		(radius * self.radial_pixel_size()).atan2(self.distance)
+	}
+
+	/// Magnitude of the scattering vector at `radius` pixels from the beam
+	/// centre, if the wavelength is known.
+	pub fn radius_to_q(&self, radius: f64) -> Option<f64> {
+		let wavelength = self.wavelength?;
+		Some(two_theta_to_q(self.radius_to_two_theta(radius), wavelength))
+	}
+
+	/// Radius in pixels at which `q` is observed.
+	pub fn q_to_radius(&self, q: f64) -> Option<f64> {
+		let wavelength = self.wavelength?;
+		let sin_theta = q * wavelength * 1e10 / (4.0 * f64::consts::PI);
+		if !(0.0..1.0).contains(&sin_theta) {
+			return None;
+		}
+		let two_theta = 2.0 * sin_theta.asin();
+		if f64::consts::FRAC_PI_2 <= two_theta {
+			return None;
+		}
+		Some(self.distance * two_theta.tan() / self.radial_pixel_size())
+	}
+
+	/// Length of a pixel along a radius. Pixels that are not square are taken
+	/// as squares of the same area, as radii in pixels are the same length in
+	/// every direction.
+	fn radial_pixel_size(&self) -> f64 {
+		(self.pixel_size.0 * self.pixel_size.1).sqrt()
+	}
+
+	/// Radius in pixels at which a resolution of `d` ångström is observed.
+	pub fn resolution_to_radius(&self, d: f64) -> Option<f64> {
+		self.q_to_radius(resolution_to_q(d))
+	}
+
+	/// Circles around the beam centre for every resolution in ångström that
+	/// reaches the detector plane. Requires a wavelength.
+	pub fn resolution_rings(&self, resolutions: &[f64]) -> Vec<ResolutionRing> {
+		resolutions
+			.iter()
+			.filter_map(|&resolution| {
+				let radius = self.resolution_to_radius(resolution)?;
+				Some(ResolutionRing { resolution, centre: self.beam_centre, radius })
+			})
+			.collect()
+	}
+
+	/// Inner and outer radius in pixels of the ring between a low and a high
+	/// resolution limit in ångström, e.g. for [`annulus_stats`].
+	///
+	/// [`annulus_stats`]: crate::analysis::annulus_stats
+	pub fn resolution_range(&self, low: f64, high: f64) -> Option<(f64, f64)> {
+		Some((self.resolution_to_radius(low)?, self.resolution_to_radius(high)?))
+	}
+
+	/// Writes the geometry as a pyFAI PONI file, without detector rotations.
+	pub fn write_poni(&self, mut writer: impl Write) -> std::io::Result<()> {
+		writeln!(writer, "# Nota: C-Order, 1 refers to the Y axis, 2 to the X axis")?;
+		writeln!(writer, "poni_version: 2")?;
+		writeln!(writer, "Detector: Detector")?;
+		writeln!(
+			writer,
+			"Detector_config: {{\"pixel1\": {}, \"pixel2\": {}, \"max_shape\": null}}",
+			self.pixel_size.1, self.pixel_size.0
+		)?;
+		writeln!(writer, "Distance: {}", self.distance)?;
+		// The PONI is measured from the outer corner of the first pixel.
+		writeln!(writer, "Poni1: {}", (self.beam_centre.1 + 0.5) * self.pixel_size.1)?;
+		writeln!(writer, "Poni2: {}", (self.beam_centre.0 + 0.5) * self.pixel_size.0)?;
+		writeln!(writer, "Rot1: 0.0")?;
+		writeln!(writer, "Rot2: 0.0")?;
+		writeln!(writer, "Rot3: 0.0")?;
+		if let Some(wavelength) = self.wavelength {
+			writeln!(writer, "Wavelength: {wavelength}")?;
+		}
+		Ok(())
+	}
+
+	/// Writes the geometry as a single panel CrystFEL geometry file. CrystFEL
+	/// assumes square pixels, so only the fast pixel size is used.
+	pub fn write_crystfel_geom(&self, mut writer: impl Write, width: usize, height: usize) -> std::io::Result<()> {
+		if let Some(wavelength) = self.wavelength {
+			writeln!(writer, "wavelength = {wavelength} m")?;
+		}
+		writeln!(writer, "adu_per_photon = 1")?;
+		writeln!(writer, "clen = {}", self.distance)?;
+		writeln!(writer, "res = {}", 1.0 / self.pixel_size.0)?;
+		writeln!(writer)?;
+		writeln!(writer, "p0/min_fs = 0")?;
+		writeln!(writer, "p0/max_fs = {}", width.saturating_sub(1))?;
+		writeln!(writer, "p0/min_ss = 0")?;
+		writeln!(writer, "p0/max_ss = {}", height.saturating_sub(1))?;
+		writeln!(writer, "p0/corner_x = {}", -(self.beam_centre.0 + 0.5))?;
+		writeln!(writer, "p0/corner_y = {}", -(self.beam_centre.1 + 0.5))?;
+		writeln!(writer, "p0/fs = +1.0x")?;
+		writeln!(writer, "p0/ss = +1.0y")
+	}
+}
+
+/// Magnitude of the scattering vector in inverse ångström of a resolution in
+/// ångström.
+pub fn resolution_to_q(d: f64) -> f64 {
+	2.0 * f64::consts::PI / d
+}
+
+fn two_theta_to_q(two_theta: f64, wavelength: f64) -> f64 {
+	4.0 * f64::consts::PI * (two_theta / 2.0).sin() / (wavelength * 1e10)
+}
+
+/// Zoom and pan of a view onto the detector, mapping display coordinates to
+/// detector pixels as `detector = display / zoom + pan`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayTransform {
+	pub zoom: f64,
+	pub pan: (f64, f64),
+}
+
+impl Default for DisplayTransform {
+	fn default() -> Self {
+		Self { zoom: 1.0, pan: (0.0, 0.0) }
+	}
+}
+
+impl DisplayTransform {
+	pub fn display_to_detector(&self, (x, y): (f64, f64)) -> (f64, f64) {
+		(x / self.zoom + self.pan.0, y / self.zoom + self.pan.1)
+	}
+
+	pub fn detector_to_display(&self, (x, y): (f64, f64)) -> (f64, f64) {
+		((x - self.pan.0) * self.zoom, (y - self.pan.1) * self.zoom)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{DisplayTransform, Geometry, Polar};
+
+	use std::f64;
+
+	fn geometry() -> Geometry {
+		Geometry { beam_centre: (1440.0, 1440.0), distance: 0.2, pixel_size: (172e-6, 172e-6), wavelength: Some(1e-10) }
+	}
+
+	fn assert_close(expected: f64, actual: f64) {
+		assert!(
+			(expected - actual).abs() <= 1e-9,
+			"expected {} and actual {} are not equal",
+			expected,
+			actual
+		);
+	}
+
+	#[test]
+	fn polar_round_trip() {
+		let geometry = geometry();
+		let polar = geometry.detector_to_polar((1440.0, 1540.0));
+		assert_close(100.0, polar.radius);
+		assert_close(f64::consts::FRAC_PI_2, polar.phi);
+
+		let (x, y) = geometry.polar_to_detector(Polar { radius: 100.0, phi: f64::consts::PI });
+		assert_close(1340.0, x);
+		assert_close(1440.0, y);
+	}
+
+	#[test]
+	fn q_round_trip() {
+		let geometry = geometry();
+		assert_close(0.0, geometry.q((1440.0, 1440.0)).unwrap());
+		let q = geometry.q((1940.0, 1440.0)).unwrap();
+		assert_close(500.0, geometry.q_to_radius(q).unwrap());
+	}
+
+	#[test]
+	fn rectangular_pixels() {
+		let geometry = Geometry { pixel_size: (100e-6, 400e-6), ..geometry() };
+		let q = geometry.radius_to_q(500.0).unwrap();
+		assert_close(500.0, geometry.q_to_radius(q).unwrap());
+		assert_close((500.0 * 200e-6f64).atan2(0.2), geometry.radius_to_two_theta(500.0));
+	}
+
+	#[test]
+	fn q_needs_wavelength() {
+		let geometry = Geometry { wavelength: None, ..geometry() };
+		assert_eq!(geometry.q((0.0, 0.0)), None);
+		assert_eq!(geometry.q_to_radius(1.0), None);
+		assert_eq!(geometry.radius_to_q(1.0), None);
+	}
+
+	#[test]
+	fn resolution_rings() {
+		let geometry = geometry();
+		// λ = 1 Å and d = 0.5 Å gives 2θ = 180°, which never reaches the detector.
+		let rings = geometry.resolution_rings(&[2.0, 0.5]);
+		assert_eq!(rings.len(), 1);
+		let expected = 0.2 * (2.0 * 0.25f64.asin()).tan() / 172e-6;
+		assert_close(expected, rings[0].radius);
+		assert_eq!(rings[0].centre, (1440.0, 1440.0));
+
+		let (inner, outer) = geometry.resolution_range(4.0, 2.0).unwrap();
+		assert!(inner < outer);
+		assert_close(expected, outer);
+	}
+
+	#[test]
+	fn poni_export() {
+		let mut poni = Vec::new();
+		geometry().write_poni(&mut poni).unwrap();
+		let poni = String::from_utf8(poni).unwrap();
+		assert!(poni.contains("Distance: 0.2\n"));
+		assert!(poni.contains("Poni1: 0.24776"));
+		assert!(poni.contains("Wavelength: 0.0000000001\n"));
+	}
+
+	#[test]
+	fn crystfel_export() {
+		let mut geom = Vec::new();
+		geometry().write_crystfel_geom(&mut geom, 2880, 2880).unwrap();
+		let geom = String::from_utf8(geom).unwrap();
+		assert!(geom.contains("p0/max_fs = 2879\n"));
+		assert!(geom.contains("p0/corner_x = -1440.5\n"));
+		assert!(geom.contains("clen = 0.2\n"));
+	}
+
+	#[test]
+	fn display_round_trip() {
+		let transform = DisplayTransform { zoom: 2.0, pan: (100.0, 50.0) };
+		assert_eq!(transform.display_to_detector((20.0, 40.0)), (110.0, 70.0));
+		assert_eq!(transform.detector_to_display((110.0, 70.0)), (20.0, 40.0));
+	}
+}
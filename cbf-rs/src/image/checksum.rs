@@ -0,0 +1,118 @@
+use super::{Image, ImageEnum};
+
+/// Lookup table of the reflected IEEE CRC-32 polynomial.
+const CRC32_TABLE: [u32; 256] = {
+	let mut table = [0; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u32;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 1 == 1 {
+				(crc >> 1) ^ 0xEDB8_8320
+			} else {
+				crc >> 1
+			};
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+};
+
+/// Incremental CRC-32 (IEEE), as used by zlib and PNG.
+#[derive(Debug, Clone)]
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+	fn default() -> Self {
+		Self(!0)
+	}
+}
+
+impl Crc32 {
+	pub fn update(&mut self, bytes: &[u8]) {
+		for &byte in bytes {
+			self.0 = CRC32_TABLE[((self.0 ^ byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+		}
+	}
+
+	pub fn finish(&self) -> u32 {
+		!self.0
+	}
+}
+
+/// Pixel types with a fixed little endian byte representation.
+pub trait LeBytes {
+	type Bytes: AsRef<[u8]>;
+
+	fn le_bytes(&self) -> Self::Bytes;
+}
+
+macro_rules! le_bytes {
+	($($type:ty,)*) => {
+		$(impl LeBytes for $type {
+			type Bytes = [u8; std::mem::size_of::<$type>()];
+
+			fn le_bytes(&self) -> Self::Bytes {
+				self.to_le_bytes()
+			}
+		})*
+	};
+}
+
+le_bytes!(u8, i8, u16, i16, u32, i32, f32, u64, i64, f64,);
+
+impl<P: LeBytes> Image<P> {
+	/// CRC-32 of the pixels as little endian bytes. Cheap enough to detect
+	/// identical frames, but the dimensions are not included and it is no
+	/// protection against deliberate tampering.
+	pub fn crc32(&self) -> u32 {
+		let mut crc = Crc32::default();
+		for pixel in self.pixels() {
+			crc.update(pixel.le_bytes().as_ref());
+		}
+		crc.finish()
+	}
+}
+
+impl ImageEnum {
+	/// CRC-32 of the pixels as little endian bytes of their own type.
+	pub fn crc32(&self) -> u32 {
+		match self {
+			ImageEnum::U8(image) => image.crc32(),
+			ImageEnum::I8(image) => image.crc32(),
+			ImageEnum::U16(image) => image.crc32(),
+			ImageEnum::I16(image) => image.crc32(),
+			ImageEnum::U32(image) => image.crc32(),
+			ImageEnum::I32(image) => image.crc32(),
+			ImageEnum::F32(image) => image.crc32(),
+			ImageEnum::U64(image) => image.crc32(),
+			ImageEnum::I64(image) => image.crc32(),
+			ImageEnum::F64(image) => image.crc32(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Crc32;
+	use crate::image::Image;
+
+	#[test]
+	fn crc32_check_value() {
+		let mut crc = Crc32::default();
+		crc.update(b"12345");
+		crc.update(b"6789");
+		assert_eq!(crc.finish(), 0xCBF4_3926);
+	}
+
+	#[test]
+	fn image_crc32() {
+		let image = Image::new(3, 3, b"123456789".to_vec().into()).unwrap();
+		assert_eq!(image.crc32(), 0xCBF4_3926);
+		let wider = Image::new(3, 3, b"123456789".map(u16::from).into()).unwrap();
+		assert_ne!(wider.crc32(), image.crc32());
+	}
+}
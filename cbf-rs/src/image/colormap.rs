@@ -0,0 +1,163 @@
+mod tables;
+
+/// Number of entries in a colour lookup table.
+pub const COLORMAP_LEN: usize = 256;
+
+/// Table mapping intensity levels to RGBA colours.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colormap {
+	entries: Box<[[u8; 4]; COLORMAP_LEN]>,
+}
+
+/// Built-in colour maps. Viridis, magma and inferno are perceptually uniform
+/// and readable with the common forms of colour blindness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BuiltinColormap {
+	#[default]
+	Grey,
+	Viridis,
+	Magma,
+	Inferno,
+}
+
+impl BuiltinColormap {
+	pub const ALL: [BuiltinColormap; 4] = [Self::Grey, Self::Viridis, Self::Magma, Self::Inferno];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Grey => "grey",
+			Self::Viridis => "viridis",
+			Self::Magma => "magma",
+			Self::Inferno => "inferno",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|map| map.name().eq_ignore_ascii_case(name))
+	}
+}
+
+impl From<BuiltinColormap> for Colormap {
+	fn from(map: BuiltinColormap) -> Self {
+		let table = match map {
+			BuiltinColormap::Grey => return Colormap::from_fn(|i| [i as u8, i as u8, i as u8, 255]),
+			BuiltinColormap::Viridis => &tables::VIRIDIS,
+			BuiltinColormap::Magma => &tables::MAGMA,
+			BuiltinColormap::Inferno => &tables::INFERNO,
+		};
+		Colormap::from_fn(|i| {
+			let channel = |c: usize| (table[i][c] * 255.0).round() as u8;
+			[channel(0), channel(1), channel(2), 255]
+		})
+	}
+}
+
+impl Default for Colormap {
+	fn default() -> Self {
+		BuiltinColormap::default().into()
+	}
+}
+
+impl Colormap {
+	fn from_fn(f: impl FnMut(usize) -> [u8; 4]) -> Self {
+		Self { entries: Box::new(std::array::from_fn(f)) }
+	}
+
+	/// Table from 256 RGBA entries packed as 1024 bytes.
+	pub fn from_rgba(bytes: &[u8]) -> Option<Self> {
+		if bytes.len() != COLORMAP_LEN * 4 {
+			return None;
+		}
+		let mut chunks = bytes.chunks_exact(4);
+		Some(Self::from_fn(|_| chunks.next().unwrap().try_into().unwrap()))
+	}
+
+	/// Parses a text table of 256 lines with three or four channels each,
+	/// separated by spaces or commas, such as the output of
+	/// `numpy.savetxt(f, cmap(numpy.linspace(0, 1, 256)))`. Channels are read as
+	/// fractions when every value is at most 1 and as bytes otherwise, and a
+	/// missing alpha is opaque. Empty lines and lines starting with `#` are
+	/// skipped.
+	pub fn parse(text: &str) -> Option<Self> {
+		let rows = text
+			.lines()
+			.map(str::trim)
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(|line| {
+				line.split(|c: char| c.is_whitespace() || c == ',')
+					.filter(|s| !s.is_empty())
+					.map(|s| s.parse::<f64>().ok().filter(|v| v.is_finite() && 0.0 <= *v))
+					.collect::<Option<Vec<_>>>()
+					.filter(|row| matches!(row.len(), 3 | 4))
+			})
+			.collect::<Option<Vec<_>>>()?;
+		if rows.len() != COLORMAP_LEN {
+			return None;
+		}
+		let scale = if rows.iter().flatten().all(|v| *v <= 1.0) {
+			255.0
+		} else {
+			1.0
+		};
+		if rows.iter().flatten().any(|v| 255.0 < v * scale) {
+			return None;
+		}
+		let mut rows = rows.into_iter();
+		Some(Self::from_fn(|_| {
+			let row = rows.next().unwrap();
+			let channel = |c: usize| row.get(c).map_or(255, |v| (v * scale).round() as u8);
+			[channel(0), channel(1), channel(2), channel(3)]
+		}))
+	}
+
+	/// Colour of `level`, clamped to the range from 0 to 1.
+	pub fn colour(&self, level: f64) -> [u8; 4] {
+		let index = (level.clamp(0.0, 1.0) * (COLORMAP_LEN - 1) as f64).round() as usize;
+		self.entries[index]
+	}
+
+	pub fn entries(&self) -> &[[u8; 4]; COLORMAP_LEN] {
+		&self.entries
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{BuiltinColormap, Colormap, COLORMAP_LEN};
+
+	#[test]
+	fn builtin_endpoints() {
+		let viridis = Colormap::from(BuiltinColormap::Viridis);
+		assert_eq!(viridis.colour(0.0), [68, 1, 84, 255]);
+		assert_eq!(viridis.colour(1.0), [253, 231, 37, 255]);
+		assert_eq!(Colormap::from(BuiltinColormap::Magma).colour(1.0), [252, 253, 191, 255]);
+		assert_eq!(
+			Colormap::from(BuiltinColormap::Inferno).colour(1.0),
+			[252, 255, 164, 255]
+		);
+		assert_eq!(Colormap::default().colour(0.5), [128, 128, 128, 255]);
+		assert_eq!(BuiltinColormap::from_name("Magma"), Some(BuiltinColormap::Magma));
+	}
+
+	#[test]
+	fn parses_fractions_and_bytes() {
+		let fractions: String = (0..COLORMAP_LEN)
+			.map(|i| format!("{} 0, 0.5\n", i as f64 / 255.0))
+			.collect();
+		let map = Colormap::parse(&format!("# r g b\n{fractions}")).unwrap();
+		assert_eq!(map.colour(1.0), [255, 0, 128, 255]);
+
+		let bytes: String = (0..COLORMAP_LEN).map(|i| format!("{i} 0 0 128\n")).collect();
+		assert_eq!(Colormap::parse(&bytes).unwrap().colour(0.0), [0, 0, 0, 128]);
+
+		assert_eq!(Colormap::parse("0 0 0\n"), None);
+		assert_eq!(Colormap::parse(&bytes.replace("128", "300")), None);
+	}
+
+	#[test]
+	fn from_rgba() {
+		let bytes: Vec<u8> = (0..COLORMAP_LEN).flat_map(|i| [i as u8, 0, 0, 255]).collect();
+		assert_eq!(Colormap::from_rgba(&bytes).unwrap().colour(1.0), [255, 0, 0, 255]);
+		assert_eq!(Colormap::from_rgba(&bytes[4..]), None);
+	}
+}
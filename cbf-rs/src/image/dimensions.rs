@@ -0,0 +1,36 @@
+use thiserror::Error as ThisError;
+
+#[derive(Debug, ThisError, PartialEq, Eq)]
+#[error("image dimensions overflow")]
+pub struct DimensionOverflow;
+
+pub fn pixel_count(width: usize, height: usize) -> Result<usize, DimensionOverflow> {
+	width.checked_mul(height).ok_or(DimensionOverflow)
+}
+
+pub fn element_count(width: usize, height: usize, depth: usize) -> Result<usize, DimensionOverflow> {
+	pixel_count(width, height)?.checked_mul(depth).ok_or(DimensionOverflow)
+}
+
+/// Index of the pixel at column `x` of row `y` in a row-major buffer.
+pub fn pixel_index(x: usize, y: usize, width: usize) -> Result<usize, DimensionOverflow> {
+	y.checked_mul(width)
+		.and_then(|i| i.checked_add(x))
+		.ok_or(DimensionOverflow)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{element_count, pixel_count, pixel_index, DimensionOverflow};
+
+	#[test]
+	fn checked_arithmetic() {
+		assert_eq!(pixel_count(2880, 2880), Ok(8294400));
+		assert_eq!(element_count(2880, 2880, 2), Ok(16588800));
+		assert_eq!(pixel_index(3, 2, 10), Ok(23));
+
+		assert_eq!(pixel_count(usize::MAX, 2), Err(DimensionOverflow));
+		assert_eq!(element_count(usize::MAX / 2, 2, 2), Err(DimensionOverflow));
+		assert_eq!(pixel_index(1, usize::MAX, 1), Err(DimensionOverflow));
+	}
+}
@@ -0,0 +1,141 @@
+use super::Image;
+
+/// Number of histogram bins used for equalization.
+const BINS: usize = 256;
+
+/// Fraction of the finite pixels at or below each pixel, with the lowest
+/// value at 0 and the highest at 1. Non-finite pixels are placed at 0.
+fn ranks(pixels: &[f64]) -> Vec<f64> {
+	let mut sorted: Vec<f64> = pixels.iter().copied().filter(|p| p.is_finite()).collect();
+	sorted.sort_unstable_by(f64::total_cmp);
+	let lowest = sorted.first().map_or(0, |low| sorted.partition_point(|v| v <= low));
+	let range = (sorted.len() - lowest) as f64;
+	pixels
+		.iter()
+		.map(|&p| {
+			if !p.is_finite() || range == 0.0 {
+				return 0.0;
+			}
+			(sorted.partition_point(|v| *v <= p) - lowest) as f64 / range
+		})
+		.collect()
+}
+
+/// Normalized cumulative histogram, mapping the lowest occupied bin to 0 and
+/// the highest to 1. Counts above `limit` are clipped and spread evenly over
+/// all bins.
+fn levels(histogram: &[f64; BINS], limit: f64) -> [f64; BINS] {
+	let excess: f64 = histogram.iter().map(|h| (h - limit).max(0.0)).sum();
+	let mut cumulative = 0.0;
+	let cdf = histogram.map(|h| {
+		cumulative += h.min(limit) + excess / BINS as f64;
+		cumulative
+	});
+	let low = cdf[histogram.iter().position(|h| 0.0 < *h).unwrap_or(0)];
+	let range = cumulative - low;
+	cdf.map(|c| if 0.0 < range { ((c - low) / range).max(0.0) } else { 0.0 })
+}
+
+impl Image<f64> {
+	/// Display levels from 0 to 1 spreading the pixel values evenly over the
+	/// range, so that weak features in a large background stay visible.
+	pub fn equalized(&self) -> Image<f64> {
+		let pixels = ranks(&self.pixels).into();
+		Image { width: self.width, height: self.height, pixels }
+	}
+
+	/// Contrast limited adaptive equalization over a grid of `tiles` by
+	/// `tiles` regions, interpolating bilinearly between the regions. Regions
+	/// share histogram bins spanning equal shares of the whole image. No bin of
+	/// a region holds more than `clip_limit` times the mean count, which keeps
+	/// noise in flat regions from being amplified. Returns `None` without tiles
+	/// or when `clip_limit` is less than 1.
+	pub fn locally_equalized(&self, tiles: usize, clip_limit: f64) -> Option<Image<f64>> {
+		if tiles == 0 || clip_limit.is_nan() || clip_limit < 1.0 {
+			return None;
+		}
+		let tile_width = self.width.div_ceil(tiles).max(1);
+		let tile_height = self.height.div_ceil(tiles).max(1);
+		let bins: Vec<usize> = ranks(&self.pixels)
+			.iter()
+			.map(|r| (r * (BINS - 1) as f64).round() as usize)
+			.collect();
+
+		let mut histograms = vec![[0.0; BINS]; tiles * tiles];
+		for (y, row) in bins.chunks(self.width.max(1)).enumerate() {
+			for (x, &bin) in row.iter().enumerate() {
+				histograms[(y / tile_height) * tiles + x / tile_width][bin] += 1.0;
+			}
+		}
+		let levels: Vec<_> = histograms
+			.iter()
+			.map(|h| levels(h, clip_limit * h.iter().sum::<f64>() / BINS as f64))
+			.collect();
+
+		// Neighbouring tiles and the weight of the second one along an axis.
+		let neighbours = |position: usize, size: usize| {
+			let t = ((position as f64 + 0.5) / size as f64 - 0.5).clamp(0.0, (tiles - 1) as f64);
+			let first = t.floor() as usize;
+			(first, (first + 1).min(tiles - 1), t - first as f64)
+		};
+		let mut pixels = Vec::with_capacity(self.pixels.len());
+		for (y, row) in bins.chunks(self.width.max(1)).enumerate() {
+			let (top, bottom, wy) = neighbours(y, tile_height);
+			for (x, &bin) in row.iter().enumerate() {
+				let (left, right, wx) = neighbours(x, tile_width);
+				let level = |tx: usize, ty: usize| levels[ty * tiles + tx][bin];
+				let upper = level(left, top) * (1.0 - wx) + level(right, top) * wx;
+				let lower = level(left, bottom) * (1.0 - wx) + level(right, bottom) * wx;
+				pixels.push(upper * (1.0 - wy) + lower * wy);
+			}
+		}
+		Image::new(self.width, self.height, pixels.into())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::image::Image;
+
+	#[test]
+	fn spreads_levels() {
+		let image = Image::new(5, 1, vec![0.0, 0.0, 1.0, 100.0, f64::NAN].into()).unwrap();
+		let levels = image.equalized();
+		assert_eq!(levels.pixels(), [0.0, 0.0, 0.5, 1.0, 0.0]);
+	}
+
+	#[test]
+	fn flat_image() {
+		let image = Image::new(2, 2, vec![3.0; 4].into()).unwrap();
+		assert_eq!(image.equalized().pixels(), [0.0; 4]);
+		let empty = Image::new(2, 1, vec![f64::NAN; 2].into()).unwrap();
+		assert_eq!(empty.equalized().pixels(), [0.0; 2]);
+		assert_eq!(image.locally_equalized(2, 2.0).unwrap().pixels(), [0.0; 4]);
+	}
+
+	#[test]
+	fn local_contrast() {
+		// A faint step on the left next to a bright region on the right.
+		let pixels: Vec<f64> = (0..16)
+			.flat_map(|_| {
+				(0..16).map(|x| {
+					if x < 4 {
+						0.0
+					} else if x < 8 {
+						1.0
+					} else {
+						1000.0
+					}
+				})
+			})
+			.collect();
+		let image = Image::new(16, 16, pixels.into()).unwrap();
+		let global = image.equalized();
+		let local = image.locally_equalized(2, 100.0).unwrap();
+		let step = |image: &Image<f64>| image.pixels()[5] - image.pixels()[2];
+		assert!(0.0 < step(&global));
+		assert!(step(&global) <= step(&local));
+		assert!(image.locally_equalized(0, 4.0).is_none());
+		assert!(image.locally_equalized(2, 0.5).is_none());
+	}
+}
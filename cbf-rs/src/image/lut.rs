@@ -0,0 +1,66 @@
+use num_traits::AsPrimitive;
+
+use super::Image;
+
+impl<P: Copy + AsPrimitive<usize>> Image<P> {
+	/// Replaces every pixel value `v` with `lut[v]`. Values outside the table,
+	/// including negative ones, are left unchanged.
+	pub fn apply_lut(&mut self, lut: &[P]) {
+		for pixel in self.pixels.iter_mut() {
+			if let Some(value) = lut.get((*pixel).as_()) {
+				*pixel = *value;
+			}
+		}
+	}
+}
+
+impl<P: Copy> Image<P> {
+	pub fn apply_fn(&mut self, mut f: impl FnMut(P) -> P) {
+		for pixel in self.pixels.iter_mut() {
+			*pixel = f(*pixel);
+		}
+	}
+}
+
+impl Image<u16> {
+	/// Applies a table covering every `u16` value, without bounds checks.
+	pub fn apply_full_lut(&mut self, lut: &[u16; 1 << 16]) {
+		let mut chunks = self.pixels.chunks_exact_mut(8);
+		for chunk in chunks.by_ref() {
+			for pixel in chunk {
+				*pixel = lut[*pixel as usize];
+			}
+		}
+		for pixel in chunks.into_remainder() {
+			*pixel = lut[*pixel as usize];
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::image::Image;
+
+	#[test]
+	fn partial_lut() {
+		let mut image = Image::new(2, 2, vec![0i32, 1, 5, -1].into()).unwrap();
+		image.apply_lut(&[10, 11, 12]);
+		assert_eq!(image.pixels(), &[10, 11, 5, -1]);
+	}
+
+	#[test]
+	fn full_u16_lut() {
+		let lut: Box<[u16; 1 << 16]> = (0..=u16::MAX)
+			.map(|v| v.saturating_mul(2))
+			.collect::<Vec<_>>()
+			.try_into()
+			.unwrap();
+		let pixels: Vec<u16> = (0..11).chain([u16::MAX]).collect();
+		let mut image = Image::new(3, 4, pixels.clone().into()).unwrap();
+		let mut expected = Image::new(3, 4, pixels.into()).unwrap();
+		image.apply_full_lut(&lut);
+		expected.apply_fn(|v| v.saturating_mul(2));
+		assert_eq!(image.pixels(), expected.pixels());
+		assert_eq!(image.pixels()[11], u16::MAX);
+	}
+}
@@ -0,0 +1,147 @@
+use std::{fmt, str::FromStr};
+
+use super::{dimensions::pixel_count, Image};
+
+/// Pixels set to `true` are excluded from analysis.
+pub type Mask = Image<bool>;
+
+impl Mask {
+	pub fn empty(width: usize, height: usize) -> Option<Self> {
+		Image::new(width, height, vec![false; pixel_count(width, height).ok()?].into())
+	}
+
+	pub fn is_masked(&self, x: usize, y: usize) -> bool {
+		x < self.width && y < self.height && self.pixels[y * self.width + x]
+	}
+
+	/// Share of the pixels that are masked, 0 for an empty mask.
+	pub fn masked_fraction(&self) -> f64 {
+		if self.pixels.is_empty() {
+			return 0.0;
+		}
+		self.pixels.iter().filter(|&&m| m).count() as f64 / self.pixels.len() as f64
+	}
+}
+
+/// A change to a mask, in pixel coordinates of the masked image.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaskEdit {
+	Rectangle {
+		x: usize,
+		y: usize,
+		width: usize,
+		height: usize,
+		masked: bool,
+	},
+	Circle {
+		x: f64,
+		y: f64,
+		radius: f64,
+		masked: bool,
+	},
+	Clear,
+}
+
+impl MaskEdit {
+	pub fn apply(&self, mask: &mut Mask) {
+		let width = mask.width;
+		match *self {
+			MaskEdit::Rectangle { x, y, width: w, height: h, masked } => {
+				let columns = x.min(width)..x.saturating_add(w).min(width);
+				for row in mask.rows_mut().skip(y).take(h) {
+					row[columns.clone()].fill(masked);
+				}
+			}
+			MaskEdit::Circle { x: cx, y: cy, radius, masked } => {
+				for (y, row) in mask.rows_mut().enumerate() {
+					let dy = y as f64 - cy;
+					for (x, pixel) in row.iter_mut().enumerate() {
+						let dx = x as f64 - cx;
+						if dx * dx + dy * dy <= radius * radius {
+							*pixel = masked;
+						}
+					}
+				}
+			}
+			MaskEdit::Clear => mask.pixels.fill(false),
+		}
+	}
+}
+
+impl fmt::Display for MaskEdit {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			MaskEdit::Rectangle { x, y, width, height, masked } => {
+				write!(f, "rectangle {x} {y} {width} {height} {masked}")
+			}
+			MaskEdit::Circle { x, y, radius, masked } => write!(f, "circle {x} {y} {radius} {masked}"),
+			MaskEdit::Clear => write!(f, "clear"),
+		}
+	}
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseMaskEditError;
+
+impl FromStr for MaskEdit {
+	type Err = ParseMaskEditError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut parts = s.split_whitespace();
+		macro_rules! next {
+			() => {
+				parts
+					.next()
+					.and_then(|p| p.parse().ok())
+					.ok_or(ParseMaskEditError)?
+			};
+		}
+		let edit = match parts.next() {
+			Some("rectangle") => {
+				MaskEdit::Rectangle { x: next!(), y: next!(), width: next!(), height: next!(), masked: next!() }
+			}
+			Some("circle") => MaskEdit::Circle { x: next!(), y: next!(), radius: next!(), masked: next!() },
+			Some("clear") => MaskEdit::Clear,
+			_ => return Err(ParseMaskEditError),
+		};
+		match parts.next() {
+			Some(_) => Err(ParseMaskEditError),
+			None => Ok(edit),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Mask, MaskEdit};
+
+	#[test]
+	fn apply_edits() {
+		let mut mask = Mask::empty(4, 4).unwrap();
+		MaskEdit::Rectangle { x: 1, y: 1, width: 10, height: 2, masked: true }.apply(&mut mask);
+		assert!(!mask.is_masked(0, 1));
+		assert!(mask.is_masked(1, 1));
+		assert!(mask.is_masked(3, 2));
+		assert!(!mask.is_masked(3, 3));
+
+		MaskEdit::Circle { x: 3.0, y: 2.0, radius: 0.5, masked: false }.apply(&mut mask);
+		assert!(!mask.is_masked(3, 2));
+		assert!(mask.is_masked(2, 2));
+
+		MaskEdit::Clear.apply(&mut mask);
+		assert!(mask.pixels().iter().all(|p| !p));
+	}
+
+	#[test]
+	fn text_round_trip() {
+		for edit in [
+			MaskEdit::Rectangle { x: 1, y: 2, width: 3, height: 4, masked: true },
+			MaskEdit::Circle { x: 1.5, y: 2.0, radius: 10.25, masked: false },
+			MaskEdit::Clear,
+		] {
+			assert_eq!(edit.to_string().parse(), Ok(edit));
+		}
+		assert!("circle 1 2".parse::<MaskEdit>().is_err());
+		assert!("clear 1".parse::<MaskEdit>().is_err());
+	}
+}
@@ -1,5 +1,15 @@
-use self::pixel::Pixels;
+use self::{
+	dimensions::{pixel_count, pixel_index},
+	pixel::Pixels,
+};
 
+pub mod checksum;
+pub mod colormap;
+pub mod dimensions;
+mod equalize;
+mod lut;
+pub mod mask;
+pub mod mosaic;
 pub mod pixel;
 
 pub struct Image<P> {
@@ -9,6 +19,13 @@ pub struct Image<P> {
 }
 
 impl<P> Image<P> {
+	pub fn new(width: usize, height: usize, pixels: Box<[P]>) -> Option<Self> {
+		if pixel_count(width, height).ok()? != pixels.len() {
+			return None;
+		}
+		Some(Self { width, height, pixels })
+	}
+
 	pub fn get_pixel(&self, coordinate: impl ImageCoordinate) -> Option<&P> {
 		Some(&self.pixels[coordinate.index(self.width, self.height)?])
 	}
@@ -16,6 +33,36 @@ impl<P> Image<P> {
 	pub fn pixels(&self) -> &[P] {
 		&self.pixels
 	}
+
+	pub fn pixels_mut(&mut self) -> &mut [P] {
+		&mut self.pixels
+	}
+
+	/// Whether the image is at most one pixel wide or high, as read from a
+	/// strip detector.
+	pub fn is_strip(&self) -> bool {
+		self.width <= 1 || self.height <= 1
+	}
+
+	/// Rows of pixels from the top, each `width` pixels long. An image without
+	/// columns has no rows.
+	pub fn rows(&self) -> std::slice::Chunks<'_, P> {
+		self.pixels.chunks(self.width.max(1))
+	}
+
+	pub fn rows_mut(&mut self) -> std::slice::ChunksMut<'_, P> {
+		self.pixels.chunks_mut(self.width.max(1))
+	}
+
+	/// Consecutive runs of `len` pixels in row major order, the last one
+	/// possibly shorter. Panics if `len` is zero.
+	pub fn chunks(&self, len: usize) -> std::slice::Chunks<'_, P> {
+		self.pixels.chunks(len)
+	}
+
+	pub fn map<Q>(&self, f: impl FnMut(&P) -> Q) -> Image<Q> {
+		Image { width: self.width, height: self.height, pixels: self.pixels.iter().map(f).collect() }
+	}
 }
 
 pub trait ImageCoordinate {
@@ -24,10 +71,10 @@ pub trait ImageCoordinate {
 
 impl ImageCoordinate for usize {
 	fn index(&self, width: usize, height: usize) -> Option<usize> {
-		if width * height - 1 < *self {
-			None
-		} else {
+		if *self < pixel_count(width, height).ok()? {
 			Some(*self)
+		} else {
+			None
 		}
 	}
 }
@@ -39,11 +86,11 @@ impl ImageCoordinate for (isize, isize) {
 		let x = x + (width / 2) as isize;
 		let y = y + (height / 2) as isize;
 
-		if x < 0 || y < 0 || width < x as usize || height < y as usize {
+		if x < 0 || y < 0 || width <= x as usize || height <= y as usize {
 			return None;
 		}
 
-		let index = y as usize * width + x as usize;
+		let index = pixel_index(x as usize, y as usize, width).ok()?;
 		index.index(width, height)
 	}
 }
@@ -92,6 +139,74 @@ impl ImageEnum {
 		}
 	}
 
+	/// See [`Image::is_strip`].
+	pub fn is_strip(&self) -> bool {
+		self.width() <= 1 || self.height() <= 1
+	}
+
+	/// Size of the pixel buffer in bytes.
+	pub fn byte_size(&self) -> usize {
+		match self {
+			ImageEnum::U8(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::I8(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::U16(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::I16(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::U32(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::I32(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::F32(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::U64(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::I64(image) => std::mem::size_of_val(image.pixels()),
+			ImageEnum::F64(image) => std::mem::size_of_val(image.pixels()),
+		}
+	}
+
+	pub fn to_f64(&self) -> Image<f64> {
+		match self {
+			ImageEnum::U8(image) => image.map(|p| *p as f64),
+			ImageEnum::I8(image) => image.map(|p| *p as f64),
+			ImageEnum::U16(image) => image.map(|p| *p as f64),
+			ImageEnum::I16(image) => image.map(|p| *p as f64),
+			ImageEnum::U32(image) => image.map(|p| *p as f64),
+			ImageEnum::I32(image) => image.map(|p| *p as f64),
+			ImageEnum::F32(image) => image.map(|p| *p as f64),
+			ImageEnum::U64(image) => image.map(|p| *p as f64),
+			ImageEnum::I64(image) => image.map(|p| *p as f64),
+			ImageEnum::F64(image) => image.map(|p| *p),
+		}
+	}
+
+	pub fn to_f32(&self) -> Image<f32> {
+		match self {
+			ImageEnum::U8(image) => image.map(|p| *p as f32),
+			ImageEnum::I8(image) => image.map(|p| *p as f32),
+			ImageEnum::U16(image) => image.map(|p| *p as f32),
+			ImageEnum::I16(image) => image.map(|p| *p as f32),
+			ImageEnum::U32(image) => image.map(|p| *p as f32),
+			ImageEnum::I32(image) => image.map(|p| *p as f32),
+			ImageEnum::F32(image) => image.map(|p| *p),
+			ImageEnum::U64(image) => image.map(|p| *p as f32),
+			ImageEnum::I64(image) => image.map(|p| *p as f32),
+			ImageEnum::F64(image) => image.map(|p| *p as f32),
+		}
+	}
+
+	pub fn is_float(&self) -> bool {
+		matches!(self, ImageEnum::F32(_) | ImageEnum::F64(_))
+	}
+
+	/// Whether the pixel type can hold negative values, which includes floats.
+	pub fn is_signed(&self) -> bool {
+		matches!(
+			self,
+			ImageEnum::I8(_)
+				| ImageEnum::I16(_)
+				| ImageEnum::I32(_)
+				| ImageEnum::I64(_)
+				| ImageEnum::F32(_)
+				| ImageEnum::F64(_)
+		)
+	}
+
 	pub fn from_pixels(width: usize, height: usize, pixels: Pixels) -> Self {
 		macro_rules! from_pixels {
 			($($name:ident),*) => {
@@ -107,3 +222,66 @@ impl ImageEnum {
 		from_pixels!(U8, I8, U16, I16, U32, I32, F32, U64, I64, F64)
 	}
 }
+
+macro_rules! downcast {
+	($($name:ident: $type:ty, $as:ident, $into:ident;)*) => {
+		impl ImageEnum {
+			$(
+				pub fn $as(&self) -> Option<&Image<$type>> {
+					match self {
+						ImageEnum::$name(image) => Some(image),
+						_ => None,
+					}
+				}
+
+				pub fn $into(self) -> Option<Image<$type>> {
+					match self {
+						ImageEnum::$name(image) => Some(image),
+						_ => None,
+					}
+				}
+			)*
+		}
+	};
+}
+
+downcast!(
+	U8: u8, as_u8, into_u8;
+	I8: i8, as_i8, into_i8;
+	U16: u16, as_u16, into_u16;
+	I16: i16, as_i16, into_i16;
+	U32: u32, as_u32, into_u32;
+	I32: i32, as_i32, into_i32;
+	F32: f32, as_f32, into_f32;
+	U64: u64, as_u64, into_u64;
+	I64: i64, as_i64, into_i64;
+	F64: f64, as_f64, into_f64;
+);
+
+#[cfg(test)]
+mod tests {
+	use super::{pixel::Pixels, Image, ImageEnum};
+
+	#[test]
+	fn downcasts() {
+		let image = ImageEnum::from_pixels(1, 1, Pixels::from(vec![-3i32]));
+		assert!(image.is_signed() && !image.is_float());
+		assert_eq!(image.as_i32().map(|i| i.pixels()), Some(&[-3][..]));
+		assert!(image.as_u32().is_none());
+		assert!(image.into_f64().is_none());
+
+		let image = ImageEnum::from_pixels(1, 1, Pixels::from(vec![1.5f32]));
+		assert!(image.is_signed() && image.is_float());
+		assert_eq!(image.into_f32().map(|i| i.pixels()[0]), Some(1.5));
+	}
+
+	#[test]
+	fn rows_and_chunks() {
+		let image = Image::new(3, 2, vec![1u8, 2, 3, 4, 5, 6].into()).unwrap();
+		assert_eq!(image.rows().collect::<Vec<_>>(), [&[1, 2, 3][..], &[4, 5, 6]]);
+		assert_eq!(image.chunks(4).collect::<Vec<_>>(), [&[1, 2, 3, 4][..], &[5, 6]]);
+		let empty = Image::<u8>::new(0, 5, Box::new([])).unwrap();
+		assert_eq!(empty.rows().count(), 0);
+		assert!(empty.is_strip() && !image.is_strip());
+	}
+}
@@ -0,0 +1,90 @@
+use super::{dimensions::pixel_count, Image};
+
+/// Where the images of a [`mosaic`] are placed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MosaicLayout {
+	/// Row by row, `columns` to a row, in cells the size of the largest image
+	/// separated by gaps of `gap_x` and `gap_y` pixels
+	Grid { columns: usize, gap_x: usize, gap_y: usize },
+	/// Top left corner of each image, later images covering earlier ones
+	Offsets(Vec<(usize, usize)>),
+}
+
+impl MosaicLayout {
+	fn offsets<P>(&self, images: &[Image<P>]) -> Option<Vec<(usize, usize)>> {
+		match self {
+			MosaicLayout::Grid { columns: 0, .. } => None,
+			&MosaicLayout::Grid { columns, gap_x, gap_y } => {
+				let cell_width = images.iter().map(|i| i.width).max().unwrap_or(0).checked_add(gap_x)?;
+				let cell_height = images.iter().map(|i| i.height).max().unwrap_or(0).checked_add(gap_y)?;
+				(0..images.len())
+					.map(|i| {
+						Some((
+							(i % columns).checked_mul(cell_width)?,
+							(i / columns).checked_mul(cell_height)?,
+						))
+					})
+					.collect()
+			}
+			MosaicLayout::Offsets(offsets) => (offsets.len() == images.len()).then(|| offsets.clone()),
+		}
+	}
+}
+
+/// Stitches `images` into one, such as the modules of a detector delivered as
+/// separate binary sections. Pixels covered by no image are set to `fill`.
+/// Returns `None` if the layout does not place every image, or if the result
+/// would be too large.
+pub fn mosaic<P: Copy>(images: &[Image<P>], layout: &MosaicLayout, fill: P) -> Option<Image<P>> {
+	let offsets = layout.offsets(images)?;
+	let mut width = 0;
+	let mut height = 0;
+	for (image, &(x, y)) in images.iter().zip(&offsets) {
+		width = width.max(x.checked_add(image.width)?);
+		height = height.max(y.checked_add(image.height)?);
+	}
+	let mut pixels = vec![fill; pixel_count(width, height).ok()?];
+	for (image, &(x, y)) in images.iter().zip(&offsets) {
+		for (row, source) in image.rows().enumerate() {
+			let start = (y + row) * width + x;
+			pixels[start..start + image.width].copy_from_slice(source);
+		}
+	}
+	Image::new(width, height, pixels.into())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{mosaic, MosaicLayout};
+	use crate::image::Image;
+
+	fn module(value: i32, width: usize, height: usize) -> Image<i32> {
+		Image::new(width, height, vec![value; width * height].into()).unwrap()
+	}
+
+	#[test]
+	fn grid_with_gaps() {
+		let modules = [module(1, 2, 1), module(2, 2, 1), module(3, 2, 1)];
+		let layout = MosaicLayout::Grid { columns: 2, gap_x: 1, gap_y: 1 };
+		let image = mosaic(&modules, &layout, -1).unwrap();
+		assert_eq!((image.width, image.height), (5, 3));
+		#[rustfmt::skip]
+		assert_eq!(image.pixels(), [
+			1, 1, -1, 2, 2,
+			-1, -1, -1, -1, -1,
+			3, 3, -1, -1, -1,
+		]);
+		assert!(mosaic(&modules, &MosaicLayout::Grid { columns: 0, gap_x: 0, gap_y: 0 }, 0).is_none());
+	}
+
+	#[test]
+	fn explicit_offsets() {
+		let modules = [module(1, 2, 2), module(2, 1, 1)];
+		let image = mosaic(&modules, &MosaicLayout::Offsets(vec![(0, 0), (1, 1)]), 0).unwrap();
+		assert_eq!(image.pixels(), [1, 1, 1, 2]);
+		assert!(mosaic(&modules, &MosaicLayout::Offsets(vec![(0, 0)]), 0).is_none());
+		assert!(mosaic(&modules, &MosaicLayout::Offsets(vec![(0, 0), (usize::MAX, 0)]), 0).is_none());
+		let empty = mosaic::<i32>(&[], &MosaicLayout::Offsets(Vec::new()), 0).unwrap();
+		assert_eq!((empty.width, empty.height), (0, 0));
+	}
+}
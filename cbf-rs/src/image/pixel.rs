@@ -11,6 +11,23 @@ pub enum Pixels {
 	F64(Box<[f64]>),
 }
 
+impl Pixels {
+	pub(crate) fn len(&self) -> usize {
+		match self {
+			Pixels::U8(pixels) => pixels.len(),
+			Pixels::I8(pixels) => pixels.len(),
+			Pixels::U16(pixels) => pixels.len(),
+			Pixels::I16(pixels) => pixels.len(),
+			Pixels::U32(pixels) => pixels.len(),
+			Pixels::I32(pixels) => pixels.len(),
+			Pixels::F32(pixels) => pixels.len(),
+			Pixels::U64(pixels) => pixels.len(),
+			Pixels::I64(pixels) => pixels.len(),
+			Pixels::F64(pixels) => pixels.len(),
+		}
+	}
+}
+
 macro_rules! pixels_from_vec {
 	($($name:ident: $type:ty,)*) => {
 		$(impl From<Vec<$type>> for Pixels {
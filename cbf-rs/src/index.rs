@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use crate::{decoder::Decoder, image::ImageEnum, metadata::Metadata, Error};
+
+/// Location and metadata of one binary section in a file.
+#[derive(Debug, Clone)]
+pub struct IndexedFrame {
+	/// Bytes to decode the frame from, running from the end of the previous
+	/// frame to the end of this one
+	pub range: Range<usize>,
+	pub metadata: Metadata,
+}
+
+/// Where each frame of a file is, found by [`Decoder::index_frames`] without
+/// decoding any pixels.
+///
+/// The index never changes once built, so threads can share it and decode
+/// different frames of the same data at once.
+#[derive(Debug, Clone, Default)]
+pub struct FrameIndex {
+	frames: Box<[IndexedFrame]>,
+}
+
+impl FrameIndex {
+	pub(crate) fn new(frames: Vec<IndexedFrame>) -> Self {
+		Self { frames: frames.into_boxed_slice() }
+	}
+
+	pub fn len(&self) -> usize {
+		self.frames.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.frames.is_empty()
+	}
+
+	pub fn frames(&self) -> &[IndexedFrame] {
+		&self.frames
+	}
+
+	pub fn get(&self, index: usize) -> Option<&IndexedFrame> {
+		self.frames.get(index)
+	}
+
+	/// Decodes frame `index` of `data`, the bytes the index was built from.
+	pub fn read_frame(&self, data: &[u8], index: usize, decoder: &Decoder) -> Result<ImageEnum, Error> {
+		let frame = self.frames.get(index).ok_or(Error::NoImage)?;
+		let section = data.get(frame.range.clone()).ok_or(Error::NoImage)?;
+		decoder.read_image(section)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+
+	use crate::{decoder::Decoder, Error};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn concurrent_frames() {
+		let data = [EXAMPLE_DATA, EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		let decoder = Decoder::default();
+		let index = decoder.index_frames(data.as_slice()).expect("to index");
+		assert_eq!(index.len(), 3);
+		assert_eq!(index.get(0).unwrap().range.start, 0);
+		assert_eq!(index.get(2).unwrap().range.start, index.get(1).unwrap().range.end);
+		assert_eq!(index.get(1).unwrap().metadata.width, Some(2880));
+
+		let expected = decoder.read_image(EXAMPLE_DATA).unwrap().to_f64();
+		let (index, data, decoder) = (&index, &data, &decoder);
+		thread::scope(|scope| {
+			let handles: Vec<_> = (0..index.len())
+				.map(|i| scope.spawn(move || index.read_frame(data, i, decoder)))
+				.collect();
+			for handle in handles {
+				assert_eq!(handle.join().unwrap().unwrap().to_f64().pixels(), expected.pixels());
+			}
+		});
+		assert!(matches!(index.read_frame(data, 3, decoder), Err(Error::NoImage)));
+	}
+}
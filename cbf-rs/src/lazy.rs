@@ -0,0 +1,155 @@
+use std::ops::Range;
+
+use crate::{
+	analysis::Roi,
+	compression::ByteOffsetReader,
+	declared_pixels, decode_pixels,
+	decoder::ReadOptions,
+	image::{pixel::Pixels, ImageEnum},
+	metadata::{Conversion, Metadata},
+	Error,
+};
+
+/// An image whose headers are parsed but whose pixels are only decompressed
+/// when asked for.
+///
+/// Byte offset data is decompressed no further than the last row asked for,
+/// and the position of every row passed on the way is remembered, so later
+/// requests start from the nearest row before them instead of from the first
+/// pixel. Other compressions are decompressed whole on the first request.
+pub struct LazyImage {
+	metadata: Metadata,
+	width: usize,
+	height: usize,
+	/// Compressed data of the section, after the binary header
+	data: Box<[u8]>,
+	options: ReadOptions,
+	/// Position in `data` of the start of every row passed so far, and the
+	/// value of the pixel before it
+	rows: Vec<(usize, i64)>,
+	/// All pixels, for compressions decoded whole
+	pixels: Option<Box<[i64]>>,
+}
+
+impl LazyImage {
+	pub(crate) fn new(metadata: Metadata, width: usize, height: usize, data: Box<[u8]>, options: ReadOptions) -> Self {
+		Self { metadata, width, height, data, options, rows: vec![(0, 0)], pixels: None }
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	/// Decodes the rows in `rows` into an image as wide as this one.
+	pub fn decode_rows(&mut self, rows: Range<usize>) -> Result<ImageEnum, Error> {
+		let pixels = self.rows(rows.clone())?;
+		let pixels = declared_pixels(pixels, &self.metadata, &self.options)?;
+		Ok(ImageEnum::from_pixels(self.width, rows.len(), pixels))
+	}
+
+	/// Decodes the pixels within `roi`.
+	pub fn decode_roi(&mut self, roi: Roi) -> Result<ImageEnum, Error> {
+		let (Some(right), Some(bottom)) = (roi.x.checked_add(roi.width), roi.y.checked_add(roi.height)) else {
+			return Err(Error::RegionOutOfBounds);
+		};
+		if self.width < right {
+			return Err(Error::RegionOutOfBounds);
+		}
+		let rows = self.rows(roi.y..bottom)?;
+		let pixels = (rows.chunks(self.width.max(1)))
+			.flat_map(|row| &row[roi.x..right])
+			.copied()
+			.collect();
+		let pixels = declared_pixels(pixels, &self.metadata, &self.options)?;
+		Ok(ImageEnum::from_pixels(roi.width, roi.height, pixels))
+	}
+
+	/// Decodes every pixel.
+	pub fn decode(&mut self) -> Result<ImageEnum, Error> {
+		self.decode_rows(0..self.height)
+	}
+
+	fn rows(&mut self, rows: Range<usize>) -> Result<Vec<i64>, Error> {
+		if rows.start > rows.end || self.height < rows.end {
+			return Err(Error::RegionOutOfBounds);
+		}
+		if self.metadata.content_type.conversion == Some(Conversion::ByteOffset) {
+			return self.byte_offset_rows(rows);
+		}
+		if self.pixels.is_none() {
+			// Narrowing to the declared type is left to the rows returned.
+			let options = ReadOptions { strict_element_type: false, ..self.options.clone() };
+			match decode_pixels(&self.data[..], &self.metadata, &options, None)? {
+				Pixels::I64(pixels) => self.pixels = Some(pixels),
+				_ => return Err(Error::UnsupportedPixelFormat),
+			}
+		}
+		let pixels = self.pixels.as_deref().unwrap_or_default();
+		Ok(pixels[rows.start * self.width..rows.end * self.width].to_vec())
+	}
+
+	fn byte_offset_rows(&mut self, rows: Range<usize>) -> Result<Vec<i64>, Error> {
+		if rows.is_empty() {
+			return Ok(Vec::new());
+		}
+		let first = rows.start.min(self.rows.len() - 1);
+		let (position, value) = self.rows[first];
+		let mut reader = ByteOffsetReader::new(&self.data[position..], self.options.overflow)
+			.byte_order(self.metadata.byte_order)
+			.resume_from(value);
+		let mut pixels = vec![0; rows.len() * self.width];
+		let mut skipped = vec![0; self.width];
+		for row in first..rows.end {
+			if row == self.rows.len() {
+				self.rows
+					.push((self.data.len() - reader.get_ref().len(), reader.value()));
+			}
+			match row.checked_sub(rows.start) {
+				Some(index) => reader.read(&mut pixels[index * self.width..][..self.width])?,
+				None => reader.read(&mut skipped)?,
+			}
+		}
+		Ok(pixels)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{analysis::Roi, decoder::Decoder, Error};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn decodes_parts() {
+		let decoder = Decoder::default();
+		let full = decoder.read_image(EXAMPLE_DATA).unwrap().into_i64().unwrap();
+		let mut lazy = decoder.read_lazy_image(EXAMPLE_DATA).expect("to read headers");
+		assert_eq!((lazy.width(), lazy.height()), (2880, 2880));
+
+		let rows = lazy.decode_rows(1439..1441).unwrap().into_i64().unwrap();
+		assert_eq!(rows.pixels(), &full.pixels()[1439 * 2880..1441 * 2880]);
+		let earlier = lazy.decode_rows(1..2).unwrap().into_i64().unwrap();
+		assert_eq!(earlier.pixels(), &full.pixels()[2880..2 * 2880]);
+
+		let roi = Roi { x: 1000, y: 1440, width: 3, height: 2 };
+		let region = lazy.decode_roi(roi).unwrap().into_i64().unwrap();
+		let expected: Vec<_> = (1440..1442)
+			.flat_map(|y| full.pixels()[y * 2880 + 1000..][..3].to_vec())
+			.collect();
+		assert_eq!(region.pixels(), expected);
+
+		assert!(matches!(lazy.decode_rows(2879..2881), Err(Error::RegionOutOfBounds)));
+		let outside = Roi { x: 2879, y: 0, width: 2, height: 1 };
+		assert!(matches!(lazy.decode_roi(outside), Err(Error::RegionOutOfBounds)));
+		let overflowing = Roi { x: 1, y: 1, width: usize::MAX, height: usize::MAX };
+		assert!(matches!(lazy.decode_roi(overflowing), Err(Error::RegionOutOfBounds)));
+	}
+}
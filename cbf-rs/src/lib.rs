@@ -1,97 +1,352 @@
+//! Reading of Crystallographic Binary Files and radial analysis of the
+//! images in them.
+//!
+//! [`prelude`] collects the stable surface of the crate. Modules documented
+//! as low level expose building blocks whose signatures may change between
+//! minor versions.
+
 pub mod analysis;
+#[cfg(feature = "async")]
+pub mod asynchronous;
+/// Low level: the compression schemes of binary sections.
 pub mod compression;
+#[cfg(feature = "zip")]
+pub mod container;
+/// Low level: reader adapter counting consumed bytes.
+pub mod counting;
+pub mod dataset;
+/// Low level: tools for investigating decoding problems.
+pub mod debug;
+pub mod decoder;
+pub mod encoder;
+/// Low level: the transfer encodings of binary sections.
+pub mod encoding;
+/// Low level: rendering of figures and images for reports.
+pub mod export;
+pub mod geometry;
 pub mod image;
+pub mod index;
+pub mod lazy;
+#[cfg(feature = "mmap")]
+pub mod mapped;
+/// Low level: MD5 digests of binary sections.
+#[cfg(feature = "md5")]
+pub mod md5;
+pub mod memory;
 pub mod metadata;
+/// Low level: decoding of many files on worker threads.
+pub mod pool;
+pub mod prelude;
+pub mod source;
+/// Low level: reader adapter copying consumed bytes to a writer.
+pub mod tee;
+pub mod throughput;
+pub mod validate;
+pub mod warning;
 
-use std::io::{BufRead, Error as IOError, Read};
+use std::{
+	io::{BufRead, BufReader, Error as IOError, Read, Write},
+	path::Path,
+	time::Instant,
+};
 
-use compression::read_byte_offset;
+use compression::{from_bytes::to_little_endian, ByteOffsetReader};
 use thiserror::Error as ThisError;
 
-use image::{pixel::Pixels, ImageEnum};
-use metadata::{read_metadata, ByteOrder, Conversion, ElementType, Encoding, Error as MetadataError, Metadata};
+use decoder::{Decoder, ImageIter, NamedImage};
+pub use decoder::{FrameConsistency, Limits, ReadOptions};
+pub use encoder::{Encoder, WriteOptions};
+use image::{dimensions::DimensionOverflow, pixel::Pixels, ImageEnum};
+use metadata::{cif::CifHeader, Conversion, ElementType, Encoding, Error as MetadataError, Metadata};
+pub use source::open;
+pub use warning::Warning;
 
-pub fn read_all_images(mut reader: impl BufRead) -> Result<Vec<ImageEnum>, Error> {
-	let mut images = Vec::new();
+pub fn read_all_images(reader: impl BufRead) -> Result<Vec<ImageEnum>, Error> {
+	Decoder::default().read_all_images(reader)
+}
 
-	while let Some(image) = try_read_next_image(&mut reader)? {
-		images.push(image);
-	}
+/// Iterates over the images of `reader`, reading one image at a time.
+pub fn images<R: BufRead>(reader: R) -> ImageIter<R> {
+	Decoder::default().images(reader)
+}
 
-	Ok(images)
+pub fn read_all_images_with(reader: impl BufRead, options: &ReadOptions) -> Result<Vec<ImageEnum>, Error> {
+	Decoder::new(options.clone()).read_all_images(reader)
 }
 
-fn try_read_next_image(reader: impl BufRead) -> Result<Option<ImageEnum>, Error> {
-	match read_image(reader) {
-		Ok(image) => Ok(Some(image)),
-		Err(Error::NoImage) => Ok(None),
-		Err(error) => Err(error),
-	}
+pub fn read_all_named_images(reader: impl BufRead) -> Result<Vec<NamedImage>, Error> {
+	Decoder::default().read_all_named_images(reader)
 }
 
-pub fn read_image(mut reader: impl BufRead) -> Result<ImageEnum, Error> {
-	progress_reader_to_cbf_start(&mut reader)?;
-	let metadata = read_metadata(&mut reader)?;
-	read_binary_header(&mut reader)?;
-	let pixels = read_pixels(&mut reader, &metadata)?;
-	progress_reader_to_cbf_end(&mut reader)?;
-	Ok(ImageEnum::from_pixels(
-		metadata.width.ok_or(Error::MissingDimension)?,
-		metadata.height.ok_or(Error::MissingDimension)?,
-		pixels,
-	))
+pub fn read_all_headers(reader: impl BufRead) -> Result<Vec<Metadata>, Error> {
+	Decoder::default().read_all_headers(reader)
 }
 
-fn read_pixels(reader: impl Read, metadata: &Metadata) -> Result<Pixels, Error> {
-	if metadata.byte_order != ByteOrder::LittleEndian {
-		return Err(Error::UnsupportedByteOrder);
-	}
+pub fn read_image(reader: impl BufRead) -> Result<ImageEnum, Error> {
+	Decoder::default().read_image(reader)
+}
+
+pub fn read_image_with(reader: impl BufRead, options: &ReadOptions) -> Result<ImageEnum, Error> {
+	Decoder::new(options.clone()).read_image(reader)
+}
+
+/// Reads the next image along with the CIF data blocks before it, holding
+/// values such as the detector distance, wavelength and pixel size.
+pub fn read_image_with_header(reader: impl BufRead) -> Result<(CifHeader, ImageEnum), Error> {
+	Decoder::default().read_image_with_header(reader)
+}
+
+/// Buffers `reader` and reads the first image in it with the default options.
+pub fn read_first_image(reader: impl Read) -> Result<ImageEnum, Error> {
+	read_image(BufReader::new(reader))
+}
+
+/// Opens the plain or gzipped CBF file at `path` and reads its first image
+/// with the default options.
+pub fn read_image_from_path(path: impl AsRef<Path>) -> Result<ImageEnum, Error> {
+	open(path.as_ref())?.next().unwrap_or(Err(Error::NoImage))
+}
+
+/// Reads the next image, along with the number of bytes consumed from
+/// `reader`, including any content skipped before the binary section.
+pub fn read_image_counted(reader: impl BufRead) -> Result<(ImageEnum, usize), Error> {
+	Decoder::default().read_image_counted(reader)
+}
+
+/// Reads the first image in `data`, along with the number of bytes consumed.
+pub fn read_image_from_slice(data: &[u8]) -> Result<(ImageEnum, usize), Error> {
+	let mut rest = data;
+	let image = read_image(&mut rest)?;
+	Ok((image, data.len() - rest.len()))
+}
+
+/// Reads all images in `data`, along with the number of bytes consumed.
+pub fn read_all_images_from_slice(data: &[u8]) -> Result<(Vec<ImageEnum>, usize), Error> {
+	let mut rest = data;
+	let images = read_all_images(&mut rest)?;
+	Ok((images, data.len() - rest.len()))
+}
+
+/// Writes `image` as a CBF file with the default options: byte offset
+/// compression and, with the `md5` feature, an MD5 digest.
+pub fn write_image(writer: impl Write, image: &ImageEnum) -> Result<(), Error> {
+	Encoder::default().write_image(writer, image)
+}
+
+pub fn write_image_with(writer: impl Write, image: &ImageEnum, options: &WriteOptions) -> Result<(), Error> {
+	Encoder::new(options.clone()).write_image(writer, image)
+}
+
+/// Writes `images` as one CBF file, each in a `data_` block of its own.
+pub fn write_all_images(writer: impl Write, images: &[ImageEnum]) -> Result<(), Error> {
+	Encoder::default().write_all_images(writer, images)
+}
+
+pub fn write_all_images_with(writer: impl Write, images: &[ImageEnum], options: &WriteOptions) -> Result<(), Error> {
+	Encoder::new(options.clone()).write_all_images(writer, images)
+}
+
+/// Largest size in bytes of the pixel buffer [`read_pixels`] allocates for
+/// `metadata`, whatever element type it decodes to.
+fn decoded_size(metadata: &Metadata) -> Option<usize> {
+	metadata.element_count.checked_mul(std::mem::size_of::<i64>())
+}
+
+/// Pixels decoded between checks of the deadline.
+const DEADLINE_CHECK_INTERVAL: usize = 1 << 16;
+
+/// Boundary line opening a binary section.
+pub(crate) const SECTION_START: &str = "--CIF-BINARY-FORMAT-SECTION--\r\n";
+/// Boundary line closing a binary section.
+pub(crate) const SECTION_END: &str = "--CIF-BINARY-FORMAT-SECTION----\r\n";
+const BINARY_HEADER: [u8; 4] = [0x0C, 0x1A, 0x04, 0xD5];
+
+fn read_pixels(
+	reader: impl Read,
+	metadata: &Metadata,
+	options: &ReadOptions,
+	deadline: Option<Instant>,
+) -> Result<Pixels, Error> {
 	if metadata.content_type.mime_type != "application" || metadata.content_type.subtype != "octet-stream" {
 		return Err(Error::UnsupportedContentType);
 	}
-	if metadata.content_transfer_encoding.encoding != Encoding::Binary {
-		return Err(Error::UnsupportedEncoding);
+	match metadata.content_transfer_encoding.encoding {
+		Encoding::Binary => decode_pixels(reader, metadata, options, deadline),
+		#[cfg(feature = "base64")]
+		Encoding::Base64 => decode_pixels(encoding::Base64Reader::new(reader), metadata, options, deadline),
+		Encoding::Base32K => decode_pixels(encoding::Base32KReader::new(reader), metadata, options, deadline),
+		Encoding::QuotedPrintable => decode_pixels(
+			encoding::QuotedPrintableReader::new(reader),
+			metadata,
+			options,
+			deadline,
+		),
+		// The layout CBFlib writes these in is not checked against its files.
+		Encoding::Base8 | Encoding::Base10 | Encoding::Base16 => Err(Error::UnsupportedEncoding),
+		#[cfg(not(feature = "base64"))]
+		Encoding::Base64 => Err(Error::UnsupportedEncoding),
 	}
+}
+
+/// Decompresses the pixels of a section with its transfer encoding removed.
+fn decode_pixels(
+	reader: impl Read,
+	metadata: &Metadata,
+	options: &ReadOptions,
+	deadline: Option<Instant>,
+) -> Result<Pixels, Error> {
 	match metadata.content_type.conversion {
+		None => read_uncompressed(reader, metadata, options, deadline),
 		Some(Conversion::ByteOffset) => {
-			macro_rules! read_byte_offset {
-				($($name:ident: $zero:expr,)*) => {
-					match metadata.element_type {
-						$(ElementType::$name => {
-							let mut pixels = vec![$zero; metadata.element_count];
-							read_byte_offset(reader, &mut pixels)?;
-							Ok(pixels.into())
-						})*
-						_ => Err(Error::UnsupportedPixelFormat),
-					}
-				};
+			if !is_byte_offset_type(&metadata.element_type) {
+				return Err(Error::UnsupportedPixelFormat);
 			}
-			read_byte_offset!(
-				Unsigned8bitInteger: 0i64,
-				Signed8bitInteger: 0i64,
-				Unsigned16bitInteger: 0i64,
-				Signed16bitInteger: 0i64,
-				Unsigned32bitInteger: 0i64,
-				Signed32bitInteger: 0i64,
-			)
+			// Deltas are accumulated in i64 whatever the declared type, so
+			// intermediate values may leave its range.
+			let mut pixels = vec![0i64; metadata.element_count];
+			let mut reader = ByteOffsetReader::new(reader, options.overflow).byte_order(metadata.byte_order);
+			for chunk in pixels.chunks_mut(DEADLINE_CHECK_INTERVAL) {
+				check_deadline(deadline)?;
+				reader.read(chunk)?;
+			}
+			declared_pixels(pixels, metadata, options)
 		}
+		// CBFlib's packed data starts with a header and resets its predictions
+		// at section boundaries, neither of which is checked against its files.
+		Some(Conversion::Packed(_)) => Err(Error::UnsupportedCompression),
 		_ => Err(Error::UnsupportedCompression),
 	}
 }
 
+/// Reads uncompressed elements stored in the declared byte order. Integers are
+/// widened to `i64` like decompressed ones, floats are kept as they are.
+fn read_uncompressed(
+	mut reader: impl Read,
+	metadata: &Metadata,
+	options: &ReadOptions,
+	deadline: Option<Instant>,
+) -> Result<Pixels, Error> {
+	macro_rules! read_elements {
+		($type:ty, $pixel:ty) => {{
+			const SIZE: usize = std::mem::size_of::<$type>();
+			let mut pixels = vec![<$pixel>::default(); metadata.element_count];
+			let mut bytes = Vec::new();
+			for chunk in pixels.chunks_mut(DEADLINE_CHECK_INTERVAL) {
+				check_deadline(deadline)?;
+				bytes.resize(chunk.len() * SIZE, 0);
+				reader.read_exact(&mut bytes)?;
+				for (pixel, bytes) in chunk.iter_mut().zip(bytes.chunks_exact(SIZE)) {
+					let bytes = to_little_endian(bytes.try_into().unwrap(), metadata.byte_order);
+					*pixel = <$type>::from_le_bytes(bytes).into();
+				}
+			}
+			pixels
+		}};
+	}
+	match metadata.element_type {
+		ElementType::Unsigned8bitInteger => declared_pixels(read_elements!(u8, i64), metadata, options),
+		ElementType::Signed8bitInteger => declared_pixels(read_elements!(i8, i64), metadata, options),
+		ElementType::Unsigned16bitInteger => declared_pixels(read_elements!(u16, i64), metadata, options),
+		ElementType::Signed16bitInteger => declared_pixels(read_elements!(i16, i64), metadata, options),
+		ElementType::Unsigned32bitInteger => declared_pixels(read_elements!(u32, i64), metadata, options),
+		ElementType::Signed32bitInteger => declared_pixels(read_elements!(i32, i64), metadata, options),
+		ElementType::Signed32bitReal => Ok(read_elements!(f32, f32).into()),
+		ElementType::Signed64bitReal => Ok(read_elements!(f64, f64).into()),
+		_ => Err(Error::UnsupportedPixelFormat),
+	}
+}
+
+/// Decoded pixels, narrowed to the declared element type if the options ask
+/// for it.
+fn declared_pixels(pixels: Vec<i64>, metadata: &Metadata, options: &ReadOptions) -> Result<Pixels, Error> {
+	if options.strict_element_type {
+		narrow(pixels, &metadata.element_type)
+	} else {
+		Ok(pixels.into())
+	}
+}
+
+fn is_byte_offset_type(element_type: &ElementType) -> bool {
+	matches!(
+		element_type,
+		ElementType::Unsigned8bitInteger
+			| ElementType::Signed8bitInteger
+			| ElementType::Unsigned16bitInteger
+			| ElementType::Signed16bitInteger
+			| ElementType::Unsigned32bitInteger
+			| ElementType::Signed32bitInteger
+	)
+}
+
+/// Converts decoded pixels to the declared element type, failing on the
+/// first value outside of its range.
+fn narrow(pixels: Vec<i64>, element_type: &ElementType) -> Result<Pixels, Error> {
+	macro_rules! narrow {
+		($($name:ident: $type:ty,)*) => {
+			match element_type {
+				$(ElementType::$name => pixels
+					.into_iter()
+					.enumerate()
+					.map(|(index, value)| <$type>::try_from(value).map_err(|_| Error::ElementOutOfRange { index, value }))
+					.collect::<Result<Vec<$type>, _>>()
+					.map(Pixels::from),)*
+				_ => Err(Error::UnsupportedPixelFormat),
+			}
+		};
+	}
+	narrow!(
+		Unsigned8bitInteger: u8,
+		Signed8bitInteger: i8,
+		Unsigned16bitInteger: u16,
+		Signed16bitInteger: i16,
+		Unsigned32bitInteger: u32,
+		Signed32bitInteger: i32,
+	)
+}
+
+/// Checks that decoded pixels fit in the declared element type, like
+/// [`narrow`] without converting them.
+fn check_declared_range(pixels: &[i64], element_type: &ElementType) -> Result<(), Error> {
+	let range = match element_type {
+		ElementType::Unsigned8bitInteger => u8::MIN.into()..=u8::MAX.into(),
+		ElementType::Signed8bitInteger => i8::MIN.into()..=i8::MAX.into(),
+		ElementType::Unsigned16bitInteger => u16::MIN.into()..=u16::MAX.into(),
+		ElementType::Signed16bitInteger => i16::MIN.into()..=i16::MAX.into(),
+		ElementType::Unsigned32bitInteger => u32::MIN.into()..=u32::MAX.into(),
+		ElementType::Signed32bitInteger => i32::MIN.into()..=i32::MAX.into(),
+		_ => return Err(Error::UnsupportedPixelFormat),
+	};
+	match pixels.iter().position(|value| !range.contains(value)) {
+		Some(index) => Err(Error::ElementOutOfRange { index, value: pixels[index] }),
+		None => Ok(()),
+	}
+}
+
+fn check_deadline(deadline: Option<Instant>) -> Result<(), Error> {
+	match deadline {
+		Some(deadline) if deadline <= Instant::now() => Err(Error::TimedOut),
+		_ => Ok(()),
+	}
+}
+
 fn progress_reader_to_cbf_start(reader: impl BufRead) -> Result<(), Error> {
-	match progress_reader_to(reader, "--CIF-BINARY-FORMAT-SECTION--\r\n")? {
+	progress_reader_to_cbf_start_with(reader, |_| {})
+}
+
+/// Like [`progress_reader_to_cbf_start`], passing every skipped line to `on_line`.
+fn progress_reader_to_cbf_start_with(reader: impl BufRead, on_line: impl FnMut(&str)) -> Result<(), Error> {
+	match progress_reader_to(reader, SECTION_START, on_line)? {
 		Reached::Needle => Ok(()),
 		Reached::End => Err(Error::NoImage),
 	}
 }
 
 fn progress_reader_to_cbf_end(reader: impl BufRead) -> Result<(), Error> {
-	progress_reader_to(reader, "--CIF-BINARY-FORMAT-SECTION----\r\n")?;
+	progress_reader_to(reader, SECTION_END, |_| {})?;
 	Ok(())
 }
 
-fn progress_reader_to(mut reader: impl BufRead, needle: &str) -> Result<Reached, Error> {
+fn progress_reader_to(mut reader: impl BufRead, needle: &str, mut on_line: impl FnMut(&str)) -> Result<Reached, Error> {
 	let mut line = String::new();
 
 	loop {
@@ -106,15 +361,32 @@ fn progress_reader_to(mut reader: impl BufRead, needle: &str) -> Result<Reached,
 		if line == needle {
 			return Ok(Reached::Needle);
 		}
+		on_line(&line);
 	}
 }
 
+/// Whether the data of the section starts with the binary header. Sections
+/// with a text transfer encoding have none.
+fn has_binary_header(metadata: &Metadata) -> bool {
+	metadata.content_transfer_encoding.encoding == Encoding::Binary
+}
+
+/// Length of the binary header, data and padding following the MIME headers
+/// of a section with a binary header, or an error if the declared sizes
+/// overflow.
+#[cfg(feature = "async")]
+fn binary_section_len(metadata: &Metadata) -> Result<usize, Error> {
+	(BINARY_HEADER.len().checked_add(metadata.size))
+		.and_then(|len| len.checked_add(metadata.padding.unwrap_or(0)))
+		.ok_or(Error::LimitExceeded)
+}
+
 fn read_binary_header(mut reader: impl Read) -> Result<(), Error> {
 	let mut header = [0; 4];
 
 	reader.read_exact(&mut header)?;
 
-	if header != [0x0C, 0x1A, 0x04, 0xD5] {
+	if header != BINARY_HEADER {
 		return Err(Error::UnrecognisedBinaryHeader);
 	}
 
@@ -148,13 +420,44 @@ pub enum Error {
 	UnrecognisedBinaryHeader,
 	#[error("missing dimension")]
 	MissingDimension,
+	#[error(transparent)]
+	DimensionOverflow(#[from] DimensionOverflow),
+	#[error("dimensions do not match the number of elements")]
+	DimensionMismatch,
+	#[error("declared size exceeds limit")]
+	LimitExceeded,
+	#[error("memory limit exceeded")]
+	MemoryLimitExceeded,
+	#[error("unsupported file format")]
+	UnsupportedFormat,
+	#[error("decoding exceeded its deadline")]
+	TimedOut,
+	#[error("padding after the binary data is not NUL")]
+	InvalidPadding,
+	#[error("pixel {index} has value {value} outside of the declared element type")]
+	ElementOutOfRange { index: usize, value: i64 },
+	#[error("binary data has MD5 digest {found} instead of {expected}")]
+	ChecksumMismatch { expected: String, found: String },
+	#[error("region lies outside of the image")]
+	RegionOutOfBounds,
+	#[error("frame {} differs in dimensions or element type from the first frame", .0.index)]
+	InconsistentFrame(decoder::Inconsistency),
+	#[error("written file deviates from the strict CBF/imgCIF layout: {0:?}")]
+	StrictViolation(Vec<validate::Violation>),
+	#[error("image has no pixels")]
+	EmptyImage,
+	#[error("sections of {0} planes are not supported")]
+	UnsupportedDepth(usize),
 }
 
 #[cfg(test)]
 mod tests {
 	use std::io::{Cursor, Read};
 
-	use super::{image::ImageEnum, read_image};
+	use super::{
+		image::ImageEnum, metadata::ByteOrder, read_all_images_from_slice, read_first_image, read_image,
+		read_image_from_path, read_image_from_slice, read_image_with, Error, ReadOptions,
+	};
 
 	#[test]
 	fn read_real_image() {
@@ -178,4 +481,312 @@ mod tests {
 		reader.read_to_string(&mut rest).expect("to read rest as string");
 		assert_eq!(rest, ";\r\n");
 	}
+
+	#[test]
+	fn read_from_slice() {
+		const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+		let (image, consumed) = read_image_from_slice(EXAMPLE_DATA).expect("to read image");
+		assert_eq!(image.width(), 2880);
+		assert_eq!(&EXAMPLE_DATA[consumed..], b";\r\n");
+
+		let data = [EXAMPLE_DATA, EXAMPLE_DATA].concat();
+		let (images, consumed) = read_all_images_from_slice(&data).expect("to read images");
+		assert_eq!(images.len(), 2);
+		assert_eq!(consumed, data.len());
+	}
+
+	/// Byte offset encodes `values` as a single CBF section of one row.
+	fn byte_offset_cbf(element_type: &str, values: &[i64]) -> Vec<u8> {
+		byte_offset_cbf_in(ByteOrder::LittleEndian, element_type, values)
+	}
+
+	fn byte_offset_cbf_in(byte_order: ByteOrder, element_type: &str, values: &[i64]) -> Vec<u8> {
+		// Multi-byte fields, including the escape markers, are written in
+		// `byte_order`.
+		let ordered = |mut bytes: Vec<u8>| {
+			if byte_order == ByteOrder::BigEndian {
+				bytes.reverse();
+			}
+			bytes
+		};
+		let mut data = Vec::new();
+		let mut previous = 0;
+		for &value in values {
+			let delta = value - previous;
+			previous = value;
+			if (-127..=127).contains(&delta) {
+				data.push(delta as i8 as u8);
+			} else if (-32767..=32767).contains(&delta) {
+				data.push(0x80);
+				data.extend(ordered((delta as i16).to_le_bytes().into()));
+			} else if (-2147483647..=2147483647).contains(&delta) {
+				data.push(0x80);
+				data.extend(ordered(vec![0x00, 0x80]));
+				data.extend(ordered((delta as i32).to_le_bytes().into()));
+			} else {
+				data.push(0x80);
+				data.extend(ordered(vec![0x00, 0x80]));
+				data.extend(ordered(vec![0x00, 0x00, 0x00, 0x80]));
+				data.extend(ordered(delta.to_le_bytes().into()));
+			}
+		}
+		let header = format!(
+			"--CIF-BINARY-FORMAT-SECTION--\r\n\
+Content-Type: application/octet-stream;\r\n     conversions=\"x-CBF_BYTE_OFFSET\"\r\n\
+Content-Transfer-Encoding: BINARY\r\n\
+X-Binary-Size: {}\r\n\
+X-Binary-Element-Type: \"{element_type}\"\r\n\
+X-Binary-Element-Byte-Order: {byte_order}\r\n\
+X-Binary-Number-of-Elements: {}\r\n\
+X-Binary-Size-Fastest-Dimension: {}\r\n\
+X-Binary-Size-Second-Dimension: 1\r\n\
+\r\n",
+			data.len(),
+			values.len(),
+			values.len(),
+		);
+		[
+			header.as_bytes(),
+			&[0x0C, 0x1A, 0x04, 0xD5],
+			&data,
+			b"\r\n--CIF-BINARY-FORMAT-SECTION----\r\n",
+		]
+		.concat()
+	}
+
+	#[test]
+	fn big_endian() {
+		// Deltas of every width, the last but one taking eight bytes.
+		let values = [5, 1000, -70_000, -2_000_000_000, 2_000_000_000, 0];
+		let data = byte_offset_cbf_in(ByteOrder::BigEndian, "signed 32-bit integer", &values);
+		assert_ne!(data, byte_offset_cbf("signed 32-bit integer", &values));
+		let ImageEnum::I64(image) = read_image(data.as_slice()).expect("to read big endian image") else {
+			panic!("expected i64 pixels")
+		};
+		assert_eq!(image.pixels(), values);
+	}
+
+	/// A section of signed 32-bit integers compressed with `conversions` into
+	/// `data`, with the dimension headers in `dimensions`.
+	fn compressed_cbf(conversions: &str, element_count: usize, dimensions: &str, data: &[u8]) -> Vec<u8> {
+		let header = format!(
+			"--CIF-BINARY-FORMAT-SECTION--\r\n\
+Content-Type: application/octet-stream;\r\n     conversions={conversions}\r\n\
+Content-Transfer-Encoding: BINARY\r\n\
+X-Binary-Size: {}\r\n\
+X-Binary-Element-Type: \"signed 32-bit integer\"\r\n\
+X-Binary-Element-Byte-Order: LITTLE_ENDIAN\r\n\
+X-Binary-Number-of-Elements: {element_count}\r\n\
+{dimensions}\
+\r\n",
+			data.len(),
+		);
+		[
+			header.as_bytes(),
+			&[0x0C, 0x1A, 0x04, 0xD5],
+			data,
+			b"\r\n--CIF-BINARY-FORMAT-SECTION----\r\n",
+		]
+		.concat()
+	}
+
+	#[test]
+	fn packed_unsupported() {
+		let dimensions = "X-Binary-Size-Fastest-Dimension: 4\r\nX-Binary-Size-Second-Dimension: 4\r\n";
+		for conversions in [
+			"\"x-CBF_PACKED\"",
+			"\"x-CBF_PACKED\";flat",
+			"\"x-CBF_PACKED\";uncorrelated_sections",
+		] {
+			let file = compressed_cbf(conversions, 16, dimensions, &[0; 16]);
+			assert!(matches!(
+				read_image(file.as_slice()),
+				Err(Error::UnsupportedCompression)
+			));
+		}
+	}
+
+	#[test]
+	fn canonical_unsupported() {
+		let file = compressed_cbf(
+			"\"x-CBF_CANONICAL\"",
+			12,
+			"X-Binary-Size-Fastest-Dimension: 4\r\nX-Binary-Size-Second-Dimension: 3\r\n",
+			&[0; 12],
+		);
+		assert!(matches!(
+			read_image(file.as_slice()),
+			Err(Error::UnsupportedCompression)
+		));
+	}
+
+	#[test]
+	fn background_offset_delta_unsupported() {
+		let file = compressed_cbf(
+			"\"x-CBF_BACKGROUND_OFFSET_DELTA\"",
+			2,
+			"X-Binary-Size-Fastest-Dimension: 2\r\nX-Binary-Size-Second-Dimension: 1\r\n",
+			&[0x00, 0x01],
+		);
+		assert!(matches!(
+			read_image(file.as_slice()),
+			Err(Error::UnsupportedCompression)
+		));
+	}
+
+	#[cfg(feature = "base64")]
+	#[test]
+	fn base64_section() {
+		use std::io::Write;
+
+		use crate::decoder::Decoder;
+
+		let mut writer = crate::encoding::Base64Writer::new(Vec::new());
+		writer
+			.write_all(&[0x01, 0x01, 0x80, 0x2A, 0x01, 0x80, 0xCF, 0xFE])
+			.unwrap();
+		let encoded = writer.finish().unwrap();
+		let file = [
+			b"--CIF-BINARY-FORMAT-SECTION--\r\n\
+Content-Type: application/octet-stream;\r\n     conversions=\"x-CBF_BYTE_OFFSET\"\r\n\
+Content-Transfer-Encoding: BASE64\r\n\
+X-Binary-Size: 8\r\n\
+X-Binary-Element-Type: \"signed 32-bit integer\"\r\n\
+X-Binary-Element-Byte-Order: LITTLE_ENDIAN\r\n\
+X-Binary-Number-of-Elements: 4\r\n\
+X-Binary-Size-Fastest-Dimension: 2\r\n\
+X-Binary-Size-Second-Dimension: 2\r\n\
+\r\n",
+			encoded.as_slice(),
+			b"--CIF-BINARY-FORMAT-SECTION----\r\n",
+		]
+		.concat();
+		let ImageEnum::I64(image) = read_image(file.as_slice()).expect("to read base64 image") else {
+			panic!("expected i64 pixels")
+		};
+		assert_eq!(image.pixels(), [1, 2, 300, -5]);
+		let headers = Decoder::default()
+			.read_all_headers([&file[..], &file[..]].concat().as_slice())
+			.unwrap();
+		assert_eq!(headers.len(), 2);
+	}
+
+	#[test]
+	fn text_encoded_sections() {
+		let section = |encoding: &str, data: &str| {
+			format!(
+				"--CIF-BINARY-FORMAT-SECTION--\r\n\
+Content-Type: application/octet-stream;\r\n     conversions=\"x-CBF_BYTE_OFFSET\"\r\n\
+Content-Transfer-Encoding: {encoding}\r\n\
+X-Binary-Size: 4\r\n\
+X-Binary-Element-Type: \"signed 32-bit integer\"\r\n\
+X-Binary-Element-Byte-Order: LITTLE_ENDIAN\r\n\
+X-Binary-Number-of-Elements: 2\r\n\
+X-Binary-Size-Fastest-Dimension: 2\r\n\
+\r\n\
+{data}\r\n\
+--CIF-BINARY-FORMAT-SECTION----\r\n"
+			)
+		};
+		for (encoding, data) in [
+			("QUOTED-PRINTABLE", "=05=80=0A=01"),
+			("X-BASE32K", "\u{42C0}\u{4280}\u{6000}="),
+		] {
+			let file = section(encoding, data);
+			let ImageEnum::I64(image) = read_image(file.as_bytes()).expect("to read encoded image") else {
+				panic!("expected i64 pixels")
+			};
+			assert_eq!(image.pixels(), [5, 271], "{encoding}");
+		}
+		let file = section("X-BASE16", "05 80 0A 01");
+		assert!(matches!(read_image(file.as_bytes()), Err(Error::UnsupportedEncoding)));
+	}
+
+	#[test]
+	fn golden_path() {
+		let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/examples/snap_V4_00013.cbf");
+		let image = read_image_from_path(path).expect("to read file");
+		assert_eq!((image.width(), image.height()), (2880, 2880));
+		let first = read_first_image(std::fs::File::open(path).unwrap()).expect("to read reader");
+		assert_eq!(first.to_f64().pixels(), image.to_f64().pixels());
+		assert!(matches!(read_first_image(&b"no sections"[..]), Err(Error::NoImage)));
+		assert!(matches!(read_image_from_path("does/not/exist.cbf"), Err(Error::IO(_))));
+	}
+
+	#[cfg(feature = "md5")]
+	#[test]
+	fn verify_checksum() {
+		const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+		let options = ReadOptions::default().verify_checksum();
+		read_image_with(EXAMPLE_DATA, &options).expect("digest to match");
+
+		let mut corrupted = EXAMPLE_DATA.to_vec();
+		let data_start = corrupted
+			.windows(4)
+			.position(|w| w == [0x0C, 0x1A, 0x04, 0xD5])
+			.unwrap() + 4;
+		corrupted[data_start] ^= 0x01;
+		read_image(&corrupted[..]).expect("to read without verifying");
+		assert!(matches!(
+			read_image_with(&corrupted[..], &options),
+			Err(Error::ChecksumMismatch { .. })
+		));
+	}
+
+	#[test]
+	fn strict_element_types() {
+		let options = ReadOptions::default().strict_element_type();
+		let cases: [(&str, i64, i64); 6] = [
+			("unsigned 8-bit integer", u8::MIN.into(), u8::MAX.into()),
+			("signed 8-bit integer", i8::MIN.into(), i8::MAX.into()),
+			("unsigned 16-bit integer", u16::MIN.into(), u16::MAX.into()),
+			("signed 16-bit integer", i16::MIN.into(), i16::MAX.into()),
+			("unsigned 32-bit integer", u32::MIN.into(), u32::MAX.into()),
+			("signed 32-bit integer", i32::MIN.into(), i32::MAX.into()),
+		];
+		for (element_type, min, max) in cases {
+			let data = byte_offset_cbf(element_type, &[min, max]);
+			let image = read_image_with(&data[..], &options).expect(element_type);
+			let pixels: Vec<i64> = match image {
+				ImageEnum::U8(i) => i.pixels().iter().map(|&p| p.into()).collect(),
+				ImageEnum::I8(i) => i.pixels().iter().map(|&p| p.into()).collect(),
+				ImageEnum::U16(i) => i.pixels().iter().map(|&p| p.into()).collect(),
+				ImageEnum::I16(i) => i.pixels().iter().map(|&p| p.into()).collect(),
+				ImageEnum::U32(i) => i.pixels().iter().map(|&p| p.into()).collect(),
+				ImageEnum::I32(i) => i.pixels().iter().map(|&p| p.into()).collect(),
+				_ => panic!("{element_type} decoded as the wrong type"),
+			};
+			assert_eq!(pixels, [min, max], "{element_type}");
+
+			let data = byte_offset_cbf(element_type, &[max, min - 1]);
+			assert!(
+				matches!(
+					read_image_with(&data[..], &options),
+					Err(Error::ElementOutOfRange { index: 1, .. })
+				),
+				"{element_type} below range"
+			);
+		}
+	}
+
+	#[test]
+	fn strip_detector() {
+		let mut data = byte_offset_cbf("signed 32-bit integer", &[4, 8, 15, 16, 23, 42]);
+		let line = b"X-Binary-Size-Second-Dimension: 1\r\n";
+		let start = data.windows(line.len()).position(|w| w == line).unwrap();
+		data.drain(start..start + line.len());
+		let image = read_image(&data[..]).expect("to read strip");
+		assert_eq!((image.width(), image.height()), (6, 1));
+		let profile = crate::analysis::strip_profile(&image, 3).unwrap();
+		assert_eq!(profile[..], [6.0, 15.5, 32.5]);
+	}
+
+	#[test]
+	fn lenient_element_types() {
+		let data = byte_offset_cbf("unsigned 16-bit integer", &[-1, 70000]);
+		let ImageEnum::I64(image) = read_image(&data[..]).expect("to read image") else {
+			panic!("expected i64 pixels")
+		};
+		assert_eq!(image.pixels(), [-1, 70000]);
+	}
 }
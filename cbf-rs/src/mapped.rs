@@ -0,0 +1,74 @@
+use std::{fs::File, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{decoder::Decoder, image::ImageEnum, index::FrameIndex, Error};
+
+/// A memory mapped multi-frame file with its [`FrameIndex`].
+///
+/// Decoding only reads the mapping and the index, so threads can share a
+/// `MappedFile` by reference and decode different frames at once, which is
+/// the fastest way to reprocess large files offline.
+pub struct MappedFile {
+	map: Mmap,
+	index: FrameIndex,
+	decoder: Decoder,
+}
+
+impl MappedFile {
+	/// Maps the file at `path` and indexes its frames with `decoder`.
+	///
+	/// The file must not be modified while it is mapped.
+	pub fn open(path: impl AsRef<Path>, decoder: Decoder) -> Result<Self, Error> {
+		let file = File::open(path)?;
+		// SAFETY: The mapping is only read, and the caller is told not to
+		// modify the file while it is mapped.
+		let map = unsafe { Mmap::map(&file)? };
+		let index = decoder.index_frames(&map[..])?;
+		Ok(Self { map, index, decoder })
+	}
+
+	pub fn index(&self) -> &FrameIndex {
+		&self.index
+	}
+
+	pub fn len(&self) -> usize {
+		self.index.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.index.is_empty()
+	}
+
+	/// Decodes frame `index`.
+	pub fn read_frame(&self, index: usize) -> Result<ImageEnum, Error> {
+		self.index.read_frame(&self.map, index, &self.decoder)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::thread;
+
+	use super::MappedFile;
+	use crate::decoder::Decoder;
+
+	#[test]
+	fn shared_between_threads() {
+		let path = std::env::temp_dir().join(format!("cbf-rs-mapped-{}.cbf", std::process::id()));
+		let example = include_bytes!("./examples/snap_V4_00013.cbf");
+		std::fs::write(&path, [&example[..], &example[..]].concat()).unwrap();
+		let file = MappedFile::open(&path, Decoder::default()).expect("to map");
+		assert_eq!(file.len(), 2);
+
+		let file = &file;
+		let sums: Vec<f64> = thread::scope(|scope| {
+			let handles: Vec<_> = (0..file.len())
+				.map(|i| scope.spawn(move || file.read_frame(i).unwrap().to_f64().pixels().iter().sum()))
+				.collect();
+			handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+		});
+		assert_eq!(sums[0], sums[1]);
+		std::fs::remove_file(path).unwrap();
+	}
+}
@@ -0,0 +1,24 @@
+use std::io::Write;
+
+use ::md5::{Digest, Md5};
+
+use crate::encoding::Base64Writer;
+
+/// Base64 encoded MD5 digest of `data`, the value of a `Content-MD5` header.
+pub fn content_md5(data: &[u8]) -> String {
+	let mut writer = Base64Writer::new(Vec::new());
+	writer.write_all(&Md5::digest(data)).unwrap();
+	let encoded = writer.finish().unwrap();
+	String::from_utf8_lossy(&encoded).trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::content_md5;
+
+	#[test]
+	fn header_value() {
+		assert_eq!(content_md5(b""), "1B2M2Y8AsgTpgAmY7PhCfg==");
+		assert_eq!(content_md5(b"abc"), "kAFQmDzST7DWlj99KOF/cg==");
+	}
+}
@@ -0,0 +1,67 @@
+use std::sync::{
+	atomic::{AtomicUsize, Ordering},
+	Arc,
+};
+
+/// Shared account of the bytes held in decoded pixel buffers, capped at a
+/// fixed limit. Clones share the same account.
+///
+/// Decoders reserve the largest size each pixel buffer can take before
+/// allocating it, and release the excess once it is decoded; the bytes of the
+/// image stay reserved until its owner calls [`release`] with
+/// [`ImageEnum::byte_size`].
+///
+/// [`release`]: MemoryBudget::release
+/// [`ImageEnum::byte_size`]: crate::image::ImageEnum::byte_size
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+	limit: usize,
+	used: Arc<AtomicUsize>,
+}
+
+impl MemoryBudget {
+	pub fn new(limit: usize) -> Self {
+		Self { limit, used: Arc::new(AtomicUsize::new(0)) }
+	}
+
+	pub fn limit(&self) -> usize {
+		self.limit
+	}
+
+	pub fn used(&self) -> usize {
+		self.used.load(Ordering::Acquire)
+	}
+
+	/// Reserves `bytes`, or returns `false` if that would exceed the limit.
+	pub fn reserve(&self, bytes: usize) -> bool {
+		self.used
+			.fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+				used.checked_add(bytes).filter(|total| *total <= self.limit)
+			})
+			.is_ok()
+	}
+
+	pub fn release(&self, bytes: usize) {
+		let _ = self.used.fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+			Some(used.saturating_sub(bytes))
+		});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::MemoryBudget;
+
+	#[test]
+	fn reserve_and_release() {
+		let budget = MemoryBudget::new(100);
+		let shared = budget.clone();
+		assert!(budget.reserve(60));
+		assert!(!shared.reserve(41));
+		assert!(shared.reserve(40));
+		assert_eq!(budget.used(), 100);
+		budget.release(60);
+		assert_eq!(shared.used(), 40);
+		assert!(!budget.reserve(usize::MAX));
+	}
+}
@@ -0,0 +1,276 @@
+/// The CIF text of a file, up to its first binary section.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CifHeader {
+	pub blocks: Vec<DataBlock>,
+}
+
+impl CifHeader {
+	/// The block named `name`, ignoring case as CIF does.
+	pub fn block(&self, name: &str) -> Option<&DataBlock> {
+		self.blocks.iter().find(|block| block.name.eq_ignore_ascii_case(name))
+	}
+
+	/// Value of `tag` in the first block that has it.
+	pub fn get(&self, tag: &str) -> Option<&str> {
+		self.blocks.iter().find_map(|block| block.get(tag))
+	}
+}
+
+/// A `data_` block. Items before the first `data_` line are gathered in a
+/// block without name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataBlock {
+	pub name: String,
+	/// Tag-value pairs outside of loops, in file order
+	pub items: Vec<(String, String)>,
+	pub loops: Vec<Loop>,
+}
+
+impl DataBlock {
+	/// Value of the item `tag`, ignoring case as CIF does.
+	pub fn get(&self, tag: &str) -> Option<&str> {
+		(self.items.iter())
+			.find(|(name, _)| name.eq_ignore_ascii_case(tag))
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// The loop with a column named `tag`.
+	pub fn get_loop(&self, tag: &str) -> Option<&Loop> {
+		self.loops.iter().find(|l| l.position(tag).is_some())
+	}
+}
+
+/// A `loop_` table, one row per packet of values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Loop {
+	pub tags: Vec<String>,
+	/// Rows of as many values as there are tags. A last, incomplete row is
+	/// dropped.
+	pub rows: Vec<Vec<String>>,
+}
+
+impl Loop {
+	/// Values of the column named `tag`.
+	pub fn column(&self, tag: &str) -> Option<impl Iterator<Item = &str>> {
+		let position = self.position(tag)?;
+		Some(self.rows.iter().map(move |row| row[position].as_str()))
+	}
+
+	fn position(&self, tag: &str) -> Option<usize> {
+		self.tags.iter().position(|name| name.eq_ignore_ascii_case(tag))
+	}
+}
+
+enum Token {
+	Data(String),
+	Loop,
+	Tag(String),
+	Value(String),
+}
+
+/// Parses tag-value pairs and loops of CIF `text`, as found before the binary
+/// sections of a CBF file. Save frames are not supported, their keywords are
+/// ignored. A text field left open at the end of `text`, like the one holding
+/// the binary section, has the value of the lines read so far.
+pub fn parse_cif(text: &str) -> CifHeader {
+	let mut header = CifHeader::default();
+	let mut block = DataBlock::default();
+	let mut state = State::Item(None);
+	for token in tokenize(text) {
+		state = match (token, state) {
+			(Token::Data(name), state) => {
+				state.finish(&mut block);
+				let block = std::mem::replace(&mut block, DataBlock { name, ..Default::default() });
+				if block != DataBlock::default() {
+					header.blocks.push(block);
+				}
+				State::Item(None)
+			}
+			(Token::Loop, state) => {
+				state.finish(&mut block);
+				State::LoopTags(Loop::default())
+			}
+			(Token::Tag(tag), State::LoopTags(mut l)) => {
+				l.tags.push(tag);
+				State::LoopTags(l)
+			}
+			(Token::Tag(tag), state) => {
+				state.finish(&mut block);
+				State::Item(Some(tag))
+			}
+			(Token::Value(value), State::Item(Some(tag))) => {
+				block.items.push((tag, value));
+				State::Item(None)
+			}
+			(Token::Value(_), State::Item(None)) => State::Item(None),
+			(Token::Value(value), State::LoopTags(l)) => State::LoopValues(l, vec![value]).next_row(),
+			(Token::Value(value), State::LoopValues(l, mut row)) => {
+				row.push(value);
+				State::LoopValues(l, row).next_row()
+			}
+		};
+	}
+	state.finish(&mut block);
+	if block != DataBlock::default() {
+		header.blocks.push(block);
+	}
+	header
+}
+
+enum State {
+	/// Outside of loops, after a tag still waiting for its value
+	Item(Option<String>),
+	LoopTags(Loop),
+	/// In the values of a loop, with the values of the current row
+	LoopValues(Loop, Vec<String>),
+}
+
+impl State {
+	/// Starts a new row if `row` is complete.
+	fn next_row(self) -> Self {
+		match self {
+			State::LoopValues(mut l, row) if row.len() == l.tags.len() => {
+				l.rows.push(row);
+				State::LoopValues(l, Vec::new())
+			}
+			state => state,
+		}
+	}
+
+	/// Adds a tag without value or an ended loop to `block`.
+	fn finish(self, block: &mut DataBlock) {
+		match self {
+			State::Item(Some(tag)) => block.items.push((tag, String::new())),
+			State::Item(None) => {}
+			State::LoopTags(l) | State::LoopValues(l, _) => block.loops.push(l),
+		}
+	}
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+	let mut tokens = Vec::new();
+	let mut lines = text.lines();
+	while let Some(line) = lines.next() {
+		if let Some(first) = line.strip_prefix(';') {
+			let mut field: Vec<&str> = Vec::new();
+			if !first.trim().is_empty() {
+				field.push(first.trim_end());
+			}
+			for line in lines.by_ref() {
+				if line.starts_with(';') {
+					break;
+				}
+				field.push(line);
+			}
+			tokens.push(Token::Value(field.join("\n")));
+			continue;
+		}
+		tokenize_line(line, &mut tokens);
+	}
+	tokens
+}
+
+fn tokenize_line(mut line: &str, tokens: &mut Vec<Token>) {
+	loop {
+		line = line.trim_start();
+		let Some(first) = line.chars().next() else {
+			return;
+		};
+		if first == '#' {
+			return;
+		}
+		if first == '\'' || first == '"' {
+			// A quote only closes the value if followed by whitespace.
+			let end = line[1..]
+				.match_indices(first)
+				.map(|(i, _)| i + 1)
+				.find(|&i| line[i + 1..].chars().next().is_none_or(char::is_whitespace));
+			let end = end.unwrap_or(line.len());
+			tokens.push(Token::Value(line[1..end].to_owned()));
+			line = line.get(end + 1..).unwrap_or("");
+			continue;
+		}
+		let end = line.find(char::is_whitespace).unwrap_or(line.len());
+		let word = &line[..end];
+		line = &line[end..];
+		let lowercase = word.to_ascii_lowercase();
+		if word.starts_with('_') {
+			tokens.push(Token::Tag(word.to_owned()));
+		} else if lowercase.starts_with("data_") {
+			tokens.push(Token::Data(word[5..].to_owned()));
+		} else if lowercase == "loop_" {
+			tokens.push(Token::Loop);
+		} else if !(lowercase.starts_with("save_") || lowercase == "global_" || lowercase == "stop_") {
+			tokens.push(Token::Value(word.to_owned()));
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::parse_cif;
+	use crate::decoder::Decoder;
+
+	#[test]
+	fn parses_items_and_loops() {
+		let header = parse_cif(
+			"###CBF: VERSION 1.5\r\n\
+data_first\r\n\
+_diffrn.id DS1 # comment\r\n\
+_diffrn_source.type 'rotating anode'\r\n\
+_diffrn_detector.details\r\n\
+;\r\n\
+multi line\r\n\
+text\r\n\
+;\r\n\
+loop_\r\n\
+_diffrn_radiation_wavelength.id\r\n\
+_diffrn_radiation_wavelength.wavelength\r\n\
+WAVELENGTH1 0.71073\r\n\
+WAVELENGTH2 \"1.5418\"\r\n\
+_array_data.data\r\n\
+; \r\n",
+		);
+		assert_eq!(header.blocks.len(), 1);
+		let block = header.block("FIRST").expect("named block");
+		assert_eq!(block.get("_diffrn.id"), Some("DS1"));
+		assert_eq!(block.get("_DIFFRN_SOURCE.TYPE"), Some("rotating anode"));
+		assert_eq!(block.get("_diffrn_detector.details"), Some("multi line\ntext"));
+		assert_eq!(block.get("_array_data.data"), Some(""));
+		let wavelengths = block.get_loop("_diffrn_radiation_wavelength.wavelength").expect("loop");
+		assert_eq!(wavelengths.rows.len(), 2);
+		let column: Vec<_> = wavelengths
+			.column("_diffrn_radiation_wavelength.wavelength")
+			.unwrap()
+			.collect();
+		assert_eq!(column, ["0.71073", "1.5418"]);
+	}
+
+	#[test]
+	fn quotes_inside_values() {
+		let header = parse_cif("_a 'it's here' _b \"x\"y\" _c");
+		let block = &header.blocks[0];
+		assert_eq!(block.name, "");
+		assert_eq!(block.get("_a"), Some("it's here"));
+		assert_eq!(block.get("_b"), Some("x\"y"));
+		assert_eq!(block.get("_c"), Some(""));
+	}
+
+	#[test]
+	fn read_example() {
+		const EXAMPLE_DATA: &[u8] = include_bytes!("../examples/snap_V4_00013.cbf");
+		let (header, image) = Decoder::default()
+			.read_image_with_header(Cursor::new(EXAMPLE_DATA))
+			.expect("to read image");
+		assert_eq!(image.width(), 2880);
+		assert_eq!(header.get("_array_data.header_convention"), Some("PILATUS_1.2"));
+		let contents = header
+			.block("snap_V4_00013")
+			.unwrap()
+			.get("_array_data.header_contents")
+			.unwrap();
+		assert!(contents.starts_with("# Detector: Varex XRD-4343CT\n"));
+	}
+}
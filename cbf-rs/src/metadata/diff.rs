@@ -0,0 +1,137 @@
+use super::Metadata;
+
+/// Fields expected to change from frame to frame, with the compressed data.
+pub const PER_FRAME_FIELDS: [&str; 2] = ["size", "md5_digest"];
+
+/// A field whose value differs between two [`Metadata`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldDiff {
+	pub field: &'static str,
+	pub left: String,
+	pub right: String,
+}
+
+/// Compares `left` and `right` field by field, returning the fields that differ.
+pub fn diff(left: &Metadata, right: &Metadata) -> Vec<FieldDiff> {
+	fields(left)
+		.into_iter()
+		.zip(fields(right))
+		.filter(|((_, l), (_, r))| l != r)
+		.map(|((field, left), (_, right))| FieldDiff { field, left, right })
+		.collect()
+}
+
+/// Compares every frame against the first one, returning the index of the
+/// frame alongside each difference. The [`PER_FRAME_FIELDS`] are left out.
+pub fn diff_frames(frames: &[Metadata]) -> Vec<(usize, FieldDiff)> {
+	diff_frames_with(frames, false)
+}
+
+/// Compares every frame against the first one like [`diff_frames`], also
+/// comparing the [`PER_FRAME_FIELDS`] if `per_frame` is set.
+pub fn diff_frames_with(frames: &[Metadata], per_frame: bool) -> Vec<(usize, FieldDiff)> {
+	let Some((first, rest)) = frames.split_first() else {
+		return Vec::new();
+	};
+	rest.iter()
+		.enumerate()
+		.flat_map(|(i, frame)| diff(first, frame).into_iter().map(move |d| (i + 1, d)))
+		.filter(|(_, d)| per_frame || !PER_FRAME_FIELDS.contains(&d.field))
+		.collect()
+}
+
+fn fields(metadata: &Metadata) -> [(&'static str, String); 13] {
+	let Metadata {
+		content_type,
+		content_transfer_encoding,
+		size,
+		padding,
+		byte_order,
+		md5_digest,
+		element_type,
+		element_count,
+		width,
+		height,
+		depth,
+	} = metadata;
+	[
+		(
+			"content_type",
+			format!("{}/{}", content_type.mime_type, content_type.subtype),
+		),
+		("conversion", format!("{:?}", content_type.conversion)),
+		("encoding", format!("{:?}", content_transfer_encoding.encoding)),
+		("charset", format!("{:?}", content_transfer_encoding.charset)),
+		("size", size.to_string()),
+		("padding", format!("{padding:?}")),
+		("byte_order", format!("{byte_order:?}")),
+		("md5_digest", format!("{md5_digest:?}")),
+		("element_type", format!("{element_type:?}")),
+		("element_count", element_count.to_string()),
+		("width", format!("{width:?}")),
+		("height", format!("{height:?}")),
+		("depth", format!("{depth:?}")),
+	]
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::{diff, diff_frames, diff_frames_with, FieldDiff};
+	use crate::metadata::read_metadata;
+
+	const HEADER: &str = "\
+Content-Type: application/octet-stream; conversions=\"x-CBF_BYTE_OFFSET\"\r
+Content-Transfer-Encoding: BINARY\r
+X-Binary-Size: 100\r
+X-Binary-Element-Type: \"signed 32-bit integer\"\r
+X-Binary-Element-Byte-Order: LITTLE_ENDIAN\r
+X-Binary-Number-of-Elements: 16\r
+X-Binary-Size-Fastest-Dimension: 4\r
+X-Binary-Size-Second-Dimension: 4\r
+\r\n";
+
+	#[test]
+	fn identical_metadata() {
+		let left = read_metadata(Cursor::new(HEADER)).unwrap();
+		let right = read_metadata(Cursor::new(HEADER)).unwrap();
+		assert!(diff(&left, &right).is_empty());
+	}
+
+	#[test]
+	fn changed_fields() {
+		let left = read_metadata(Cursor::new(HEADER)).unwrap();
+		let changed = HEADER
+			.replace("Size: 100", "Size: 120")
+			.replace("Fastest-Dimension: 4", "Fastest-Dimension: 2")
+			.replace("Second-Dimension: 4", "Second-Dimension: 8");
+		let right = read_metadata(Cursor::new(changed)).unwrap();
+		assert_eq!(
+			diff(&left, &right),
+			vec![
+				FieldDiff { field: "size", left: "100".to_owned(), right: "120".to_owned() },
+				FieldDiff { field: "width", left: "Some(4)".to_owned(), right: "Some(2)".to_owned() },
+				FieldDiff { field: "height", left: "Some(4)".to_owned(), right: "Some(8)".to_owned() },
+			]
+		);
+	}
+
+	#[test]
+	fn frames_against_first() {
+		let frames = [
+			HEADER.to_owned(),
+			HEADER.replace("Size: 100", "Size: 90"),
+			HEADER.replace("Byte-Order: LITTLE_ENDIAN", "Byte-Order: BIG_ENDIAN"),
+		]
+		.map(|h| read_metadata(Cursor::new(h)).unwrap());
+		let diffs = diff_frames(&frames);
+		assert_eq!(diffs.len(), 1);
+		assert_eq!(diffs[0].0, 2);
+		assert_eq!(diffs[0].1.field, "byte_order");
+
+		let diffs = diff_frames_with(&frames, true);
+		assert_eq!(diffs.len(), 2);
+		assert_eq!((diffs[0].0, diffs[0].1.field), (1, "size"));
+	}
+}
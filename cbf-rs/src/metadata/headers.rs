@@ -14,6 +14,7 @@ use nom::{
 	sequence::{delimited, pair, preceded, separated_pair, terminated},
 	Err, IResult,
 };
+use smol_str::{SmolStr, StrExt};
 use thiserror::Error as ThisError;
 
 #[derive(Debug, ThisError)]
@@ -24,24 +25,75 @@ pub enum Error {
 	IO(#[from] IOError),
 }
 
-pub fn read_headers(mut reader: impl BufRead) -> Result<HashMap<String, String>, Error> {
-	let mut headers = HashMap::new();
+/// MIME headers of a binary section in file order, with lowercase names.
+///
+/// Sections have around a dozen short headers, so they are kept inline in a
+/// vector rather than hashed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers(Vec<(SmolStr, SmolStr)>);
+
+impl Headers {
+	/// Looks up a header by name, ignoring case.
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.0
+			.iter()
+			.find(|(key, _)| key.eq_ignore_ascii_case(name))
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// Sets a header, replacing any earlier value with the same name.
+	pub fn insert(&mut self, name: &str, value: &str) {
+		let name = name.to_ascii_lowercase_smolstr();
+		match self.0.iter_mut().find(|(key, _)| *key == name) {
+			Some((_, old)) => *old = value.into(),
+			None => self.0.push((name, value.into())),
+		}
+	}
+
+	pub fn keys(&self) -> impl Iterator<Item = &str> {
+		self.0.iter().map(|(key, _)| key.as_str())
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+	}
+
+	pub fn len(&self) -> usize {
+		self.0.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+}
+
+pub fn read_headers(reader: impl BufRead) -> Result<HashMap<String, String>, Error> {
+	let headers = read_ordered_headers(reader)?;
+	Ok(headers
+		.iter()
+		.map(|(key, value)| (key.to_owned(), value.to_owned()))
+		.collect())
+}
+
+/// Reads the headers like [`read_headers`], keeping them in file order
+/// without allocating for each one.
+pub fn read_ordered_headers(mut reader: impl BufRead) -> Result<Headers, Error> {
+	let mut headers = Headers::default();
 
 	let mut line = String::new();
 	reader.read_line(&mut line)?;
 	while !line.is_empty() && line != "\r\n" {
-		let (mut key, value) = read_header(&mut reader, &mut line)?;
-		key.make_ascii_lowercase();
-		headers.insert(key, value);
+		let (key, value) = read_header(&mut reader, &mut line)?;
+		headers.insert(&key, &value);
 	}
 
 	Ok(headers)
 }
 
-fn read_header(mut reader: impl BufRead, line: &mut String) -> Result<(String, String), Error> {
+fn read_header(mut reader: impl BufRead, line: &mut String) -> Result<(SmolStr, SmolStr), Error> {
 	match field(line) {
 		Ok((rest, (key, value))) => {
-			let result = (key.to_owned(), value.into_owned());
+			let result = (key.into(), value.as_ref().into());
 			*line = rest.to_owned();
 			Ok(result)
 		}
@@ -106,7 +158,7 @@ mod tests {
 		Needed::*,
 	};
 
-	use super::{field_body, field_body_contents, field_name, read_headers};
+	use super::{field_body, field_body_contents, field_name, read_headers, read_ordered_headers};
 
 	#[test]
 	fn test_real_headers() {
@@ -177,6 +229,14 @@ Content-MD5:     kL8G8UnwN1oKBdHWVkb0CQ==\r
 		);
 	}
 
+	#[test]
+	fn section_headers() {
+		let header_text = "X-Binary-ID: 1\r\nContent-Type: application/octet-stream\r\nx-binary-id: 2\r\n\r\n";
+		let headers = read_ordered_headers(Cursor::new(header_text)).expect("to parse headers");
+		assert_eq!(headers.get("X-Binary-ID"), Some("2"), "case-insensitive lookup");
+		let keys: Vec<_> = headers.keys().collect();
+		assert_eq!(keys, ["x-binary-id", "content-type"]);
+	}
 	#[test]
 	fn test_field_name() {
 		assert_eq!(field_name("X-Binary-ID:"), Ok((":", "X-Binary-ID")));
@@ -0,0 +1,304 @@
+use std::io::BufRead;
+
+use super::cif::CifHeader;
+use crate::geometry::Geometry;
+
+/// A length in metres.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Length(pub f64);
+
+impl Length {
+	/// Parses a value followed by a unit, e.g. `0.2 m`, `172 um` or `1.0332 A`.
+	fn parse(value: &str, unit: &str) -> Option<Self> {
+		let scale = match unit {
+			"m" => 1.0,
+			"mm" => 1e-3,
+			"um" | "µm" | "micron" | "microns" => 1e-6,
+			"nm" => 1e-9,
+			"A" | "Å" | "Angstrom" | "Angstroms" => 1e-10,
+			_ => return None,
+		};
+		Some(Self(value.parse::<f64>().ok()? * scale))
+	}
+
+	pub fn metres(self) -> f64 {
+		self.0
+	}
+
+	pub fn millimetres(self) -> f64 {
+		self.0 * 1e3
+	}
+
+	pub fn angstroms(self) -> f64 {
+		self.0 * 1e10
+	}
+}
+
+/// A duration in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Time(pub f64);
+
+impl Time {
+	fn parse(value: &str, unit: &str) -> Option<Self> {
+		let scale = match unit {
+			"s" => 1.0,
+			"ms" => 1e-3,
+			"us" | "µs" => 1e-6,
+			"ns" => 1e-9,
+			_ => return None,
+		};
+		Some(Self(value.parse::<f64>().ok()? * scale))
+	}
+
+	pub fn seconds(self) -> f64 {
+		self.0
+	}
+}
+
+/// An energy in electronvolts.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Energy(pub f64);
+
+impl Energy {
+	fn parse(value: &str, unit: &str) -> Option<Self> {
+		let scale = match unit {
+			"eV" => 1.0,
+			"keV" => 1e3,
+			_ => return None,
+		};
+		Some(Self(value.parse::<f64>().ok()? * scale))
+	}
+
+	pub fn electronvolts(self) -> f64 {
+		self.0
+	}
+}
+
+/// A point in time in seconds since 1970-01-01T00:00:00, in whatever time zone
+/// the detector clock uses.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Timestamp(pub f64);
+
+impl Timestamp {
+	/// Parses `YYYY-MM-DDTHH:MM:SS` with optional fractional seconds, also
+	/// accepting a space or slashes as separators.
+	fn parse(text: &str) -> Option<Self> {
+		let (date, time) = text.split_once(['T', ' '])?;
+		let mut date = date.split(['-', '/']).map(str::parse::<i64>);
+		let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+		let mut time = time.trim().split(':');
+		let (hour, minute) = (time.next()?.parse::<u32>().ok()?, time.next()?.parse::<u32>().ok()?);
+		let second = time.next()?.parse::<f64>().ok()?;
+		if date.next().is_some()
+			|| time.next().is_some()
+			|| !(1..=12).contains(&month)
+			|| !(1..=31).contains(&day)
+			|| 23 < hour
+			|| 59 < minute
+			|| !(0.0..61.0).contains(&second)
+		{
+			return None;
+		}
+		let days = days_from_civil(year, month, day);
+		Some(Self(
+			(days * 86_400 + i64::from(hour * 3600 + minute * 60)) as f64 + second,
+		))
+	}
+
+	pub fn seconds(self) -> f64 {
+		self.0
+	}
+}
+
+/// Days from 1970-01-01 to a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = year.div_euclid(400);
+	let year_of_era = year - era * 400;
+	let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	era * 146_097 + day_of_era - 719_468
+}
+
+/// Values of a Pilatus style mini-header, the `# Key value unit` lines of
+/// `_array_data.header_contents`, converted to SI units.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MiniHeader {
+	/// Model and serial number, e.g. `PILATUS 6M, S/N 60-0001`
+	pub detector: Option<String>,
+	pub detector_distance: Option<Length>,
+	/// Pixel size (fast, slow)
+	pub pixel_size: Option<(Length, Length)>,
+	pub wavelength: Option<Length>,
+	/// Beam centre in pixels (fast, slow)
+	pub beam_xy: Option<(f64, f64)>,
+	pub exposure_time: Option<Time>,
+	/// Time from the start of one frame to the start of the next, the exposure
+	/// time plus the readout dead time
+	pub exposure_period: Option<Time>,
+	/// Start of the acquisition of the frame
+	pub timestamp: Option<Timestamp>,
+	/// Goniometer angle at the start of the exposure, in degrees
+	pub start_angle: Option<f64>,
+	/// Energy threshold of the counting pixels
+	pub threshold: Option<Energy>,
+	/// Dead time of the counting electronics, for count-rate correction
+	pub tau: Option<Time>,
+}
+
+impl MiniHeader {
+	/// Parses the `_array_data.header_contents` of the first block of
+	/// `header` that has one.
+	pub fn from_cif(header: &CifHeader) -> Option<Self> {
+		header.get("_array_data.header_contents").map(parse_miniheader)
+	}
+
+	/// Geometry described by the header, if it contains a beam centre,
+	/// detector distance and pixel size.
+	pub fn geometry(&self) -> Option<Geometry> {
+		let (fast, slow) = self.pixel_size?;
+		Some(Geometry {
+			beam_centre: self.beam_xy?,
+			distance: self.detector_distance?.metres(),
+			pixel_size: (fast.metres(), slow.metres()),
+			wavelength: self.wavelength.map(Length::metres),
+		})
+	}
+}
+
+/// Parses the mini-header lines of `text`. Lines that are not recognised, or
+/// whose units are unknown, are ignored.
+pub fn parse_miniheader(text: &str) -> MiniHeader {
+	let mut header = MiniHeader::default();
+	for line in text.lines() {
+		let Some(line) = line.trim().strip_prefix('#') else {
+			continue;
+		};
+		if let Some(timestamp) = Timestamp::parse(line.trim()) {
+			header.timestamp = Some(timestamp);
+			continue;
+		}
+		// The detector name is free text, which is kept whole.
+		match line.split_once(':') {
+			Some((key, detector)) if key.trim().eq_ignore_ascii_case("detector") => {
+				header.detector = Some(detector.trim().to_owned());
+				continue;
+			}
+			_ => {}
+		}
+		let mut words = line.split(|c: char| c.is_whitespace() || c == ',' || c == '(' || c == ')' || c == ':');
+		let Some(key) = words.find(|w| !w.is_empty()) else {
+			continue;
+		};
+		let words: Vec<_> = words.filter(|w| !w.is_empty()).collect();
+		match (key.to_ascii_lowercase().as_str(), words.as_slice()) {
+			("detector_distance", [value, unit, ..]) => header.detector_distance = Length::parse(value, unit),
+			("pixel_size", [fast, fast_unit, "x", slow, slow_unit, ..]) => {
+				header.pixel_size = Length::parse(fast, fast_unit).zip(Length::parse(slow, slow_unit));
+			}
+			("wavelength", [value, unit, ..]) => header.wavelength = Length::parse(value, unit),
+			("beam_xy", [x, y, ..]) => header.beam_xy = x.parse().ok().zip(y.parse().ok()),
+			("exposure_time", [value, unit, ..]) => header.exposure_time = Time::parse(value, unit),
+			("exposure_period", [value, unit, ..]) => header.exposure_period = Time::parse(value, unit),
+			("start_angle", [value, ..]) => header.start_angle = value.parse().ok(),
+			("threshold_setting", [value, unit, ..]) => header.threshold = Energy::parse(value, unit),
+			("tau", ["=", value, unit, ..] | [value, unit, ..]) => header.tau = Time::parse(value, unit),
+			_ => {}
+		}
+	}
+	header
+}
+
+/// Reads the text preceding the first binary section and parses its
+/// mini-header.
+pub fn read_miniheader(mut reader: impl BufRead) -> std::io::Result<MiniHeader> {
+	let mut text = String::new();
+	let mut line = Vec::new();
+	loop {
+		line.clear();
+		if reader.read_until(b'\n', &mut line)? == 0 || line.starts_with(b"--CIF-BINARY-FORMAT-SECTION--") {
+			break;
+		}
+		text.push_str(&String::from_utf8_lossy(&line));
+	}
+	Ok(parse_miniheader(&text))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::{parse_miniheader, read_miniheader, Energy, Length, MiniHeader, Time, Timestamp};
+	use crate::metadata::cif::parse_cif;
+
+	const PILATUS_HEADER: &str = "\
+# Detector: PILATUS 6M, S/N 60-0001\r
+# Pixel_size 172e-6 m x 172e-6 m\r
+# Exposure_time 97 ms\r
+# Exposure_period 0.1000000 s\r
+# Wavelength 1.0332 A\r
+# Detector_distance 250.00 mm\r
+# Beam_xy (1231.50, 1263.50) pixels\r
+# Start_angle 12.5000 deg.\r
+# Threshold_setting: 6000 eV\r
+# Tau = 383.8e-09 s\r
+";
+
+	#[test]
+	fn parses_units() {
+		let header = parse_miniheader(PILATUS_HEADER);
+		assert_eq!(header.pixel_size, Some((Length(172e-6), Length(172e-6))));
+		assert_eq!(header.exposure_time, Some(Time(0.097)));
+		assert_eq!(header.exposure_period, Some(Time(0.1)));
+		assert!((header.wavelength.unwrap().angstroms() - 1.0332).abs() < 1e-12);
+		assert_eq!(header.detector_distance, Some(Length(0.25)));
+		assert_eq!(header.beam_xy, Some((1231.5, 1263.5)));
+		assert_eq!(header.start_angle, Some(12.5));
+		assert_eq!(header.detector.as_deref(), Some("PILATUS 6M, S/N 60-0001"));
+		assert_eq!(header.threshold, Some(Energy(6000.0)));
+		assert!((header.tau.unwrap().seconds() - 383.8e-9).abs() < 1e-18);
+
+		let geometry = header.geometry().expect("complete geometry");
+		assert_eq!(geometry.distance, 0.25);
+	}
+
+	#[test]
+	fn ignores_unknown_units() {
+		let header = parse_miniheader("# Detector_distance 20 furlongs\n# Exposure_time 1 s\n");
+		assert_eq!(header.detector_distance, None);
+		assert_eq!(header.exposure_time, Some(Time(1.0)));
+		assert_eq!(header.geometry(), None);
+	}
+
+	#[test]
+	fn read_example() {
+		const EXAMPLE_DATA: &[u8] = include_bytes!("../examples/snap_V4_00013.cbf");
+		let header = read_miniheader(Cursor::new(EXAMPLE_DATA)).expect("to read header");
+		assert_eq!(header.pixel_size, Some((Length(150e-6), Length(150e-6))));
+		assert_eq!(header.wavelength, None);
+		assert_eq!(header.detector.as_deref(), Some("Varex XRD-4343CT"));
+		// 2023-03-27T13:29:25.340
+		assert!((header.timestamp.unwrap().seconds() - 1_679_923_765.34).abs() < 1e-6);
+	}
+
+	#[test]
+	fn from_cif() {
+		let cif = parse_cif(&format!(
+			"data_frame\r\n_array_data.header_contents\r\n;\r\n{PILATUS_HEADER};\r\n"
+		));
+		let header = MiniHeader::from_cif(&cif).expect("header contents");
+		assert_eq!(header, parse_miniheader(PILATUS_HEADER));
+		assert_eq!(MiniHeader::from_cif(&parse_cif("data_frame\r\n")), None);
+	}
+
+	#[test]
+	fn parses_timestamps() {
+		assert_eq!(Timestamp::parse("1970-01-01T00:00:00"), Some(Timestamp(0.0)));
+		assert_eq!(
+			Timestamp::parse("2000/03/01 00:00:01.5"),
+			Some(Timestamp(951_868_801.5))
+		);
+		assert_eq!(Timestamp::parse("Detector: PILATUS 6M"), None);
+		assert_eq!(Timestamp::parse("2000-13-01T00:00:00"), None);
+	}
+}
@@ -1,10 +1,22 @@
+pub mod cif;
+pub mod diff;
 pub mod headers;
+pub mod miniheader;
+pub mod template;
 
-use std::{collections::HashMap, io::BufRead, str::FromStr};
+use std::{
+	fmt::{self, Display},
+	io::BufRead,
+	str::FromStr,
+};
 
 use thiserror::Error as ThisError;
 
-use headers::Error as HeadersError;
+use crate::{validate::is_known_header, warning::Warning};
+
+pub use diff::{diff, diff_frames, diff_frames_with, FieldDiff, PER_FRAME_FIELDS};
+use headers::{Error as HeadersError, Headers};
+pub use template::{FrameMetadata, MetadataTemplate};
 
 #[derive(Debug, ThisError)]
 pub enum Error {
@@ -39,11 +51,27 @@ pub enum ErrorKind {
 }
 
 pub fn read_metadata(reader: impl BufRead) -> Result<Metadata, Error> {
-	let headers = headers::read_headers(reader)?;
+	let headers = headers::read_ordered_headers(reader)?;
 	parse_metadata(headers)
 }
 
-fn parse_metadata(headers: HashMap<String, String>) -> Result<Metadata, Error> {
+/// Like [`read_metadata`], noting unknown headers and a missing digest in
+/// `warnings`.
+pub(crate) fn read_metadata_warned(reader: impl BufRead, warnings: &mut Vec<Warning>) -> Result<Metadata, Error> {
+	let headers = headers::read_ordered_headers(reader)?;
+	warnings.extend(
+		(headers.keys())
+			.filter(|name| !is_known_header(name))
+			.map(|name| Warning::UnknownHeader { name: name.to_owned() }),
+	);
+	let metadata = parse_metadata(headers)?;
+	if metadata.md5_digest.is_none() {
+		warnings.push(Warning::MissingMd5);
+	}
+	Ok(metadata)
+}
+
+fn parse_metadata(headers: Headers) -> Result<Metadata, Error> {
 	macro_rules! field {
 		($field_name:literal) => {
 			headers.get($field_name).map(|s| s.parse()).transpose()
@@ -76,7 +104,7 @@ fn parse_metadata(headers: HashMap<String, String>) -> Result<Metadata, Error> {
 	})
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Metadata {
 	pub content_type: ContentType,
 	pub content_transfer_encoding: ContentTransferEncoding,
@@ -91,7 +119,7 @@ pub struct Metadata {
 	pub depth: Option<usize>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ContentType {
 	pub mime_type: String,
 	pub subtype: String,
@@ -119,7 +147,7 @@ impl FromStr for ContentType {
 	}
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Conversion {
 	Packed(Option<PackedKind>),
 	Canonical,
@@ -153,13 +181,28 @@ fn parse_params_to_conversion(params: &str) -> Result<Option<Conversion>, Error>
 	Ok(conversion)
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl Display for Conversion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Conversion::Packed(None) => write!(f, "\"x-CBF_PACKED\""),
+			Conversion::Packed(Some(PackedKind::UncorrelatedSections)) => {
+				write!(f, "\"x-CBF_PACKED\";uncorrelated_sections")
+			}
+			Conversion::Packed(Some(PackedKind::Flat)) => write!(f, "\"x-CBF_PACKED\";flat"),
+			Conversion::Canonical => write!(f, "\"x-CBF_CANONICAL\""),
+			Conversion::ByteOffset => write!(f, "\"x-CBF_BYTE_OFFSET\""),
+			Conversion::BackgroundOffsetDelta => write!(f, "\"x-CBF_BACKGROUND_OFFSET_DELTA\""),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PackedKind {
 	UncorrelatedSections,
 	Flat,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ContentTransferEncoding {
 	pub encoding: Encoding,
 	pub charset: Option<Charset>,
@@ -185,7 +228,7 @@ impl FromStr for ContentTransferEncoding {
 	}
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Encoding {
 	Base8,
 	Base10,
@@ -213,7 +256,21 @@ impl FromStr for Encoding {
 	}
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl Display for Encoding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Encoding::Base8 => "X-BASE8",
+			Encoding::Base10 => "X-BASE10",
+			Encoding::Base16 => "X-BASE16",
+			Encoding::Base32K => "X-BASE32K",
+			Encoding::Base64 => "BASE64",
+			Encoding::Binary => "BINARY",
+			Encoding::QuotedPrintable => "QUOTED-PRINTABLE",
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Charset {
 	UsAscii,
 	UTF8,
@@ -233,7 +290,7 @@ impl FromStr for Charset {
 	}
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ByteOrder {
 	LittleEndian,
 	BigEndian,
@@ -251,7 +308,16 @@ impl FromStr for ByteOrder {
 	}
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl Display for ByteOrder {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			ByteOrder::LittleEndian => "LITTLE_ENDIAN",
+			ByteOrder::BigEndian => "BIG_ENDIAN",
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ElementType {
 	Unsigned1bitInteger,
 	Unsigned8bitInteger,
@@ -265,6 +331,19 @@ pub enum ElementType {
 	Signed32bitComplex,
 }
 
+impl ElementType {
+	/// Bits taken by one element.
+	pub fn bits(self) -> usize {
+		match self {
+			ElementType::Unsigned1bitInteger => 1,
+			ElementType::Unsigned8bitInteger | ElementType::Signed8bitInteger => 8,
+			ElementType::Unsigned16bitInteger | ElementType::Signed16bitInteger => 16,
+			ElementType::Unsigned32bitInteger | ElementType::Signed32bitInteger | ElementType::Signed32bitReal => 32,
+			ElementType::Signed64bitReal | ElementType::Signed32bitComplex => 64,
+		}
+	}
+}
+
 impl FromStr for ElementType {
 	type Err = Error;
 
@@ -285,6 +364,23 @@ impl FromStr for ElementType {
 	}
 }
 
+impl Display for ElementType {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			ElementType::Unsigned1bitInteger => "unsigned 1-bit integer",
+			ElementType::Unsigned8bitInteger => "unsigned 8-bit integer",
+			ElementType::Signed8bitInteger => "signed 8-bit integer",
+			ElementType::Unsigned16bitInteger => "unsigned 16-bit integer",
+			ElementType::Signed16bitInteger => "signed 16-bit integer",
+			ElementType::Unsigned32bitInteger => "unsigned 32-bit integer",
+			ElementType::Signed32bitInteger => "signed 32-bit integer",
+			ElementType::Signed32bitReal => "signed 32-bit real IEEE",
+			ElementType::Signed64bitReal => "signed 64-bit real IEEE",
+			ElementType::Signed32bitComplex => "signed 32-bit complex IEEE",
+		})
+	}
+}
+
 fn is_whitespace_or_quote(c: char) -> bool {
 	c.is_whitespace() || c == '"'
 }
@@ -0,0 +1,202 @@
+use std::io::{Result, Write};
+
+#[cfg(feature = "md5")]
+use crate::md5::content_md5;
+
+use super::{ByteOrder, ContentTransferEncoding, ContentType, Conversion, ElementType, Encoding, Metadata};
+
+/// Header fields shared by every frame of a series. The fields that change
+/// from frame to frame, the binary ID, element count, size and MD5 digest,
+/// are filled in by [`MetadataTemplate::fill`].
+#[derive(Debug, Clone)]
+pub struct MetadataTemplate {
+	conversion: Option<Conversion>,
+	element_type: ElementType,
+	byte_order: ByteOrder,
+	width: Option<usize>,
+	height: Option<usize>,
+	depth: Option<usize>,
+	padding: Option<usize>,
+	md5: bool,
+	next_id: usize,
+}
+
+/// Headers of one binary section, as produced by a [`MetadataTemplate`].
+#[derive(Debug, Clone)]
+pub struct FrameMetadata {
+	pub binary_id: usize,
+	pub metadata: Metadata,
+}
+
+impl MetadataTemplate {
+	/// Byte offset compressed little endian sections with MD5 digests,
+	/// numbered from 1.
+	pub fn new(element_type: ElementType) -> Self {
+		Self {
+			conversion: Some(Conversion::ByteOffset),
+			element_type,
+			byte_order: ByteOrder::LittleEndian,
+			width: None,
+			height: None,
+			depth: None,
+			padding: None,
+			md5: true,
+			next_id: 1,
+		}
+	}
+
+	pub fn conversion(mut self, conversion: Option<Conversion>) -> Self {
+		self.conversion = conversion;
+		self
+	}
+
+	pub fn byte_order(mut self, byte_order: ByteOrder) -> Self {
+		self.byte_order = byte_order;
+		self
+	}
+
+	pub fn dimensions(mut self, width: usize, height: usize) -> Self {
+		self.width = Some(width);
+		self.height = Some(height);
+		self
+	}
+
+	pub fn depth(mut self, depth: usize) -> Self {
+		self.depth = Some(depth);
+		self
+	}
+
+	pub fn padding(mut self, padding: usize) -> Self {
+		self.padding = Some(padding);
+		self
+	}
+
+	/// Whether to include MD5 digests, which are only computed with the `md5`
+	/// feature.
+	pub fn md5(mut self, md5: bool) -> Self {
+		self.md5 = md5;
+		self
+	}
+
+	pub fn first_binary_id(mut self, id: usize) -> Self {
+		self.next_id = id;
+		self
+	}
+
+	/// Headers for the next frame, whose binary section holds `element_count`
+	/// elements encoded as `data`.
+	pub fn fill(&mut self, element_count: usize, data: &[u8]) -> FrameMetadata {
+		let binary_id = self.next_id;
+		self.next_id += 1;
+		#[cfg(feature = "md5")]
+		let md5_digest = self.md5.then(|| content_md5(data));
+		#[cfg(not(feature = "md5"))]
+		let md5_digest = None;
+		let metadata = Metadata {
+			content_type: ContentType {
+				mime_type: "application".to_owned(),
+				subtype: "octet-stream".to_owned(),
+				conversion: self.conversion,
+			},
+			content_transfer_encoding: ContentTransferEncoding { encoding: Encoding::Binary, charset: None },
+			size: data.len(),
+			padding: self.padding,
+			byte_order: self.byte_order,
+			md5_digest,
+			element_type: self.element_type,
+			element_count,
+			width: self.width,
+			height: self.height,
+			depth: self.depth,
+		};
+		FrameMetadata { binary_id, metadata }
+	}
+}
+
+impl FrameMetadata {
+	/// Writes the MIME headers, followed by the empty line ending them.
+	pub fn write_to(&self, mut writer: impl Write) -> Result<()> {
+		let metadata = &self.metadata;
+		let content_type = &metadata.content_type;
+		write!(
+			writer,
+			"Content-Type: {}/{}",
+			content_type.mime_type, content_type.subtype
+		)?;
+		match &content_type.conversion {
+			Some(conversion) => write!(writer, ";\r\n     conversions={conversion}\r\n")?,
+			None => write!(writer, "\r\n")?,
+		}
+		write!(
+			writer,
+			"Content-Transfer-Encoding: {}\r\n",
+			metadata.content_transfer_encoding.encoding
+		)?;
+		write!(writer, "X-Binary-Size: {}\r\n", metadata.size)?;
+		write!(writer, "X-Binary-ID: {}\r\n", self.binary_id)?;
+		write!(writer, "X-Binary-Element-Type: \"{}\"\r\n", metadata.element_type)?;
+		write!(writer, "X-Binary-Element-Byte-Order: {}\r\n", metadata.byte_order)?;
+		if let Some(md5) = &metadata.md5_digest {
+			write!(writer, "Content-MD5: {md5}\r\n")?;
+		}
+		write!(writer, "X-Binary-Number-of-Elements: {}\r\n", metadata.element_count)?;
+		let dimensions = [
+			("Fastest", metadata.width),
+			("Second", metadata.height),
+			("Third", metadata.depth),
+		];
+		for (name, value) in dimensions {
+			if let Some(value) = value {
+				write!(writer, "X-Binary-Size-{name}-Dimension: {value}\r\n")?;
+			}
+		}
+		if let Some(padding) = metadata.padding {
+			write!(writer, "X-Binary-Size-Padding: {padding}\r\n")?;
+		}
+		write!(writer, "\r\n")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::MetadataTemplate;
+	use crate::metadata::{read_metadata, Conversion, ElementType};
+
+	#[test]
+	fn fills_frames() {
+		let mut template = MetadataTemplate::new(ElementType::Signed32bitInteger)
+			.dimensions(2, 1)
+			.padding(1)
+			.first_binary_id(7);
+		let first = template.fill(2, &[0, 1]);
+		let second = template.fill(2, &[0, 2, 3]);
+		assert_eq!((first.binary_id, second.binary_id), (7, 8));
+		assert_eq!((first.metadata.size, second.metadata.size), (2, 3));
+		#[cfg(feature = "md5")]
+		assert_ne!(first.metadata.md5_digest, second.metadata.md5_digest);
+		assert!(template.md5(false).fill(2, &[]).metadata.md5_digest.is_none());
+	}
+
+	#[test]
+	fn written_headers_parse() {
+		let frame = MetadataTemplate::new(ElementType::Unsigned16bitInteger)
+			.dimensions(3, 4)
+			.fill(12, b"data");
+		let mut text = Vec::new();
+		frame.write_to(&mut text).unwrap();
+		assert!(text.ends_with(b"\r\n\r\n"));
+
+		let metadata = read_metadata(Cursor::new(text)).unwrap();
+		assert_eq!(metadata.content_type.conversion, Some(Conversion::ByteOffset));
+		assert_eq!(metadata.element_type, ElementType::Unsigned16bitInteger);
+		assert_eq!(metadata.element_count, 12);
+		assert_eq!(metadata.size, 4);
+		assert_eq!(metadata.md5_digest, frame.metadata.md5_digest);
+		assert_eq!(
+			(metadata.width, metadata.height, metadata.depth),
+			(Some(3), Some(4), None)
+		);
+	}
+}
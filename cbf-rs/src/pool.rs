@@ -0,0 +1,121 @@
+use std::{
+	io::Cursor,
+	sync::{
+		mpsc::{Receiver, SyncSender},
+		Arc, Mutex,
+	},
+	thread::{self, JoinHandle},
+};
+
+use crate::{decoder::Decoder, image::ImageEnum, Error};
+
+/// Encoded file contents tagged with an index identifying it to the caller.
+pub type Job = (usize, Vec<u8>);
+
+/// Decoded image, or the reason it could not be decoded, tagged with the
+/// index of its job.
+pub type Decoded = (usize, Result<ImageEnum, Error>);
+
+/// Spawns `n` worker threads decoding jobs from `files` into `images` until
+/// either channel is disconnected. Results are sent in completion order.
+///
+/// Use bounded channels on both ends: with frames of tens of megabytes, a
+/// small bound on `images` keeps workers from decoding further ahead than the
+/// consumer can keep up with.
+pub fn spawn_decoder_pool(
+	n: usize,
+	decoder: &Decoder,
+	files: Receiver<Job>,
+	images: SyncSender<Decoded>,
+) -> Vec<JoinHandle<()>> {
+	let files = Arc::new(Mutex::new(files));
+	(0..n)
+		.map(|_| {
+			let decoder = decoder.clone();
+			let files = files.clone();
+			let images = images.clone();
+			thread::spawn(move || loop {
+				let job = files.lock().ok().and_then(|files| files.recv().ok());
+				let Some((index, data)) = job else {
+					return;
+				};
+				if images.send((index, decoder.read_image(Cursor::new(data)))).is_err() {
+					return;
+				}
+			})
+		})
+		.collect()
+}
+
+#[cfg(feature = "crossbeam")]
+pub mod crossbeam {
+	use std::{
+		io::Cursor,
+		thread::{self, JoinHandle},
+	};
+
+	use crossbeam_channel::{Receiver, Sender};
+
+	use super::{Decoded, Job};
+	use crate::decoder::Decoder;
+
+	/// Like [`super::spawn_decoder_pool`], but sharing the work through
+	/// crossbeam channels instead of a locked std receiver.
+	pub fn spawn_decoder_pool(
+		n: usize,
+		decoder: &Decoder,
+		files: Receiver<Job>,
+		images: Sender<Decoded>,
+	) -> Vec<JoinHandle<()>> {
+		(0..n)
+			.map(|_| {
+				let decoder = decoder.clone();
+				let files = files.clone();
+				let images = images.clone();
+				thread::spawn(move || {
+					for (index, data) in files {
+						if images.send((index, decoder.read_image(Cursor::new(data)))).is_err() {
+							return;
+						}
+					}
+				})
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::mpsc::sync_channel;
+
+	use super::spawn_decoder_pool;
+	use crate::decoder::Decoder;
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn decodes_all_jobs() {
+		let (files_tx, files_rx) = sync_channel(1);
+		let (images_tx, images_rx) = sync_channel(1);
+		let workers = spawn_decoder_pool(2, &Decoder::default(), files_rx, images_tx);
+
+		let producer = std::thread::spawn(move || {
+			for index in 0..3 {
+				files_tx.send((index, EXAMPLE_DATA.to_vec())).unwrap();
+			}
+			files_tx.send((3, b"not a cbf".to_vec())).unwrap();
+		});
+
+		let mut results: Vec<_> = images_rx
+			.iter()
+			.map(|(index, result)| (index, result.is_ok()))
+			.collect();
+		results.sort();
+		assert_eq!(results, [(0, true), (1, true), (2, true), (3, false)]);
+
+		producer.join().unwrap();
+		for worker in workers {
+			worker.join().unwrap();
+		}
+	}
+}
@@ -0,0 +1,27 @@
+//! The recommended surface of the crate, kept stable across minor versions.
+//!
+//! ```
+//! use cbf_rs::prelude::*;
+//! ```
+
+pub use crate::{
+	analysis::{
+		analyze_stream, annulus_stats, radial_difraction_analysis, radial_profile, radial_profile_f32,
+		sampler_methods::nearest_neighbour, strip_profile, AnalysisConfig, AngularSampling, AnnulusStats, BinSpacing,
+		Preset, Roi,
+	},
+	decoder::Decoder,
+	encoder::Encoder,
+	geometry::Geometry,
+	image::{
+		colormap::{BuiltinColormap, Colormap},
+		mask::Mask,
+		pixel::Pixels,
+		Image, ImageEnum,
+	},
+	images,
+	metadata::Metadata,
+	open, read_all_headers, read_all_images, read_all_images_from_slice, read_all_images_with, read_first_image,
+	read_image, read_image_from_path, read_image_from_slice, read_image_with, write_all_images, write_image, Error,
+	Limits, ReadOptions, WriteOptions,
+};
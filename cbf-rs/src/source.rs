@@ -0,0 +1,205 @@
+use std::{
+	fs::File,
+	io::{BufRead, BufReader, Read},
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	decoder::{Decoder, Frame},
+	image::ImageEnum,
+	Error,
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const HDF5_MAGIC: &[u8] = b"\x89HDF\r\n\x1a\n";
+const SMV_MAGIC: &[u8] = b"{\nHEADER_BYTES=";
+
+/// Where [`open`] reads from.
+pub enum Source {
+	Path(PathBuf),
+	Reader(Box<dyn Read + Send>),
+}
+
+impl Source {
+	pub fn reader(reader: impl Read + Send + 'static) -> Self {
+		Source::Reader(Box::new(reader))
+	}
+}
+
+impl From<&Path> for Source {
+	fn from(path: &Path) -> Self {
+		Source::Path(path.to_owned())
+	}
+}
+
+impl From<PathBuf> for Source {
+	fn from(path: PathBuf) -> Self {
+		Source::Path(path)
+	}
+}
+
+impl From<&str> for Source {
+	fn from(path: &str) -> Self {
+		Source::Path(path.into())
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	Cbf,
+	GzipCbf,
+}
+
+/// Frames of an opened file, in file order.
+pub struct FrameSource {
+	format: Format,
+	reader: Box<dyn BufRead + Send>,
+	decoder: Decoder,
+	finished: bool,
+}
+
+impl FrameSource {
+	pub fn format(&self) -> Format {
+		self.format
+	}
+
+	pub fn read_next(&mut self) -> Result<Option<ImageEnum>, Error> {
+		self.read_next_with(|decoder, reader| decoder.read_image(reader))
+	}
+
+	/// Like [`FrameSource::read_next`], also returning the CIF text and the
+	/// headers of the binary section.
+	pub fn read_next_frame(&mut self) -> Result<Option<Frame>, Error> {
+		self.read_next_with(|decoder, reader| decoder.read_frame(reader))
+	}
+
+	fn read_next_with<T>(
+		&mut self,
+		read: impl FnOnce(&Decoder, &mut Box<dyn BufRead + Send>) -> Result<T, Error>,
+	) -> Result<Option<T>, Error> {
+		if self.finished {
+			return Ok(None);
+		}
+		match read(&self.decoder, &mut self.reader) {
+			Ok(value) => Ok(Some(value)),
+			Err(Error::NoImage) => {
+				self.finished = true;
+				Ok(None)
+			}
+			Err(error) => {
+				self.finished = true;
+				Err(error)
+			}
+		}
+	}
+}
+
+impl Iterator for FrameSource {
+	type Item = Result<ImageEnum, Error>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		self.read_next().transpose()
+	}
+}
+
+/// Opens a file or reader, detecting whether it holds a plain or gzipped CBF.
+pub fn open(source: impl Into<Source>) -> Result<FrameSource, Error> {
+	open_with(source, Decoder::default())
+}
+
+pub fn open_with(source: impl Into<Source>, decoder: Decoder) -> Result<FrameSource, Error> {
+	let reader = match source.into() {
+		Source::Path(path) => Box::new(File::open(path)?) as Box<dyn Read + Send>,
+		Source::Reader(reader) => reader,
+	};
+	let (format, reader) = sniff(BufReader::new(reader))?;
+	let reader = match format {
+		Format::Cbf => Box::new(reader) as Box<dyn BufRead + Send>,
+		Format::GzipCbf => gunzip(reader)?,
+	};
+	Ok(FrameSource { format, reader, decoder, finished: false })
+}
+
+fn sniff<R: BufRead>(mut reader: R) -> Result<(Format, R), Error> {
+	let start = reader.fill_buf()?;
+	if start.starts_with(GZIP_MAGIC) {
+		Ok((Format::GzipCbf, reader))
+	} else if start.starts_with(HDF5_MAGIC) || start.starts_with(SMV_MAGIC) {
+		Err(Error::UnsupportedFormat)
+	} else {
+		Ok((Format::Cbf, reader))
+	}
+}
+
+#[cfg(feature = "gzip")]
+fn gunzip(reader: impl BufRead + Send + 'static) -> Result<Box<dyn BufRead + Send>, Error> {
+	Ok(Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_reader: impl BufRead + Send + 'static) -> Result<Box<dyn BufRead + Send>, Error> {
+	Err(Error::UnsupportedFormat)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::{open, Format, Source};
+	use crate::Error;
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn open_path() {
+		let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/examples/snap_V4_00013.cbf");
+		let mut source = open(path).expect("to open file");
+		assert_eq!(source.format(), Format::Cbf);
+		let image = source.next().expect("a frame").expect("to read frame");
+		assert_eq!(image.width(), 2880);
+		assert!(source.next().is_none());
+	}
+
+	#[test]
+	fn read_frames() {
+		let mut source = open(Source::reader(Cursor::new(EXAMPLE_DATA))).expect("to open reader");
+		let frame = source.read_next_frame().expect("to read frame").expect("a frame");
+		assert_eq!(frame.metadata.width, Some(2880));
+		assert_eq!(frame.image.width(), 2880);
+		assert!(frame.header.get("_array_data.header_convention") == Some("PILATUS_1.2"));
+		assert!(source.read_next_frame().expect("to reach the end").is_none());
+	}
+
+	#[test]
+	fn rejects_hdf5() {
+		let source = open(Source::reader(Cursor::new(b"\x89HDF\r\n\x1a\n\0\0\0\0")));
+		assert!(matches!(source, Err(Error::UnsupportedFormat)));
+	}
+
+	#[cfg(feature = "gzip")]
+	#[test]
+	fn open_gzip() {
+		use std::io::Write;
+
+		let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+		encoder.write_all(EXAMPLE_DATA).unwrap();
+		let compressed = encoder.finish().unwrap();
+
+		let source = open(Source::reader(Cursor::new(compressed))).expect("to open gzipped data");
+		assert_eq!(source.format(), Format::GzipCbf);
+		assert_eq!(source.count(), 1);
+	}
+
+	#[cfg(not(feature = "gzip"))]
+	#[test]
+	fn gzip_needs_feature() {
+		let source = open(Source::reader(Cursor::new([0x1F, 0x8B, 0x08, 0x00])));
+		assert!(matches!(source, Err(Error::UnsupportedFormat)));
+	}
+
+	#[test]
+	fn open_reader() {
+		let source = open(Source::reader(Cursor::new(EXAMPLE_DATA))).expect("to open reader");
+		assert_eq!(source.filter(Result::is_ok).count(), 1);
+	}
+}
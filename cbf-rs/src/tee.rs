@@ -0,0 +1,85 @@
+use std::io::{BufRead, Error, Read, Result, Write};
+
+/// Reader adapter writing every byte consumed from `reader` to `writer`, so a
+/// stream can be decoded and archived untouched in a single pass.
+///
+/// Bytes are written as they are consumed, so after decoding only the bytes
+/// up to the end of the last image read have been written. Errors from the
+/// writer are returned by the next read.
+pub struct TeeReader<R, W> {
+	reader: R,
+	writer: W,
+	error: Option<Error>,
+}
+
+impl<R: BufRead, W: Write> TeeReader<R, W> {
+	pub fn new(reader: R, writer: W) -> Self {
+		Self { reader, writer, error: None }
+	}
+
+	pub fn writer(&self) -> &W {
+		&self.writer
+	}
+
+	/// Copies the rest of the stream to the writer and returns both halves.
+	pub fn finish(mut self) -> Result<(R, W)> {
+		std::io::copy(&mut self, &mut std::io::sink())?;
+		self.writer.flush()?;
+		Ok((self.reader, self.writer))
+	}
+}
+
+impl<R: BufRead, W: Write> Read for TeeReader<R, W> {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		let available = self.fill_buf()?;
+		let n = available.len().min(buf.len());
+		buf[..n].copy_from_slice(&available[..n]);
+		self.consume(n);
+		match self.error.take() {
+			Some(error) => Err(error),
+			None => Ok(n),
+		}
+	}
+}
+
+impl<R: BufRead, W: Write> BufRead for TeeReader<R, W> {
+	fn fill_buf(&mut self) -> Result<&[u8]> {
+		if let Some(error) = self.error.take() {
+			return Err(error);
+		}
+		self.reader.fill_buf()
+	}
+
+	fn consume(&mut self, amt: usize) {
+		// The buffer is already filled, so this does not read again.
+		let written = self
+			.reader
+			.fill_buf()
+			.and_then(|buf| self.writer.write_all(&buf[..amt]));
+		if let Err(error) = written {
+			self.error.get_or_insert(error);
+		}
+		self.reader.consume(amt);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::TeeReader;
+	use crate::read_image;
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn archives_while_decoding() {
+		let mut tee = TeeReader::new(Cursor::new(EXAMPLE_DATA), Vec::new());
+		read_image(&mut tee).expect("to read image");
+		assert!(EXAMPLE_DATA.starts_with(tee.writer()));
+		assert!(10_000_000 < tee.writer().len());
+
+		let (_, archive) = tee.finish().expect("to copy the rest");
+		assert_eq!(archive, EXAMPLE_DATA);
+	}
+}
@@ -0,0 +1,89 @@
+use std::{
+	io::Read,
+	time::{Duration, Instant},
+};
+
+use crate::{decoder::Decoder, Error};
+
+/// How [`measure`] decodes the dataset.
+#[derive(Clone)]
+pub struct MeasureOptions {
+	/// Number of passes over the dataset
+	pub iterations: usize,
+	pub decoder: Decoder,
+}
+
+impl Default for MeasureOptions {
+	fn default() -> Self {
+		Self { iterations: 3, decoder: Decoder::default() }
+	}
+}
+
+/// Decoding throughput over all passes of a measurement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throughput {
+	pub frames: usize,
+	/// Encoded bytes decoded
+	pub bytes: u64,
+	/// Time spent decoding, excluding reading the input
+	pub elapsed: Duration,
+}
+
+impl Throughput {
+	pub fn frames_per_second(&self) -> f64 {
+		self.frames as f64 / self.elapsed.as_secs_f64()
+	}
+
+	pub fn megabytes_per_second(&self) -> f64 {
+		self.bytes as f64 / 1e6 / self.elapsed.as_secs_f64()
+	}
+}
+
+/// Decodes the dataset produced by `reader_factory` once per iteration and
+/// reports the decoding throughput. Each pass reads the whole input into a
+/// buffer reused across passes before timing starts, so only decoding is
+/// measured. Frames are decoded with [`Decoder::read_image_into`] into one
+/// pixel buffer, so float frames are not supported.
+pub fn measure<R: Read>(
+	mut reader_factory: impl FnMut() -> std::io::Result<R>,
+	options: &MeasureOptions,
+) -> Result<Throughput, Error> {
+	let mut buffer = Vec::new();
+	let mut pixels = Vec::new();
+	let mut throughput = Throughput { frames: 0, bytes: 0, elapsed: Duration::ZERO };
+	for _ in 0..options.iterations {
+		buffer.clear();
+		reader_factory()?.read_to_end(&mut buffer)?;
+
+		let start = Instant::now();
+		let mut reader = buffer.as_slice();
+		loop {
+			match options.decoder.read_image_into(&mut reader, &mut pixels) {
+				Ok(_) => throughput.frames += 1,
+				Err(Error::NoImage) => break,
+				Err(error) => return Err(error),
+			}
+		}
+		throughput.elapsed += start.elapsed();
+		throughput.bytes += buffer.len() as u64;
+	}
+	Ok(throughput)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+
+	use super::{measure, MeasureOptions};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn measure_example() {
+		let options = MeasureOptions { iterations: 2, ..MeasureOptions::default() };
+		let throughput = measure(|| Ok(Cursor::new(EXAMPLE_DATA)), &options).expect("to decode example");
+		assert_eq!(throughput.frames, 2);
+		assert_eq!(throughput.bytes, 2 * EXAMPLE_DATA.len() as u64);
+		assert!(throughput.frames_per_second() > 0.0);
+	}
+}
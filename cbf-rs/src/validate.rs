@@ -0,0 +1,214 @@
+use crate::debug::{find, next_line, BINARY_MARKER, BINARY_SECTION_END, BINARY_SECTION_START};
+
+/// Longest line allowed in the CIF text, per CIF 1.1.
+const MAX_CIF_LINE_LENGTH: usize = 2048;
+/// Longest MIME header line allowed, per RFC 2822.
+const MAX_HEADER_LINE_LENGTH: usize = 998;
+/// MIME headers defined by imgCIF, and whether each one is required. The
+/// specification leaves their order free.
+const KNOWN_HEADERS: [(&str, bool); 12] = [
+	("content-type", true),
+	("content-transfer-encoding", true),
+	("x-binary-size", true),
+	("x-binary-id", true),
+	("x-binary-element-type", true),
+	("x-binary-element-byte-order", true),
+	("content-md5", false),
+	("x-binary-number-of-elements", true),
+	("x-binary-size-fastest-dimension", false),
+	("x-binary-size-second-dimension", false),
+	("x-binary-size-third-dimension", false),
+	("x-binary-size-padding", false),
+];
+
+/// A deviation from the strict CBF/imgCIF layout, located by byte offset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+	/// The file does not start with `###CBF: VERSION`
+	MissingMagic,
+	LineTooLong {
+		offset: usize,
+		length: usize,
+	},
+	/// A line of a binary section's headers not terminated by CRLF
+	MissingCrlf {
+		offset: usize,
+	},
+	/// A binary section marker with trailing characters or a bare LF
+	MalformedMarker {
+		offset: usize,
+	},
+	MissingHeader {
+		offset: usize,
+		name: &'static str,
+	},
+	UnknownHeader {
+		offset: usize,
+		name: String,
+	},
+	InvalidBinarySize {
+		offset: usize,
+	},
+	MissingBinaryMarker {
+		offset: usize,
+	},
+	MissingSectionEnd {
+		offset: usize,
+	},
+}
+
+/// Checks `data` against the strict CBF/imgCIF layout and returns every
+/// violation found. An empty result means the file may be submitted to
+/// archives with strict ingest checks.
+pub fn validate_strict(data: &[u8]) -> Vec<Violation> {
+	let mut violations = Vec::new();
+	if !data.starts_with(b"###CBF: VERSION") {
+		violations.push(Violation::MissingMagic);
+	}
+
+	let mut offset = 0;
+	while offset < data.len() {
+		let line = next_line(data, offset);
+		let content = trim_line_end(line);
+		if MAX_CIF_LINE_LENGTH < content.len() {
+			violations.push(Violation::LineTooLong { offset, length: content.len() });
+		}
+		if content.starts_with(b"--CIF-BINARY-FORMAT-SECTION--") && !content.ends_with(b"----") {
+			if line != BINARY_SECTION_START {
+				violations.push(Violation::MalformedMarker { offset });
+			}
+			offset = validate_section(data, offset + line.len(), &mut violations);
+			continue;
+		}
+		offset += line.len();
+	}
+	violations
+}
+
+fn validate_section(data: &[u8], start: usize, violations: &mut Vec<Violation>) -> usize {
+	let mut offset = start;
+	let mut names = Vec::new();
+	let mut size = None;
+	loop {
+		let line = next_line(data, offset);
+		if !line.ends_with(b"\r\n") {
+			violations.push(Violation::MissingCrlf { offset });
+		}
+		let content = trim_line_end(line);
+		if line.is_empty() || content.is_empty() {
+			offset += line.len();
+			break;
+		}
+		if MAX_HEADER_LINE_LENGTH < content.len() {
+			violations.push(Violation::LineTooLong { offset, length: content.len() });
+		}
+		// Lines starting with whitespace continue the previous header.
+		if !content[0].is_ascii_whitespace() {
+			let text = String::from_utf8_lossy(content);
+			let (name, value) = text.split_once(':').unwrap_or((&text, ""));
+			let name = name.trim().to_ascii_lowercase();
+			if name == "x-binary-size" {
+				size = value.trim().parse::<usize>().ok();
+				if size.is_none() {
+					violations.push(Violation::InvalidBinarySize { offset });
+				}
+			}
+			names.push((offset, name));
+		}
+		offset += line.len();
+	}
+	check_headers(start, &names, violations);
+
+	if data.get(offset..offset + BINARY_MARKER.len()) != Some(&BINARY_MARKER) {
+		violations.push(Violation::MissingBinaryMarker { offset });
+		return offset;
+	}
+	let Some(size) = size else {
+		return offset;
+	};
+	let end = (offset + BINARY_MARKER.len()).saturating_add(size).min(data.len());
+	match find(&data[end..], &BINARY_SECTION_END[..BINARY_SECTION_END.len() - 2]) {
+		Some(position) => {
+			let marker = end + position;
+			let line = next_line(data, marker);
+			if line != BINARY_SECTION_END {
+				violations.push(Violation::MalformedMarker { offset: marker });
+			}
+			marker + line.len()
+		}
+		None => {
+			violations.push(Violation::MissingSectionEnd { offset: end });
+			data.len()
+		}
+	}
+}
+
+/// Whether `name`, in lower case, is a MIME header defined by imgCIF.
+pub(crate) fn is_known_header(name: &str) -> bool {
+	KNOWN_HEADERS.iter().any(|(known, _)| *known == name)
+}
+
+fn check_headers(start: usize, names: &[(usize, String)], violations: &mut Vec<Violation>) {
+	for (offset, name) in names {
+		if !is_known_header(name) {
+			violations.push(Violation::UnknownHeader { offset: *offset, name: name.clone() });
+		}
+	}
+	for (name, required) in KNOWN_HEADERS {
+		if required && !names.iter().any(|(_, n)| n == name) {
+			violations.push(Violation::MissingHeader { offset: start, name });
+		}
+	}
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+	let line = line.strip_suffix(b"\n").unwrap_or(line);
+	line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{validate_strict, Violation};
+
+	const EXAMPLE_DATA: &[u8] = include_bytes!("./examples/snap_V4_00013.cbf");
+
+	#[test]
+	fn example() {
+		assert_eq!(validate_strict(EXAMPLE_DATA), []);
+	}
+
+	#[test]
+	fn compliant_section() {
+		let data = b"###CBF: VERSION 1.5\r\n\r\ndata_a\r\n\r\n_array_data.data\r\n;\r\n\
+--CIF-BINARY-FORMAT-SECTION--\r\n\
+Content-Type: application/octet-stream;\r\n     conversions=\"x-CBF_BYTE_OFFSET\"\r\n\
+Content-Transfer-Encoding: BINARY\r\n\
+X-Binary-Size: 2\r\n\
+X-Binary-ID: 1\r\n\
+X-Binary-Element-Type: \"signed 32-bit integer\"\r\n\
+X-Binary-Element-Byte-Order: LITTLE_ENDIAN\r\n\
+X-Binary-Number-of-Elements: 2\r\n\
+X-Binary-Size-Fastest-Dimension: 2\r\n\
+X-Binary-Size-Second-Dimension: 1\r\n\
+\r\n\x0C\x1A\x04\xD5\x01\x01\r\n--CIF-BINARY-FORMAT-SECTION----\r\n;\r\n";
+		assert_eq!(validate_strict(data), []);
+
+		let broken = replace(
+			&replace(data, b"X-Binary-ID: 1\r\n", b""),
+			b"SECTION----\r\n",
+			b"SECTION----\n",
+		);
+		assert_eq!(
+			validate_strict(&broken),
+			[
+				Violation::MissingHeader { offset: 85, name: "x-binary-id" },
+				Violation::MalformedMarker { offset: 422 }
+			]
+		);
+	}
+
+	fn replace(data: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+		let position = data.windows(from.len()).position(|w| w == from).unwrap();
+		[&data[..position], to, &data[position + from.len()..]].concat()
+	}
+}
@@ -0,0 +1,66 @@
+use std::fmt;
+
+use crate::image::mask::Mask;
+
+/// A non-fatal issue noticed while reading or analysing, reported alongside
+/// the result so that applications can show it to their users.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+	/// A MIME header of a binary section not defined by imgCIF, in lower case
+	UnknownHeader { name: String },
+	/// A deviation from the format accepted by a lenient option
+	ToleratedDeviation(Deviation),
+	/// More of the image is masked than the caller's limit, a sign of a mask
+	/// meant for another detector or geometry
+	HighMaskedFraction { fraction: f64 },
+	/// A binary section without a `Content-MD5` header, which cannot be
+	/// checked for corruption
+	MissingMd5,
+}
+
+/// A warning along with the index of the image it concerns.
+pub type FrameWarning = (usize, Warning);
+
+/// A format deviation a [`ReadOptions`](crate::ReadOptions) can be set to
+/// accept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Deviation {
+	/// `X-Binary-Size-Padding` bytes that are not NUL
+	NonNulPadding,
+}
+
+impl Warning {
+	/// Warns if more than `limit`, a fraction, of the pixels are masked.
+	pub fn for_mask(mask: &Mask, limit: f64) -> Option<Self> {
+		let fraction = mask.masked_fraction();
+		(limit < fraction).then_some(Warning::HighMaskedFraction { fraction })
+	}
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Warning::UnknownHeader { name } => write!(f, "unknown header {name}"),
+			Warning::ToleratedDeviation(Deviation::NonNulPadding) => write!(f, "padding is not NUL"),
+			Warning::HighMaskedFraction { fraction } => write!(f, "{:.0}% of the pixels are masked", fraction * 100.0),
+			Warning::MissingMd5 => write!(f, "no MD5 digest"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::Warning;
+	use crate::image::mask::{Mask, MaskEdit};
+
+	#[test]
+	fn masked_fraction() {
+		let mut mask = Mask::empty(4, 2).unwrap();
+		MaskEdit::Rectangle { x: 0, y: 0, width: 3, height: 2, masked: true }.apply(&mut mask);
+		let warning = Warning::for_mask(&mask, 0.5).unwrap();
+		assert_eq!(warning, Warning::HighMaskedFraction { fraction: 0.75 });
+		assert_eq!(warning.to_string(), "75% of the pixels are masked");
+		assert!(Warning::for_mask(&mask, 0.8).is_none());
+		assert!(Warning::for_mask(&Mask::empty(0, 0).unwrap(), 0.0).is_none());
+	}
+}
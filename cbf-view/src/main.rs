@@ -0,0 +1,450 @@
+//! Native viewer for CBF files: `cbf-view [FILE]`, or drop a file on the
+//! window. Dropping a `.lut` or `.txt` file with 256 RGBA entries uses it as
+//! the colour map. A `.cbfa` container of analysis results is reopened with the
+//! frames named as its input, and its profiles can be drawn in place of the
+//! profile of the current frame.
+
+mod render;
+
+use std::{
+	env, fs,
+	io::BufReader,
+	path::{Path, PathBuf},
+};
+
+use cbf_rs::{
+	analysis::Profile,
+	container::Container,
+	geometry::DisplayTransform,
+	image::colormap::{BuiltinColormap, Colormap},
+	metadata::{miniheader::MiniHeader, Metadata},
+	prelude::{open, Geometry, ImageEnum},
+};
+use eframe::egui::{
+	self, pos2, vec2, CentralPanel, Color32, ColorImage, ComboBox, Grid, Panel, Rect, ScrollArea, Sense, Slider,
+	Stroke, TextureHandle, TextureOptions, Ui,
+};
+
+use render::Scaling;
+
+const RING_COLOUR: Color32 = Color32::from_rgb(80, 200, 255);
+
+struct File {
+	path: PathBuf,
+	frames: Vec<ImageEnum>,
+	headers: Vec<Metadata>,
+	geometry: Option<Geometry>,
+}
+
+impl File {
+	fn load(path: &Path) -> Result<Self, String> {
+		let mut source = open(path).map_err(|e| e.to_string())?;
+		let (mut frames, mut headers, mut geometry) = (Vec::new(), Vec::new(), None);
+		while let Some(frame) = source.read_next_frame().map_err(|e| e.to_string())? {
+			geometry = geometry.or_else(|| MiniHeader::from_cif(&frame.header).and_then(|h| h.geometry()));
+			frames.push(frame.image);
+			headers.push(frame.metadata);
+		}
+		if frames.is_empty() {
+			return Err("no frames".to_owned());
+		}
+		Ok(Self { path: path.to_owned(), frames, headers, geometry })
+	}
+}
+
+/// Analysis results reopened from a `.cbfa` container.
+struct Results {
+	path: PathBuf,
+	container: Container,
+	/// Name of the profile drawn instead of the one of the current frame
+	profile: Option<String>,
+}
+
+impl Results {
+	fn load(path: &Path) -> Result<Self, String> {
+		let file = fs::File::open(path).map_err(|e| e.to_string())?;
+		let container = Container::read_from(BufReader::new(file)).map_err(|e| e.to_string())?;
+		let profile = container.profiles.keys().next().cloned();
+		Ok(Self { path: path.to_owned(), container, profile })
+	}
+
+	fn profile(&self) -> Option<&Profile<f64>> {
+		self.container.profiles.get(self.profile.as_ref()?)
+	}
+
+	/// The file the results were computed from, if the provenance names one,
+	/// relative to the container.
+	fn input(&self) -> Option<PathBuf> {
+		let input = self.container.provenance.get("input")?;
+		Some(self.path.parent().unwrap_or(Path::new("")).join(input))
+	}
+}
+
+/// What is shown of the current frame, recomputed when the frame changes.
+struct FrameView {
+	index: usize,
+	texture: TextureHandle,
+	profile: Vec<f64>,
+}
+
+struct Viewer {
+	file: Option<File>,
+	results: Option<Results>,
+	error: Option<String>,
+	frame: usize,
+	view: Option<FrameView>,
+	transform: DisplayTransform,
+	fit: bool,
+	resolutions: String,
+	colormap: Colormap,
+	colormap_name: String,
+	scaling: Scaling,
+}
+
+impl Viewer {
+	fn new(path: Option<PathBuf>) -> Self {
+		let mut viewer = Self {
+			file: None,
+			results: None,
+			error: None,
+			frame: 0,
+			view: None,
+			transform: DisplayTransform::default(),
+			fit: true,
+			resolutions: "2 3 4".to_owned(),
+			colormap: Colormap::default(),
+			colormap_name: BuiltinColormap::default().name().to_owned(),
+			scaling: Scaling::default(),
+		};
+		if let Some(path) = path {
+			viewer.open(&path);
+		}
+		viewer
+	}
+
+	fn open(&mut self, path: &Path) {
+		if path.extension().is_some_and(|e| e == "cbfa") {
+			self.open_results(path);
+		} else {
+			self.open_frames(path);
+		}
+	}
+
+	fn open_frames(&mut self, path: &Path) {
+		match File::load(path) {
+			Ok(file) => {
+				self.file = Some(file);
+				self.results = None;
+				self.error = None;
+				self.frame = 0;
+				self.view = None;
+				self.fit = true;
+			}
+			Err(error) => self.error = Some(format!("{}: {error}", path.display())),
+		}
+	}
+
+	fn open_results(&mut self, path: &Path) {
+		match Results::load(path) {
+			Ok(results) => {
+				self.error = None;
+				if let Some(input) = results.input().filter(|input| input.is_file()) {
+					self.open_frames(&input);
+				}
+				self.results = Some(results);
+				self.view = None;
+			}
+			Err(error) => self.error = Some(format!("{}: {error}", path.display())),
+		}
+	}
+
+	/// Geometry saved with the results, or read from the frames.
+	fn geometry(&self) -> Option<Geometry> {
+		(self.results.as_ref().and_then(|r| r.container.geometry.clone()))
+			.or_else(|| self.file.as_ref()?.geometry.clone())
+	}
+
+	fn load_colormap(&mut self, path: &Path) {
+		let colormap = fs::read(path).map_err(|e| e.to_string()).and_then(|data| {
+			Colormap::from_rgba(&data)
+				.or_else(|| std::str::from_utf8(&data).ok().and_then(Colormap::parse))
+				.ok_or_else(|| "expected 256 RGBA entries".to_owned())
+		});
+		match colormap {
+			Ok(colormap) => {
+				self.colormap = colormap;
+				self.colormap_name = path
+					.file_name()
+					.map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+				self.view = None;
+				self.error = None;
+			}
+			Err(error) => self.error = Some(format!("{}: {error}", path.display())),
+		}
+	}
+
+	fn colormap_picker(&mut self, ui: &mut Ui) {
+		let mut selected = None;
+		ComboBox::from_label("Colour map")
+			.selected_text(&self.colormap_name)
+			.show_ui(ui, |ui| {
+				for map in BuiltinColormap::ALL {
+					if ui
+						.selectable_label(self.colormap_name == map.name(), map.name())
+						.clicked()
+					{
+						selected = Some(map);
+					}
+				}
+			});
+		if let Some(map) = selected {
+			self.colormap = map.into();
+			self.colormap_name = map.name().to_owned();
+			self.view = None;
+		}
+		let scaling = self.scaling;
+		ComboBox::from_label("Scaling")
+			.selected_text(scaling.name())
+			.show_ui(ui, |ui| {
+				for option in Scaling::ALL {
+					ui.selectable_value(&mut self.scaling, option, option.name());
+				}
+			});
+		if self.scaling != scaling {
+			self.view = None;
+		}
+	}
+
+	fn current_view(&mut self, ui: &Ui) -> Option<&FrameView> {
+		let centre = self.geometry().map(|g| g.beam_centre);
+		let file = self.file.as_ref()?;
+		let image = file.frames.get(self.frame)?;
+		if self.view.as_ref().is_none_or(|v| v.index != self.frame) {
+			let size = [image.width(), image.height()];
+			let pixels =
+				ColorImage::from_rgba_unmultiplied(size, &render::to_rgba(image, &self.colormap, self.scaling));
+			self.view = Some(FrameView {
+				index: self.frame,
+				texture: ui.ctx().load_texture("frame", pixels, TextureOptions::NEAREST),
+				profile: render::profile(image, centre),
+			});
+		}
+		self.view.as_ref()
+	}
+
+	fn results_panel(&mut self, ui: &mut Ui) {
+		let Some(results) = &mut self.results else {
+			return;
+		};
+		ui.heading(
+			results
+				.path
+				.file_name()
+				.map_or_else(String::new, |n| n.to_string_lossy().into_owned()),
+		);
+		if !results.container.profiles.is_empty() {
+			ComboBox::from_label("Profile")
+				.selected_text(results.profile.as_deref().unwrap_or("current frame"))
+				.show_ui(ui, |ui| {
+					ui.selectable_value(&mut results.profile, None, "current frame");
+					for name in results.container.profiles.keys() {
+						ui.selectable_value(&mut results.profile, Some(name.clone()), name.as_str());
+					}
+				});
+		}
+		Grid::new("results").striped(true).show(ui, |ui| {
+			for (key, value) in &results.container.provenance {
+				ui.label(key.as_str());
+				ui.label(value.as_str());
+				ui.end_row();
+			}
+			for (name, mask) in &results.container.masks {
+				ui.label(format!("mask {name}"));
+				ui.label(format!("{:.1} % masked", 100.0 * mask.masked_fraction()));
+				ui.end_row();
+			}
+		});
+		ui.separator();
+	}
+
+	fn side_panel(&mut self, ui: &mut Ui) {
+		self.colormap_picker(ui);
+		self.results_panel(ui);
+		let geometry = self.geometry();
+		let Some(file) = &self.file else {
+			ui.label("Drop a CBF or .cbfa file on the window.");
+			return;
+		};
+		ui.heading(
+			file.path
+				.file_name()
+				.map_or_else(String::new, |n| n.to_string_lossy().into_owned()),
+		);
+		if 1 < file.frames.len() {
+			ui.add(Slider::new(&mut self.frame, 0..=file.frames.len() - 1).text("frame"));
+		}
+		ui.horizontal(|ui| {
+			ui.label("Rings (Å)");
+			ui.text_edit_singleline(&mut self.resolutions);
+		});
+		if geometry.is_none_or(|g| g.wavelength.is_none()) {
+			ui.label("No geometry in the file, rings are not drawn.");
+		}
+		ui.separator();
+		ScrollArea::vertical().show(ui, |ui| {
+			if let Some(metadata) = file.headers.get(self.frame) {
+				Grid::new("metadata").striped(true).show(ui, |ui| {
+					for (name, value) in metadata_rows(metadata) {
+						ui.label(name);
+						ui.label(value);
+						ui.end_row();
+					}
+				});
+			}
+		});
+	}
+
+	fn image_panel(&mut self, ui: &mut Ui) {
+		let geometry = self.geometry();
+		let resolutions = render::parse_resolutions(&self.resolutions);
+		let fit = std::mem::take(&mut self.fit);
+		let mut transform = self.transform;
+		let Some(view) = self.current_view(ui) else {
+			return;
+		};
+
+		let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::drag());
+		let rect = response.rect;
+		let [width, height] = view.texture.size().map(|s| s as f64);
+		if fit {
+			let zoom = (rect.width() as f64 / width).min(rect.height() as f64 / height);
+			transform = DisplayTransform { zoom, pan: (0.0, 0.0) };
+		}
+		if response.dragged() {
+			let delta = response.drag_delta();
+			transform.pan.0 -= delta.x as f64 / transform.zoom;
+			transform.pan.1 -= delta.y as f64 / transform.zoom;
+		}
+		if let Some(cursor) = response.hover_pos() {
+			let scroll = ui.input(|i| i.smooth_scroll_delta.y) as f64;
+			if scroll != 0.0 {
+				let cursor = ((cursor.x - rect.min.x) as f64, (cursor.y - rect.min.y) as f64);
+				let anchor = transform.display_to_detector(cursor);
+				transform.zoom *= (scroll * 0.002).exp();
+				transform.pan = (
+					anchor.0 - cursor.0 / transform.zoom,
+					anchor.1 - cursor.1 / transform.zoom,
+				);
+			}
+		}
+
+		let to_screen = |position: (f64, f64)| {
+			let (x, y) = transform.detector_to_display(position);
+			rect.min + vec2(x as f32, y as f32)
+		};
+		let image_rect = Rect::from_min_max(to_screen((0.0, 0.0)), to_screen((width, height)));
+		let uv = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+		painter.image(view.texture.id(), image_rect, uv, Color32::WHITE);
+
+		// Detector positions are measured from the centre of the first pixel.
+		for ring in geometry.map(|g| g.resolution_rings(&resolutions)).unwrap_or_default() {
+			let centre = to_screen((ring.centre.0 + 0.5, ring.centre.1 + 0.5));
+			let radius = (ring.radius * transform.zoom) as f32;
+			painter.circle_stroke(centre, radius, Stroke::new(1.0, RING_COLOUR));
+			painter.text(
+				centre + vec2(4.0, -radius - 4.0),
+				egui::Align2::LEFT_BOTTOM,
+				format!("{} Å", ring.resolution),
+				egui::FontId::monospace(12.0),
+				RING_COLOUR,
+			);
+		}
+		self.transform = transform;
+	}
+
+	fn profile_panel(&mut self, ui: &mut Ui) {
+		if let Some(profile) = self.results.as_ref().and_then(Results::profile) {
+			plot_profile(ui, &profile.values);
+		} else if let Some(view) = self.current_view(ui) {
+			plot_profile(ui, &view.profile);
+		}
+	}
+}
+
+/// Draws `profile` scaled to fill the remaining space of `ui`.
+fn plot_profile(ui: &mut Ui, profile: &[f64]) {
+	let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::hover());
+	let rect = response.rect.shrink(4.0);
+	let (min, max) = profile
+		.iter()
+		.filter(|v| v.is_finite())
+		.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+			(min.min(*v), max.max(*v))
+		});
+	if profile.len() < 2 || max <= min {
+		return;
+	}
+	let last = (profile.len() - 1) as f32;
+	let points = (profile.iter().enumerate())
+		.filter(|(_, v)| v.is_finite())
+		.map(|(i, v)| {
+			let x = rect.left() + rect.width() * i as f32 / last;
+			let y = rect.bottom() - rect.height() * ((v - min) / (max - min)) as f32;
+			pos2(x, y)
+		})
+		.collect();
+	painter.line(points, Stroke::new(1.0, ui.visuals().text_color()));
+}
+
+fn metadata_rows(metadata: &Metadata) -> Vec<(&'static str, String)> {
+	let optional = |value: Option<usize>| value.map_or("-".to_owned(), |v| v.to_string());
+	vec![
+		("Width", optional(metadata.width)),
+		("Height", optional(metadata.height)),
+		("Depth", optional(metadata.depth)),
+		("Element type", format!("{:?}", metadata.element_type)),
+		("Elements", metadata.element_count.to_string()),
+		("Compression", format!("{:?}", metadata.content_type.conversion)),
+		("Encoding", format!("{:?}", metadata.content_transfer_encoding.encoding)),
+		("Byte order", format!("{:?}", metadata.byte_order)),
+		("Binary size", metadata.size.to_string()),
+		("Padding", optional(metadata.padding)),
+		("MD5", metadata.md5_digest.clone().unwrap_or_else(|| "-".to_owned())),
+	]
+}
+
+impl eframe::App for Viewer {
+	fn ui(&mut self, ui: &mut Ui, _frame: &mut eframe::Frame) {
+		let dropped = ui.input(|i| i.raw.dropped_files.first().map(|f| f.path().to_owned()));
+		if let Some(path) = dropped {
+			match path.extension().and_then(|e| e.to_str()) {
+				Some("lut" | "txt") => self.load_colormap(&path),
+				_ => self.open(&path),
+			}
+		}
+
+		if let Some(error) = &self.error {
+			let error = error.clone();
+			Panel::top("error").show(ui, |ui| {
+				ui.colored_label(ui.visuals().error_fg_color, error);
+			});
+		}
+		Panel::left("metadata")
+			.default_size(280.0)
+			.show(ui, |ui| self.side_panel(ui));
+		Panel::bottom("profile")
+			.default_size(160.0)
+			.resizable(true)
+			.show(ui, |ui| self.profile_panel(ui));
+		CentralPanel::no_frame().show(ui, |ui| self.image_panel(ui));
+	}
+}
+
+fn main() -> eframe::Result {
+	let path = env::args_os().nth(1).map(PathBuf::from);
+	eframe::run_native(
+		"cbf-view",
+		eframe::NativeOptions::default(),
+		Box::new(|_| Ok(Box::new(Viewer::new(path)))),
+	)
+}
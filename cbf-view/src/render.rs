@@ -0,0 +1,149 @@
+use cbf_rs::{
+	analysis::{radial_difraction_analysis, radial_profile, sampler_methods::masked_nearest_neighbour, AnalysisConfig},
+	image::{
+		colormap::Colormap,
+		mask::{Mask, MaskEdit},
+		Image, ImageEnum,
+	},
+};
+
+/// How intensities are mapped onto the colour map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scaling {
+	#[default]
+	Log,
+	/// Histogram equalization over the whole frame
+	Equalized,
+	/// Contrast limited equalization over regions of the frame
+	LocalEqualized,
+}
+
+impl Scaling {
+	pub const ALL: [Scaling; 3] = [Self::Log, Self::Equalized, Self::LocalEqualized];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Log => "log",
+			Self::Equalized => "equalized",
+			Self::LocalEqualized => "local equalized",
+		}
+	}
+}
+
+/// RGBA pixels of `image` with high intensities at the top of `colormap`. On
+/// the logarithmic scale negative pixels, used by many detectors to mark gaps,
+/// are drawn with the bottom colour.
+pub fn to_rgba(image: &ImageEnum, colormap: &Colormap, scaling: Scaling) -> Vec<u8> {
+	let pixels = image.to_f64();
+	let levels = match scaling {
+		Scaling::Log => None,
+		Scaling::Equalized => Some(pixels.equalized()),
+		Scaling::LocalEqualized => pixels.locally_equalized(8, 3.0),
+	};
+	if let Some(levels) = levels {
+		return levels.pixels().iter().flat_map(|&l| colormap.colour(l)).collect();
+	}
+	let max = pixels
+		.pixels()
+		.iter()
+		.copied()
+		.filter(|p| p.is_finite())
+		.fold(0.0, f64::max);
+	let scale = (1.0 + max).ln();
+	pixels
+		.pixels()
+		.iter()
+		.flat_map(|&p| {
+			let level = if 0.0 < p && 0.0 < scale {
+				(1.0 + p.min(max)).ln() / scale
+			} else {
+				0.0
+			};
+			colormap.colour(level)
+		})
+		.collect()
+}
+
+/// Radial profile with the same sampling as the web viewer, about the beam
+/// centre where one is known and falls on the frame, so it matches the rings
+/// drawn, and about the image centre otherwise. The centre is in pixels from
+/// the centre of the first pixel.
+///
+/// Profiles are always taken about the centre of the image, so the frame is
+/// placed on a larger canvas centred on the beam, with everything outside the
+/// frame masked.
+pub fn profile(image: &ImageEnum, centre: Option<(f64, f64)>) -> Vec<f64> {
+	let (width, height) = (image.width(), image.height());
+	let centre = centre
+		.map(|(x, y)| (x.round(), y.round()))
+		.filter(|&(x, y)| (0.0..width as f64).contains(&x) && (0.0..height as f64).contains(&y));
+	let Some((x, y)) = centre else {
+		let config = AnalysisConfig::new(width / 2, 1000, std::f64::consts::SQRT_2).unwrap();
+		return radial_profile(image, &config).into_vec();
+	};
+	let (x, y) = (x as usize, y as usize);
+	let (half_width, half_height) = (x.max(width - x), y.max(height - y));
+	let (left, top) = (half_width - x, half_height - y);
+	let mut canvas = Image::new(
+		2 * half_width,
+		2 * half_height,
+		vec![0.0; 4 * half_width * half_height].into(),
+	)
+	.unwrap();
+	let frame = image.to_f64();
+	for (canvas_row, row) in canvas.rows_mut().skip(top).zip(frame.rows()) {
+		canvas_row[left..][..width].copy_from_slice(row);
+	}
+	let mut mask = Mask::new(canvas.width, canvas.height, vec![true; canvas.pixels().len()].into()).unwrap();
+	MaskEdit::Rectangle { x: left, y: top, width, height, masked: false }.apply(&mut mask);
+	let config = AnalysisConfig::new(half_width, 1000, std::f64::consts::SQRT_2).unwrap();
+	radial_difraction_analysis(&canvas, &config, masked_nearest_neighbour(&mask)).into_vec()
+}
+
+/// Resolutions in ångström from a list separated by spaces or commas,
+/// ignoring anything that is not a positive number.
+pub fn parse_resolutions(text: &str) -> Vec<f64> {
+	text.split(|c: char| c.is_whitespace() || c == ',')
+		.filter_map(|s| s.parse().ok())
+		.filter(|d: &f64| 0.0 < *d)
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use cbf_rs::image::{colormap::Colormap, ImageEnum};
+
+	use super::{parse_resolutions, profile, to_rgba, Scaling};
+
+	#[test]
+	fn equalized() {
+		let image = ImageEnum::from_pixels(3, 1, vec![0i32, 500, 1000].into());
+		let rgba = to_rgba(&image, &Colormap::default(), Scaling::Equalized);
+		assert_eq!(rgba[4..8], [128, 128, 128, 255]);
+	}
+
+	#[test]
+	fn greyscale() {
+		let image = ImageEnum::from_pixels(3, 1, vec![-1i32, 0, 100].into());
+		assert_eq!(
+			to_rgba(&image, &Colormap::default(), Scaling::Log),
+			[0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255, 255]
+		);
+	}
+
+	#[test]
+	fn beam_centred_profile() {
+		// Pixels hold their distance from the beam, which is off the image centre.
+		let pixels = (0..6 * 8)
+			.map(|i: i32| (((i % 8 - 2).pow(2) + (i / 8 - 1).pow(2)) as f64).sqrt())
+			.collect::<Vec<f64>>();
+		let image = ImageEnum::from_pixels(8, 6, pixels.into());
+		assert_eq!(profile(&image, Some((2.0, 1.0)))[0], 0.0);
+		assert_ne!(profile(&image, None)[0], 0.0);
+	}
+
+	#[test]
+	fn resolutions() {
+		assert_eq!(parse_resolutions("2, 3.5 x -1  4"), [2.0, 3.5, 4.0]);
+	}
+}